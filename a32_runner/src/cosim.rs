@@ -0,0 +1,477 @@
+//! Lockstep co-simulation of a program on the a32_core emulator and on an
+//! HDL implementation of the CPU (hdl_lib/05_cpu), stopping at the first
+//! architectural divergence.
+//!
+//! The HDL CPU is driven through its memory ports (`instr_addr`/`instr_data`,
+//! `mem_addr`/`mem_rdata`/`mem_wdata`/`mem_read`/`mem_write`/`mem_byte`)
+//! by a flat RAM holding the same image as the emulator. Each time the HDL
+//! retires an instruction the emulator steps once, then registers, flags,
+//! PC and memory writes are compared.
+
+use a32_core::{Machine, Reg, SimConfig, StepOutcome};
+use hdl_core::elab::Netlist;
+use hdl_core::mem::a32b_segments;
+use hdl_core::sim::Simulator;
+use hdl_core::value::BitVec;
+use std::collections::VecDeque;
+
+const MMIO_GETC: u32 = 0xFFFF_0004;
+
+/// Cycles the HDL CPU may take to raise `halted` once the emulator stopped
+const HALT_DRAIN_CYCLES: u64 = 8;
+
+/// Where the harness reads the architectural state of the HDL CPU
+#[derive(Clone, Debug)]
+pub struct Probes {
+    /// Register path pattern, `{}` is the register number (`u_rf/regs({})`)
+    pub regs: String,
+    /// N, Z, C and V flag signals
+    pub flags: [String; 4],
+    /// Address of the next instruction once the previous one retired
+    pub pc: String,
+    /// 1-bit signal high on the cycles that retire an instruction;
+    /// every cycle retires one when unset (single-cycle CPU)
+    pub retire: Option<String>,
+}
+
+impl Default for Probes {
+    fn default() -> Self {
+        Self {
+            regs: "u_rf/regs({})".to_string(),
+            flags: ["flag_n", "flag_z", "flag_c", "flag_v"].map(str::to_string),
+            pc: "instr_addr".to_string(),
+            retire: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CosimOptions {
+    pub probes: Probes,
+    pub max_cycles: u64,
+}
+
+/// First point where the two CPUs disagree
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    /// Retired instructions before the diverging one
+    pub instruction: u64,
+    pub pc: u32,
+    pub instr: u32,
+    pub detail: String,
+}
+
+/// How a co-simulation run ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CosimEnd {
+    Exit(u32),
+    /// The emulator trapped; the HDL CPU has no trap logic
+    Trap(&'static str),
+    /// `max_cycles` ran out before the program stopped
+    CycleLimit,
+}
+
+impl std::fmt::Display for CosimEnd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CosimEnd::Exit(code) => write!(f, "exit {}", code),
+            CosimEnd::Trap(code) => write!(f, "trap {}", code),
+            CosimEnd::CycleLimit => write!(f, "cycle limit"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CosimReport {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub end: CosimEnd,
+    pub divergence: Option<Divergence>,
+}
+
+/// A store seen on either side; `value` is None for MMIO stores the
+/// emulator does not keep in RAM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Store {
+    addr: u32,
+    size: u8,
+    value: Option<u32>,
+}
+
+impl Store {
+    fn describe(&self) -> String {
+        let value = self.value.map(|v| format!(" = {:#010x}", v)).unwrap_or_default();
+        format!("{}-byte store at {:#010x}{}", self.size, self.addr, value)
+    }
+
+    fn matches(&self, other: &Store) -> bool {
+        self.addr == other.addr
+            && self.size == other.size
+            && (self.value.is_none() || other.value.is_none() || self.value == other.value)
+    }
+}
+
+/// HDL CPU plus the RAM behind its memory ports
+struct HdlCpu {
+    sim: Simulator,
+    ram: Vec<u8>,
+    probes: Probes,
+}
+
+impl HdlCpu {
+    fn signal(&self, name: &str) -> Result<u32, String> {
+        self.sim
+            .get_signal(name)
+            .map(|v| v.to_u64_trunc() as u32)
+            .map_err(|e| format!("hdl: {}", e))
+    }
+
+    fn set(&mut self, name: &str, value: u32, width: usize) -> Result<(), String> {
+        self.sim
+            .set_signal(name, BitVec::from_u64(width, value as u64))
+            .map_err(|e| format!("hdl: {}", e))
+    }
+
+    fn reg_path(&self, reg: usize) -> String {
+        self.probes.regs.replace("{}", &reg.to_string())
+    }
+
+    fn read_word(&self, addr: u32) -> u32 {
+        if addr == MMIO_GETC {
+            return 0xFFFF_FFFF;
+        }
+        let at = (addr & !3) as usize;
+        match self.ram.get(at..at + 4) {
+            Some(b) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            None => 0,
+        }
+    }
+
+    /// Feeds the fetch and data read ports until they settle
+    fn drive_memory(&mut self) -> Result<(), String> {
+        for _ in 0..4 {
+            let instr = self.read_word(self.signal("instr_addr")?);
+            self.set("instr_data", instr, 32)?;
+            let rdata = if self.signal("mem_read")? != 0 {
+                let addr = self.signal("mem_addr")?;
+                if self.signal("mem_byte")? != 0 {
+                    (self.read_word(addr) >> ((addr & 3) * 8)) & 0xFF
+                } else {
+                    self.read_word(addr)
+                }
+            } else {
+                0
+            };
+            self.set("mem_rdata", rdata, 32)?;
+            self.sim.eval_comb().map_err(|e| format!("hdl: {}", e))?;
+            if self.signal("instr_data")? == self.read_word(self.signal("instr_addr")?) {
+                return Ok(());
+            }
+        }
+        Err("hdl: memory ports do not settle".to_string())
+    }
+
+    /// One clock cycle; returns whether an instruction retired and the
+    /// store it performed
+    fn cycle(&mut self) -> Result<(bool, Option<Store>), String> {
+        self.drive_memory()?;
+        let retired = match &self.probes.retire {
+            Some(name) => self.signal(name)? != 0,
+            None => true,
+        };
+        let store = if self.signal("mem_write")? != 0 {
+            let addr = self.signal("mem_addr")?;
+            let data = self.signal("mem_wdata")?;
+            Some(if self.signal("mem_byte")? != 0 {
+                Store { addr, size: 1, value: Some(data & 0xFF) }
+            } else {
+                Store { addr: addr & !3, size: 4, value: Some(data) }
+            })
+        } else {
+            None
+        };
+        self.set("clk", 1, 1)?;
+        self.sim.tick().map_err(|e| format!("hdl: {}", e))?;
+        self.set("clk", 0, 1)?;
+        self.sim.tock().map_err(|e| format!("hdl: {}", e))?;
+        if let Some(store) = store {
+            let bytes = store.value.unwrap_or(0).to_le_bytes();
+            let at = store.addr as usize;
+            if let Some(slot) = self.ram.get_mut(at..at + store.size as usize) {
+                slot.copy_from_slice(&bytes[..store.size as usize]);
+            }
+        }
+        self.drive_memory()?;
+        Ok((retired, store))
+    }
+
+    fn halted(&self) -> bool {
+        self.signal("halted").is_ok_and(|h| h != 0)
+    }
+}
+
+/// Runs `image` on both CPUs; errors are setup problems (missing port,
+/// unknown probe), not divergences
+pub fn cosim(netlist: &Netlist, image: &[u8], config: SimConfig, options: &CosimOptions) -> Result<CosimReport, String> {
+    let mut machine = Machine::from_a32b(image, config.clone()).map_err(|e| e.to_string())?;
+    let mut ram = vec![0u8; config.ram_size as usize];
+    for (vaddr, data) in a32b_segments(image).map_err(|e| e.to_string())? {
+        let at = vaddr as usize;
+        ram.get_mut(at..at + data.len())
+            .ok_or_else(|| format!("segment at {:#x} outside RAM", vaddr))?
+            .copy_from_slice(data);
+    }
+    let mut hdl = HdlCpu {
+        sim: Simulator::new(netlist.clone()),
+        ram,
+        probes: options.probes.clone(),
+    };
+
+    // Reset, then start the HDL CPU from the emulator's initial state
+    if hdl.sim.signal_info("reset").is_some() {
+        hdl.set("reset", 1, 1)?;
+        hdl.cycle()?;
+        hdl.set("reset", 0, 1)?;
+    }
+    for reg in 0..15 {
+        let path = hdl.reg_path(reg);
+        hdl.set(&path, machine.cpu().reg(Reg::from_u8(reg as u8).unwrap_or(Reg::R0)), 32)?;
+    }
+    let flags = machine.cpu().flags();
+    for (name, value) in options.probes.flags.clone().iter().zip([flags.n, flags.z, flags.c, flags.v]) {
+        hdl.set(name, value as u32, 1)?;
+    }
+    hdl.drive_memory()?;
+
+    let mut report = CosimReport { instructions: 0, cycles: 0, end: CosimEnd::CycleLimit, divergence: None };
+    let diverge = |report: &mut CosimReport, pc: u32, instr: u32, detail: String| {
+        report.divergence = Some(Divergence { instruction: report.instructions, pc, instr, detail });
+    };
+    let start_pc = hdl.signal(&options.probes.pc)?;
+    if start_pc != machine.cpu().pc() {
+        let detail = format!("PC: emulator {:#010x}, hdl {:#010x}", machine.cpu().pc(), start_pc);
+        diverge(&mut report, machine.cpu().pc(), 0, detail);
+        return Ok(report);
+    }
+
+    let mut hdl_stores = VecDeque::new();
+    let mut emu_stores = VecDeque::new();
+    while report.cycles < options.max_cycles {
+        let (retired, store) = hdl.cycle()?;
+        report.cycles += 1;
+        hdl_stores.extend(store);
+        if !retired {
+            continue;
+        }
+
+        let pc = machine.cpu().pc();
+        let instr = machine.read_u32(pc).unwrap_or(0);
+        machine.clear_mem_access();
+        let outcome = machine.step();
+        if let Some(access) = machine.last_mem_access().filter(|a| a.is_write) {
+            let value = match access.size {
+                1 => machine.mem().read8(access.addr).map(u32::from),
+                _ => machine.read_u32(access.addr),
+            };
+            emu_stores.push_back(Store { addr: access.addr, size: access.size, value });
+        }
+
+        // Stores may be seen a few cycles before the instruction retires
+        while let (Some(h), Some(e)) = (hdl_stores.front(), emu_stores.front()) {
+            if !h.matches(e) {
+                let detail = format!("store: emulator {}, hdl {}", e.describe(), h.describe());
+                diverge(&mut report, pc, instr, detail);
+                return Ok(report);
+            }
+            hdl_stores.pop_front();
+            emu_stores.pop_front();
+        }
+        if let Some(detail) = compare_state(&hdl, &machine, &outcome)? {
+            diverge(&mut report, pc, instr, detail);
+            return Ok(report);
+        }
+        report.instructions += 1;
+
+        let end = match &outcome {
+            StepOutcome::Continue => {
+                if hdl.halted() {
+                    diverge(&mut report, pc, instr, "hdl halted, emulator still running".to_string());
+                    return Ok(report);
+                }
+                continue;
+            }
+            StepOutcome::Exit(exit) => CosimEnd::Exit(exit.code),
+            // The HDL CPU has no trap logic: a trap ends the comparison
+            StepOutcome::Trap(trap) => {
+                report.end = CosimEnd::Trap(trap.code.as_str());
+                return Ok(report);
+            }
+        };
+        for _ in 0..HALT_DRAIN_CYCLES {
+            if hdl.halted() || hdl.sim.signal_info("halted").is_none() {
+                break;
+            }
+            let (_, store) = hdl.cycle()?;
+            report.cycles += 1;
+            hdl_stores.extend(store);
+        }
+        if hdl.sim.signal_info("halted").is_some() && !hdl.halted() {
+            diverge(&mut report, pc, instr, format!("emulator stopped ({}), hdl did not halt", end));
+        } else if let Some(e) = emu_stores.front() {
+            diverge(&mut report, pc, instr, format!("store: emulator {}, hdl none", e.describe()));
+        }
+        report.end = end;
+        return Ok(report);
+    }
+    Ok(report)
+}
+
+/// First register, flag or PC difference after an instruction
+fn compare_state(hdl: &HdlCpu, machine: &Machine, outcome: &StepOutcome) -> Result<Option<String>, String> {
+    let cpu = machine.cpu();
+    for reg in 0..15 {
+        let emu = cpu.reg(Reg::from_u8(reg as u8).unwrap_or(Reg::R0));
+        let got = hdl.signal(&hdl.reg_path(reg))?;
+        if emu != got {
+            return Ok(Some(format!("R{}: emulator {:#010x}, hdl {:#010x}", reg, emu, got)));
+        }
+    }
+    let flags = cpu.flags();
+    for ((name, signal), emu) in ["N", "Z", "C", "V"].iter().zip(&hdl.probes.flags).zip([flags.n, flags.z, flags.c, flags.v]) {
+        let got = hdl.signal(signal)? != 0;
+        if emu != got {
+            return Ok(Some(format!("flag {}: emulator {}, hdl {}", name, emu as u8, got as u8)));
+        }
+    }
+    // A halted CPU's next PC is meaningless
+    if matches!(outcome, StepOutcome::Continue) {
+        let got = hdl.signal(&hdl.probes.pc)?;
+        if cpu.pc() != got {
+            return Ok(Some(format!("PC: emulator {:#010x}, hdl {:#010x}", cpu.pc(), got)));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hdl_core::elab::elaborate;
+    use hdl_core::parser::parse_str;
+
+    /// Single-cycle CPU knowing only MOV/ADD immediate and HALT; every
+    /// other ALU immediate instruction is executed as an ADD
+    const TINY_CPU: &str = r#"
+entity TinyCpu is
+  port(
+    clk : in bit;
+    reset : in bit;
+    instr_addr : out bits(31 downto 0);
+    instr_data : in bits(31 downto 0);
+    mem_addr : out bits(31 downto 0);
+    mem_rdata : in bits(31 downto 0);
+    mem_wdata : out bits(31 downto 0);
+    mem_read : out bit;
+    mem_write : out bit;
+    mem_byte : out bit;
+    halted : out bit
+  );
+end entity;
+
+architecture rtl of TinyCpu is
+  type regs_t is array (0 to 15) of bits(31 downto 0);
+  signal regs : regs_t;
+  signal pc : bits(31 downto 0);
+  signal halt_r : bit;
+  signal flag_n : bit;
+  signal flag_z : bit;
+  signal flag_c : bit;
+  signal flag_v : bit;
+  signal rd : bits(3 downto 0);
+  signal rn : bits(3 downto 0);
+  signal imm : bits(31 downto 0);
+begin
+  instr_addr <= pc;
+  halted <= halt_r;
+  mem_addr <= x"00000000";
+  mem_wdata <= x"00000000";
+  mem_read <= '0';
+  mem_write <= '0';
+  mem_byte <= '0';
+  rd <= instr_data(19 downto 16);
+  rn <= instr_data(15 downto 12);
+  imm <= x"00000" & instr_data(11 downto 0);
+
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      if reset = '1' then
+        pc <= x"00000000";
+        halt_r <= '0';
+      elsif halt_r = '0' then
+        if instr_data(27 downto 25) = b"100" then
+          halt_r <= '1';
+        else
+          pc <= pc + x"00000004";
+          if instr_data(22) = '1' then
+            regs(rd) <= regs(rn) + imm;
+          else
+            regs(rd) <= imm;
+          end if;
+        end if;
+      end if;
+    end if;
+  end process;
+end architecture;
+"#;
+
+    fn run(program: &str, max_cycles: u64) -> CosimReport {
+        let netlist = elaborate(&parse_str(TINY_CPU).unwrap(), "TinyCpu").unwrap();
+        let image = a32_asm::assemble_a32b(program).unwrap();
+        let options = CosimOptions {
+            probes: Probes { regs: "regs({})".to_string(), ..Probes::default() },
+            max_cycles,
+        };
+        cosim(&netlist, &image, SimConfig::default(), &options).unwrap()
+    }
+
+    const PROGRAM: &str = "_start:\n  MOV R0, #1\n  MOV R1, #2\n  ADD R2, R1, #5\n";
+
+    #[test]
+    fn test_cosim_agrees_until_exit() {
+        let report = run(&format!("{}  HALT\n", PROGRAM), 100);
+        assert!(report.divergence.is_none(), "{:?}", report.divergence);
+        assert_eq!(report.end, CosimEnd::Exit(0));
+        assert_eq!(report.instructions, 4);
+    }
+
+    #[test]
+    fn test_cosim_reports_first_divergence() {
+        let report = run(&format!("{}  SUB R3, R2, #1\n  HALT\n", PROGRAM), 100);
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.instruction, 3);
+        assert_eq!(divergence.pc, 12);
+        assert_eq!(divergence.detail, "R3: emulator 0x00000006, hdl 0x00000008");
+    }
+
+    #[test]
+    fn test_cosim_cycle_limit() {
+        let report = run(&format!("{}  HALT\n", PROGRAM), 2);
+        assert!(report.divergence.is_none());
+        assert_eq!(report.end, CosimEnd::CycleLimit);
+        assert_eq!(report.instructions, 2);
+    }
+
+    /// The single-cycle CPU of hdl_lib against the tests/ programs it
+    /// implements; R15 reads and BL's link are not there yet (see TODO.md)
+    #[test]
+    fn test_cosim_hdl_lib_cpu() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let netlist = crate::load_hdl_cpu("CPU", &[root.join("hdl_lib")]).unwrap();
+        let options = CosimOptions { probes: Probes::default(), max_cycles: 1000 };
+        for name in ["T01_alu_flags", "T02_predication", "T08_suffix_order", "T11_div_zero", "T12_svc_putc", "T13_svc_exit"] {
+            let cases = crate::collect_cases(&root.join("tests").join(name).with_extension("a32")).unwrap();
+            crate::cosim_case(&cases[0], &netlist, &options).unwrap();
+        }
+    }
+}
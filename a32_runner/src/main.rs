@@ -2,11 +2,18 @@ use a32_asm::ast::Expr;
 use a32_asm::parser::parse_expr_str;
 use a32_asm::{assemble_a32b_with_config, AsmConfig, AsmError};
 use a32_core::{Machine, Reg, SimConfig, TrapCode};
+use hdl_core::ast::Design;
+use hdl_core::elab::{elaborate, Netlist};
+use hdl_core::library::{resolve, SearchPath};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+mod cosim;
+
+use cosim::{cosim, CosimEnd, CosimOptions, Probes};
+
 fn main() {
     if let Err(err) = run() {
         eprintln!("error: {}", err);
@@ -14,17 +21,56 @@ fn main() {
     }
 }
 
+const USAGE: &str = "usage: a32_runner [--hdl <Entity> [--lib <dir>]... [--regs <pattern>] [--flags <n,z,c,v>] \
+[--pc <signal>] [--retire <signal>] [--max-cycles <n>]] [path]";
+
 fn run() -> Result<(), Box<dyn std::error::Error>> {
     let mut args = env::args().skip(1);
-    let target = args.next().unwrap_or_else(|| "tests".to_string());
-    let path = PathBuf::from(target);
+    let mut target = None;
+    let mut hdl_top = None;
+    let mut libs = Vec::new();
+    let mut options = CosimOptions {
+        probes: Probes::default(),
+        max_cycles: 10_000_000,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}\n{}", arg, USAGE));
+        match arg.as_str() {
+            "--hdl" => hdl_top = Some(value()?),
+            "--lib" => libs.push(PathBuf::from(value()?)),
+            "--regs" => options.probes.regs = value()?,
+            "--flags" => {
+                let list = value()?;
+                let names: Vec<String> = list.split(',').map(|s| s.trim().to_string()).collect();
+                options.probes.flags = names.try_into().map_err(|_| format!("--flags needs 4 signals (n,z,c,v), got {}", list))?;
+            }
+            "--pc" => options.probes.pc = value()?,
+            "--retire" => options.probes.retire = Some(value()?),
+            "--max-cycles" => options.max_cycles = parse_u32(&value()?)? as u64,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE).into()),
+            _ if target.is_none() => target = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let path = PathBuf::from(target.unwrap_or_else(|| "tests".to_string()));
     let cases = collect_cases(&path)?;
     if cases.is_empty() {
         return Err("no tests found".into());
     }
+    if libs.is_empty() {
+        libs.push(PathBuf::from("hdl_lib"));
+    }
+    let netlist = match &hdl_top {
+        Some(top) => Some(load_hdl_cpu(top, &libs)?),
+        None => None,
+    };
     let mut failures = Vec::new();
     for case in cases {
-        if let Err(err) = run_case(&case) {
+        let result = match &netlist {
+            Some(netlist) => cosim_case(&case, netlist, &options),
+            None => run_case(&case),
+        };
+        if let Err(err) = result {
             failures.push(err);
         }
     }
@@ -38,6 +84,57 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Elaborates the HDL CPU, loading `<top>.hdl` and the entities it
+/// instantiates from the `--lib` search path
+fn load_hdl_cpu(top: &str, libs: &[PathBuf]) -> Result<Netlist, Box<dyn std::error::Error>> {
+    let mut design = Design::default();
+    resolve(&mut design, &[top], &mut SearchPath::new(libs.to_vec()))?;
+    Ok(elaborate(&design, top).map_err(|e| format!("{}: {}", top, e))?)
+}
+
+/// Runs a test on the emulator and the HDL CPU in lockstep; tests that
+/// expect an assembly error have nothing to run and are skipped
+fn cosim_case(case: &TestCase, netlist: &Netlist, options: &CosimOptions) -> Result<(), String> {
+    if !case.ref_path.exists() {
+        return Err(format!("{}: missing ref file", case.name));
+    }
+    let spec = parse_ref(&case.ref_path)?;
+    if spec.expected_error.is_some() {
+        return Ok(());
+    }
+    let source = fs::read_to_string(&case.asm_path).map_err(|e| format!("{}: {}", case.name, e))?;
+    let build = build_program(&source, case, &spec).map_err(|err| format!("{}: {}", case.name, err))?;
+    let stack_top = build
+        .stack_size
+        .map(|size| spec.config.ram_size.saturating_sub(size))
+        .filter(|top| *top <= spec.config.ram_size);
+    let sim_config = SimConfig {
+        ram_size: spec.config.ram_size,
+        strict_traps: spec.config.strict_traps,
+        max_steps: 1_000_000,
+        stack_top,
+    };
+    let report = cosim(netlist, &build.bytes, sim_config, options).map_err(|e| format!("{}: {}", case.name, e))?;
+    match report.divergence {
+        Some(d) => Err(format!(
+            "{}: diverged at instruction {} (pc {:#010x}, instr {:#010x}): {}",
+            case.name, d.instruction, d.pc, d.instr, d.detail
+        )),
+        // A CPU that hangs or never retires does not agree with the emulator
+        None if report.end == CosimEnd::CycleLimit => Err(format!(
+            "{}: no exit within {} cycles ({} instructions retired)",
+            case.name, report.cycles, report.instructions
+        )),
+        None => {
+            println!(
+                "{}: {} instructions in {} cycles, {}",
+                case.name, report.instructions, report.cycles, report.end
+            );
+            Ok(())
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct TestCase {
     name: String,
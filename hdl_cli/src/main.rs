@@ -1,131 +1,472 @@
+use hdl_core::ast::Design;
+use hdl_core::dot::DotView;
 use hdl_core::elab::elaborate;
+use hdl_core::equiv::{check_equivalence, Equivalence};
+use hdl_core::error_messages::classify;
+use hdl_core::export::gate_netlist;
+use hdl_core::library::{find_unit, resolve as resolve_units, SearchPath};
+use hdl_core::lint::lint_architecture;
 use hdl_core::parser::parse_str;
-use hdl_core::sim::Simulator;
+use hdl_core::test_runner::{run_test_design, script_files, ScriptFiles, TestOptions, TestResult};
 use hdl_core::value::BitVec;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 fn main() {
-    if let Err(err) = run() {
-        eprintln!("error: {}", err);
-        std::process::exit(1);
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
     }
 }
 
-fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("usage: hdl_cli <test.tst>");
+const USAGE: &str = "usage: hdl_cli [--lib <dir>]... <test.tst | dir | glob>...
+       hdl_cli equiv [--lib <dir>]... <EntityA> <EntityB> [file.hdl]...
+       hdl_cli stats [--lib <dir>]... [--depth <n>] <Entity>... [file.hdl]...
+       hdl_cli export [--lib <dir>]... [--format verilog|blif|json] [-o <file>] <Entity> [file.hdl]...
+       hdl_cli dot [--lib <dir>]... [--flat] [--scope <path>] [-o <file>] <Entity> [file.hdl]...
+       hdl_cli lint [--lib <dir>]... <file.hdl | Entity>...";
+
+/// Runs every test given on the command line, returns true if all passed
+fn run() -> Result<bool, Box<dyn std::error::Error>> {
+    let mut targets = Vec::new();
+    let mut lib_dirs = Vec::new();
+    let mut depth = 1;
+    let mut format = String::from("verilog");
+    let mut output = None;
+    let mut view = DotView::Level;
+    let mut scope = String::new();
+    let mut args = env::args().skip(1).peekable();
+    let command = args.next_if(|arg| arg == "equiv" || arg == "stats" || arg == "export" || arg == "dot" || arg == "lint");
+    while let Some(arg) = args.next() {
+        if arg == "--lib" {
+            lib_dirs.push(PathBuf::from(args.next().ok_or("--lib requires a directory")?));
+        } else if arg == "--depth" && command.as_deref() == Some("stats") {
+            let n = args.next().ok_or("--depth requires a number")?;
+            depth = n.parse().map_err(|_| format!("invalid depth {}", n))?;
+        } else if arg == "--format" && command.as_deref() == Some("export") {
+            format = args.next().ok_or("--format requires verilog, blif or json")?;
+        } else if arg == "--flat" && command.as_deref() == Some("dot") {
+            view = DotView::Flat;
+        } else if arg == "--scope" && command.as_deref() == Some("dot") {
+            scope = args.next().ok_or("--scope requires an instance path")?;
+        } else if arg == "-o" && matches!(command.as_deref(), Some("export" | "dot")) {
+            output = Some(PathBuf::from(args.next().ok_or("-o requires a file")?));
+        } else {
+            targets.push(arg);
+        }
+    }
+    if targets.is_empty() {
+        eprintln!("{}", USAGE);
         std::process::exit(2);
     }
-    let test_path = &args[1];
-    let script = fs::read_to_string(test_path)?;
-    let mut sim: Option<Simulator> = None;
-    let mut clock_name: String = "clk".to_string();
+    match command.as_deref() {
+        Some("equiv") => return run_equiv(&targets, &lib_dirs),
+        Some("stats") => return run_stats(&targets, &lib_dirs, depth),
+        Some("export") => return run_export(&targets, &lib_dirs, &format, output.as_deref()),
+        Some("dot") => return run_dot(&targets, &lib_dirs, &scope, view, output.as_deref()),
+        Some("lint") => return run_lint(&targets, &lib_dirs),
+        _ => {}
+    }
 
-    for (line_no, raw) in script.lines().enumerate() {
-        let line = raw.trim();
-        if line.is_empty() || line.starts_with("#") || line.starts_with("//") || line.starts_with("--") {
-            continue;
+    let mut tests = Vec::new();
+    for target in &targets {
+        let found = collect_tests(target)?;
+        if found.is_empty() {
+            return Err(format!("no .tst file matches {}", target).into());
         }
-        let mut parts = line.split_whitespace();
-        let cmd = match parts.next() {
-            Some(c) => c,
-            None => continue,
-        };
-        match cmd {
-            "load" => {
-                let top = parts.next().ok_or("load requires top entity")?;
-                let files: Vec<String> = parts.map(|s| s.to_string()).collect();
-                if files.is_empty() {
-                    return Err("load requires at least one file".into());
-                }
-                let mut design = hdl_core::ast::Design { entities: Vec::new(), architectures: Vec::new() };
-                for file in files {
-                    let src = fs::read_to_string(&file)?;
-                    let d = parse_str(&src)?;
-                    design.entities.extend(d.entities);
-                    design.architectures.extend(d.architectures);
-                }
-                let netlist = elaborate(&design, top)?;
-                sim = Some(Simulator::new(netlist));
-            }
-            "clock" => {
-                clock_name = parts.next().ok_or("clock requires signal name")?.to_string();
-            }
-            "set" => {
-                let name = parts.next().ok_or("set requires signal")?;
-                let value_str = parts.collect::<Vec<&str>>().join(" ");
-                let value = parse_value(&value_str)?;
-                sim.as_mut().ok_or("simulator not loaded")?.set_signal(name, value)?;
-            }
-            "eval" => {
-                sim.as_mut().ok_or("simulator not loaded")?.eval_comb()?;
+        tests.extend(found);
+    }
+    let mut failed = 0;
+    for test in &tests {
+        match run_test_file(test, &lib_dirs) {
+            Ok(result) if result.passed => {
+                println!("PASS {} ({}/{})", test.display(), result.passed_checks, result.total_checks);
             }
-            "tick" => {
-                if let Some(ref mut s) = sim {
-                    s.set_signal(&clock_name, BitVec::new(1, 1))?;
-                    s.tick()?;
-                } else {
-                    return Err("simulator not loaded".into());
+            Ok(result) => {
+                failed += 1;
+                println!("FAIL {} ({}/{})", test.display(), result.passed_checks, result.total_checks);
+                for failure in &result.failures {
+                    print!("{}", failure.format());
                 }
             }
-            "tock" => {
-                if let Some(ref mut s) = sim {
-                    s.set_signal(&clock_name, BitVec::new(1, 0))?;
-                    s.tock()?;
-                } else {
-                    return Err("simulator not loaded".into());
-                }
+            Err(err) => {
+                failed += 1;
+                println!("FAIL {}: {}", test.display(), err);
             }
-            "step" => {
-                if let Some(ref mut s) = sim {
-                    s.set_signal(&clock_name, BitVec::new(1, 1))?;
-                    s.tick()?;
-                    s.set_signal(&clock_name, BitVec::new(1, 0))?;
-                    s.tock()?;
-                } else {
-                    return Err("simulator not loaded".into());
-                }
+        }
+    }
+    if tests.len() > 1 {
+        println!(
+            "\n{} passed, {} failed, {} total",
+            tests.len() - failed,
+            failed,
+            tests.len()
+        );
+    }
+    Ok(failed == 0)
+}
+
+/// `equiv A B [files]`: proves that two entities compute the same outputs,
+/// or prints the inputs that tell them apart
+fn run_equiv(args: &[String], lib_dirs: &[PathBuf]) -> Result<bool, Box<dyn std::error::Error>> {
+    let (files, entities): (Vec<&String>, Vec<&String>) = args.iter().partition(|a| a.ends_with(".hdl"));
+    let [left, right] = entities[..] else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let design = load_files(&files, &[left, right], lib_dirs)?;
+
+    match check_equivalence(&design, left, right)? {
+        Equivalence::Equivalent { method, input_bits } => {
+            println!("{} and {} are equivalent ({}, {} input bits)", left, right, method.as_str(), input_bits);
+            Ok(true)
+        }
+        Equivalence::Different(cex) => {
+            println!("{} and {} differ", left, right);
+            for (name, value) in &cex.inputs {
+                println!("  {} = {}", name, hex(value));
             }
-            "expect" => {
-                let name = parts.next().ok_or("expect requires signal")?;
-                let value_str = parts.collect::<Vec<&str>>().join(" ");
-                let expected = parse_value(&value_str)?;
-                let got = sim.as_ref().ok_or("simulator not loaded")?.get_signal(name)?;
-                let resized = expected.resize_zero(got.width());
-                if resized != got {
-                    return Err(format!("line {}: expect {} != got", line_no + 1, name).into());
-                }
+            for (name, l, r) in &cex.outputs {
+                println!("  {}: {} ({}) vs {} ({})", name, hex(l), left, hex(r), right);
             }
-            _ => return Err(format!("unknown command: {}", cmd).into()),
+            Ok(false)
         }
     }
+}
 
-    Ok(())
+/// `stats A [B...] [files]`: gate counts per instance down to `depth` and
+/// critical path of each entity, one report after the other
+fn run_stats(args: &[String], lib_dirs: &[PathBuf], depth: usize) -> Result<bool, Box<dyn std::error::Error>> {
+    let (files, entities): (Vec<&String>, Vec<&String>) = args.iter().partition(|a| a.ends_with(".hdl"));
+    if entities.is_empty() {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    }
+    let roots: Vec<&str> = entities.iter().map(|e| e.as_str()).collect();
+    let design = load_files(&files, &roots, lib_dirs)?;
+    for (i, top) in entities.iter().enumerate() {
+        let netlist = elaborate(&design, top).map_err(|e| format!("{}: {}", top, e))?;
+        if i > 0 {
+            println!();
+        }
+        println!("== {}", top);
+        print!("{}", netlist.design_stats().report(depth));
+    }
+    Ok(true)
+}
+
+/// `export A [files]`: flattens A to single-bit gates and flip-flops and
+/// writes it as structural Verilog, BLIF or Yosys JSON (stdout by default)
+fn run_export(
+    args: &[String],
+    lib_dirs: &[PathBuf],
+    format: &str,
+    output: Option<&Path>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let (files, entities): (Vec<&String>, Vec<&String>) = args.iter().partition(|a| a.ends_with(".hdl"));
+    let [top] = entities[..] else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let design = load_files(&files, &[top], lib_dirs)?;
+    let netlist = elaborate(&design, top).map_err(|e| format!("{}: {}", top, e))?;
+    let gates = gate_netlist(&netlist).map_err(|e| format!("{}: {}", top, e))?;
+    let text = match format {
+        "verilog" => gates.to_verilog(),
+        "blif" => gates.to_blif()?,
+        "json" => gates.to_yosys_json(),
+        _ => return Err(format!("unknown export format {} (verilog, blif or json)", format).into()),
+    };
+    match output {
+        Some(path) => fs::write(path, text)?,
+        None => print!("{}", text),
+    }
+    Ok(true)
 }
 
-fn parse_value(s: &str) -> Result<BitVec, Box<dyn std::error::Error>> {
-    let t = s.trim();
-    if t.starts_with("b\"") && t.ends_with('"') {
-        let inner = &t[2..t.len() - 1];
-        return Ok(BitVec::from_bits_msb(inner)?);
+/// `dot A [files]`: Graphviz schematic of A, or of the instance at `scope`,
+/// one level deep or flattened down to primitives
+fn run_dot(
+    args: &[String],
+    lib_dirs: &[PathBuf],
+    scope: &str,
+    view: DotView,
+    output: Option<&Path>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let (files, entities): (Vec<&String>, Vec<&String>) = args.iter().partition(|a| a.ends_with(".hdl"));
+    let [top] = entities[..] else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let design = load_files(&files, &[top], lib_dirs)?;
+    let netlist = elaborate(&design, top).map_err(|e| format!("{}: {}", top, e))?;
+    let text = netlist.to_dot(scope, view)?;
+    match output {
+        Some(path) => fs::write(path, text)?,
+        None => print!("{}", text),
     }
-    if t.starts_with("x\"") && t.ends_with('"') {
-        let inner = &t[2..t.len() - 1];
-        return Ok(BitVec::from_hex_msb(inner)?);
+    Ok(true)
+}
+
+/// `lint [files] [entities]`: elaboration errors, then warnings, of the
+/// architectures of the files and of the named entities, as
+/// `file:line:col: code message`; false if any
+fn run_lint(args: &[String], lib_dirs: &[PathBuf]) -> Result<bool, Box<dyn std::error::Error>> {
+    let (files, entities): (Vec<&String>, Vec<&String>) = args.iter().partition(|a| a.ends_with(".hdl"));
+    // Entity linted and the file its architecture comes from
+    let mut units: Vec<(String, String)> = Vec::new();
+    for file in &files {
+        let src = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+        let design = parse_str(&src).map_err(|e| format!("{}: {}", file, e))?;
+        units.extend(design.architectures.into_iter().map(|a| (a.entity, file.to_string())));
     }
-    if t.starts_with("0x") || t.starts_with("0X") {
-        let inner = &t[2..];
-        return Ok(BitVec::from_hex_msb(inner)?);
+    let mut search = SearchPath::new(lib_dirs.to_vec());
+    for entity in &entities {
+        let origin = match find_unit(entity, &mut search)? {
+            Some((file, unit)) if unit.architectures.iter().any(|a| &a.entity == *entity) => file,
+            _ => entity.to_string(),
+        };
+        units.push((entity.to_string(), origin));
     }
-    if t.starts_with("0b") || t.starts_with("0B") {
-        let inner = &t[2..];
-        return Ok(BitVec::from_bits_msb(inner)?);
+    let roots: Vec<&str> = units.iter().map(|(entity, _)| entity.as_str()).collect();
+    let design = load_files(&files, &roots, lib_dirs)?;
+    let mut clean = true;
+    // An error in a child shared by several units is reported once
+    let mut reported = HashSet::new();
+    for (entity, origin) in &units {
+        let Err(err) = elaborate(&design, entity) else {
+            continue;
+        };
+        clean = false;
+        if !reported.insert(err.to_string()) {
+            continue;
+        }
+        let code = classify(&err.message).map_or("error:", |code| code.as_str());
+        match err.span {
+            Some(span) => println!("{}:{}:{}: {} {}", origin, span.line, span.col, code, err.message),
+            None => println!("{}: {} {}", origin, code, err.message),
+        }
     }
-    if t == "0" || t == "1" {
-        return Ok(BitVec::new(1, if t == "1" { 1 } else { 0 }));
+    for (entity, origin) in &units {
+        let arch = design
+            .architectures
+            .iter()
+            .find(|a| &a.entity == entity)
+            .ok_or_else(|| format!("missing architecture for {}", entity))?;
+        for lint in lint_architecture(&design, arch) {
+            println!("{}:{}:{}: {} {}", origin, lint.span.line, lint.span.col, lint.code.as_str(), lint.message);
+            clean = false;
+        }
+    }
+    Ok(clean)
+}
+
+/// Parses the files given on the command line, then looks up the entities
+/// and packages they lack in the `--lib` dirs
+fn load_files(files: &[&String], roots: &[&str], lib_dirs: &[PathBuf]) -> Result<Design, Box<dyn std::error::Error>> {
+    let files: Vec<PathBuf> = files.iter().map(PathBuf::from).collect();
+    assemble(&files, Path::new("."), roots, SearchPath::new(lib_dirs.to_vec()))
+}
+
+fn hex(value: &BitVec) -> String {
+    let digits = value.width().div_ceil(4).max(1);
+    let text: String = (0..digits)
+        .rev()
+        .map(|d| {
+            let nibble = value.slice(d * 4, 4).to_u64_trunc() as u32;
+            char::from_digit(nibble, 16).unwrap_or('0').to_ascii_uppercase()
+        })
+        .collect();
+    format!("0x{}", text)
+}
+
+/// Loads the design named by the script, runs it against its `.cmp` table
+/// and writes the VCD trace and the `.out` table it produced
+fn run_test_file(test: &Path, lib_dirs: &[PathBuf]) -> Result<TestResult, Box<dyn std::error::Error>> {
+    let script = fs::read_to_string(test)?;
+    let files = script_files(&script)?;
+    let design = load_design(test, &files, lib_dirs)?;
+    let base = test_dir(test);
+    let compare = match &files.compare_to {
+        Some(file) => {
+            let path = resolve(base, file);
+            Some(fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?)
+        }
+        None => None,
+    };
+    let mut inputs = HashMap::new();
+    for file in &files.inputs {
+        let path = resolve(base, file);
+        let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        inputs.insert(file.clone(), bytes);
+    }
+    let options = TestOptions {
+        compare,
+        files: inputs,
+        ..TestOptions::default()
+    };
+    let result = run_test_design(&design, &script, &options)?;
+    if let (Some(path), Some(vcd)) = (&result.vcd_file, &result.vcd) {
+        fs::write(path, vcd)?;
+    }
+    for (file, bytes) in &result.dumps {
+        fs::write(base.join(file), bytes)?;
+    }
+    if let Some(output) = &result.output {
+        // Next to the test, like the nand2tetris tools
+        let path = match &files.output_file {
+            Some(file) => base.join(file),
+            None => test.with_extension("out"),
+        };
+        fs::write(path, output)?;
+    }
+    Ok(result)
+}
+
+/// Directory of a test file, `.` for a bare file name
+fn test_dir(test: &Path) -> &Path {
+    match test.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Paths are relative to the working directory, or else to the test
+fn resolve(base: &Path, file: &str) -> PathBuf {
+    let path = PathBuf::from(file);
+    if path.exists() {
+        path
+    } else {
+        base.join(path)
+    }
+}
+
+/// Assembles the files listed on the `load` line, then the entities the
+/// design lacks from the `--lib` dirs (or the test's own directory, without
+/// its subdirectories)
+fn load_design(test: &Path, script: &ScriptFiles, lib_dirs: &[PathBuf]) -> Result<Design, Box<dyn std::error::Error>> {
+    let base = test_dir(test);
+    let files: Vec<PathBuf> = script.load.iter().map(PathBuf::from).collect();
+    let search = if lib_dirs.is_empty() {
+        SearchPath::flat(vec![base.to_path_buf()])
+    } else {
+        SearchPath::new(lib_dirs.to_vec())
+    };
+    let roots: Vec<&str> = [script.top.as_str()].into_iter().filter(|t| !t.is_empty()).collect();
+    assemble(&files, base, &roots, search)
+}
+
+/// Parses `files` (relative to the working directory, or else to `base`),
+/// then loads the `roots` and the entities and packages still missing
+/// from the search path
+fn assemble(
+    files: &[PathBuf],
+    base: &Path,
+    roots: &[&str],
+    mut search: SearchPath,
+) -> Result<Design, Box<dyn std::error::Error>> {
+    let mut design = Design::default();
+    for file in files {
+        let path = resolve(base, &file.to_string_lossy());
+        let src = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let d = parse_str(&src).map_err(|e| format!("{}: {}", path.display(), e))?;
+        design.append(d);
+    }
+    resolve_units(&mut design, roots, &mut search)?;
+    Ok(design)
+}
+
+/// A .tst file, every .tst under a directory, or the files matching a glob
+fn collect_tests(target: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut tests = Vec::new();
+    if target.contains(['*', '?']) {
+        for path in glob(target)? {
+            if path.is_dir() {
+                collect_files(&path, "tst", &mut tests)?;
+            } else if path.extension().is_some_and(|e| e == "tst") {
+                tests.push(path);
+            }
+        }
+    } else {
+        let path = PathBuf::from(target);
+        if path.is_dir() {
+            collect_files(&path, "tst", &mut tests)?;
+        } else {
+            tests.push(path);
+        }
+    }
+    Ok(tests)
+}
+
+/// Files with the given extension under `dir`, recursively, in path order
+fn collect_files(dir: &Path, ext: &str, out: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_files(&path, ext, out)?;
+        } else if path.extension().is_some_and(|e| e == ext) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Expands `*` and `?` in each path component, e.g. `hdl_tests/0*/*.tst`
+fn glob(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let absolute = pattern.starts_with('/');
+    let mut paths = vec![PathBuf::from(if absolute { "/" } else { "" })];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for path in &paths {
+            if !component.contains(['*', '?']) {
+                next.push(path.join(component));
+                continue;
+            }
+            let dir = if path.as_os_str().is_empty() { Path::new(".") } else { path.as_path() };
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut matches: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| wildcard_match(component, &entry.file_name().to_string_lossy()))
+                .map(|entry| path.join(entry.file_name()))
+                .collect();
+            matches.sort();
+            next.extend(matches);
+        }
+        paths = next;
+    }
+    Ok(paths.into_iter().filter(|p| p.exists()).collect())
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    // Classic backtracking on the last `*`
+    let (mut pi, mut ni) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
     }
-    let val: i64 = t.parse()?;
-    Ok(BitVec::from_i64(32, val))
+    p[pi..].iter().all(|&c| c == '*')
 }
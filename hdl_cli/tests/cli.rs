@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const NOT_GATE: &str = "entity NotGate is
+  port(a : in bit; y : out bit);
+end entity;
+
+architecture rtl of NotGate is
+begin
+  y <= not a;
+end architecture;
+";
+
+/// Empty scratch directory for one test
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hdl_cli_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(dir: &Path, file: &str, text: &str) {
+    let path = dir.join(file);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, text).unwrap();
+}
+
+fn hdl_cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hdl_cli")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_pass_and_fail_exit_status() {
+    let dir = scratch("status");
+    write(&dir, "NotGate.hdl", NOT_GATE);
+    write(&dir, "pass.tst", "load NotGate\nset a 0\neval\nexpect y 1\n");
+    write(&dir, "fail.tst", "load NotGate\nset a 0\neval\nexpect y 0\n");
+
+    let pass = hdl_cli(&[dir.join("pass.tst").to_str().unwrap()]);
+    assert_eq!(pass.status.code(), Some(0));
+    assert!(stdout(&pass).starts_with("PASS "));
+
+    let fail = hdl_cli(&[dir.join("fail.tst").to_str().unwrap()]);
+    assert_eq!(fail.status.code(), Some(1));
+    assert!(stdout(&fail).starts_with("FAIL "));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_error_exit_status() {
+    let dir = scratch("error");
+    write(&dir, "missing.tst", "load Missing\neval\n");
+    let missing = hdl_cli(&[dir.join("missing.tst").to_str().unwrap()]);
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(stdout(&missing).contains("missing.tst: "));
+
+    let unmatched = hdl_cli(&[dir.join("*.none").to_str().unwrap()]);
+    assert_eq!(unmatched.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&unmatched.stderr).contains("no .tst file matches"));
+
+    assert_eq!(hdl_cli(&[]).status.code(), Some(2));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_batch_glob_and_summary() {
+    let dir = scratch("batch");
+    write(&dir, "NotGate.hdl", NOT_GATE);
+    write(&dir, "a/one.tst", "load NotGate.hdl\nset a 1\neval\nexpect y 0\n");
+    write(&dir, "b/two.tst", "load NotGate.hdl\nset a 1\neval\nexpect y 1\n");
+    write(&dir, "b/skip.txt", "");
+
+    // Tests name their files relative to the working directory
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_hdl_cli")).current_dir(&dir).args(args).output().unwrap();
+    let glob = run(&["*/*.tst"]);
+    assert_eq!(glob.status.code(), Some(1));
+    let text = stdout(&glob);
+    assert!(text.contains("PASS a/one.tst (1/1)"), "{}", text);
+    assert!(text.contains("FAIL b/two.tst (0/1)"), "{}", text);
+    assert!(text.ends_with("\n1 passed, 1 failed, 2 total\n"), "{}", text);
+
+    let folder = run(&["a"]);
+    assert_eq!(folder.status.code(), Some(0));
+    assert_eq!(stdout(&folder), "PASS a/one.tst (1/1)\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_search_path_without_lib_is_the_test_directory() {
+    let dir = scratch("search");
+    write(&dir, "nested/NotGate.hdl", NOT_GATE);
+    write(&dir, "not.tst", "load NotGate\nset a 0\neval\nexpect y 1\n");
+    let test = dir.join("not.tst");
+
+    let flat = hdl_cli(&[test.to_str().unwrap()]);
+    assert_eq!(flat.status.code(), Some(1));
+    assert!(stdout(&flat).ends_with("not.tst: unknown entity NotGate\n"), "{}", stdout(&flat));
+
+    // A bare file name searches the working directory
+    write(&dir, "NotGate.hdl", NOT_GATE);
+    let bare = Command::new(env!("CARGO_BIN_EXE_hdl_cli")).current_dir(&dir).arg("not.tst").output().unwrap();
+    assert_eq!(stdout(&bare), "PASS not.tst (1/1)\n");
+
+    let lib = hdl_cli(&["--lib", dir.to_str().unwrap(), test.to_str().unwrap()]);
+    assert_eq!(lib.status.code(), Some(0), "{}", stdout(&lib));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_lint_reports_errors_then_warnings() {
+    let dir = scratch("lint");
+    write(
+        &dir,
+        "lib/Unclocked.hdl",
+        "entity Unclocked is
+  port(clk : in bit; d : in bit; q : out bit);
+end entity;
+
+architecture rtl of Unclocked is
+  signal dead : bit;
+begin
+  process(clk)
+  begin
+    q <= d;
+  end process;
+end architecture;
+",
+    );
+    let lib = dir.join("lib");
+    let path = lib.join("Unclocked.hdl");
+
+    // An entity is reported under the file the library found it in
+    let lint = hdl_cli(&["lint", "--lib", lib.to_str().unwrap(), "Unclocked"]);
+    assert_eq!(lint.status.code(), Some(1));
+    assert_eq!(
+        stdout(&lint),
+        format!(
+            "{0}: E602 process must start with a rising_edge or falling_edge guard\n\
+             {0}:6:3: W901 signal dead is never used\n",
+            path.display()
+        )
+    );
+
+    write(&dir, "NotGate.hdl", NOT_GATE);
+    let clean = hdl_cli(&["lint", dir.join("NotGate.hdl").to_str().unwrap()]);
+    assert_eq!(clean.status.code(), Some(0));
+    assert_eq!(stdout(&clean), "");
+    fs::remove_dir_all(dir).unwrap();
+}
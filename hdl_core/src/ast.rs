@@ -1,14 +1,49 @@
 use crate::error::Span;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Design {
     pub entities: Vec<Entity>,
     pub architectures: Vec<Architecture>,
+    pub packages: Vec<Package>,
+}
+
+impl Design {
+    /// Adds the units of another source file
+    pub fn append(&mut self, other: Design) {
+        self.entities.extend(other.entities);
+        self.architectures.extend(other.architectures);
+        self.packages.extend(other.packages);
+    }
+}
+
+/// `package name is ... end package;`: constants, types and components
+/// shared by the units that `use` it
+#[derive(Clone, Debug)]
+pub struct Package {
+    pub name: String,
+    pub uses: Vec<UseClause>,
+    pub types: Vec<TypeDecl>,
+    pub constants: Vec<ConstantDecl>,
+    pub components: Vec<ComponentDecl>,
+    pub span: Option<Span>,
+}
+
+/// `use work.pkg.all;` or `use work.pkg.NAME;`
+#[derive(Clone, Debug)]
+pub struct UseClause {
+    pub library: String,
+    pub package: String,
+    /// `None` for `.all`
+    pub item: Option<String>,
+    pub span: Option<Span>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Entity {
     pub name: String,
+    /// Context clauses before the entity; they also apply to its architecture
+    pub uses: Vec<UseClause>,
+    pub generics: Vec<Generic>,
     pub ports: Vec<Port>,
     pub span: Option<Span>,
 }
@@ -21,6 +56,14 @@ pub struct Port {
     pub span: Option<Span>,
 }
 
+/// Integer generic: `N : integer := 32`
+#[derive(Clone, Debug)]
+pub struct Generic {
+    pub name: String,
+    pub default: Option<IntExpr>,
+    pub span: Option<Span>,
+}
+
 #[derive(Clone, Debug)]
 pub enum Direction {
     In,
@@ -31,10 +74,39 @@ pub enum Direction {
 pub enum Type {
     Bit,
     Bits {
-        msb: i64,
-        lsb: i64,
+        msb: IntExpr,
+        lsb: IntExpr,
         dir: RangeDir,
     },
+    /// `integer range 0 to 15`; a plain `integer` spans 32-bit signed values
+    Integer {
+        low: IntExpr,
+        high: IntExpr,
+    },
+    /// Enumeration or array declared by a `type ... is` of the architecture
+    /// or of a used package
+    Named(String),
+}
+
+/// Constant integer expression (ranges, indices, generics), evaluated at elaboration
+#[derive(Clone, Debug)]
+pub enum IntExpr {
+    Lit(i64),
+    Name(String),
+    Neg(Box<IntExpr>),
+    Binary {
+        op: IntOp,
+        left: Box<IntExpr>,
+        right: Box<IntExpr>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
 }
 
 #[derive(Clone, Debug)]
@@ -47,12 +119,52 @@ pub enum RangeDir {
 pub struct Architecture {
     pub name: String,
     pub entity: String,
+    pub uses: Vec<UseClause>,
+    pub types: Vec<TypeDecl>,
+    pub constants: Vec<ConstantDecl>,
     pub signals: Vec<SignalDecl>,
     pub components: Vec<ComponentDecl>,
     pub stmts: Vec<ConcurrentStmt>,
     pub span: Option<Span>,
 }
 
+#[derive(Clone, Debug)]
+pub struct TypeDecl {
+    pub name: String,
+    pub def: TypeDef,
+    pub span: Option<Span>,
+}
+
+#[derive(Clone, Debug)]
+pub enum TypeDef {
+    /// `type state_t is (IDLE, FETCH, EXEC);`, literals encoded 0, 1, 2...
+    Enum(Vec<String>),
+    /// `type regs_t is array (0 to 15) of bits(31 downto 0);`
+    Array {
+        left: IntExpr,
+        right: IntExpr,
+        dir: RangeDir,
+        elem: Box<Type>,
+    },
+}
+
+/// `constant OP_ADD : bits(3 downto 0) := b"0011";`
+#[derive(Clone, Debug)]
+pub struct ConstantDecl {
+    pub name: String,
+    pub ty: Type,
+    pub value: ConstantValue,
+    pub span: Option<Span>,
+}
+
+/// Integer constants take a constant integer expression, so they can size
+/// ranges like generics do
+#[derive(Clone, Debug)]
+pub enum ConstantValue {
+    Int(IntExpr),
+    Expr(Expr),
+}
+
 #[derive(Clone, Debug)]
 pub struct SignalDecl {
     pub names: Vec<String>,
@@ -64,6 +176,7 @@ pub struct SignalDecl {
 #[derive(Clone, Debug)]
 pub struct ComponentDecl {
     pub name: String,
+    pub generics: Vec<Generic>,
     pub ports: Vec<Port>,
     pub span: Option<Span>,
 }
@@ -73,6 +186,7 @@ pub enum ConcurrentStmt {
     Assign(AssignStmt),
     Process(ProcessStmt),
     Instance(InstanceStmt),
+    Generate(GenerateStmt),
 }
 
 #[derive(Clone, Debug)]
@@ -84,7 +198,8 @@ pub struct AssignStmt {
 
 #[derive(Clone, Debug)]
 pub struct ProcessStmt {
-    pub clk: String,
+    /// `process(clk)` or `process(clk, rst)` for an asynchronous reset
+    pub sensitivity: Vec<String>,
     pub stmts: Vec<SeqStmt>,
     pub span: Option<Span>,
 }
@@ -93,10 +208,43 @@ pub struct ProcessStmt {
 pub struct InstanceStmt {
     pub name: String,
     pub entity: String,
+    pub generic_map: Vec<GenericAssoc>,
     pub port_map: Vec<Assoc>,
     pub span: Option<Span>,
 }
 
+#[derive(Clone, Debug)]
+pub struct GenericAssoc {
+    pub name: String,
+    pub value: IntExpr,
+    pub span: Option<Span>,
+}
+
+/// `label: for i in 0 to N-1 generate ... end generate;`
+/// or `label: if N > 1 generate ... end generate;`
+#[derive(Clone, Debug)]
+pub struct GenerateStmt {
+    pub label: String,
+    pub scheme: GenerateScheme,
+    pub stmts: Vec<ConcurrentStmt>,
+    pub span: Option<Span>,
+}
+
+#[derive(Clone, Debug)]
+pub enum GenerateScheme {
+    For {
+        var: String,
+        left: IntExpr,
+        right: IntExpr,
+        dir: RangeDir,
+    },
+    If {
+        op: BinaryOp,
+        left: IntExpr,
+        right: IntExpr,
+    },
+}
+
 #[derive(Clone, Debug)]
 pub struct Assoc {
     pub port: String,
@@ -137,10 +285,23 @@ pub enum CaseChoice {
 #[derive(Clone, Debug)]
 pub struct Target {
     pub name: String,
-    pub sel: Option<Selector>,
+    pub sel: Option<SelectorExpr>,
     pub span: Option<Span>,
 }
 
+/// Selection as written in the source; resolved to a `Selector` at elaboration
+#[derive(Clone, Debug)]
+pub enum SelectorExpr {
+    Index(IntExpr),
+    /// Run-time index of an array element (`regs(ir(3 downto 0))`)
+    Expr(Box<Expr>),
+    Range {
+        msb: IntExpr,
+        lsb: IntExpr,
+        dir: RangeDir,
+    },
+}
+
 #[derive(Clone, Debug)]
 pub enum Selector {
     Index(i64),
@@ -154,6 +315,13 @@ pub enum Expr {
     Unary { op: UnaryOp, expr: Box<Expr> },
     Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
     Call { name: String, args: Vec<Expr> },
+    /// `value when cond else otherwise`, only as the value of an assignment;
+    /// `otherwise` may be another `When`
+    When { value: Box<Expr>, cond: Box<Expr>, otherwise: Box<Expr> },
+    /// `(msb downto lsb => value)`: the bit `value` copied on each position of
+    /// the range; `range` is `None` for `(others => value)`, which fills the
+    /// whole assignment target
+    Aggregate { range: Option<(IntExpr, IntExpr, RangeDir)>, value: Box<Expr> },
 }
 
 #[derive(Clone, Debug)]
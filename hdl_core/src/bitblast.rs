@@ -0,0 +1,405 @@
+//! Évaluation bit à bit d'un netlist : chaque bit de chaque signal devient
+//! un noeud d'une logique booléenne (BDD pour l'équivalence, portes pour
+//! l'export), avec les règles de largeur et d'extension du simulateur.
+
+use crate::ast::{BinaryOp, Selector, UnaryOp};
+use crate::elab::{ExprRef, Netlist, PrimitiveNet, SignalType, TargetRef};
+use crate::error::Error;
+use crate::graph::{CombGraph, CombNode};
+use crate::value::ValueKind;
+
+/// Noeuds constants, communs à toutes les logiques
+pub(crate) const FALSE: u32 = 0;
+pub(crate) const TRUE: u32 = 1;
+
+pub(crate) fn constant(bit: bool) -> u32 {
+    if bit {
+        TRUE
+    } else {
+        FALSE
+    }
+}
+
+/// Opérations booléennes sur des noeuds ; `FALSE` et `TRUE` sont les
+/// constantes
+pub(crate) trait BitLogic {
+    fn not(&mut self, f: u32) -> Result<u32, Error>;
+    fn and(&mut self, f: u32, g: u32) -> Result<u32, Error>;
+    fn or(&mut self, f: u32, g: u32) -> Result<u32, Error>;
+    fn xor(&mut self, f: u32, g: u32) -> Result<u32, Error>;
+    /// Si-alors-sinon : `f ? g : h`
+    fn ite(&mut self, f: u32, g: u32, h: u32) -> Result<u32, Error>;
+}
+
+/// Valeur symbolique : un noeud par bit (lsb d'abord) et le type de
+/// valeur qui décide de l'extension, comme dans le simulateur
+pub(crate) struct Sym {
+    pub bits: Vec<u32>,
+    pub kind: ValueKind,
+}
+
+impl Sym {
+    pub fn new(bits: Vec<u32>, kind: ValueKind) -> Self {
+        Sym { bits, kind }
+    }
+}
+
+pub(crate) fn resize_zero(bits: &[u32], width: usize) -> Vec<u32> {
+    (0..width).map(|i| bits.get(i).copied().unwrap_or(FALSE)).collect()
+}
+
+pub(crate) fn resize_sign(bits: &[u32], width: usize) -> Vec<u32> {
+    let sign = bits.last().copied().unwrap_or(FALSE);
+    (0..width).map(|i| bits.get(i).copied().unwrap_or(sign)).collect()
+}
+
+/// Extension d'une valeur à la largeur d'une cible (`value_to_width`)
+pub(crate) fn to_width(value: &Sym, width: usize) -> Vec<u32> {
+    match value.kind {
+        ValueKind::Arithmetic => resize_sign(&value.bits, width),
+        _ => resize_zero(&value.bits, width),
+    }
+}
+
+/// Évaluation symbolique d'un netlist combinatoire, noeud par noeud dans
+/// l'ordre topologique du graphe
+pub(crate) struct SymEval<'a, L: BitLogic> {
+    pub netlist: &'a Netlist,
+    pub logic: &'a mut L,
+    /// Noeud de chaque bit de chaque signal, None tant qu'il n'est pas calculé
+    pub bits: Vec<Vec<Option<u32>>>,
+}
+
+impl<'a, L: BitLogic> SymEval<'a, L> {
+    pub fn new(netlist: &'a Netlist, logic: &'a mut L) -> Self {
+        let bits = netlist.signals.iter().map(|s| vec![None; s.width]).collect();
+        SymEval { netlist, logic, bits }
+    }
+
+    /// Calcule tous les noeuds combinatoires ; les bits déjà fixés (entrées,
+    /// sorties de registres) sont gardés, les autres bits sans pilote
+    /// prennent leur valeur initiale
+    pub fn run(&mut self) -> Result<(), Error> {
+        let graph = CombGraph::build(self.netlist);
+        let mut driven: Vec<Vec<bool>> = self.netlist.signals.iter().map(|s| vec![false; s.width]).collect();
+        for span in &graph.writes {
+            let bits = &mut driven[span.signal];
+            let hi = span.hi.min(bits.len().saturating_sub(1));
+            bits.iter_mut().take(hi + 1).skip(span.lo).for_each(|b| *b = true);
+        }
+        for (sig, signal) in self.netlist.signals.iter().enumerate() {
+            for (bit, &is_driven) in driven[sig].iter().enumerate() {
+                if !is_driven && self.bits[sig][bit].is_none() {
+                    self.bits[sig][bit] = Some(constant(signal.value.get(bit) == 1));
+                }
+            }
+        }
+        for &node in &graph.order {
+            let (target, value) = match graph.nodes[node] {
+                CombNode::Assign(i) => {
+                    let assign = &self.netlist.assigns[i];
+                    (&assign.target, self.expr(&assign.expr)?)
+                }
+                CombNode::Primitive(i) => self.primitive(&self.netlist.primitives[i])?,
+            };
+            self.apply(target, &value)?;
+        }
+        Ok(())
+    }
+
+    pub fn signal(&self, sig: usize) -> Result<Vec<u32>, Error> {
+        self.bit_range(sig, 0, self.bits[sig].len())
+    }
+
+    fn bit_range(&self, sig: usize, lo: usize, end: usize) -> Result<Vec<u32>, Error> {
+        self.bits[sig][lo..end]
+            .iter()
+            .map(|b| b.ok_or_else(|| Error::new(format!("{} read before it is computed", self.netlist.signals[sig].name))))
+            .collect()
+    }
+
+    pub fn positions(&self, sig: usize, sel: &Selector) -> Result<(usize, usize), Error> {
+        let signal = &self.netlist.signals[sig];
+        let min = signal.lsb.min(signal.msb);
+        let max = signal.lsb.max(signal.msb);
+        let pos = |idx: i64| {
+            if idx < min || idx > max {
+                Err(Error::new("index out of range"))
+            } else {
+                Ok((idx - min) as usize)
+            }
+        };
+        match sel {
+            Selector::Index(i) => Ok((pos(*i)?, pos(*i)?)),
+            Selector::Range { msb, lsb, .. } => Ok((pos((*msb).min(*lsb))?, pos((*msb).max(*lsb))?)),
+        }
+    }
+
+    pub fn read(&self, target: &TargetRef) -> Result<Vec<u32>, Error> {
+        match &target.sel {
+            None => self.signal(target.signal),
+            Some(sel) => {
+                let (lo, hi) = self.positions(target.signal, sel)?;
+                self.bit_range(target.signal, lo, hi + 1)
+            }
+        }
+    }
+
+    pub fn apply(&mut self, target: &TargetRef, value: &Sym) -> Result<(), Error> {
+        let width = self.netlist.signals[target.signal].width;
+        let bits = to_width(value, width);
+        let (lo, hi) = match &target.sel {
+            None => (0, width.saturating_sub(1)),
+            Some(sel) => self.positions(target.signal, sel)?,
+        };
+        for (i, bit) in (lo..=hi).zip(bits) {
+            if i < width {
+                self.bits[target.signal][i] = Some(bit);
+            }
+        }
+        Ok(())
+    }
+
+    fn primitive(&mut self, prim: &'a PrimitiveNet) -> Result<(&'a TargetRef, Sym), Error> {
+        let bitwise = |bits| Sym::new(bits, ValueKind::Bitwise);
+        Ok(match prim {
+            PrimitiveNet::Nand2 { a, b, y } => {
+                let and = self.binary(BinaryOp::And, a, b)?;
+                (y, bitwise(self.map(&and.bits, |l, x| l.not(x))?))
+            }
+            PrimitiveNet::Not1 { a, y } => {
+                let a = self.expr(a)?;
+                (y, bitwise(self.map(&a.bits, |l, x| l.not(x))?))
+            }
+            PrimitiveNet::And2 { a, b, y } => (y, self.binary(BinaryOp::And, a, b)?),
+            PrimitiveNet::Or2 { a, b, y } => (y, self.binary(BinaryOp::Or, a, b)?),
+            PrimitiveNet::Xor2 { a, b, y } => (y, self.binary(BinaryOp::Xor, a, b)?),
+            PrimitiveNet::Mux2 { a, b, sel, y } => {
+                let sel = self.expr(sel)?;
+                let cond = self.is_true(&sel.bits)?;
+                let (a, b) = (self.expr(a)?, self.expr(b)?);
+                let width = a.bits.len().max(b.bits.len());
+                let (a, b) = (resize_zero(&a.bits, width), resize_zero(&b.bits, width));
+                let mut out = Vec::with_capacity(width);
+                for (x, y) in a.into_iter().zip(b) {
+                    out.push(self.logic.ite(cond, y, x)?);
+                }
+                (y, bitwise(out))
+            }
+            PrimitiveNet::Dff { .. } => return Err(Error::new("dff is not combinational")),
+            PrimitiveNet::Ram { .. } | PrimitiveNet::Rom { .. } => {
+                return Err(Error::new("memories (ram, rom) cannot be reduced to gates"));
+            }
+        })
+    }
+
+    fn map(&mut self, bits: &[u32], mut f: impl FnMut(&mut L, u32) -> Result<u32, Error>) -> Result<Vec<u32>, Error> {
+        bits.iter().map(|&b| f(self.logic, b)).collect()
+    }
+
+    fn zip(
+        &mut self,
+        a: &[u32],
+        b: &[u32],
+        mut f: impl FnMut(&mut L, u32, u32) -> Result<u32, Error>,
+    ) -> Result<Vec<u32>, Error> {
+        a.iter().zip(b).map(|(&x, &y)| f(self.logic, x, y)).collect()
+    }
+
+    /// Vrai si un des 64 bits de poids faible est à 1 (`value_is_true`)
+    pub fn is_true(&mut self, bits: &[u32]) -> Result<u32, Error> {
+        let mut any = FALSE;
+        for &bit in bits.iter().take(64) {
+            any = self.logic.or(any, bit)?;
+        }
+        Ok(any)
+    }
+
+    fn add(&mut self, a: &[u32], b: &[u32], mut carry: u32) -> Result<Vec<u32>, Error> {
+        let mut out = Vec::with_capacity(a.len());
+        for (&x, &y) in a.iter().zip(b) {
+            let half = self.logic.xor(x, y)?;
+            out.push(self.logic.xor(half, carry)?);
+            let both = self.logic.and(x, y)?;
+            let propagate = self.logic.and(half, carry)?;
+            carry = self.logic.or(both, propagate)?;
+        }
+        Ok(out)
+    }
+
+    fn sub(&mut self, a: &[u32], b: &[u32]) -> Result<Vec<u32>, Error> {
+        let nb = self.map(b, |l, x| l.not(x))?;
+        self.add(a, &nb, TRUE)
+    }
+
+    pub fn equal(&mut self, a: &[u32], b: &[u32]) -> Result<u32, Error> {
+        let mut eq = TRUE;
+        for (&x, &y) in a.iter().zip(b) {
+            let diff = self.logic.xor(x, y)?;
+            let same = self.logic.not(diff)?;
+            eq = self.logic.and(eq, same)?;
+        }
+        Ok(eq)
+    }
+
+    /// a < b en complément à 2, après extension de signe à la même largeur
+    fn less_signed(&mut self, a: &[u32], b: &[u32]) -> Result<u32, Error> {
+        let width = a.len().max(b.len());
+        if width == 0 {
+            return Ok(FALSE);
+        }
+        let (a, b) = (resize_sign(a, width), resize_sign(b, width));
+        // Comparaison non signée du lsb vers le msb
+        let mut lt = FALSE;
+        for (&x, &y) in a.iter().zip(&b).take(width - 1) {
+            let nx = self.logic.not(x)?;
+            let strictly = self.logic.and(nx, y)?;
+            let diff = self.logic.xor(x, y)?;
+            lt = self.logic.ite(diff, strictly, lt)?;
+        }
+        // Bit de signe : a négatif et b positif suffit
+        let (sa, sb) = (a[width - 1], b[width - 1]);
+        let nsb = self.logic.not(sb)?;
+        let neg_pos = self.logic.and(sa, nsb)?;
+        let diff = self.logic.xor(sa, sb)?;
+        self.logic.ite(diff, neg_pos, lt)
+    }
+
+    /// Décalage par une quantité symbolique (64 bits de poids faible)
+    fn shift(&mut self, bits: &[u32], count: &[u32], left: bool) -> Result<Vec<u32>, Error> {
+        let width = bits.len();
+        let mut out = bits.to_vec();
+        for (stage, &c) in count.iter().enumerate().take(64) {
+            let amount = 1usize.checked_shl(stage as u32).unwrap_or(usize::MAX);
+            let shifted: Vec<u32> = (0..width)
+                .map(|i| {
+                    let src = if left { i.checked_sub(amount) } else { i.checked_add(amount).filter(|&s| s < width) };
+                    src.map_or(FALSE, |s| out[s])
+                })
+                .collect();
+            for i in 0..width {
+                out[i] = self.logic.ite(c, shifted[i], out[i])?;
+            }
+        }
+        Ok(out)
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: &ExprRef, right: &ExprRef) -> Result<Sym, Error> {
+        let (l, r) = (self.expr(left)?, self.expr(right)?);
+        let width = l.bits.len().max(r.bits.len());
+        let bit = |b: u32| Sym::new(vec![b], ValueKind::Literal);
+        Ok(match op {
+            BinaryOp::Add | BinaryOp::Sub => {
+                let (a, b) = (resize_sign(&l.bits, width), resize_sign(&r.bits, width));
+                let bits = if matches!(op, BinaryOp::Add) { self.add(&a, &b, FALSE)? } else { self.sub(&a, &b)? };
+                Sym::new(bits, ValueKind::Arithmetic)
+            }
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => {
+                let (a, b) = (resize_zero(&l.bits, width), resize_zero(&r.bits, width));
+                let bits = match op {
+                    BinaryOp::And => self.zip(&a, &b, |l, x, y| l.and(x, y))?,
+                    BinaryOp::Or => self.zip(&a, &b, |l, x, y| l.or(x, y))?,
+                    _ => self.zip(&a, &b, |l, x, y| l.xor(x, y))?,
+                };
+                Sym::new(bits, ValueKind::Bitwise)
+            }
+            BinaryOp::Concat => {
+                let mut bits = r.bits;
+                bits.extend(l.bits);
+                Sym::new(bits, ValueKind::Bitwise)
+            }
+            BinaryOp::Shl | BinaryOp::Shr => {
+                let bits = self.shift(&l.bits, &r.bits, matches!(op, BinaryOp::Shl))?;
+                Sym::new(bits, ValueKind::Bitwise)
+            }
+            BinaryOp::Eq | BinaryOp::Ne => {
+                let (a, b) = (to_width(&l, width), to_width(&r, width));
+                let eq = self.equal(&a, &b)?;
+                bit(if matches!(op, BinaryOp::Eq) { eq } else { self.logic.not(eq)? })
+            }
+            BinaryOp::Lt => bit(self.less_signed(&l.bits, &r.bits)?),
+            BinaryOp::Gt => bit(self.less_signed(&r.bits, &l.bits)?),
+            BinaryOp::Le => {
+                let gt = self.less_signed(&r.bits, &l.bits)?;
+                bit(self.logic.not(gt)?)
+            }
+            BinaryOp::Ge => {
+                let lt = self.less_signed(&l.bits, &r.bits)?;
+                bit(self.logic.not(lt)?)
+            }
+        })
+    }
+
+    pub fn expr(&mut self, expr: &ExprRef) -> Result<Sym, Error> {
+        match expr {
+            ExprRef::Literal(v) => Ok(Sym::new(
+                (0..v.bits.width()).map(|i| constant(v.bits.get(i) == 1)).collect(),
+                v.kind,
+            )),
+            ExprRef::Target(t) => Ok(Sym::new(self.read(t)?, ValueKind::Bitwise)),
+            ExprRef::Unary { op, expr } => {
+                let v = self.expr(expr)?;
+                match op {
+                    UnaryOp::Not => Ok(Sym::new(self.map(&v.bits, |l, x| l.not(x))?, ValueKind::Bitwise)),
+                    UnaryOp::Neg => {
+                        let zero = vec![FALSE; v.bits.len()];
+                        Ok(Sym::new(self.sub(&zero, &v.bits)?, ValueKind::Arithmetic))
+                    }
+                }
+            }
+            ExprRef::Binary { op, left, right } => self.binary(*op, left, right),
+            ExprRef::Call { name, args } => {
+                let lower = name.to_ascii_lowercase();
+                if (lower == "resize" || lower == "sresize") && args.len() == 2 {
+                    let value = self.expr(&args[0])?;
+                    let size = self.expr(&args[1])?;
+                    let mut width = 0usize;
+                    for (i, &b) in size.bits.iter().enumerate().take(64) {
+                        match b {
+                            TRUE => width |= 1 << i,
+                            FALSE => {}
+                            _ => return Err(Error::new("resize width must be constant")),
+                        }
+                    }
+                    return Ok(if lower == "sresize" {
+                        Sym::new(resize_sign(&value.bits, width), ValueKind::Arithmetic)
+                    } else {
+                        Sym::new(resize_zero(&value.bits, width), ValueKind::Bitwise)
+                    });
+                }
+                Err(Error::new("unsupported function call"))
+            }
+            ExprRef::Element { array, index } => self.element(*array, index),
+            ExprRef::Select { cond, then, otherwise } => {
+                let cond = self.expr(cond)?;
+                let cond = self.is_true(&cond.bits)?;
+                let (then, otherwise) = (self.expr(then)?, self.expr(otherwise)?);
+                let width = then.bits.len().max(otherwise.bits.len());
+                let kind = if then.kind == otherwise.kind { then.kind } else { ValueKind::Bitwise };
+                let (a, b) = (to_width(&then, width), to_width(&otherwise, width));
+                let bits = self.zip(&a, &b, |l, x, y| l.ite(cond, x, y))?;
+                Ok(Sym::new(bits, kind))
+            }
+        }
+    }
+
+    /// Élément d'un tableau à un indice symbolique : arbre de multiplexeurs
+    /// sur les comparaisons de l'indice à chaque position
+    fn element(&mut self, array: usize, index: &ExprRef) -> Result<Sym, Error> {
+        let SignalType::Array { low, high, elem_width } = self.netlist.signals[array].ty else {
+            return Err(Error::new(format!("{} is not an array", self.netlist.signals[array].name)));
+        };
+        let index = to_width(&self.expr(index)?, 64);
+        let all = self.signal(array)?;
+        let mut out = all[..elem_width].to_vec();
+        for k in low + 1..=high {
+            let key: Vec<u32> = (0..64).map(|i| constant((k >> i) & 1 == 1)).collect();
+            let hit = self.equal(&index, &key)?;
+            let lo = (k - low) as usize * elem_width;
+            for (i, &bit) in all[lo..lo + elem_width].iter().enumerate() {
+                out[i] = self.logic.ite(hit, bit, out[i])?;
+            }
+        }
+        Ok(Sym::new(out, ValueKind::Bitwise))
+    }
+}
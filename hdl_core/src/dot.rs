@@ -0,0 +1,635 @@
+//! Schéma d'un design au format Graphviz DOT.
+//!
+//! Deux vues d'une portée de la hiérarchie (`""` pour le top) :
+//! - un niveau : ports de l'entité, instances filles en boîtes noires,
+//!   affectations et process de l'architecture ;
+//! - aplatie : toutes les primitives, affectations et process sous la
+//!   portée, regroupés par instance dans des `cluster`.
+//!
+//! Un arc relie l'élément qui écrit un signal à chaque élément qui le lit,
+//! étiqueté par le nom local du signal et par le nombre de bits transportés
+//! (`a /4`). Les affectations qui ne font que recopier un signal (liaisons
+//! de ports, `c(0) <= cin`) deviennent de simples arcs.
+
+use crate::ast::{BinaryOp, Direction, UnaryOp};
+use crate::elab::{CaseChoiceRef, ExprRef, Netlist, PrimitiveNet, SeqStmtRef, TargetRef};
+use crate::error::Error;
+use crate::graph::{collect_reads, target_span, BitSpan};
+use std::collections::{BTreeMap, HashSet};
+
+/// Étendue du schéma
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DotView {
+    /// Un seul niveau, les instances filles en boîtes noires
+    Level,
+    /// Toute la sous-hiérarchie jusqu'aux primitives
+    Flat,
+}
+
+#[derive(Clone, Debug)]
+enum Element {
+    Input(String),
+    Output(String),
+    /// Instance d'entité vue comme une boîte noire
+    Instance(usize),
+    Primitive(usize),
+    Assign(usize),
+    Process(usize),
+}
+
+struct Node {
+    element: Element,
+    /// Portée qui contient l'élément (regroupement en `cluster`)
+    scope: usize,
+    reads: Vec<BitSpan>,
+    writes: Vec<BitSpan>,
+    /// Simple recopie, remplacée par des arcs
+    wire: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Arc {
+    from: usize,
+    to: usize,
+    label: String,
+    width: usize,
+}
+
+impl Netlist {
+    /// Schéma DOT de la portée `path` (`""` pour le top)
+    pub fn to_dot(&self, path: &str, view: DotView) -> Result<String, Error> {
+        let root = self
+            .instance(path)
+            .ok_or_else(|| Error::new(format!("unknown instance {}", path)))?;
+        let prefix = match self.instances[root].path.as_str() {
+            "" => String::new(),
+            p => format!("{}/", p),
+        };
+        // Portées dont le contenu est dessiné : la racine, ses blocs generate
+        // et, en vue aplatie, toutes les instances en dessous
+        let mut primitive = vec![false; self.instances.len()];
+        for &id in &self.primitive_instances {
+            primitive[id] = true;
+        }
+        let mut members = vec![false; self.instances.len()];
+        let mut boxes = Vec::new();
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            members[id] = true;
+            for &child in &self.instances[id].children {
+                let node = &self.instances[child];
+                if primitive[child] || node.entity.is_none() || view == DotView::Flat {
+                    if !primitive[child] {
+                        stack.push(child);
+                    }
+                } else {
+                    boxes.push(child);
+                }
+            }
+        }
+
+        let mut nodes = Vec::new();
+        for port in &self.instances[root].ports {
+            let span = self.whole(port.signal);
+            nodes.push(match port.dir {
+                Direction::In => Node {
+                    element: Element::Input(port.name.clone()),
+                    scope: root,
+                    reads: Vec::new(),
+                    writes: vec![span],
+                    wire: false,
+                },
+                Direction::Out => Node {
+                    element: Element::Output(port.name.clone()),
+                    scope: root,
+                    reads: vec![span],
+                    writes: Vec::new(),
+                    wire: false,
+                },
+            });
+        }
+        boxes.sort_unstable();
+        for &id in &boxes {
+            let node = &self.instances[id];
+            let (ins, outs): (Vec<_>, Vec<_>) = node.ports.iter().partition(|p| matches!(p.dir, Direction::In));
+            nodes.push(Node {
+                element: Element::Instance(id),
+                scope: node.parent.unwrap_or(root),
+                reads: ins.iter().map(|p| self.whole(p.signal)).collect(),
+                writes: outs.iter().map(|p| self.whole(p.signal)).collect(),
+                wire: false,
+            });
+        }
+        for (i, prim) in self.primitives.iter().enumerate() {
+            let scope = self.instances[self.primitive_instances[i]].parent.unwrap_or(root);
+            if !members[scope] {
+                continue;
+            }
+            let mut reads = Vec::new();
+            let write = match prim {
+                PrimitiveNet::Nand2 { a, b, y }
+                | PrimitiveNet::And2 { a, b, y }
+                | PrimitiveNet::Or2 { a, b, y }
+                | PrimitiveNet::Xor2 { a, b, y } => {
+                    collect_reads(a, &self.signals, &mut reads);
+                    collect_reads(b, &self.signals, &mut reads);
+                    y
+                }
+                PrimitiveNet::Not1 { a, y } => {
+                    collect_reads(a, &self.signals, &mut reads);
+                    y
+                }
+                PrimitiveNet::Mux2 { a, b, sel, y } => {
+                    for e in [a, b, sel] {
+                        collect_reads(e, &self.signals, &mut reads);
+                    }
+                    y
+                }
+                PrimitiveNet::Dff { clk, d, q } => {
+                    collect_reads(clk, &self.signals, &mut reads);
+                    collect_reads(d, &self.signals, &mut reads);
+                    q
+                }
+                PrimitiveNet::Ram { clk, we, addr, din, dout, .. } => {
+                    for e in [clk, we, addr, din] {
+                        collect_reads(e, &self.signals, &mut reads);
+                    }
+                    dout
+                }
+                PrimitiveNet::Rom { addr, dout, .. } => {
+                    collect_reads(addr, &self.signals, &mut reads);
+                    dout
+                }
+            };
+            nodes.push(Node {
+                element: Element::Primitive(i),
+                scope,
+                reads,
+                writes: vec![target_span(write, &self.signals)],
+                wire: false,
+            });
+        }
+        for (i, assign) in self.assigns.iter().enumerate() {
+            if !members[assign.scope] {
+                continue;
+            }
+            let mut reads = Vec::new();
+            collect_reads(&assign.expr, &self.signals, &mut reads);
+            nodes.push(Node {
+                element: Element::Assign(i),
+                scope: assign.scope,
+                reads,
+                writes: vec![target_span(&assign.target, &self.signals)],
+                wire: matches!(assign.expr, ExprRef::Target(_)),
+            });
+        }
+        for (i, process) in self.processes.iter().enumerate() {
+            if !members[process.scope] {
+                continue;
+            }
+            let mut reads = vec![self.whole(process.clk)];
+            let mut writes = Vec::new();
+            if let Some(reset) = &process.reset {
+                collect_reads(&reset.cond, &self.signals, &mut reads);
+                self.seq_pins(&reset.stmts, &mut reads, &mut writes);
+            }
+            self.seq_pins(&process.stmts, &mut reads, &mut writes);
+            nodes.push(Node {
+                element: Element::Process(i),
+                scope: process.scope,
+                reads,
+                writes,
+                wire: false,
+            });
+        }
+
+        let arcs = self.arcs(&nodes, &prefix);
+        Ok(self.render(&nodes, &arcs, root, view, &prefix))
+    }
+
+    fn whole(&self, signal: usize) -> BitSpan {
+        target_span(&TargetRef { signal, sel: None }, &self.signals)
+    }
+
+    fn seq_pins(&self, stmts: &[SeqStmtRef], reads: &mut Vec<BitSpan>, writes: &mut Vec<BitSpan>) {
+        for stmt in stmts {
+            match stmt {
+                SeqStmtRef::Assign(target, expr) => {
+                    collect_reads(expr, &self.signals, reads);
+                    writes.push(target_span(target, &self.signals));
+                }
+                SeqStmtRef::AssignElement { array, index, expr } => {
+                    collect_reads(index, &self.signals, reads);
+                    collect_reads(expr, &self.signals, reads);
+                    writes.push(self.whole(*array));
+                }
+                SeqStmtRef::If(i) => {
+                    collect_reads(&i.cond, &self.signals, reads);
+                    self.seq_pins(&i.then_stmts, reads, writes);
+                    for (cond, block) in &i.elsif {
+                        collect_reads(cond, &self.signals, reads);
+                        self.seq_pins(block, reads, writes);
+                    }
+                    self.seq_pins(&i.else_stmts, reads, writes);
+                }
+                SeqStmtRef::Case(c) => {
+                    collect_reads(&c.expr, &self.signals, reads);
+                    for (choice, block) in &c.arms {
+                        if let CaseChoiceRef::Target(t) = choice {
+                            reads.push(target_span(t, &self.signals));
+                        }
+                        self.seq_pins(block, reads, writes);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Arcs entre éléments, les recopies court-circuitées
+    fn arcs(&self, nodes: &[Node], prefix: &str) -> Vec<Arc> {
+        let mut writers: Vec<Vec<(usize, BitSpan)>> = vec![Vec::new(); self.signals.len()];
+        for (n, node) in nodes.iter().enumerate() {
+            for span in &node.writes {
+                writers[span.signal].push((n, *span));
+            }
+        }
+        // Bits de chaque (écrivain, lecteur, signal) effectivement transportés
+        let mut carried: BTreeMap<(usize, usize, usize), HashSet<usize>> = BTreeMap::new();
+        for (r, node) in nodes.iter().enumerate() {
+            for read in &node.reads {
+                for &(w, write) in &writers[read.signal] {
+                    let (lo, hi) = (read.lo.max(write.lo), read.hi.min(write.hi));
+                    if w != r && lo <= hi {
+                        carried.entry((w, r, read.signal)).or_default().extend(lo..=hi);
+                    }
+                }
+            }
+        }
+        let mut arcs: Vec<Arc> = carried
+            .into_iter()
+            .map(|((from, to, signal), bits)| Arc {
+                from,
+                to,
+                label: local_name(&self.signals[signal].name, prefix),
+                width: bits.len(),
+            })
+            .collect();
+        for (n, node) in nodes.iter().enumerate() {
+            if !node.wire {
+                continue;
+            }
+            let (through, rest): (Vec<Arc>, Vec<Arc>) = arcs.into_iter().partition(|a| a.from == n || a.to == n);
+            arcs = rest;
+            let ins = through.iter().filter(|a| a.to == n);
+            for a in ins {
+                for b in through.iter().filter(|b| b.from == n) {
+                    // Le nom du côté de la portée dessinée plutôt que celui
+                    // d'un port d'instance
+                    let label = if a.label.contains('/') { &b.label } else { &a.label };
+                    arcs.push(Arc {
+                        from: a.from,
+                        to: b.to,
+                        label: label.clone(),
+                        width: a.width.min(b.width),
+                    });
+                }
+            }
+        }
+        // Arcs parallèles portant le même signal (bits d'un bus recopiés un
+        // par un) : un seul arc avec la somme des largeurs
+        let mut merged: BTreeMap<(usize, usize, String), usize> = BTreeMap::new();
+        for arc in arcs {
+            *merged.entry((arc.from, arc.to, arc.label)).or_default() += arc.width;
+        }
+        merged
+            .into_iter()
+            .filter(|((from, to, _), _)| from != to)
+            .map(|((from, to, label), width)| Arc { from, to, label, width })
+            .collect()
+    }
+
+    fn render(&self, nodes: &[Node], arcs: &[Arc], root: usize, view: DotView, prefix: &str) -> String {
+        let title = self.instances[root].entity.clone().unwrap_or_else(|| self.instances[root].name.clone());
+        let mut out = format!("digraph {} {{\n", dot_str(&title));
+        out.push_str("  rankdir=LR;\n  node [fontname=\"Helvetica\", fontsize=10];\n  edge [fontname=\"Helvetica\", fontsize=9];\n");
+        let used: HashSet<usize> = arcs.iter().flat_map(|a| [a.from, a.to]).collect();
+        let visible = |n: usize| !nodes[n].wire || used.contains(&n);
+        let mut by_scope: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for n in (0..nodes.len()).filter(|&n| visible(n)) {
+            let scope = match view {
+                DotView::Level => root,
+                DotView::Flat => nodes[n].scope,
+            };
+            by_scope.entry(scope).or_default().push(n);
+        }
+        self.render_scope(&mut out, nodes, &by_scope, root, prefix, 1);
+        // Entrées à gauche, sorties à droite
+        let rank = |input: bool| -> Vec<String> {
+            (0..nodes.len())
+                .filter(|&n| match nodes[n].element {
+                    Element::Input(_) => input,
+                    Element::Output(_) => !input,
+                    _ => false,
+                })
+                .map(|n| format!("n{}", n))
+                .collect()
+        };
+        for (kind, list) in [("source", rank(true)), ("sink", rank(false))] {
+            if !list.is_empty() {
+                out.push_str(&format!("  {{ rank={}; {}; }}\n", kind, list.join("; ")));
+            }
+        }
+        for arc in arcs {
+            let label = match arc.width {
+                1 => arc.label.clone(),
+                w => format!("{} /{}", arc.label, w),
+            };
+            let bold = if arc.width > 1 { ", penwidth=2" } else { "" };
+            out.push_str(&format!("  n{} -> n{} [label={}{}];\n", arc.from, arc.to, dot_str(&label), bold));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn render_scope(
+        &self,
+        out: &mut String,
+        nodes: &[Node],
+        by_scope: &BTreeMap<usize, Vec<usize>>,
+        scope: usize,
+        prefix: &str,
+        depth: usize,
+    ) {
+        let indent = "  ".repeat(depth);
+        for &n in by_scope.get(&scope).into_iter().flatten() {
+            let (label, attrs) = self.node_style(&nodes[n].element, prefix);
+            out.push_str(&format!("{}n{} [label={}{}];\n", indent, n, dot_str(&label), attrs));
+        }
+        for &child in &self.instances[scope].children {
+            if !subtree_has_nodes(self, by_scope, child) {
+                continue;
+            }
+            let node = &self.instances[child];
+            let label = match &node.entity {
+                Some(entity) => format!("{} : {}", node.name, entity),
+                None => node.name.clone(),
+            };
+            out.push_str(&format!("{}subgraph cluster_{} {{\n", indent, child));
+            out.push_str(&format!("{}  label={}; style=rounded; color=gray;\n", indent, dot_str(&label)));
+            self.render_scope(out, nodes, by_scope, child, prefix, depth + 1);
+            out.push_str(&format!("{}}}\n", indent));
+        }
+    }
+
+    fn node_style(&self, element: &Element, prefix: &str) -> (String, &'static str) {
+        match element {
+            Element::Input(name) => (name.clone(), ", shape=cds, style=filled, fillcolor=\"#dbeafe\""),
+            Element::Output(name) => (name.clone(), ", shape=cds, style=filled, fillcolor=\"#dcfce7\""),
+            Element::Instance(id) => {
+                let node = &self.instances[*id];
+                let entity = node.entity.as_deref().unwrap_or("");
+                let label = format!("{}\n{}", local_name(&node.path, prefix), entity);
+                (label, ", shape=box, style=\"filled,bold\", fillcolor=\"#f3f4f6\"")
+            }
+            Element::Primitive(i) => {
+                let node = &self.instances[self.primitive_instances[*i]];
+                let entity = node.entity.as_deref().unwrap_or("");
+                (format!("{}\n{}", local_name(&node.path, prefix), entity), ", shape=box, style=rounded")
+            }
+            Element::Assign(i) => {
+                let assign = &self.assigns[*i];
+                let text = format!(
+                    "{} <= {}",
+                    self.target_text(&assign.target, prefix),
+                    self.expr_text(&assign.expr, prefix)
+                );
+                (shorten(text), ", shape=ellipse")
+            }
+            Element::Process(i) => {
+                let process = &self.processes[*i];
+                let clk = local_name(&self.signals[process.clk].name, prefix);
+                (format!("process\n{}({})", process.edge.as_str(), clk), ", shape=box, style=dashed")
+            }
+        }
+    }
+
+    fn target_text(&self, target: &TargetRef, prefix: &str) -> String {
+        let name = local_name(&self.signals[target.signal].name, prefix);
+        let span = target_span(target, &self.signals);
+        let sig = &self.signals[target.signal];
+        let base = sig.lsb.min(sig.msb);
+        match &target.sel {
+            None => name,
+            Some(_) if span.lo == span.hi => format!("{}({})", name, span.lo as i64 + base),
+            Some(_) => format!("{}({}..{})", name, span.hi as i64 + base, span.lo as i64 + base),
+        }
+    }
+
+    fn expr_text(&self, expr: &ExprRef, prefix: &str) -> String {
+        match expr {
+            ExprRef::Literal(value) => match value.bits.width() {
+                1 => format!("'{}'", value.bits.get(0)),
+                _ => value.bits.to_u64_trunc().to_string(),
+            },
+            ExprRef::Target(t) => self.target_text(t, prefix),
+            ExprRef::Unary { op, expr } => {
+                let op = match op {
+                    UnaryOp::Not => "not ",
+                    UnaryOp::Neg => "-",
+                };
+                format!("{}{}", op, self.operand_text(expr, prefix))
+            }
+            ExprRef::Binary { op, left, right } => {
+                let op = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                    BinaryOp::Xor => "xor",
+                    BinaryOp::Concat => "&",
+                    BinaryOp::Shl => "<<",
+                    BinaryOp::Shr => ">>",
+                    BinaryOp::Eq => "=",
+                    BinaryOp::Ne => "/=",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Le => "<=",
+                    BinaryOp::Gt => ">",
+                    BinaryOp::Ge => ">=",
+                };
+                format!("{} {} {}", self.operand_text(left, prefix), op, self.operand_text(right, prefix))
+            }
+            ExprRef::Call { name, args } => {
+                let args: Vec<String> = args.iter().map(|a| self.expr_text(a, prefix)).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+            ExprRef::Element { array, index } => {
+                let name = local_name(&self.signals[*array].name, prefix);
+                format!("{}({})", name, self.expr_text(index, prefix))
+            }
+            ExprRef::Select { cond, then, otherwise } => format!(
+                "{} when {} else {}",
+                self.operand_text(then, prefix),
+                self.operand_text(cond, prefix),
+                self.operand_text(otherwise, prefix)
+            ),
+        }
+    }
+
+    fn operand_text(&self, expr: &ExprRef, prefix: &str) -> String {
+        match expr {
+            ExprRef::Unary { .. } | ExprRef::Binary { .. } | ExprRef::Select { .. } => {
+                format!("({})", self.expr_text(expr, prefix))
+            }
+            _ => self.expr_text(expr, prefix),
+        }
+    }
+}
+
+fn subtree_has_nodes(netlist: &Netlist, by_scope: &BTreeMap<usize, Vec<usize>>, scope: usize) -> bool {
+    by_scope.contains_key(&scope)
+        || netlist.instances[scope]
+            .children
+            .iter()
+            .any(|&child| subtree_has_nodes(netlist, by_scope, child))
+}
+
+/// Nom d'un net relatif à la portée dessinée
+fn local_name(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix).unwrap_or(name).to_string()
+}
+
+/// Étiquettes d'affectation limitées à une ligne lisible
+fn shorten(text: String) -> String {
+    const MAX: usize = 40;
+    if text.chars().count() <= MAX {
+        text
+    } else {
+        let cut: String = text.chars().take(MAX - 3).collect();
+        format!("{}...", cut)
+    }
+}
+
+fn dot_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elab::elaborate;
+    use crate::parser::parse_str;
+
+    const DESIGN: &str = r#"
+entity FullAdder is
+  port(a : in bit; b : in bit; cin : in bit; s : out bit; cout : out bit);
+end entity;
+
+architecture rtl of FullAdder is
+  signal p, g, t : bit;
+begin
+  xp: Xor2 port map (a => a, b => b, y => p);
+  xs: Xor2 port map (a => p, b => cin, y => s);
+  ag: And2 port map (a => a, b => b, y => g);
+  at: And2 port map (a => p, b => cin, y => t);
+  oc: Or2 port map (a => g, b => t, y => cout);
+end architecture;
+
+entity Ripple4 is
+  port(a : in bits(3 downto 0); b : in bits(3 downto 0); cin : in bit;
+       s : out bits(3 downto 0); cout : out bit);
+end entity;
+
+architecture rtl of Ripple4 is
+  signal c : bits(4 downto 0);
+begin
+  c(0) <= cin;
+  g: for i in 0 to 3 generate
+    fa: FullAdder port map (a => a(i), b => b(i), cin => c(i), s => s(i), cout => c(i + 1));
+  end generate;
+  cout <= c(4);
+end architecture;
+
+entity Acc is
+  port(clk : in bit; x : in bits(3 downto 0); y : out bits(3 downto 0));
+end entity;
+
+architecture rtl of Acc is
+  signal sum, acc : bits(3 downto 0);
+begin
+  sum <= acc + x;
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      acc <= sum;
+    end if;
+  end process;
+  y <= acc;
+end architecture;
+"#;
+
+    fn netlist(top: &str) -> Netlist {
+        elaborate(&parse_str(DESIGN).unwrap(), top).unwrap()
+    }
+
+    /// Identifiant DOT du noeud dont l'étiquette commence par `label`
+    fn node(dot: &str, label: &str) -> String {
+        let line = dot
+            .lines()
+            .find(|l| l.contains(&format!("[label=\"{}", label)))
+            .unwrap_or_else(|| panic!("no node {} in\n{}", label, dot));
+        line.trim().split(' ').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_one_level_shows_instances_as_boxes() {
+        let dot = netlist("Ripple4").to_dot("", DotView::Level).unwrap();
+        assert!(dot.starts_with("digraph \"Ripple4\" {"));
+        assert!(!dot.contains("xor2"), "primitives stay inside the boxes");
+        assert!(!dot.contains("cluster"));
+        let (a, fa0, fa1, fa3) = (node(&dot, "a"), node(&dot, "g(0)/fa"), node(&dot, "g(1)/fa"), node(&dot, "g(3)/fa"));
+        assert!(dot.contains(&format!("{} -> {} [label=\"a\"];", a, fa0)));
+        // La retenue passe par c(1) sans noeud d'affectation intermédiaire
+        assert!(dot.contains(&format!("{} -> {} [label=\"c\"];", fa0, fa1)));
+        assert!(dot.contains(&format!("{} -> {} [label=\"cin\"];", node(&dot, "cin"), fa0)));
+        assert!(dot.contains(&format!("{} -> {} [label=\"c\"];", fa3, node(&dot, "cout"))));
+        assert!(!dot.contains("<="), "port bindings are plain edges");
+    }
+
+    #[test]
+    fn test_flat_view_reaches_primitives() {
+        let dot = netlist("Ripple4").to_dot("", DotView::Flat).unwrap();
+        assert_eq!(dot.matches("\\nxor2\"").count(), 8);
+        assert_eq!(dot.matches("subgraph cluster_").count(), 8, "one cluster per generate block and adder");
+        assert!(dot.contains("label=\"fa : FullAdder\""));
+        let xp = node(&dot, "g(2)/fa/xp");
+        let xs = node(&dot, "g(2)/fa/xs");
+        assert!(dot.contains(&format!("{} -> {} [label=\"g(2)/fa/p\"];", xp, xs)));
+
+        let inner = netlist("Ripple4").to_dot("g(1)/fa", DotView::Level).unwrap();
+        assert!(inner.starts_with("digraph \"FullAdder\" {"));
+        assert!(inner.contains(&format!("{} -> {} [label=\"p\"];", node(&inner, "xp"), node(&inner, "xs"))));
+        assert!(netlist("Ripple4").to_dot("g(9)/fa", DotView::Level).is_err());
+    }
+
+    #[test]
+    fn test_buses_carry_their_width() {
+        let dot = netlist("Acc").to_dot("", DotView::Level).unwrap();
+        let add = node(&dot, "sum <= acc + x");
+        let process = node(&dot, "process\\nrising_edge(clk)");
+        assert!(dot.contains(&format!("{} -> {} [label=\"x /4\", penwidth=2];", node(&dot, "x"), add)));
+        assert!(dot.contains(&format!("{} -> {} [label=\"sum /4\", penwidth=2];", add, process)));
+        assert!(dot.contains(&format!("{} -> {} [label=\"acc /4\", penwidth=2];", process, add)));
+        assert!(dot.contains(&format!("{} -> {} [label=\"clk\"];", node(&dot, "clk"), process)));
+        assert!(dot.contains(&format!("{} -> {} [label=\"acc /4\", penwidth=2];", process, node(&dot, "y"))));
+    }
+}
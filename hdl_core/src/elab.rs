@@ -1,5 +1,7 @@
 use crate::ast::*;
-use crate::error::Error;
+use crate::error::{Error, Span};
+use crate::error_messages::detailed;
+use crate::graph::{target_span, BitSpan, CombGraph, CombNode};
 use crate::value::{BitVec, Value, ValueKind};
 use std::collections::{HashMap, HashSet};
 
@@ -12,6 +14,80 @@ pub struct Signal {
     pub dir: RangeDir,
     pub value: BitVec,
     pub port_dir: Option<Direction>,
+    pub ty: SignalType,
+}
+
+/// How the bits of a signal read back: a plain vector, an enumeration
+/// literal or a bounded integer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SignalType {
+    #[default]
+    Bits,
+    Enum {
+        name: String,
+        literals: Vec<String>,
+    },
+    Integer {
+        low: i64,
+        high: i64,
+    },
+    /// Elements `low..=high` of `elem_width` bits, element `low` in the low bits
+    Array {
+        low: i64,
+        high: i64,
+        elem_width: usize,
+    },
+}
+
+impl SignalType {
+    /// Symbolic text of a value (literal name, decimal integer); `None` for
+    /// plain vectors
+    pub fn display(&self, value: &BitVec) -> Option<String> {
+        match self {
+            SignalType::Bits | SignalType::Array { .. } => None,
+            SignalType::Enum { literals, .. } => {
+                let code = value.to_u64_trunc();
+                Some(match literals.get(code as usize) {
+                    Some(name) => name.clone(),
+                    None => format!("?{}", code),
+                })
+            }
+            SignalType::Integer { low, .. } if *low < 0 => {
+                Some((value.resize_sign(64).to_u64_trunc() as i64).to_string())
+            }
+            SignalType::Integer { .. } => Some(value.to_u64_trunc().to_string()),
+        }
+    }
+
+    /// Encoding of an enumeration literal of this type
+    pub fn literal(&self, name: &str) -> Option<u64> {
+        match self {
+            SignalType::Enum { literals, .. } => literals.iter().position(|l| l == name).map(|i| i as u64),
+            _ => None,
+        }
+    }
+
+    /// Type name as written in the source (`state_t`, `integer range 0 to 9`)
+    pub fn describe(&self) -> String {
+        match self {
+            SignalType::Bits => "bits".to_string(),
+            SignalType::Enum { name, .. } => name.clone(),
+            SignalType::Integer { low, high } => format!("integer range {} to {}", low, high),
+            SignalType::Array { low, high, elem_width } => {
+                format!("array ({} to {}) of {} bits", low, high, elem_width)
+            }
+        }
+    }
+
+    /// Position of the first bit of element `index` in an array signal
+    pub fn element_offset(&self, index: i64) -> Option<usize> {
+        match self {
+            SignalType::Array { low, high, elem_width } if (*low..=*high).contains(&index) => {
+                Some((index - low) as usize * elem_width)
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -27,11 +103,18 @@ pub enum ExprRef {
     Unary { op: UnaryOp, expr: Box<ExprRef> },
     Binary { op: BinaryOp, left: Box<ExprRef>, right: Box<ExprRef> },
     Call { name: String, args: Vec<ExprRef> },
+    /// Element of an array signal at a run-time index
+    Element { array: usize, index: Box<ExprRef> },
+    /// `then` when `cond` is true, else `otherwise`, both taken at the wider
+    /// of their two widths
+    Select { cond: Box<ExprRef>, then: Box<ExprRef>, otherwise: Box<ExprRef> },
 }
 
 #[derive(Clone, Debug)]
 pub enum SeqStmtRef {
     Assign(TargetRef, ExprRef),
+    /// `regs(index) <= expr;` with a run-time index
+    AssignElement { array: usize, index: ExprRef, expr: ExprRef },
     If(IfRef),
     Case(CaseRef),
 }
@@ -61,11 +144,54 @@ pub enum CaseChoiceRef {
 pub struct AssignNet {
     pub target: TargetRef,
     pub expr: ExprRef,
+    /// Source of the assignment (or of the instance that created it)
+    pub span: Option<Span>,
+    /// Hierarchy node (index into `Netlist::instances`) whose architecture
+    /// holds the assignment
+    pub scope: usize,
+}
+
+/// Clock edge a process is sensitive to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl Edge {
+    fn from_call(name: &str) -> Option<Edge> {
+        match name.to_ascii_lowercase().as_str() {
+            "rising_edge" => Some(Edge::Rising),
+            "falling_edge" => Some(Edge::Falling),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Edge::Rising => "rising_edge",
+            Edge::Falling => "falling_edge",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct ProcessNet {
-    pub clk: String,
+    /// Clock signal named in the sensitivity list
+    pub clk: usize,
+    pub edge: Edge,
+    pub stmts: Vec<SeqStmtRef>,
+    pub reset: Option<AsyncReset>,
+    /// Hierarchy node whose architecture holds the process
+    pub scope: usize,
+}
+
+/// `if rst = '1' then ... elsif rising_edge(clk)`: the reset branch applies
+/// as soon as `cond` holds, without waiting for a clock edge
+#[derive(Clone, Debug)]
+pub struct AsyncReset {
+    pub signal: usize,
+    pub cond: ExprRef,
     pub stmts: Vec<SeqStmtRef>,
 }
 
@@ -98,31 +224,88 @@ pub enum PrimitiveNet {
     },
 }
 
+/// A port of an instance and the net it is bound to
+#[derive(Clone, Debug)]
+pub struct PortBinding {
+    pub name: String,
+    pub dir: Direction,
+    pub signal: usize,
+}
+
+/// One scope of the design hierarchy: the top entity, an instance, a
+/// primitive or a generate block
+#[derive(Clone, Debug)]
+pub struct InstanceNode {
+    /// Local name (instance label, `label(i)` for a generate iteration)
+    pub name: String,
+    /// Full path from the top, `""` for the top itself
+    pub path: String,
+    /// Instantiated entity or primitive; `None` for generate blocks
+    pub entity: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub ports: Vec<PortBinding>,
+    /// Signals declared in the architecture, as (local name, net)
+    pub locals: Vec<(String, usize)>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Netlist {
     pub signals: Vec<Signal>,
     pub assigns: Vec<AssignNet>,
     pub processes: Vec<ProcessNet>,
     pub primitives: Vec<PrimitiveNet>,
+    /// Source span of the instance behind each primitive
+    pub primitive_spans: Vec<Option<Span>>,
+    /// Hierarchy node of each primitive instance
+    pub primitive_instances: Vec<usize>,
     pub name_to_id: HashMap<String, usize>,
     /// Number of ROM primitives (for indexing ROM state in simulator)
     pub rom_count: usize,
+    /// Design hierarchy, the top entity first
+    pub instances: Vec<InstanceNode>,
 }
 
+/// Bits of each signal driven so far, one span per driver
+type Drivers = HashMap<usize, Vec<BitSpan>>;
+
+/// Instance hierarchy depth beyond which instantiation is assumed to recurse forever
+const MAX_DEPTH: usize = 256;
+
+/// Widest signal in bits and longest `for generate`; anything larger comes
+/// from a runaway generic rather than a real design
+const MAX_WIDTH: u64 = 1 << 20;
+const MAX_GENERATE: u64 = 1 << 16;
+
 pub fn elaborate(design: &Design, top: &str) -> Result<Netlist, Error> {
+    elaborate_with_generics(design, top, &HashMap::new())
+}
+
+/// Elaborates `top` with some of its generics overridden; the others take their default
+pub fn elaborate_with_generics(
+    design: &Design,
+    top: &str,
+    generics: &HashMap<String, i64>,
+) -> Result<Netlist, Error> {
     let mut lib = Library::new(design)?;
     let mut netlist = Netlist {
         signals: Vec::new(),
         assigns: Vec::new(),
         processes: Vec::new(),
         primitives: Vec::new(),
+        primitive_spans: Vec::new(),
+        primitive_instances: Vec::new(),
         name_to_id: HashMap::new(),
         rom_count: 0,
+        instances: Vec::new(),
     };
-    let mut drivers: HashMap<usize, usize> = HashMap::new();
-    lib.elaborate_entity(top, None, None, &mut netlist, &mut drivers)?;
-    for (sig, count) in &drivers {
-        if *count > 1 {
+    let ent = lib.entity(top)?;
+    let scope = lib.entity_scope(&ent, generics)?;
+    let mut drivers: Drivers = HashMap::new();
+    lib.elaborate_entity(top, None, None, scope, &mut netlist, &mut drivers)?;
+    for (sig, spans) in drivers.iter_mut() {
+        spans.sort_by_key(|s| s.lo);
+        if spans.windows(2).any(|w| w[1].lo <= w[0].hi) {
             return Err(Error::new(format!(
                 "multiple drivers for signal {}",
                 netlist.signals[*sig].name
@@ -130,22 +313,314 @@ pub fn elaborate(design: &Design, top: &str) -> Result<Netlist, Error> {
         }
     }
     for (idx, sig) in netlist.signals.iter().enumerate() {
-        if matches!(sig.port_dir, Some(Direction::Out)) {
-            let count = drivers.get(&idx).copied().unwrap_or(0);
-            if count != 1 {
+        if matches!(sig.port_dir, Some(Direction::Out)) && !drivers.contains_key(&idx) {
+            return Err(Error::new(format!(
+                "out port {} must be driven exactly once",
+                sig.name
+            )));
+        }
+    }
+    check_comb_loops(&netlist)?;
+    Ok(netlist)
+}
+
+/// Widths of the ports of `entity`, then of the signals of its architecture
+/// when there is one, with default generics; instances are not elaborated
+pub fn declared_widths(design: &Design, entity: &str) -> Result<Vec<(String, usize)>, Error> {
+    let arch = ArchScope::new(design, entity)?;
+    Ok(arch.netlist.signals.iter().map(|sig| (sig.name.clone(), sig.width)).collect())
+}
+
+/// Names of an entity and its architecture (ports, signals, constants,
+/// types) with default generics, for checks that read the source without
+/// elaborating the instances
+#[derive(Clone)]
+pub(crate) struct ArchScope {
+    scope: Scope,
+    netlist: Netlist,
+}
+
+impl ArchScope {
+    pub(crate) fn new(design: &Design, entity: &str) -> Result<ArchScope, Error> {
+        let lib = Library::new(design)?;
+        let ent = lib.entity(entity)?;
+        let mut scope = lib.entity_scope(&ent, &HashMap::new())?;
+        let mut netlist = Netlist {
+            signals: Vec::new(),
+            assigns: Vec::new(),
+            processes: Vec::new(),
+            primitives: Vec::new(),
+            primitive_spans: Vec::new(),
+            primitive_instances: Vec::new(),
+            name_to_id: HashMap::new(),
+            rom_count: 0,
+            instances: Vec::new(),
+        };
+        let mut define = |name: &str, shape: Shape, dir: Option<Direction>, scope: &mut Scope| {
+            let id = define_signal(&mut netlist, name, &shape, dir)?;
+            if shape.ty != SignalType::Bits {
+                scope.signal_types.insert(id, shape.ty);
+            }
+            scope.signals.insert(name.to_string(), id);
+            Ok::<(), Error>(())
+        };
+        for port in &ent.ports {
+            let shape = resolve_type(&port.ty, &scope.consts, &scope.types)?;
+            define(&port.name, shape, Some(port.dir.clone()), &mut scope)?;
+        }
+        if let Some(arch) = lib.archs.get(entity) {
+            lib.use_packages(&arch.uses, &mut scope, &mut Vec::new())?;
+            declare(&arch.types, &arch.constants, &mut scope)?;
+            for sig in &arch.signals {
+                for name in &sig.names {
+                    scope.check_new_name(name)?;
+                    define(name, resolve_type(&sig.ty, &scope.consts, &scope.types)?, None, &mut scope)?;
+                }
+            }
+        }
+        Ok(ArchScope { scope, netlist })
+    }
+
+    /// Binds a generate loop variable
+    pub(crate) fn set_const(&mut self, name: &str, value: i64) {
+        self.scope.consts.insert(name.to_string(), value);
+    }
+
+    pub(crate) fn eval(&self, expr: &IntExpr) -> Option<i64> {
+        self.scope.eval(expr).ok()
+    }
+
+    /// Integer constant (generic, `constant`, loop variable) named by `target`
+    pub(crate) fn constant(&self, target: &Target) -> Option<i64> {
+        self.scope.constant(target)
+    }
+
+    pub(crate) fn target_width(&self, target: &Target) -> Option<usize> {
+        target_width(target, &self.scope, &self.netlist).ok()
+    }
+
+    /// Width by the rules of elaboration (an integer literal is 32 bits)
+    pub(crate) fn expr_width(&self, expr: &Expr) -> Option<usize> {
+        expr_width(expr, &self.scope, &self.netlist).ok()
+    }
+
+    /// Enumeration or integer range type of `expr`, when it has one
+    pub(crate) fn expr_type(&self, expr: &Expr) -> Option<&SignalType> {
+        self.scope.expr_type(expr)
+    }
+}
+
+/// Rejects combinational cycles, bit by bit (`a <= a(1 downto 0) & x;` is
+/// fine), naming each signal on the loop
+fn check_comb_loops(netlist: &Netlist) -> Result<(), Error> {
+    let graph = CombGraph::build(netlist);
+    let Some(cycle) = graph.find_cycle(netlist) else {
+        return Ok(());
+    };
+    let steps: Vec<(String, Option<Span>)> = cycle
+        .iter()
+        .map(|&node| {
+            let signal = netlist.signals[graph.writes[node].signal].name.clone();
+            let span = match graph.nodes[node] {
+                CombNode::Assign(i) => netlist.assigns[i].span,
+                CombNode::Primitive(i) => netlist.primitive_spans[i],
+            };
+            (signal, span)
+        })
+        .collect();
+    let message = detailed::combinational_loop(&steps);
+    Err(spanned(message, steps.iter().find_map(|(_, span)| *span)))
+}
+
+/// Error placed at `span` when the source position is known
+fn spanned(message: impl Into<String>, span: Option<Span>) -> Error {
+    match span {
+        Some(span) => Error::with_span(message, span),
+        None => Error::new(message),
+    }
+}
+
+/// Values of an entity's generics: overrides first, then defaults (which may
+/// refer to the generics declared before them and to the `visible` integer
+/// constants of the packages the entity uses)
+fn bind_generics(
+    ent: &Entity,
+    overrides: &HashMap<String, i64>,
+    visible: &HashMap<String, i64>,
+) -> Result<HashMap<String, i64>, Error> {
+    for name in overrides.keys() {
+        if !ent.generics.iter().any(|g| &g.name == name) {
+            return Err(Error::new(format!("unknown generic {} on {}", name, ent.name)));
+        }
+    }
+    let mut consts = visible.clone();
+    for generic in &ent.generics {
+        let value = match (overrides.get(&generic.name), &generic.default) {
+            (Some(v), _) => *v,
+            (None, Some(default)) => eval_int(default, &consts)?,
+            (None, None) => {
                 return Err(Error::new(format!(
-                    "out port {} must be driven exactly once",
-                    sig.name
-                )));
+                    "missing value for generic {} of {}",
+                    generic.name, ent.name
+                )))
             }
+        };
+        consts.insert(generic.name.clone(), value);
+    }
+    consts.retain(|name, _| ent.generics.iter().any(|g| &g.name == name));
+    Ok(consts)
+}
+
+/// Names visible inside one entity instance: signals, integer constants
+/// (generics, integer `constant`s, generate loop variables), enumeration
+/// types and the other constants
+#[derive(Clone, Default)]
+struct Scope {
+    signals: HashMap<String, usize>,
+    consts: HashMap<String, i64>,
+    types: HashMap<String, SignalType>,
+    /// Enumeration literals and non-integer constants, folded to literals
+    values: HashMap<String, TypedValue>,
+    /// Type of the enumeration and integer signals
+    signal_types: HashMap<usize, SignalType>,
+    /// Package each name made visible by a `use` comes from
+    imported: HashMap<String, String>,
+}
+
+#[derive(Clone)]
+struct TypedValue {
+    value: Value,
+    ty: SignalType,
+}
+
+impl Scope {
+    fn eval(&self, expr: &IntExpr) -> Result<i64, Error> {
+        eval_int(expr, &self.consts)
+    }
+
+    fn signal(&self, name: &str) -> Result<usize, Error> {
+        self.signals
+            .get(name)
+            .copied()
+            .ok_or_else(|| Error::new(format!("unknown signal {}", name)))
+    }
+
+    /// Constant named by a bare identifier that is not a signal
+    fn constant(&self, target: &Target) -> Option<i64> {
+        if target.sel.is_some() || self.signals.contains_key(&target.name) {
+            return None;
         }
+        self.consts.get(&target.name).copied()
+    }
+
+    /// Enumeration literal or typed constant named by a bare identifier
+    fn typed(&self, target: &Target) -> Option<&TypedValue> {
+        if target.sel.is_some() || self.signals.contains_key(&target.name) {
+            return None;
+        }
+        self.values.get(&target.name)
+    }
+
+    /// Type an expression is known to have: an enumeration literal or a
+    /// whole enumeration/integer signal
+    fn expr_type(&self, expr: &Expr) -> Option<&SignalType> {
+        let Expr::Target(t) = expr else {
+            return None;
+        };
+        if let Some(typed) = self.typed(t) {
+            return Some(&typed.ty);
+        }
+        match (&t.sel, self.signals.get(&t.name)) {
+            (None, Some(id)) => self.signal_types.get(id),
+            _ => None,
+        }
+    }
+
+    /// Index range and element width of an array signal
+    fn array(&self, signal: usize) -> Option<(i64, i64, usize)> {
+        match self.signal_types.get(&signal) {
+            Some(SignalType::Array { low, high, elem_width }) => Some((*low, *high, *elem_width)),
+            _ => None,
+        }
+    }
+
+    /// Rejects names declared twice in the architecture or shadowing a generic
+    fn check_new_name(&self, name: &str) -> Result<(), Error> {
+        if self.signals.contains_key(name)
+            || self.consts.contains_key(name)
+            || self.values.contains_key(name)
+            || self.types.contains_key(name)
+        {
+            return Err(Error::new(format!("duplicate name {}", name)));
+        }
+        Ok(())
+    }
+
+    /// Value of an expression made only of integers and constants (`N`, `N - 1`)
+    fn const_value(&self, expr: &Expr) -> Option<i64> {
+        match expr {
+            Expr::Literal(Literal::Int(v)) => Some(*v),
+            Expr::Target(t) => self.constant(t),
+            Expr::Unary { op: UnaryOp::Neg, expr } => self.const_value(expr).map(|v| -v),
+            Expr::Binary { op: BinaryOp::Add, left, right } => {
+                Some(self.const_value(left)? + self.const_value(right)?)
+            }
+            Expr::Binary { op: BinaryOp::Sub, left, right } => {
+                Some(self.const_value(left)? - self.const_value(right)?)
+            }
+            _ => None,
+        }
+    }
+
+    fn selector(&self, sel: &SelectorExpr) -> Result<Selector, Error> {
+        Ok(match sel {
+            SelectorExpr::Index(i) => Selector::Index(self.eval(i)?),
+            SelectorExpr::Expr(_) => return Err(Error::new("run-time index only allowed on arrays")),
+            SelectorExpr::Range { msb, lsb, dir } => Selector::Range {
+                msb: self.eval(msb)?,
+                lsb: self.eval(lsb)?,
+                dir: dir.clone(),
+            },
+        })
     }
-    Ok(netlist)
+}
+
+fn eval_int(expr: &IntExpr, consts: &HashMap<String, i64>) -> Result<i64, Error> {
+    Ok(match expr {
+        IntExpr::Lit(v) => *v,
+        IntExpr::Name(name) => *consts
+            .get(name)
+            .ok_or_else(|| Error::new(format!("unknown constant {}", name)))?,
+        IntExpr::Neg(e) => eval_int(e, consts)?.checked_neg().ok_or_else(int_overflow)?,
+        IntExpr::Binary { op, left, right } => {
+            let l = eval_int(left, consts)?;
+            let r = eval_int(right, consts)?;
+            match op {
+                IntOp::Add => l.checked_add(r),
+                IntOp::Sub => l.checked_sub(r),
+                IntOp::Mul => l.checked_mul(r),
+                IntOp::Div => {
+                    if r == 0 {
+                        return Err(Error::new("division by zero in constant expression"));
+                    }
+                    l.checked_div(r)
+                }
+            }
+            .ok_or_else(int_overflow)?
+        }
+    })
+}
+
+fn int_overflow() -> Error {
+    Error::new("integer overflow in constant expression")
 }
 
 struct Library {
     entities: HashMap<String, Entity>,
     archs: HashMap<String, Architecture>,
+    packages: HashMap<String, Package>,
+    /// Index in `Netlist::instances` of each hierarchy path
+    instance_ids: HashMap<String, usize>,
 }
 
 impl Library {
@@ -162,7 +637,125 @@ impl Library {
                 return Err(Error::new(format!("multiple architectures for entity {}", arch.entity)));
             }
         }
-        Ok(Self { entities, archs })
+        let mut packages = HashMap::new();
+        for pkg in &design.packages {
+            if packages.insert(pkg.name.clone(), pkg.clone()).is_some() {
+                return Err(Error::new(format!("duplicate package {}", pkg.name)));
+            }
+        }
+        Ok(Self {
+            entities,
+            archs,
+            packages,
+            instance_ids: HashMap::new(),
+        })
+    }
+
+    /// Scope of an entity before its architecture: the packages it uses,
+    /// then its generics
+    fn entity_scope(&self, ent: &Entity, overrides: &HashMap<String, i64>) -> Result<Scope, Error> {
+        let mut scope = Scope::default();
+        self.use_packages(&ent.uses, &mut scope, &mut Vec::new())?;
+        for (name, value) in bind_generics(ent, overrides, &scope.consts)? {
+            scope.check_new_name(&name)?;
+            scope.consts.insert(name, value);
+        }
+        Ok(scope)
+    }
+
+    /// Declarations of a package, after those of the packages it uses;
+    /// `visiting` holds the packages being declared, to reject cycles
+    fn package_scope(&self, name: &str, visiting: &mut Vec<String>) -> Result<(&Package, Scope), Error> {
+        let pkg = self
+            .packages
+            .get(name)
+            .ok_or_else(|| Error::new(format!("unknown package {}", name)))?;
+        if visiting.iter().any(|p| p == name) {
+            return Err(Error::new(format!("circular use of package {}", name)));
+        }
+        visiting.push(name.to_string());
+        let mut scope = Scope::default();
+        self.use_packages(&pkg.uses, &mut scope, visiting)?;
+        declare(&pkg.types, &pkg.constants, &mut scope)?;
+        visiting.pop();
+        Ok((pkg, scope))
+    }
+
+    /// Makes the names selected by `use` clauses visible in `scope`; using
+    /// the same name of the same package twice is harmless
+    fn use_packages(&self, uses: &[UseClause], scope: &mut Scope, visiting: &mut Vec<String>) -> Result<(), Error> {
+        for clause in uses {
+            let (pkg, declared) = self.package_scope(&clause.package, visiting)?;
+            // A type brings its enumeration literals along
+            let mut names: Vec<&String> = Vec::new();
+            for decl in &pkg.types {
+                if clause.item.as_ref().is_none_or(|item| item == &decl.name) {
+                    names.push(&decl.name);
+                    if let TypeDef::Enum(literals) = &decl.def {
+                        names.extend(literals);
+                    }
+                }
+            }
+            for decl in &pkg.constants {
+                if clause.item.as_ref().is_none_or(|item| item == &decl.name) {
+                    names.push(&decl.name);
+                }
+            }
+            if let (Some(item), true) = (&clause.item, names.is_empty()) {
+                let is_component = pkg.components.iter().any(|c| &c.name == item);
+                if !is_component {
+                    return Err(Error::new(format!("package {} has no {}", pkg.name, item)));
+                }
+            }
+            for name in names {
+                if scope.imported.get(name) == Some(&pkg.name) {
+                    continue;
+                }
+                scope.check_new_name(name)?;
+                if let Some(&value) = declared.consts.get(name) {
+                    scope.consts.insert(name.clone(), value);
+                }
+                if let Some(value) = declared.values.get(name) {
+                    scope.values.insert(name.clone(), value.clone());
+                }
+                if let Some(ty) = declared.types.get(name) {
+                    scope.types.insert(name.clone(), ty.clone());
+                }
+                scope.imported.insert(name.clone(), pkg.name.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a hierarchy node under the scope its path is nested in
+    fn add_instance(&mut self, netlist: &mut Netlist, path: &str, entity: Option<String>) -> usize {
+        let id = netlist.instances.len();
+        let (parent, name) = match path.rsplit_once('/') {
+            _ if path.is_empty() => (None, entity.clone().unwrap_or_default()),
+            Some((parent, name)) => (self.instance_ids.get(parent).copied(), name.to_string()),
+            None => (self.instance_ids.get("").copied(), path.to_string()),
+        };
+        if let Some(parent) = parent {
+            netlist.instances[parent].children.push(id);
+        }
+        netlist.instances.push(InstanceNode {
+            name,
+            path: path.to_string(),
+            entity,
+            parent,
+            children: Vec::new(),
+            ports: Vec::new(),
+            locals: Vec::new(),
+        });
+        self.instance_ids.insert(path.to_string(), id);
+        id
+    }
+
+    fn entity(&self, name: &str) -> Result<Entity, Error> {
+        self.entities
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::new(format!("unknown entity {}", name)))
     }
 
     fn elaborate_entity(
@@ -170,74 +763,178 @@ impl Library {
         entity_name: &str,
         inst_prefix: Option<&str>,
         port_map: Option<&HashMap<String, usize>>,
+        mut scope: Scope,
         netlist: &mut Netlist,
-        drivers: &mut HashMap<usize, usize>,
+        drivers: &mut Drivers,
     ) -> Result<(), Error> {
-        let ent = self
-            .entities
-            .get(entity_name)
-            .ok_or_else(|| Error::new(format!("unknown entity {}", entity_name)))?
-            .clone();
+        if inst_prefix.is_some_and(|p| p.split('/').count() > MAX_DEPTH) {
+            return Err(Error::new(format!(
+                "instance hierarchy too deep in {} (recursive instantiation?)",
+                entity_name
+            )));
+        }
+        let ent = self.entity(entity_name)?;
         let arch = self
             .archs
             .get(entity_name)
             .ok_or_else(|| Error::new(format!("missing architecture for {}", entity_name)))?
             .clone();
 
-        let mut mapping: HashMap<String, usize> = HashMap::new();
+        let node = self.add_instance(netlist, inst_prefix.unwrap_or(""), Some(ent.name.clone()));
         let mut in_ports: HashSet<usize> = HashSet::new();
         for port in &ent.ports {
+            let shape = resolve_type(&port.ty, &scope.consts, &scope.types)?;
             let id = if let Some(map) = port_map {
                 map.get(&port.name)
                     .copied()
                     .ok_or_else(|| Error::new(format!("missing port mapping for {}", port.name)))?
             } else {
                 let name = scoped(inst_prefix, &port.name);
-                define_signal(netlist, &name, &port.ty, Some(port.dir.clone()))?
+                define_signal(netlist, &name, &shape, Some(port.dir.clone()))?
             };
             if matches!(port.dir, Direction::In) {
                 in_ports.insert(id);
             }
-            mapping.insert(port.name.clone(), id);
+            if shape.ty != SignalType::Bits {
+                scope.signal_types.insert(id, shape.ty);
+            }
+            scope.signals.insert(port.name.clone(), id);
+            netlist.instances[node].ports.push(PortBinding {
+                name: port.name.clone(),
+                dir: port.dir.clone(),
+                signal: id,
+            });
         }
 
+        self.use_packages(&arch.uses, &mut scope, &mut Vec::new())?;
+        declare(&arch.types, &arch.constants, &mut scope)?;
+
         for sig in &arch.signals {
+            let shape = resolve_type(&sig.ty, &scope.consts, &scope.types)?;
             for name in &sig.names {
+                scope.check_new_name(name)?;
                 let scoped_name = scoped(inst_prefix, name);
-                let id = define_signal(netlist, &scoped_name, &sig.ty, None)?;
+                let id = define_signal(netlist, &scoped_name, &shape, None)?;
                 if let Some(init) = &sig.init {
-                    let expr = convert_expr(init, &mapping)?;
+                    check_assign_type(name, &shape.ty, init, &scope)?;
+                    check_aggregates(init, &scope, netlist)?;
+                    let expr = convert_value(init, shape.width, &scope)?;
                     if let ExprRef::Literal(val) = expr {
-                        netlist.signals[id].value = val.bits.clone();
+                        netlist.signals[id].value = val.bits.resize_zero(shape.width);
                     }
                 }
-                mapping.insert(name.clone(), id);
+                if shape.ty != SignalType::Bits {
+                    scope.signal_types.insert(id, shape.ty.clone());
+                }
+                scope.signals.insert(name.clone(), id);
+                netlist.instances[node].locals.push((name.clone(), id));
             }
         }
 
-        // Track signals with indexed output assignments to avoid multiple driver errors
-        let mut indexed_out_sigs: HashSet<usize> = HashSet::new();
+        self.elaborate_stmts(&arch.stmts, inst_prefix, &scope, &in_ports, netlist, drivers)
+    }
 
-        for stmt in &arch.stmts {
+    fn elaborate_stmts(
+        &mut self,
+        stmts: &[ConcurrentStmt],
+        prefix: Option<&str>,
+        scope: &Scope,
+        in_ports: &HashSet<usize>,
+        netlist: &mut Netlist,
+        drivers: &mut Drivers,
+    ) -> Result<(), Error> {
+        for stmt in stmts {
             match stmt {
                 ConcurrentStmt::Assign(a) => {
-                    let target = convert_target(&a.target, &mapping)?;
-                    register_driver(&target, &in_ports, drivers)?;
-                    let expr = convert_expr(&a.expr, &mapping)?;
-                    netlist.assigns.push(AssignNet { target, expr });
+                    check_target_assign(&a.target, &a.expr, scope)?;
+                    check_aggregates(&a.expr, scope, netlist)?;
+                    let target = convert_target(&a.target, scope)?;
+                    register_driver(&target, in_ports, netlist, drivers)?;
+                    let expr = convert_value(&a.expr, target_width(&a.target, scope, netlist)?, scope)?;
+                    netlist.assigns.push(AssignNet {
+                        target,
+                        expr,
+                        span: a.span,
+                        scope: self.instance_ids[prefix.unwrap_or("")],
+                    });
                 }
                 ConcurrentStmt::Process(p) => {
-                    let proc = convert_process(p, &mapping)?;
+                    let mut proc = convert_process(p, scope, netlist)?;
+                    proc.scope = self.instance_ids[prefix.unwrap_or("")];
                     let mut proc_targets: HashSet<usize> = HashSet::new();
                     collect_process_targets(&proc.stmts, &mut proc_targets);
+                    if let Some(reset) = &proc.reset {
+                        collect_process_targets(&reset.stmts, &mut proc_targets);
+                    }
                     for sig in proc_targets {
                         let target = TargetRef { signal: sig, sel: None };
-                        register_driver(&target, &in_ports, drivers)?;
+                        register_driver(&target, in_ports, netlist, drivers)?;
                     }
                     netlist.processes.push(proc);
                 }
                 ConcurrentStmt::Instance(i) => {
-                    self.elaborate_instance(i, inst_prefix, &mapping, netlist, drivers, &in_ports, &mut indexed_out_sigs)?;
+                    self.elaborate_instance(i, prefix, scope, netlist, drivers, in_ports)?;
+                }
+                ConcurrentStmt::Generate(g) => {
+                    self.elaborate_generate(g, prefix, scope, in_ports, netlist, drivers)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Unrolls a generate block; instances inside are named `label(i)/inst`
+    /// (for) or `label/inst` (if)
+    fn elaborate_generate(
+        &mut self,
+        gen: &GenerateStmt,
+        prefix: Option<&str>,
+        scope: &Scope,
+        in_ports: &HashSet<usize>,
+        netlist: &mut Netlist,
+        drivers: &mut Drivers,
+    ) -> Result<(), Error> {
+        match &gen.scheme {
+            GenerateScheme::For { var, left, right, dir } => {
+                let (left, right) = (scope.eval(left)?, scope.eval(right)?);
+                let (low, high) = match dir {
+                    RangeDir::To => (left, right),
+                    RangeDir::Downto => (right, left),
+                };
+                if low <= high && high.abs_diff(low) >= MAX_GENERATE {
+                    return Err(Error::new(format!(
+                        "generate {} has {} iterations (at most {})",
+                        gen.label,
+                        high.abs_diff(low) as u128 + 1,
+                        MAX_GENERATE
+                    )));
+                }
+                let values: Vec<i64> = match dir {
+                    RangeDir::To => (left..=right).collect(),
+                    RangeDir::Downto => (right..=left).rev().collect(),
+                };
+                for value in values {
+                    let mut inner = scope.clone();
+                    inner.consts.insert(var.clone(), value);
+                    let label = scoped(prefix, &format!("{}({})", gen.label, value));
+                    self.add_instance(netlist, &label, None);
+                    self.elaborate_stmts(&gen.stmts, Some(&label), &inner, in_ports, netlist, drivers)?;
+                }
+            }
+            GenerateScheme::If { op, left, right } => {
+                let (l, r) = (scope.eval(left)?, scope.eval(right)?);
+                let taken = match op {
+                    BinaryOp::Eq => l == r,
+                    BinaryOp::Ne => l != r,
+                    BinaryOp::Lt => l < r,
+                    BinaryOp::Le => l <= r,
+                    BinaryOp::Gt => l > r,
+                    _ => l >= r,
+                };
+                if taken {
+                    let label = scoped(prefix, &gen.label);
+                    self.add_instance(netlist, &label, None);
+                    self.elaborate_stmts(&gen.stmts, Some(&label), scope, in_ports, netlist, drivers)?;
                 }
             }
         }
@@ -248,40 +945,54 @@ impl Library {
         &mut self,
         inst: &InstanceStmt,
         parent_prefix: Option<&str>,
-        parent_map: &HashMap<String, usize>,
+        parent: &Scope,
         netlist: &mut Netlist,
-        drivers: &mut HashMap<usize, usize>,
+        drivers: &mut Drivers,
         in_ports: &HashSet<usize>,
-        indexed_out_sigs: &mut HashSet<usize>,
     ) -> Result<(), Error> {
         if !self.entities.contains_key(&inst.entity) {
             let lower = inst.entity.to_ascii_lowercase();
             if is_primitive_name(&lower) {
-                return elaborate_primitive(inst, parent_map, netlist, drivers, in_ports, &lower);
+                if !inst.generic_map.is_empty() {
+                    return Err(spanned(format!("primitive {} has no generics", inst.entity), inst.span));
+                }
+                elaborate_primitive(inst, parent, netlist, drivers, in_ports, &lower)?;
+                let node = self.add_instance(netlist, &scoped(parent_prefix, &inst.name), Some(lower));
+                netlist.instances[node].ports = primitive_ports(inst, parent);
+                netlist.primitive_instances.push(node);
+                return Ok(());
             }
-            return Err(Error::new(format!("unknown entity {}", inst.entity)));
+            return Err(spanned(format!("unknown entity {}", inst.entity), inst.span));
         }
-        let ent = self
-            .entities
-            .get(&inst.entity)
-            .ok_or_else(|| Error::new(format!("unknown entity {}", inst.entity)))?
-            .clone();
+        let ent = self.entity(&inst.entity)?;
+
+        let mut overrides: HashMap<String, i64> = HashMap::new();
+        for assoc in &inst.generic_map {
+            let value = parent.eval(&assoc.value)?;
+            if overrides.insert(assoc.name.clone(), value).is_some() {
+                return Err(spanned(format!("duplicate generic mapping for {}", assoc.name), inst.span));
+            }
+        }
+        let child = self.entity_scope(&ent, &overrides).map_err(|err| match err.span {
+            Some(_) => err,
+            None => spanned(err.message, inst.span),
+        })?;
 
         let mut assoc_map: HashMap<String, &Assoc> = HashMap::new();
         for assoc in &inst.port_map {
             if assoc_map.insert(assoc.port.clone(), assoc).is_some() {
-                return Err(Error::new(format!(
-                    "duplicate port mapping for {}",
-                    assoc.port
-                )));
+                return Err(spanned(
+                    format!("duplicate port mapping for {}", assoc.port),
+                    assoc.span.or(inst.span),
+                ));
             }
         }
         for assoc in &inst.port_map {
             if !ent.ports.iter().any(|p| p.name == assoc.port) {
-                return Err(Error::new(format!(
-                    "unknown port {} on {}",
-                    assoc.port, ent.name
-                )));
+                return Err(spanned(
+                    format!("unknown port {} on {}", assoc.port, ent.name),
+                    assoc.span.or(inst.span),
+                ));
             }
         }
 
@@ -291,54 +1002,70 @@ impl Library {
         for port in &ent.ports {
             let assoc = assoc_map
                 .get(&port.name)
-                .ok_or_else(|| Error::new(format!("missing port mapping for {}", port.name)))?;
+                .ok_or_else(|| spanned(format!("missing port mapping for {}", port.name), inst.span))?;
             match port.dir {
                 Direction::In => {
-                    let port_width = type_width(&port.ty);
-                    let expr_width = expr_width(&assoc.expr, parent_map, netlist)?;
+                    let shape = resolve_type(&port.ty, &child.consts, &child.types)?;
+                    let port_width = shape.width;
+                    check_aggregates(&assoc.expr, parent, netlist)?;
+                    let expr_width = match &assoc.expr {
+                        Expr::Aggregate { range: None, .. } => port_width,
+                        expr => expr_width(expr, parent, netlist)?,
+                    };
                     if expr_width != port_width {
-                        return Err(Error::new(format!(
-                            "port width mismatch for {}",
-                            port.name
-                        )));
+                        return Err(spanned(
+                            format!("port width mismatch for {}", port.name),
+                            assoc.span.or(inst.span),
+                        ));
                     }
-                    let expr = convert_expr(&assoc.expr, parent_map)?;
+                    let expr = convert_value(&assoc.expr, port_width, parent)?;
                     let sig_name = scoped(Some(&inst_name), &port.name);
-                    let id = define_signal(netlist, &sig_name, &port.ty, Some(port.dir.clone()))?;
+                    let id = define_signal(netlist, &sig_name, &shape, Some(port.dir.clone()))?;
                     let target = TargetRef { signal: id, sel: None };
                     netlist.assigns.push(AssignNet {
                         target: target.clone(),
                         expr,
+                        span: assoc.span.or(inst.span),
+                        scope: self.instance_ids[parent_prefix.unwrap_or("")],
                     });
-                    register_driver(&target, in_ports, drivers)?;
+                    register_driver(&target, in_ports, netlist, drivers)?;
                     mapping.insert(port.name.clone(), id);
                 }
                 Direction::Out => {
                     let target_ast = match &assoc.expr {
+                        // `open`: the port drives a signal of its own that nothing reads
+                        Expr::Target(t) if t.name.eq_ignore_ascii_case("open") && t.sel.is_none() => {
+                            let shape = resolve_type(&port.ty, &child.consts, &child.types)?;
+                            let sig_name = scoped(Some(&inst_name), &port.name);
+                            let id = define_signal(netlist, &sig_name, &shape, Some(port.dir.clone()))?;
+                            mapping.insert(port.name.clone(), id);
+                            continue;
+                        }
                         Expr::Target(t) => t,
                         _ => {
-                            return Err(Error::new(format!(
-                                "out port {} must map to a signal",
-                                port.name
-                            )))
+                            return Err(spanned(
+                                format!("out port {} must map to a signal", port.name),
+                                assoc.span.or(inst.span),
+                            ))
                         }
                     };
-                    let target_ref = convert_target(target_ast, parent_map)?;
-                    let port_width = type_width(&port.ty);
+                    let target_ref = convert_target(target_ast, parent)?;
+                    let shape = resolve_type(&port.ty, &child.consts, &child.types)?;
+                    let port_width = shape.width;
                     // For indexed targets, check the selection width, not the full signal width
                     let target_width = if let Some(ref sel) = target_ref.sel {
-                        match sel {
-                            Selector::Index(_) => 1,
-                            Selector::Range { msb, lsb, .. } => (msb - lsb).unsigned_abs() as usize + 1,
-                        }
+                        selector_width(sel)
                     } else {
                         netlist.signals[target_ref.signal].width
                     };
                     if target_width != port_width {
-                        return Err(Error::new(format!(
-                            "port width mismatch for {} (expected {}, got {})",
-                            port.name, port_width, target_width
-                        )));
+                        return Err(spanned(
+                            format!(
+                                "port width mismatch for {} (expected {}, got {})",
+                                port.name, port_width, target_width
+                            ),
+                            assoc.span.or(inst.span),
+                        ));
                     }
 
                     // If output target has a selection (e.g., y(0)), create an intermediate signal
@@ -346,7 +1073,7 @@ impl Library {
                     if target_ref.sel.is_some() {
                         // Create intermediate signal for the child's output port
                         let inter_name = scoped(Some(&inst_name), &format!("{}_out", port.name));
-                        let inter_id = define_signal(netlist, &inter_name, &port.ty, None)?;
+                        let inter_id = define_signal(netlist, &inter_name, &shape, None)?;
                         mapping.insert(port.name.clone(), inter_id);
 
                         // Add assignment: parent_target = intermediate_signal
@@ -354,13 +1081,10 @@ impl Library {
                         netlist.assigns.push(AssignNet {
                             target: target_ref.clone(),
                             expr: ExprRef::Target(inter_target),
+                            span: assoc.span.or(inst.span),
+                            scope: self.instance_ids[parent_prefix.unwrap_or("")],
                         });
-                        // Track this signal for indexed output - only register once per signal
-                        if !indexed_out_sigs.contains(&target_ref.signal) {
-                            indexed_out_sigs.insert(target_ref.signal);
-                            let target = TargetRef { signal: target_ref.signal, sel: None };
-                            register_driver(&target, in_ports, drivers)?;
-                        }
+                        register_driver(&target_ref, in_ports, netlist, drivers)?;
                     } else {
                         mapping.insert(port.name.clone(), target_ref.signal);
                     }
@@ -368,45 +1092,286 @@ impl Library {
             }
         }
 
-        self.elaborate_entity(&ent.name, Some(&inst_name), Some(&mapping), netlist, drivers)?;
+        self.elaborate_entity(&ent.name, Some(&inst_name), Some(&mapping), child, netlist, drivers)?;
         Ok(())
     }
 }
 
+/// Types and constants of an architecture or a package, in the order of
+/// SPECS 21.5: enumerations, then constants, then arrays
+fn declare(types: &[TypeDecl], constants: &[ConstantDecl], scope: &mut Scope) -> Result<(), Error> {
+    for decl in types {
+        let TypeDef::Enum(literals) = &decl.def else {
+            continue;
+        };
+        scope.check_new_name(&decl.name)?;
+        let ty = SignalType::Enum {
+            name: decl.name.clone(),
+            literals: literals.clone(),
+        };
+        let width = enum_width(literals.len());
+        for (code, literal) in literals.iter().enumerate() {
+            scope.check_new_name(literal)?;
+            let value = Value {
+                bits: BitVec::from_u64(width, code as u64),
+                kind: ValueKind::Literal,
+            };
+            scope.values.insert(literal.clone(), TypedValue { value, ty: ty.clone() });
+        }
+        scope.types.insert(decl.name.clone(), ty);
+    }
+
+    for decl in constants {
+        scope.check_new_name(&decl.name)?;
+        let shape = resolve_type(&decl.ty, &scope.consts, &scope.types)?;
+        match &decl.value {
+            ConstantValue::Int(expr) => {
+                let value = scope.eval(expr)?;
+                if let SignalType::Integer { low, high } = shape.ty {
+                    if value < low || value > high {
+                        return Err(Error::new(format!(
+                            "value {} out of range {} to {} for {}",
+                            value, low, high, decl.name
+                        )));
+                    }
+                }
+                scope.consts.insert(decl.name.clone(), value);
+            }
+            ConstantValue::Expr(expr) => {
+                let value = constant_value(&decl.name, expr, &shape, scope)?;
+                scope.values.insert(decl.name.clone(), TypedValue { value, ty: shape.ty });
+            }
+        }
+    }
+
+    // Array ranges may use the constants
+    for decl in types {
+        let TypeDef::Array { left, right, dir, elem } = &decl.def else {
+            continue;
+        };
+        scope.check_new_name(&decl.name)?;
+        let (left, right) = (scope.eval(left)?, scope.eval(right)?);
+        let (low, high) = match dir {
+            RangeDir::To => (left, right),
+            RangeDir::Downto => (right, left),
+        };
+        if low > high {
+            return Err(Error::new(format!("null range for array type {}", decl.name)));
+        }
+        let elem = resolve_type(elem, &scope.consts, &scope.types)?;
+        if matches!(elem.ty, SignalType::Array { .. }) {
+            return Err(Error::new(format!("array type {} of arrays is not supported", decl.name)));
+        }
+        checked_width((high.abs_diff(low) as u128 + 1) * elem.width as u128)?;
+        let ty = SignalType::Array {
+            low,
+            high,
+            elem_width: elem.width,
+        };
+        scope.types.insert(decl.name.clone(), ty);
+    }
+    Ok(())
+}
+
+/// Bit layout and read-back type of a declared signal, port or constant
+struct Shape {
+    msb: i64,
+    lsb: i64,
+    dir: RangeDir,
+    width: usize,
+    ty: SignalType,
+}
+
+fn resolve_type(
+    ty: &Type,
+    consts: &HashMap<String, i64>,
+    types: &HashMap<String, SignalType>,
+) -> Result<Shape, Error> {
+    let vector = |width: usize, ty: SignalType| Shape {
+        msb: width as i64 - 1,
+        lsb: 0,
+        dir: RangeDir::Downto,
+        width,
+        ty,
+    };
+    Ok(match ty {
+        Type::Bit => vector(1, SignalType::Bits),
+        Type::Bits { msb, lsb, dir } => {
+            let (msb, lsb) = (eval_int(msb, consts)?, eval_int(lsb, consts)?);
+            Shape {
+                msb,
+                lsb,
+                dir: dir.clone(),
+                width: checked_width(msb.abs_diff(lsb) as u128 + 1)?,
+                ty: SignalType::Bits,
+            }
+        }
+        Type::Integer { low, high } => {
+            let (low, high) = (eval_int(low, consts)?, eval_int(high, consts)?);
+            if low > high {
+                return Err(Error::new(format!("null range {} to {}", low, high)));
+            }
+            // Unsigned when the range has no negative value, two's complement otherwise
+            let width = if low >= 0 {
+                (64 - high.leading_zeros() as usize).max(1)
+            } else {
+                int_min_width(low).max(int_min_width(high))
+            };
+            vector(width, SignalType::Integer { low, high })
+        }
+        Type::Named(name) => {
+            let ty = types
+                .get(name)
+                .ok_or_else(|| Error::new(format!("unknown type {}", name)))?;
+            let width = match ty {
+                SignalType::Enum { literals, .. } => enum_width(literals.len()),
+                SignalType::Array { low, high, elem_width } => {
+                    checked_width((high.abs_diff(*low) as u128 + 1) * *elem_width as u128)?
+                }
+                _ => return Err(Error::new(format!("{} is not a type", name))),
+            };
+            vector(width, ty.clone())
+        }
+    })
+}
+
+/// `width` bits, unless it exceeds `MAX_WIDTH`
+fn checked_width(width: u128) -> Result<usize, Error> {
+    if width > MAX_WIDTH as u128 {
+        return Err(Error::new(format!("signal of {} bits is too wide (at most {})", width, MAX_WIDTH)));
+    }
+    Ok(width as usize)
+}
+
+/// Ports only see integer constants: enumeration types are declared in architectures
+/// Bits needed to encode `count` enumeration literals
+fn enum_width(count: usize) -> usize {
+    (usize::BITS - count.saturating_sub(1).leading_zeros()).max(1) as usize
+}
+
 fn define_signal(
     netlist: &mut Netlist,
     name: &str,
-    ty: &Type,
+    shape: &Shape,
     port_dir: Option<Direction>,
 ) -> Result<usize, Error> {
     if netlist.name_to_id.contains_key(name) {
         return Err(Error::new(format!("duplicate signal {}", name)));
     }
-    let (msb, lsb, dir, width) = match ty {
-        Type::Bit => (0, 0, RangeDir::Downto, 1usize),
-        Type::Bits { msb, lsb, dir } => {
-            let w = (msb - lsb).abs() as usize + 1;
-            (*msb, *lsb, dir.clone(), w)
-        }
-    };
     let id = netlist.signals.len();
     netlist.signals.push(Signal {
         name: name.to_string(),
-        width,
-        msb,
-        lsb,
-        dir,
-        value: BitVec::new(width, 0),
+        width: shape.width,
+        msb: shape.msb,
+        lsb: shape.lsb,
+        dir: shape.dir.clone(),
+        value: BitVec::new(shape.width, 0),
         port_dir,
+        ty: shape.ty.clone(),
     });
     netlist.name_to_id.insert(name.to_string(), id);
     Ok(id)
 }
 
-fn type_width(ty: &Type) -> usize {
+/// Type rules for assigning `expr` to `name`: an enumeration only takes its
+/// own literals and signals, an integer only constants within its range
+fn check_assign_type(name: &str, ty: &SignalType, expr: &Expr, scope: &Scope) -> Result<(), Error> {
+    if let Expr::When { value, otherwise, .. } = expr {
+        check_assign_type(name, ty, value, scope)?;
+        return check_assign_type(name, ty, otherwise, scope);
+    }
+    let source = scope.expr_type(expr);
     match ty {
-        Type::Bit => 1,
-        Type::Bits { msb, lsb, .. } => (*msb - *lsb).abs() as usize + 1,
+        SignalType::Bits | SignalType::Array { .. } => Ok(()),
+        SignalType::Enum { name: ty_name, .. } => match source {
+            Some(src) if src == ty => Ok(()),
+            Some(src) => Err(Error::new(format!(
+                "type mismatch for {}: expected {}, got {}",
+                name,
+                ty_name,
+                src.describe()
+            ))),
+            None if matches!(expr, Expr::Literal(_)) || scope.const_value(expr).is_some() => Err(Error::new(format!(
+                "type mismatch for {}: expected a literal of {}",
+                name, ty_name
+            ))),
+            None => Ok(()),
+        },
+        SignalType::Integer { low, high } => {
+            if let Some(src @ SignalType::Enum { .. }) = source {
+                return Err(Error::new(format!(
+                    "type mismatch for {}: expected {}, got {}",
+                    name,
+                    ty.describe(),
+                    src.describe()
+                )));
+            }
+            match scope.const_value(expr) {
+                Some(v) if v < *low || v > *high => Err(Error::new(format!(
+                    "value {} out of range {} to {} for {}",
+                    v, low, high, name
+                ))),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+/// Checks an assignment to a whole enumeration or integer signal
+fn check_target_assign(target: &Target, expr: &Expr, scope: &Scope) -> Result<(), Error> {
+    if target.sel.is_some() {
+        return Ok(());
+    }
+    match scope.signals.get(&target.name).and_then(|id| scope.signal_types.get(id)) {
+        Some(ty) => check_assign_type(&target.name, ty, expr, scope),
+        None => Ok(()),
+    }
+}
+
+/// Folds the value of a non-integer constant to a literal of its type's width
+fn constant_value(name: &str, expr: &Expr, shape: &Shape, scope: &Scope) -> Result<Value, Error> {
+    check_assign_type(name, &shape.ty, expr, scope)?;
+    if let Some(v) = scope.const_value(expr) {
+        let fits = if v >= 0 {
+            shape.width >= 64 || v >> shape.width == 0
+        } else {
+            int_min_width(v) <= shape.width
+        };
+        if !fits {
+            return Err(Error::new(format!(
+                "value {} does not fit constant {} ({} bits)",
+                v, name, shape.width
+            )));
+        }
+        return literal_to_value(&Literal::Int(v)).map(|val| Value {
+            bits: val.bits.resize_zero(shape.width),
+            kind: val.kind,
+        });
+    }
+    let value = match convert_value(expr, shape.width, scope)? {
+        ExprRef::Literal(value) => value,
+        _ => {
+            return Err(Error::new(format!(
+                "constant {} must be a literal or another constant",
+                name
+            )))
+        }
+    };
+    if value.bits.width() != shape.width {
+        return Err(Error::new(format!(
+            "width mismatch for constant {} (expected {}, got {})",
+            name,
+            shape.width,
+            value.bits.width()
+        )));
+    }
+    Ok(value)
+}
+
+fn selector_width(sel: &Selector) -> usize {
+    match sel {
+        Selector::Index(_) => 1,
+        Selector::Range { msb, lsb, .. } => (msb.abs_diff(*lsb) as usize).saturating_add(1),
     }
 }
 
@@ -417,32 +1382,111 @@ fn scoped(prefix: Option<&str>, name: &str) -> String {
     }
 }
 
-fn convert_expr(expr: &Expr, mapping: &HashMap<String, usize>) -> Result<ExprRef, Error> {
+fn convert_expr(expr: &Expr, scope: &Scope) -> Result<ExprRef, Error> {
     Ok(match expr {
         Expr::Literal(lit) => ExprRef::Literal(literal_to_value(lit)?),
-        Expr::Target(t) => ExprRef::Target(convert_target(t, mapping)?),
+        Expr::Target(t) => match (scope.typed(t), scope.constant(t)) {
+            (Some(typed), _) => ExprRef::Literal(typed.value.clone()),
+            (None, Some(v)) => ExprRef::Literal(literal_to_value(&Literal::Int(v))?),
+            (None, None) => match convert_access(t, scope)? {
+                Access::Bits(target) => ExprRef::Target(target),
+                Access::Element { array, index } => ExprRef::Element {
+                    array,
+                    index: Box::new(index),
+                },
+            },
+        },
         Expr::Unary { op, expr } => ExprRef::Unary {
             op: *op,
-            expr: Box::new(convert_expr(expr, mapping)?),
+            expr: Box::new(convert_expr(expr, scope)?),
         },
         Expr::Binary { op, left, right } => ExprRef::Binary {
             op: *op,
-            left: Box::new(convert_expr(left, mapping)?),
-            right: Box::new(convert_expr(right, mapping)?),
+            left: Box::new(convert_expr(left, scope)?),
+            right: Box::new(convert_expr(right, scope)?),
         },
         Expr::Call { name, args } => {
+            let is_resize = name.eq_ignore_ascii_case("resize") || name.eq_ignore_ascii_case("sresize");
             let mut out = Vec::new();
-            for a in args {
-                out.push(convert_expr(a, mapping)?);
+            for (i, a) in args.iter().enumerate() {
+                match scope.const_value(a) {
+                    // Constant widths are folded so `resize(a, N - 1)` stays a literal
+                    Some(v) if is_resize && i == 1 => out.push(ExprRef::Literal(literal_to_value(&Literal::Int(v))?)),
+                    _ => out.push(convert_expr(a, scope)?),
+                }
             }
             ExprRef::Call {
                 name: name.clone(),
                 args: out,
             }
         }
+        Expr::When { value, cond, otherwise } => ExprRef::Select {
+            cond: Box::new(convert_expr(cond, scope)?),
+            then: Box::new(convert_expr(value, scope)?),
+            otherwise: Box::new(convert_expr(otherwise, scope)?),
+        },
+        Expr::Aggregate { range: Some((left, right, _)), value } => {
+            let width = scope.eval(left)?.abs_diff(scope.eval(right)?) as usize + 1;
+            convert_aggregate(value, width, scope)?
+        }
+        Expr::Aggregate { range: None, .. } => {
+            return Err(Error::new("(others => ...) only allowed as the whole value of an assignment"))
+        }
+    })
+}
+
+/// Value assigned to `width` bits: `(others => x)` is allowed there, and in
+/// each choice of a `when ... else`
+fn convert_value(expr: &Expr, width: usize, scope: &Scope) -> Result<ExprRef, Error> {
+    Ok(match expr {
+        Expr::When { value, cond, otherwise } => ExprRef::Select {
+            cond: Box::new(convert_expr(cond, scope)?),
+            then: Box::new(convert_value(value, width, scope)?),
+            otherwise: Box::new(convert_value(otherwise, width, scope)?),
+        },
+        Expr::Aggregate { range: None, value } => convert_aggregate(value, width, scope)?,
+        _ => convert_expr(expr, scope)?,
+    })
+}
+
+/// The bit `value` on `width` positions: a literal when it is constant,
+/// otherwise its sign extension
+fn convert_aggregate(value: &Expr, width: usize, scope: &Scope) -> Result<ExprRef, Error> {
+    Ok(match convert_expr(value, scope)? {
+        ExprRef::Literal(v) if v.bits.width() == 1 => ExprRef::Literal(Value {
+            bits: BitVec::new(width, v.bits.get(0)),
+            kind: ValueKind::Literal,
+        }),
+        ExprRef::Literal(_) => return Err(Error::new("aggregate value must be a single bit")),
+        value => ExprRef::Call {
+            name: "sresize".to_string(),
+            args: vec![value, ExprRef::Literal(literal_to_value(&Literal::Int(width as i64))?)],
+        },
     })
 }
 
+/// Rejects aggregates whose value is wider than one bit
+fn check_aggregates(expr: &Expr, scope: &Scope, netlist: &Netlist) -> Result<(), Error> {
+    match expr {
+        Expr::Literal(_) | Expr::Target(_) => Ok(()),
+        Expr::Unary { expr, .. } => check_aggregates(expr, scope, netlist),
+        Expr::Binary { left, right, .. } => {
+            check_aggregates(left, scope, netlist)?;
+            check_aggregates(right, scope, netlist)
+        }
+        Expr::Call { args, .. } => args.iter().try_for_each(|a| check_aggregates(a, scope, netlist)),
+        Expr::When { value, cond, otherwise } => {
+            check_aggregates(value, scope, netlist)?;
+            check_aggregates(cond, scope, netlist)?;
+            check_aggregates(otherwise, scope, netlist)
+        }
+        Expr::Aggregate { value, .. } => match expr_width(value, scope, netlist)? {
+            1 => check_aggregates(value, scope, netlist),
+            _ => Err(Error::new("aggregate value must be a single bit")),
+        },
+    }
+}
+
 fn literal_to_value(lit: &Literal) -> Result<Value, Error> {
     let (bits, kind) = match lit {
         Literal::Bit(b) => (BitVec::new(1, if *b { 1 } else { 0 }), ValueKind::Literal),
@@ -477,40 +1521,118 @@ fn int_min_width(v: i64) -> usize {
     }
 }
 
-fn convert_target(target: &Target, mapping: &HashMap<String, usize>) -> Result<TargetRef, Error> {
-    let signal = mapping
-        .get(&target.name)
-        .ok_or_else(|| Error::new(format!("unknown signal {}", target.name)))?;
-    Ok(TargetRef {
-        signal: *signal,
-        sel: target.sel.clone(),
+/// Bits a target designates: fixed bits of a signal, or an array element
+/// picked at run time
+enum Access {
+    Bits(TargetRef),
+    Element { array: usize, index: ExprRef },
+}
+
+fn convert_access(target: &Target, scope: &Scope) -> Result<Access, Error> {
+    let signal = scope.signal(&target.name)?;
+    let (Some(sel), Some((low, high, width))) = (&target.sel, scope.array(signal)) else {
+        if let Some(SelectorExpr::Index(i)) = &target.sel {
+            // `y(sel)` with a signal index: say so rather than "unknown constant"
+            if scope.eval(i).is_err() && int_to_expr(i).is_some_and(|e| convert_expr(&e, scope).is_ok()) {
+                return Err(Error::new("run-time index only allowed on arrays"));
+            }
+        }
+        return Ok(Access::Bits(TargetRef {
+            signal,
+            sel: target.sel.as_ref().map(|s| scope.selector(s)).transpose()?,
+        }));
+    };
+    let index = match sel {
+        SelectorExpr::Range { .. } => return Err(Error::new(format!("cannot slice array {}", target.name))),
+        SelectorExpr::Expr(expr) => expr.as_ref().clone(),
+        SelectorExpr::Index(i) => match scope.eval(i) {
+            // Constant index: a plain slice of the flattened array
+            Ok(i) => {
+                if i < low || i > high {
+                    return Err(Error::new(format!(
+                        "index {} out of range {} to {} for {}",
+                        i, low, high, target.name
+                    )));
+                }
+                let lo = (i - low) * width as i64;
+                let sel = match width {
+                    1 => Selector::Index(lo),
+                    _ => Selector::Range {
+                        msb: lo + width as i64 - 1,
+                        lsb: lo,
+                        dir: RangeDir::Downto,
+                    },
+                };
+                return Ok(Access::Bits(TargetRef { signal, sel: Some(sel) }));
+            }
+            Err(err) => int_to_expr(i).ok_or(err)?,
+        },
+    };
+    Ok(Access::Element {
+        array: signal,
+        index: convert_expr(&index, scope)?,
     })
 }
 
-fn target_width(
-    target: &Target,
-    mapping: &HashMap<String, usize>,
-    netlist: &Netlist,
-) -> Result<usize, Error> {
-    let signal = mapping
-        .get(&target.name)
-        .ok_or_else(|| Error::new(format!("unknown signal {}", target.name)))?;
-    let sig = &netlist.signals[*signal];
+/// Integer expression naming signals, read as a run-time index; `None`
+/// with `*` or `/`, which have no run-time counterpart
+fn int_to_expr(expr: &IntExpr) -> Option<Expr> {
+    Some(match expr {
+        IntExpr::Lit(v) => Expr::Literal(Literal::Int(*v)),
+        IntExpr::Name(name) => Expr::Target(Target {
+            name: name.clone(),
+            sel: None,
+            span: None,
+        }),
+        IntExpr::Neg(e) => Expr::Unary {
+            op: UnaryOp::Neg,
+            expr: Box::new(int_to_expr(e)?),
+        },
+        IntExpr::Binary { op, left, right } => Expr::Binary {
+            op: match op {
+                IntOp::Add => BinaryOp::Add,
+                IntOp::Sub => BinaryOp::Sub,
+                IntOp::Mul | IntOp::Div => return None,
+            },
+            left: Box::new(int_to_expr(left)?),
+            right: Box::new(int_to_expr(right)?),
+        },
+    })
+}
+
+fn convert_target(target: &Target, scope: &Scope) -> Result<TargetRef, Error> {
+    match convert_access(target, scope)? {
+        Access::Bits(target) => Ok(target),
+        Access::Element { .. } => Err(Error::new(format!(
+            "run-time index of {} only allowed in expressions and process assignments",
+            target.name
+        ))),
+    }
+}
+
+fn target_width(target: &Target, scope: &Scope, netlist: &Netlist) -> Result<usize, Error> {
+    let signal = scope.signal(&target.name)?;
+    if let (Some(_), Some((_, _, width))) = (&target.sel, scope.array(signal)) {
+        return Ok(width);
+    }
     Ok(match &target.sel {
-        None => sig.width,
-        Some(Selector::Index(_)) => 1,
-        Some(Selector::Range { msb, lsb, .. }) => (*msb - *lsb).abs() as usize + 1,
+        None => netlist.signals[signal].width,
+        Some(sel) => selector_width(&scope.selector(sel)?),
     })
 }
 
-fn expr_width(expr: &Expr, mapping: &HashMap<String, usize>, netlist: &Netlist) -> Result<usize, Error> {
+fn expr_width(expr: &Expr, scope: &Scope, netlist: &Netlist) -> Result<usize, Error> {
     Ok(match expr {
         Expr::Literal(lit) => literal_to_value(lit)?.bits.width(),
-        Expr::Target(t) => target_width(t, mapping, netlist)?,
-        Expr::Unary { expr, .. } => expr_width(expr, mapping, netlist)?,
+        Expr::Target(t) => match (scope.typed(t), scope.constant(t)) {
+            (Some(typed), _) => typed.value.bits.width(),
+            (None, Some(v)) => literal_to_value(&Literal::Int(v))?.bits.width(),
+            (None, None) => target_width(t, scope, netlist)?,
+        },
+        Expr::Unary { expr, .. } => expr_width(expr, scope, netlist)?,
         Expr::Binary { op, left, right } => {
-            let lw = expr_width(left, mapping, netlist)?;
-            let rw = expr_width(right, mapping, netlist)?;
+            let lw = expr_width(left, scope, netlist)?;
+            let rw = expr_width(right, scope, netlist)?;
             match op {
                 BinaryOp::Concat => lw + rw,
                 BinaryOp::Shl | BinaryOp::Shr => lw,
@@ -524,126 +1646,283 @@ fn expr_width(expr: &Expr, mapping: &HashMap<String, usize>, netlist: &Netlist)
                 if args.len() != 2 {
                     return Err(Error::new("resize expects 2 args"));
                 }
-                match &args[1] {
-                    Expr::Literal(Literal::Int(v)) => {
-                        if *v <= 0 {
+                match scope.const_value(&args[1]) {
+                    Some(v) => {
+                        if v <= 0 {
                             return Err(Error::new("resize width must be positive"));
                         }
-                        *v as usize
+                        v as usize
                     }
-                    _ => return Err(Error::new("resize width must be an integer literal")),
+                    None => return Err(Error::new("resize width must be a constant integer")),
                 }
-            } else if lower == "rising_edge" {
+            } else if Edge::from_call(&lower).is_some() {
                 1
             } else {
                 return Err(Error::new("unsupported function in width check"));
             }
         }
+        Expr::When { value, otherwise, .. } => {
+            // An `(others => x)` choice takes the width of the other one
+            match (expr_width(value, scope, netlist), expr_width(otherwise, scope, netlist)) {
+                (Ok(a), Ok(b)) => a.max(b),
+                (Ok(w), Err(_)) if is_others(otherwise) => w,
+                (Err(_), Ok(w)) if is_others(value) => w,
+                (a, b) => a.and(b)?,
+            }
+        }
+        Expr::Aggregate { range: Some((left, right, _)), .. } => {
+            scope.eval(left)?.abs_diff(scope.eval(right)?) as usize + 1
+        }
+        Expr::Aggregate { range: None, .. } => return Err(Error::new("(others => ...) has no width of its own")),
     })
 }
 
-fn convert_process(proc: &ProcessStmt, mapping: &HashMap<String, usize>) -> Result<ProcessNet, Error> {
+/// `(others => x)`, possibly as a choice of a `when ... else`
+fn is_others(expr: &Expr) -> bool {
+    match expr {
+        Expr::Aggregate { range, .. } => range.is_none(),
+        Expr::When { value, otherwise, .. } => is_others(value) && is_others(otherwise),
+        _ => false,
+    }
+}
+
+fn convert_process(proc: &ProcessStmt, scope: &Scope, netlist: &Netlist) -> Result<ProcessNet, Error> {
     if proc.stmts.is_empty() {
         return Err(Error::new("process has no statements"));
     }
-    let (guard, body) = match &proc.stmts[0] {
-        SeqStmt::If(ifstmt) => (ifstmt, &ifstmt.then_stmts),
-        _ => return Err(Error::new("process must start with rising_edge guard")),
+    let top = match &proc.stmts[0] {
+        SeqStmt::If(ifstmt) => ifstmt,
+        _ => return Err(Error::new("process must start with a rising_edge or falling_edge guard")),
     };
 
-    match &guard.cond {
+    // Either `if rising_edge(clk) then ...` or, with an asynchronous reset,
+    // `if rst = '1' then ... elsif rising_edge(clk) then ...`
+    let is_edge = |e: &Expr| matches!(e, Expr::Call { name, .. } if Edge::from_call(name).is_some());
+    let async_reset = expr_edge(&top.cond).is_none() && top.elsif.first().is_some_and(|(cond, _)| is_edge(cond));
+    let (guard, body, elsif) = if async_reset {
+        if top.elsif.len() > 1 || !top.else_stmts.is_empty() || proc.stmts.len() > 1 {
+            return Err(Error::new(
+                "asynchronous reset process must be: if rst = '1' then ... elsif rising_edge(clk) then ... end if",
+            ));
+        }
+        (&top.elsif[0].0, &top.elsif[0].1, &top.elsif[1..])
+    } else {
+        (&top.cond, &top.then_stmts, &top.elsif[..])
+    };
+
+    let (edge, clk) = match guard {
         Expr::Call { name, args } => {
-            if !name.eq_ignore_ascii_case("rising_edge") {
-                return Err(Error::new("process guard must be rising_edge"));
-            }
+            let edge = Edge::from_call(name)
+                .ok_or_else(|| Error::new("process guard must be rising_edge or falling_edge"))?;
             if args.len() != 1 {
-                return Err(Error::new("rising_edge expects 1 arg"));
+                return Err(Error::new(format!("{} expects 1 arg", edge.as_str())));
             }
             if let Expr::Target(t) = &args[0] {
-                if !t.name.eq_ignore_ascii_case(&proc.clk) {
-                    return Err(Error::new("rising_edge clock name mismatch"));
+                if !proc.sensitivity.iter().any(|s| t.name.eq_ignore_ascii_case(s)) {
+                    return Err(Error::new(format!("{} clock name mismatch", edge.as_str())));
                 }
+                (edge, t.name.as_str())
             } else {
-                return Err(Error::new("rising_edge arg must be a signal"));
+                return Err(Error::new(format!("{} arg must be a signal", edge.as_str())));
             }
         }
-        _ => return Err(Error::new("process guard must be rising_edge")),
-    }
+        _ => return Err(Error::new("process guard must be rising_edge or falling_edge")),
+    };
 
-    let mut out = Vec::new();
-    for s in body {
-        out.push(convert_seq_stmt(s, mapping)?);
+    // The guard is the only place an edge may be tested: a second edge
+    // (elsif falling_edge, nested rising_edge, ...) has no hardware meaning
+    let reset_stmts = if async_reset { &top.then_stmts[..] } else { &[] };
+    let stray = elsif
+        .iter()
+        .find_map(|(cond, stmts)| expr_edge(cond).or_else(|| stmts.iter().find_map(stmt_edge)))
+        .or_else(|| {
+            body.iter()
+                .chain(reset_stmts)
+                .chain(&top.else_stmts)
+                .chain(&proc.stmts[1..])
+                .find_map(stmt_edge)
+        });
+    if let Some(other) = stray {
+        return Err(Error::new(if other == edge {
+            format!("{} only allowed as the process guard", other.as_str())
+        } else {
+            format!("process on {} mixes rising_edge and falling_edge", clk)
+        }));
     }
+
+    let reset = if async_reset {
+        let rst = match &top.cond {
+            Expr::Binary {
+                op: BinaryOp::Eq,
+                left,
+                right,
+            } => match (left.as_ref(), right.as_ref()) {
+                (Expr::Target(t), Expr::Literal(Literal::Bit(_))) if t.sel.is_none() => t.name.as_str(),
+                _ => return Err(Error::new("asynchronous reset condition must be rst = '1' or rst = '0'")),
+            },
+            _ => return Err(Error::new("asynchronous reset condition must be rst = '1' or rst = '0'")),
+        };
+        if rst.eq_ignore_ascii_case(clk) || !proc.sensitivity.iter().any(|s| rst.eq_ignore_ascii_case(s)) {
+            return Err(Error::new(format!(
+                "asynchronous reset {} missing from the process sensitivity list",
+                rst
+            )));
+        }
+        if proc.sensitivity.len() != 2 {
+            return Err(Error::new(format!(
+                "process sensitivity list must be ({}, {})",
+                clk, rst
+            )));
+        }
+        Some(AsyncReset {
+            signal: scope.signal(rst)?,
+            cond: convert_expr(&top.cond, scope)?,
+            stmts: convert_seq_block(&top.then_stmts, scope, netlist)?,
+        })
+    } else {
+        if proc.sensitivity.len() != 1 {
+            return Err(Error::new(format!(
+                "process without asynchronous reset must be sensitive to {} only",
+                clk
+            )));
+        }
+        None
+    };
+
     Ok(ProcessNet {
-        clk: proc.clk.clone(),
-        stmts: out,
+        clk: scope.signal(clk)?,
+        edge,
+        stmts: convert_seq_block(body, scope, netlist)?,
+        reset,
+        scope: 0,
     })
 }
 
-fn convert_seq_stmt(stmt: &SeqStmt, mapping: &HashMap<String, usize>) -> Result<SeqStmtRef, Error> {
+fn expr_edge(expr: &Expr) -> Option<Edge> {
+    match expr {
+        Expr::Call { name, args } => Edge::from_call(name).or_else(|| args.iter().find_map(expr_edge)),
+        Expr::Unary { expr, .. } => expr_edge(expr),
+        Expr::Binary { left, right, .. } => expr_edge(left).or_else(|| expr_edge(right)),
+        Expr::When { value, cond, otherwise } => expr_edge(cond)
+            .or_else(|| expr_edge(value))
+            .or_else(|| expr_edge(otherwise)),
+        Expr::Aggregate { value, .. } => expr_edge(value),
+        _ => None,
+    }
+}
+
+fn stmt_edge(stmt: &SeqStmt) -> Option<Edge> {
+    match stmt {
+        SeqStmt::Assign(a) => expr_edge(&a.expr),
+        SeqStmt::If(i) => expr_edge(&i.cond)
+            .or_else(|| i.then_stmts.iter().find_map(stmt_edge))
+            .or_else(|| {
+                i.elsif
+                    .iter()
+                    .find_map(|(c, b)| expr_edge(c).or_else(|| b.iter().find_map(stmt_edge)))
+            })
+            .or_else(|| i.else_stmts.iter().find_map(stmt_edge)),
+        SeqStmt::Case(c) => expr_edge(&c.expr)
+            .or_else(|| c.arms.iter().find_map(|(_, b)| b.iter().find_map(stmt_edge))),
+    }
+}
+
+fn convert_seq_stmt(stmt: &SeqStmt, scope: &Scope, netlist: &Netlist) -> Result<SeqStmtRef, Error> {
     Ok(match stmt {
-        SeqStmt::Assign(a) => SeqStmtRef::Assign(convert_target(&a.target, mapping)?, convert_expr(&a.expr, mapping)?),
+        SeqStmt::Assign(a) => {
+            check_target_assign(&a.target, &a.expr, scope)?;
+            check_aggregates(&a.expr, scope, netlist)?;
+            let expr = convert_value(&a.expr, target_width(&a.target, scope, netlist)?, scope)?;
+            match convert_access(&a.target, scope)? {
+                Access::Bits(target) => SeqStmtRef::Assign(target, expr),
+                Access::Element { array, index } => SeqStmtRef::AssignElement { array, index, expr },
+            }
+        }
         SeqStmt::If(i) => SeqStmtRef::If(IfRef {
-            cond: convert_expr(&i.cond, mapping)?,
-            then_stmts: convert_seq_block(&i.then_stmts, mapping)?,
-            elsif: convert_elsif(&i.elsif, mapping)?,
-            else_stmts: convert_seq_block(&i.else_stmts, mapping)?,
+            cond: convert_expr(&i.cond, scope)?,
+            then_stmts: convert_seq_block(&i.then_stmts, scope, netlist)?,
+            elsif: convert_elsif(&i.elsif, scope, netlist)?,
+            else_stmts: convert_seq_block(&i.else_stmts, scope, netlist)?,
         }),
         SeqStmt::Case(c) => SeqStmtRef::Case(CaseRef {
-            expr: convert_expr(&c.expr, mapping)?,
-            arms: convert_case_arms(&c.arms, mapping)?,
+            expr: convert_expr(&c.expr, scope)?,
+            arms: convert_case_arms(&c.arms, scope.expr_type(&c.expr), scope, netlist)?,
         }),
     })
 }
 
-fn convert_seq_block(stmts: &[SeqStmt], mapping: &HashMap<String, usize>) -> Result<Vec<SeqStmtRef>, Error> {
+fn convert_seq_block(stmts: &[SeqStmt], scope: &Scope, netlist: &Netlist) -> Result<Vec<SeqStmtRef>, Error> {
     let mut out = Vec::new();
     for s in stmts {
-        out.push(convert_seq_stmt(s, mapping)?);
+        out.push(convert_seq_stmt(s, scope, netlist)?);
     }
     Ok(out)
 }
 
 fn convert_elsif(
     elsif: &[(Expr, Vec<SeqStmt>)],
-    mapping: &HashMap<String, usize>,
+    scope: &Scope,
+    netlist: &Netlist,
 ) -> Result<Vec<(ExprRef, Vec<SeqStmtRef>)>, Error> {
     let mut out = Vec::new();
     for (e, block) in elsif {
-        out.push((convert_expr(e, mapping)?, convert_seq_block(block, mapping)?));
+        out.push((convert_expr(e, scope)?, convert_seq_block(block, scope, netlist)?));
     }
     Ok(out)
 }
 
+/// Choices of a `case`; on an enumeration (`ty`) each choice must be one of
+/// its literals
 fn convert_case_arms(
     arms: &[(CaseChoice, Vec<SeqStmt>)],
-    mapping: &HashMap<String, usize>,
+    ty: Option<&SignalType>,
+    scope: &Scope,
+    netlist: &Netlist,
 ) -> Result<Vec<(CaseChoiceRef, Vec<SeqStmtRef>)>, Error> {
+    let enum_name = match ty {
+        Some(SignalType::Enum { name, .. }) => Some(name),
+        _ => None,
+    };
     let mut out = Vec::new();
     for (choice, block) in arms {
         let c = match choice {
-            CaseChoice::Literal(l) => CaseChoiceRef::Literal(literal_to_value(l)?),
+            CaseChoice::Literal(l) => match enum_name {
+                Some(name) => return Err(Error::new(format!("case on {} needs its literals as choices", name))),
+                None => CaseChoiceRef::Literal(literal_to_value(l)?),
+            },
             CaseChoice::Ident(id) => {
                 let target = Target {
                     name: id.clone(),
                     sel: None,
                     span: None,
                 };
-                CaseChoiceRef::Target(convert_target(&target, mapping)?)
+                if let Some(name) = enum_name {
+                    if scope.typed(&target).map(|t| &t.ty) != ty {
+                        return Err(Error::new(format!("{} is not a literal of {}", id, name)));
+                    }
+                }
+                match (scope.typed(&target), scope.constant(&target)) {
+                    (Some(typed), _) => CaseChoiceRef::Literal(typed.value.clone()),
+                    (None, Some(v)) => CaseChoiceRef::Literal(literal_to_value(&Literal::Int(v))?),
+                    (None, None) => CaseChoiceRef::Target(convert_target(&target, scope)?),
+                }
             }
             CaseChoice::Others => CaseChoiceRef::Others,
         };
-        out.push((c, convert_seq_block(block, mapping)?));
+        out.push((c, convert_seq_block(block, scope, netlist)?));
     }
     Ok(out)
 }
 
-fn collect_process_targets(stmts: &[SeqStmtRef], out: &mut HashSet<usize>) {
+pub(crate) fn collect_process_targets(stmts: &[SeqStmtRef], out: &mut HashSet<usize>) {
     for s in stmts {
         match s {
             SeqStmtRef::Assign(t, _) => {
                 out.insert(t.signal);
             }
+            SeqStmtRef::AssignElement { array, .. } => {
+                out.insert(*array);
+            }
             SeqStmtRef::If(i) => {
                 collect_process_targets(&i.then_stmts, out);
                 for (_, block) in &i.elsif {
@@ -663,17 +1942,20 @@ fn collect_process_targets(stmts: &[SeqStmtRef], out: &mut HashSet<usize>) {
 fn register_driver(
     target: &TargetRef,
     in_ports: &HashSet<usize>,
-    drivers: &mut HashMap<usize, usize>,
+    netlist: &Netlist,
+    drivers: &mut Drivers,
 ) -> Result<(), Error> {
     if in_ports.contains(&target.signal) {
         return Err(Error::new("cannot drive input port"));
     }
-    let entry = drivers.entry(target.signal).or_insert(0);
-    *entry += 1;
+    drivers
+        .entry(target.signal)
+        .or_default()
+        .push(target_span(target, &netlist.signals));
     Ok(())
 }
 
-fn is_primitive_name(name: &str) -> bool {
+pub(crate) fn is_primitive_name(name: &str) -> bool {
     matches!(name, "nand2" | "not1" | "and2" | "or2" | "xor2" | "mux2" | "dff" | "ram" | "rom")
 }
 
@@ -691,18 +1973,40 @@ fn ensure_exact_ports(assoc_map: &HashMap<String, &Assoc>, required: &[&str]) ->
     Ok(())
 }
 
-fn assoc_target_no_sel<'a>(assoc: &'a Assoc) -> Result<&'a Target, Error> {
+fn assoc_target_no_sel(assoc: &Assoc) -> Result<&Target, Error> {
     match &assoc.expr {
         Expr::Target(t) if t.sel.is_none() => Ok(t),
         _ => Err(Error::new("output port must map to a signal")),
     }
 }
 
+/// Ports of a primitive instance that are bound to a whole signal
+fn primitive_ports(inst: &InstanceStmt, scope: &Scope) -> Vec<PortBinding> {
+    inst.port_map
+        .iter()
+        .filter_map(|assoc| match &assoc.expr {
+            Expr::Target(t) if t.sel.is_none() => {
+                let signal = scope.signals.get(&t.name).copied()?;
+                let dir = match assoc.port.as_str() {
+                    "y" | "q" | "dout" => Direction::Out,
+                    _ => Direction::In,
+                };
+                Some(PortBinding {
+                    name: assoc.port.clone(),
+                    dir,
+                    signal,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
 fn elaborate_primitive(
     inst: &InstanceStmt,
-    parent_map: &HashMap<String, usize>,
+    scope: &Scope,
     netlist: &mut Netlist,
-    drivers: &mut HashMap<usize, usize>,
+    drivers: &mut Drivers,
     in_ports: &HashSet<usize>,
     kind: &str,
 ) -> Result<(), Error> {
@@ -722,30 +2026,30 @@ fn elaborate_primitive(
             let a_expr = &assoc_map["a"].expr;
             let b_expr = &assoc_map["b"].expr;
             let y_target = assoc_target_no_sel(assoc_map["y"])?;
-            let a_w = expr_width(a_expr, parent_map, netlist)?;
-            let b_w = expr_width(b_expr, parent_map, netlist)?;
-            let y_w = target_width(y_target, parent_map, netlist)?;
+            let a_w = expr_width(a_expr, scope, netlist)?;
+            let b_w = expr_width(b_expr, scope, netlist)?;
+            let y_w = target_width(y_target, scope, netlist)?;
             if a_w != b_w || a_w != y_w {
                 return Err(Error::new("nand2 width mismatch"));
             }
-            let a = convert_expr(a_expr, parent_map)?;
-            let b = convert_expr(b_expr, parent_map)?;
-            let y = convert_target(y_target, parent_map)?;
-            register_driver(&y, in_ports, drivers)?;
+            let a = convert_expr(a_expr, scope)?;
+            let b = convert_expr(b_expr, scope)?;
+            let y = convert_target(y_target, scope)?;
+            register_driver(&y, in_ports, netlist, drivers)?;
             netlist.primitives.push(PrimitiveNet::Nand2 { a, b, y });
         }
         "not1" => {
             ensure_exact_ports(&assoc_map, &["a", "y"])?;
             let a_expr = &assoc_map["a"].expr;
             let y_target = assoc_target_no_sel(assoc_map["y"])?;
-            let a_w = expr_width(a_expr, parent_map, netlist)?;
-            let y_w = target_width(y_target, parent_map, netlist)?;
+            let a_w = expr_width(a_expr, scope, netlist)?;
+            let y_w = target_width(y_target, scope, netlist)?;
             if a_w != y_w {
                 return Err(Error::new("not1 width mismatch"));
             }
-            let a = convert_expr(a_expr, parent_map)?;
-            let y = convert_target(y_target, parent_map)?;
-            register_driver(&y, in_ports, drivers)?;
+            let a = convert_expr(a_expr, scope)?;
+            let y = convert_target(y_target, scope)?;
+            register_driver(&y, in_ports, netlist, drivers)?;
             netlist.primitives.push(PrimitiveNet::Not1 { a, y });
         }
         "and2" | "or2" | "xor2" => {
@@ -753,16 +2057,16 @@ fn elaborate_primitive(
             let a_expr = &assoc_map["a"].expr;
             let b_expr = &assoc_map["b"].expr;
             let y_target = assoc_target_no_sel(assoc_map["y"])?;
-            let a_w = expr_width(a_expr, parent_map, netlist)?;
-            let b_w = expr_width(b_expr, parent_map, netlist)?;
-            let y_w = target_width(y_target, parent_map, netlist)?;
+            let a_w = expr_width(a_expr, scope, netlist)?;
+            let b_w = expr_width(b_expr, scope, netlist)?;
+            let y_w = target_width(y_target, scope, netlist)?;
             if a_w != b_w || a_w != y_w {
                 return Err(Error::new("bitwise primitive width mismatch"));
             }
-            let a = convert_expr(a_expr, parent_map)?;
-            let b = convert_expr(b_expr, parent_map)?;
-            let y = convert_target(y_target, parent_map)?;
-            register_driver(&y, in_ports, drivers)?;
+            let a = convert_expr(a_expr, scope)?;
+            let b = convert_expr(b_expr, scope)?;
+            let y = convert_target(y_target, scope)?;
+            register_driver(&y, in_ports, netlist, drivers)?;
             let prim = match kind {
                 "and2" => PrimitiveNet::And2 { a, b, y },
                 "or2" => PrimitiveNet::Or2 { a, b, y },
@@ -776,21 +2080,21 @@ fn elaborate_primitive(
             let b_expr = &assoc_map["b"].expr;
             let sel_expr = &assoc_map["sel"].expr;
             let y_target = assoc_target_no_sel(assoc_map["y"])?;
-            let a_w = expr_width(a_expr, parent_map, netlist)?;
-            let b_w = expr_width(b_expr, parent_map, netlist)?;
-            let sel_w = expr_width(sel_expr, parent_map, netlist)?;
-            let y_w = target_width(y_target, parent_map, netlist)?;
+            let a_w = expr_width(a_expr, scope, netlist)?;
+            let b_w = expr_width(b_expr, scope, netlist)?;
+            let sel_w = expr_width(sel_expr, scope, netlist)?;
+            let y_w = target_width(y_target, scope, netlist)?;
             if a_w != b_w || a_w != y_w {
                 return Err(Error::new("mux2 width mismatch"));
             }
             if sel_w != 1 {
                 return Err(Error::new("mux2 sel must be 1 bit"));
             }
-            let a = convert_expr(a_expr, parent_map)?;
-            let b = convert_expr(b_expr, parent_map)?;
-            let sel = convert_expr(sel_expr, parent_map)?;
-            let y = convert_target(y_target, parent_map)?;
-            register_driver(&y, in_ports, drivers)?;
+            let a = convert_expr(a_expr, scope)?;
+            let b = convert_expr(b_expr, scope)?;
+            let sel = convert_expr(sel_expr, scope)?;
+            let y = convert_target(y_target, scope)?;
+            register_driver(&y, in_ports, netlist, drivers)?;
             netlist.primitives.push(PrimitiveNet::Mux2 { a, b, sel, y });
         }
         "dff" => {
@@ -798,19 +2102,19 @@ fn elaborate_primitive(
             let clk_expr = &assoc_map["clk"].expr;
             let d_expr = &assoc_map["d"].expr;
             let q_target = assoc_target_no_sel(assoc_map["q"])?;
-            let clk_w = expr_width(clk_expr, parent_map, netlist)?;
-            let d_w = expr_width(d_expr, parent_map, netlist)?;
-            let q_w = target_width(q_target, parent_map, netlist)?;
+            let clk_w = expr_width(clk_expr, scope, netlist)?;
+            let d_w = expr_width(d_expr, scope, netlist)?;
+            let q_w = target_width(q_target, scope, netlist)?;
             if clk_w != 1 {
                 return Err(Error::new("dff clk must be 1 bit"));
             }
             if d_w != q_w {
                 return Err(Error::new("dff width mismatch"));
             }
-            let clk = convert_expr(clk_expr, parent_map)?;
-            let d = convert_expr(d_expr, parent_map)?;
-            let q = convert_target(q_target, parent_map)?;
-            register_driver(&q, in_ports, drivers)?;
+            let clk = convert_expr(clk_expr, scope)?;
+            let d = convert_expr(d_expr, scope)?;
+            let q = convert_target(q_target, scope)?;
+            register_driver(&q, in_ports, netlist, drivers)?;
             netlist.primitives.push(PrimitiveNet::Dff { clk, d, q });
         }
         "ram" => {
@@ -820,11 +2124,11 @@ fn elaborate_primitive(
             let addr_expr = &assoc_map["addr"].expr;
             let din_expr = &assoc_map["din"].expr;
             let dout_target = assoc_target_no_sel(assoc_map["dout"])?;
-            let clk_w = expr_width(clk_expr, parent_map, netlist)?;
-            let we_w = expr_width(we_expr, parent_map, netlist)?;
-            let addr_w = expr_width(addr_expr, parent_map, netlist)?;
-            let din_w = expr_width(din_expr, parent_map, netlist)?;
-            let dout_w = target_width(dout_target, parent_map, netlist)?;
+            let clk_w = expr_width(clk_expr, scope, netlist)?;
+            let we_w = expr_width(we_expr, scope, netlist)?;
+            let addr_w = expr_width(addr_expr, scope, netlist)?;
+            let din_w = expr_width(din_expr, scope, netlist)?;
+            let dout_w = target_width(dout_target, scope, netlist)?;
             if clk_w != 1 {
                 return Err(Error::new("ram clk must be 1 bit"));
             }
@@ -837,15 +2141,15 @@ fn elaborate_primitive(
             if din_w != dout_w {
                 return Err(Error::new("ram data width mismatch"));
             }
-            if addr_w as u32 >= usize::BITS {
-                return Err(Error::new("ram addr width too large"));
+            if addr_w > 64 {
+                return Err(Error::new("ram addr width too large (max 64)"));
             }
-            let clk = convert_expr(clk_expr, parent_map)?;
-            let we = convert_expr(we_expr, parent_map)?;
-            let addr = convert_expr(addr_expr, parent_map)?;
-            let din = convert_expr(din_expr, parent_map)?;
-            let dout = convert_target(dout_target, parent_map)?;
-            register_driver(&dout, in_ports, drivers)?;
+            let clk = convert_expr(clk_expr, scope)?;
+            let we = convert_expr(we_expr, scope)?;
+            let addr = convert_expr(addr_expr, scope)?;
+            let din = convert_expr(din_expr, scope)?;
+            let dout = convert_target(dout_target, scope)?;
+            register_driver(&dout, in_ports, netlist, drivers)?;
             netlist.primitives.push(PrimitiveNet::Ram {
                 clk,
                 we,
@@ -862,14 +2166,17 @@ fn elaborate_primitive(
             ensure_exact_ports(&assoc_map, &["addr", "dout"])?;
             let addr_expr = &assoc_map["addr"].expr;
             let dout_target = assoc_target_no_sel(assoc_map["dout"])?;
-            let addr_w = expr_width(addr_expr, parent_map, netlist)?;
-            let dout_w = target_width(dout_target, parent_map, netlist)?;
+            let addr_w = expr_width(addr_expr, scope, netlist)?;
+            let dout_w = target_width(dout_target, scope, netlist)?;
             if addr_w == 0 {
                 return Err(Error::new("rom addr width must be > 0"));
             }
-            let addr = convert_expr(addr_expr, parent_map)?;
-            let dout = convert_target(dout_target, parent_map)?;
-            register_driver(&dout, in_ports, drivers)?;
+            if addr_w > 64 {
+                return Err(Error::new("rom addr width too large (max 64)"));
+            }
+            let addr = convert_expr(addr_expr, scope)?;
+            let dout = convert_target(dout_target, scope)?;
+            register_driver(&dout, in_ports, netlist, drivers)?;
             let rom_index = netlist.rom_count;
             netlist.rom_count += 1;
             netlist.primitives.push(PrimitiveNet::Rom {
@@ -882,5 +2189,639 @@ fn elaborate_primitive(
         }
         _ => return Err(Error::new("unknown primitive")),
     }
+    netlist.primitive_spans.push(inst.span);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+
+    #[test]
+    fn test_comb_loop_reports_path() {
+        let hdl = r#"
+entity Osc is
+  port(en : in bit; y : out bit);
+end entity;
+
+architecture rtl of Osc is
+  signal x : bit;
+begin
+  x <= not (x and en);
+  y <= x;
+end architecture;
+"#;
+        let err = elaborate(&parse_str(hdl).unwrap(), "Osc").unwrap_err();
+        assert!(err.message.starts_with("[E504] Boucle combinatoire: x -> x."));
+        assert_eq!(err.span.map(|s| s.line), Some(9));
+    }
+
+    #[test]
+    fn test_comb_loop_through_instance() {
+        let hdl = r#"
+entity Latch is
+  port(en : in bit; y : out bit);
+end entity;
+
+architecture rtl of Latch is
+  signal x : bit;
+  signal z : bit;
+begin
+  u0: nand2 port map (a => x, b => en, y => z);
+  x <= z;
+  y <= x;
+end architecture;
+"#;
+        let err = elaborate(&parse_str(hdl).unwrap(), "Latch").unwrap_err();
+        assert!(err.message.contains("x -> z -> x") || err.message.contains("z -> x -> z"));
+        assert!(err.message.contains("z piloté ligne 10"));
+        assert!(err.message.contains("x piloté ligne 11"));
+    }
+
+    #[test]
+    fn test_partial_self_reads_are_not_loops() {
+        let shift = r#"
+entity Fill is
+  port(x : in bit; y : out bits(2 downto 0));
+end entity;
+
+architecture rtl of Fill is
+  signal a : bits(2 downto 0);
+begin
+  a <= a(1 downto 0) & x;
+  y <= a;
+end architecture;
+"#;
+        let netlist = elaborate(&parse_str(shift).unwrap(), "Fill").unwrap();
+        assert_eq!(simulate(netlist, &[("x", 1)], "y"), 0b111);
+
+        let carry = r#"
+entity Carry4 is
+  port(cin : in bit; g : in bits(3 downto 0); p : in bits(3 downto 0); cout : out bit);
+end entity;
+
+architecture rtl of Carry4 is
+  signal c : bits(4 downto 0);
+begin
+  c(0) <= cin;
+  c(4 downto 1) <= g or (p and c(3 downto 0));
+  cout <= c(4);
+end architecture;
+"#;
+        let netlist = elaborate(&parse_str(carry).unwrap(), "Carry4").unwrap();
+        assert_eq!(simulate(netlist.clone(), &[("cin", 1), ("g", 0), ("p", 0b1111)], "cout"), 1);
+        assert_eq!(simulate(netlist.clone(), &[("cin", 1), ("g", 0), ("p", 0b1011)], "cout"), 0);
+        assert_eq!(simulate(netlist, &[("cin", 0), ("g", 0b0010), ("p", 0b1100)], "cout"), 1);
+    }
+
+    #[test]
+    fn test_bit_loops_inside_one_signal() {
+        let hdl = |body: &str| {
+            format!(
+                "entity Ring is\n  port(y : out bits(2 downto 0));\nend entity;\n\narchitecture rtl of Ring is\n  \
+                 signal a : bits(2 downto 0);\nbegin\n  {}\n  y <= a;\nend architecture;\n",
+                body
+            )
+        };
+        for body in ["a <= a(1 downto 0) & a(2);", "a <= (a(2 downto 1) + 1) & '0';"] {
+            let err = elaborate(&parse_str(&hdl(body)).unwrap(), "Ring").unwrap_err();
+            assert!(err.message.starts_with("[E504] Boucle combinatoire: a -> a."), "{}: {}", body, err.message);
+        }
+    }
+
+    fn gates_lib() -> String {
+        [
+            include_str!("../../hdl_lib/gates/Nand2.hdl"),
+            include_str!("../../hdl_lib/gates/Not1.hdl"),
+            include_str!("../../hdl_lib/gates/And2.hdl"),
+            include_str!("../../hdl_lib/gates/Or2.hdl"),
+            include_str!("../../hdl_lib/gates/Mux2.hdl"),
+        ]
+        .join("\n")
+    }
+
+    fn simulate(netlist: Netlist, inputs: &[(&str, u64)], output: &str) -> u64 {
+        let mut sim = crate::sim::Simulator::new(netlist);
+        for (name, value) in inputs {
+            sim.set_signal(name, BitVec::from_u64(64, *value)).unwrap();
+        }
+        sim.eval_comb().unwrap();
+        sim.get_signal(output).unwrap().to_u64_trunc()
+    }
+
+    #[test]
+    fn test_generic_and_generate_defaults() {
+        let hdl = gates_lib() + include_str!("../../hdl_lib/multibit/AndN.hdl");
+        let netlist = elaborate(&parse_str(&hdl).unwrap(), "AndN").unwrap();
+        assert_eq!(netlist.signals[netlist.name_to_id["a"]].width, 32);
+        assert!(netlist.name_to_id.contains_key("g(31)/u/a"));
+        let y = simulate(netlist, &[("a", 0xF0F0_1234), ("b", 0xFF00_FF0F)], "y");
+        assert_eq!(y, 0xF000_1204);
+    }
+
+    #[test]
+    fn test_generic_map_and_override() {
+        let hdl = gates_lib()
+            + include_str!("../../hdl_lib/multibit/MuxN.hdl")
+            + r#"
+entity Top is
+  generic(W : integer := 4);
+  port(a : in bits(W-1 downto 0); b : in bits(W-1 downto 0); s : in bit; y : out bits(W-1 downto 0));
+end entity;
+
+architecture rtl of Top is
+begin
+  m: MuxN generic map (N => W) port map (a => a, b => b, sel => s, y => y);
+end architecture;
+"#;
+        let design = parse_str(&hdl).unwrap();
+        let netlist = elaborate(&design, "Top").unwrap();
+        assert_eq!(netlist.signals[netlist.name_to_id["m/a"]].width, 4);
+        assert_eq!(simulate(netlist, &[("a", 0x5), ("b", 0xA), ("s", 1)], "y"), 0xA);
+
+        let overrides = HashMap::from([("W".to_string(), 6)]);
+        let netlist = elaborate_with_generics(&design, "Top", &overrides).unwrap();
+        assert_eq!(netlist.signals[netlist.name_to_id["y"]].width, 6);
+        assert_eq!(simulate(netlist, &[("a", 0x25), ("b", 0x1A), ("s", 0)], "y"), 0x25);
+    }
+
+    #[test]
+    fn test_if_generate_and_loop_constants() {
+        let hdl = r#"
+entity Shift is
+  generic(N : integer := 4; REV : integer := 1);
+  port(a : in bits(N-1 downto 0); y : out bits(N-1 downto 0); h : out bits(N/2-1 downto 0); n : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Shift is
+begin
+  fwd: if REV = 0 generate
+    y <= a;
+  end generate;
+  rev: if REV /= 0 generate
+    g: for i in N-1 downto 0 generate
+      y(i) <= a(N-1-i);
+    end generate g;
+  end generate;
+  h <= a(N*2/4-1 downto 0);
+  n <= resize(N - 1, 8);
+end architecture;
+"#;
+        let design = parse_str(hdl).unwrap();
+        let netlist = elaborate(&design, "Shift").unwrap();
+        let mut sim = crate::sim::Simulator::new(netlist);
+        sim.set_signal("a", BitVec::from_u64(4, 0b0011)).unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.get_signal("y").unwrap().to_u64_trunc(), 0b1100);
+        assert_eq!(sim.get_signal("h").unwrap().width(), 2);
+        assert_eq!(sim.get_signal("h").unwrap().to_u64_trunc(), 0b11);
+        assert_eq!(sim.get_signal("n").unwrap().to_u64_trunc(), 3);
+
+        let overrides = HashMap::from([("REV".to_string(), 0)]);
+        let netlist = elaborate_with_generics(&design, "Shift", &overrides).unwrap();
+        assert_eq!(simulate(netlist, &[("a", 0b0011)], "y"), 0b0011);
+    }
+
+    #[test]
+    fn test_generic_errors() {
+        let hdl = gates_lib()
+            + include_str!("../../hdl_lib/multibit/NotN.hdl")
+            + r#"
+entity NoDefault is
+  generic(N : integer);
+  port(a : in bits(N-1 downto 0); y : out bits(N-1 downto 0));
+end entity;
+
+architecture rtl of NoDefault is
+begin
+  y <= not a;
+end architecture;
+
+entity BadMap is
+  port(a : in bits(3 downto 0); y : out bits(3 downto 0));
+end entity;
+
+architecture rtl of BadMap is
+begin
+  u: NotN generic map (M => 4) port map (a => a, y => y);
+end architecture;
+
+entity BadWidth is
+  port(a : in bits(3 downto 0); y : out bits(3 downto 0));
+end entity;
+
+architecture rtl of BadWidth is
+begin
+  u: NotN generic map (N => 8) port map (a => a, y => y);
+end architecture;
+"#;
+        let design = parse_str(&hdl).unwrap();
+        let err = elaborate(&design, "NoDefault").unwrap_err();
+        assert_eq!(err.message, "missing value for generic N of NoDefault");
+        let err = elaborate(&design, "BadMap").unwrap_err();
+        assert_eq!(err.message, "unknown generic M on NotN");
+        let err = elaborate(&design, "BadWidth").unwrap_err();
+        assert!(err.message.starts_with("port width mismatch for a"));
+    }
+
+    #[test]
+    fn test_generic_overflow_and_limits() {
+        let hdl = r#"
+entity Wide is
+  generic(N : integer := 4);
+  port(a : in bits(N-1 downto 0); y : out bit);
+end entity;
+
+architecture rtl of Wide is
+begin
+  y <= a(0);
+end architecture;
+
+entity Twice is
+  generic(N : integer := 4);
+  port(a : in bits(N*2-1 downto 0); y : out bit);
+end entity;
+
+architecture rtl of Twice is
+begin
+  y <= a(0);
+end architecture;
+
+entity Many is
+  generic(N : integer := 4);
+  port(a : in bit; y : out bit);
+end entity;
+
+architecture rtl of Many is
+  signal t : bit;
+begin
+  g: for i in 0 to N generate
+    t <= a;
+  end generate;
+  y <= t;
+end architecture;
+"#;
+        let design = parse_str(hdl).unwrap();
+        let huge = HashMap::from([("N".to_string(), i64::MAX)]);
+        let err = elaborate_with_generics(&design, "Wide", &huge).unwrap_err();
+        assert!(err.message.contains("too wide"), "{}", err.message);
+        let err = elaborate_with_generics(&design, "Twice", &huge).unwrap_err();
+        assert!(err.message.contains("integer overflow"), "{}", err.message);
+        let err = elaborate_with_generics(&design, "Many", &huge).unwrap_err();
+        assert!(err.message.contains("iterations"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_overlapping_bit_drivers_rejected() {
+        let hdl = r#"
+entity Dup is
+  port(a : in bits(3 downto 0); y : out bits(3 downto 0));
+end entity;
+
+architecture rtl of Dup is
+begin
+  y(1 downto 0) <= a(1 downto 0);
+  y(3 downto 1) <= a(3 downto 1);
+end architecture;
+"#;
+        let err = elaborate(&parse_str(hdl).unwrap(), "Dup").unwrap_err();
+        assert_eq!(err.message, "multiple drivers for signal y");
+    }
+
+    #[test]
+    fn test_process_edges() {
+        let process = |guard: &str, body: &str| {
+            format!(
+                r#"
+entity P is
+  port(clk : in bit; d : in bit; q : out bit);
+end entity;
+
+architecture rtl of P is
+begin
+  process(clk)
+  begin
+    if {}(clk) then
+      {}
+    end if;
+  end process;
+end architecture;
+"#,
+                guard, body
+            )
+        };
+        let elab = |src: String| elaborate(&parse_str(&src).unwrap(), "P");
+
+        let netlist = elab(process("falling_edge", "q <= d;")).unwrap();
+        assert_eq!(netlist.processes[0].edge, Edge::Falling);
+        assert_eq!(netlist.signals[netlist.processes[0].clk].name, "clk");
+
+        let err = elab(process("rising_edge", "if falling_edge(clk) then q <= d; end if;")).unwrap_err();
+        assert_eq!(err.message, "process on clk mixes rising_edge and falling_edge");
+        let err = elab(process("rising_edge", "if rising_edge(clk) then q <= d; end if;")).unwrap_err();
+        assert_eq!(err.message, "rising_edge only allowed as the process guard");
+        let err = elab(process("falling_edge", "q <= d;").replace("falling_edge(clk)", "falling_edge(d)")).unwrap_err();
+        assert_eq!(err.message, "falling_edge clock name mismatch");
+    }
+
+    #[test]
+    fn test_async_reset_process() {
+        let process = |sensitivity: &str, head: &str| {
+            format!(
+                r#"
+entity P is
+  port(clk : in bit; rst : in bit; d : in bit; q : out bit);
+end entity;
+
+architecture rtl of P is
+begin
+  process({})
+  begin
+    if {} then
+      q <= '0';
+    elsif rising_edge(clk) then
+      q <= d;
+    end if;
+  end process;
+end architecture;
+"#,
+                sensitivity, head
+            )
+        };
+        let elab = |src: String| elaborate(&parse_str(&src).unwrap(), "P");
+
+        let netlist = elab(process("clk, rst", "rst = '1'")).unwrap();
+        let proc = &netlist.processes[0];
+        let reset = proc.reset.as_ref().unwrap();
+        assert_eq!(netlist.signals[reset.signal].name, "rst");
+        assert_eq!(netlist.signals[proc.clk].name, "clk");
+        assert!(elab(process("rst, clk", "rst = '0'")).is_ok());
+
+        let err = elab(process("clk", "rst = '1'")).unwrap_err();
+        assert_eq!(err.message, "asynchronous reset rst missing from the process sensitivity list");
+        let err = elab(process("clk, rst", "rst = d")).unwrap_err();
+        assert_eq!(err.message, "asynchronous reset condition must be rst = '1' or rst = '0'");
+        let err = elab(process("clk, rst, d", "rst = '1'")).unwrap_err();
+        assert_eq!(err.message, "process sensitivity list must be (clk, rst)");
+        let sync = process("clk, rst", "rising_edge(clk)").replace("elsif rising_edge(clk)", "elsif rst = '1'");
+        let err = elab(sync).unwrap_err();
+        assert_eq!(err.message, "process without asynchronous reset must be sensitive to clk only");
+    }
+
+    #[test]
+    fn test_registered_feedback_is_not_a_loop() {
+        let hdl = r#"
+entity Toggle is
+  port(clk : in bit; q : out bit);
+end entity;
+
+architecture rtl of Toggle is
+  signal s : bit;
+  signal n : bit;
+begin
+  n <= not s;
+  u0: dff port map (clk => clk, d => n, q => s);
+  q <= s;
+end architecture;
+"#;
+        assert!(elaborate(&parse_str(hdl).unwrap(), "Toggle").is_ok());
+    }
+
+    #[test]
+    fn test_constants_enums_and_integer_ranges() {
+        let fsm = |decls: &str, body: &str| {
+            format!(
+                r#"
+entity Fsm is
+  port(clk : in bit; go : in bit; op : out bits(3 downto 0));
+end entity;
+
+architecture rtl of Fsm is
+  type state_t is (IDLE, FETCH, EXEC);
+  constant W : integer := 4;
+  constant OP_ADD : bits(W - 1 downto 0) := b"0011";
+  constant LAST : integer range 0 to 15 := 9;
+  signal state : state_t := FETCH;
+  signal cnt : integer range 0 to LAST;
+  signal delta : integer range -8 to 7;
+  {}
+begin
+  op <= OP_ADD;
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      case state is
+        when IDLE => state <= FETCH;
+        when FETCH => {}
+        when others => cnt <= LAST;
+      end case;
+    end if;
+  end process;
+end architecture;
+"#,
+                decls, body
+            )
+        };
+        let elab = |src: String| elaborate(&parse_str(&src).unwrap(), "Fsm");
+
+        let netlist = elab(fsm("", "state <= EXEC;")).unwrap();
+        let state = &netlist.signals[netlist.name_to_id["state"]];
+        assert_eq!(state.width, 2);
+        assert_eq!(state.value, BitVec::from_u64(2, 1));
+        assert_eq!(state.ty.display(&BitVec::from_u64(2, 2)).as_deref(), Some("EXEC"));
+        assert_eq!(state.ty.literal("IDLE"), Some(0));
+        let cnt = &netlist.signals[netlist.name_to_id["cnt"]];
+        assert_eq!((cnt.width, cnt.ty.clone()), (4, SignalType::Integer { low: 0, high: 9 }));
+        let delta = &netlist.signals[netlist.name_to_id["delta"]];
+        assert_eq!(delta.width, 4);
+        assert_eq!(delta.ty.display(&BitVec::from_u64(4, 0xD)).as_deref(), Some("-3"));
+        assert!(matches!(&netlist.assigns[0].expr, ExprRef::Literal(v) if v.bits == BitVec::from_u64(4, 3)));
+
+        let err = |decls: &str, body: &str| elab(fsm(decls, body)).unwrap_err().message;
+        assert_eq!(err("", "state <= b\"01\";"), "type mismatch for state: expected a literal of state_t");
+        assert_eq!(err("", "cnt <= 12;"), "value 12 out of range 0 to 9 for cnt");
+        assert_eq!(err("type mode_t is (RUN, STOP);", "state <= RUN;"), "type mismatch for state: expected state_t, got mode_t");
+        assert_eq!(err("", "cnt <= IDLE;"), "type mismatch for cnt: expected integer range 0 to 9, got state_t");
+        assert_eq!(
+            err("constant BAD : bits(2 downto 0) := b\"0011\";", ""),
+            "width mismatch for constant BAD (expected 3, got 4)"
+        );
+        assert_eq!(err("constant BIG : bits(2 downto 0) := 9;", ""), "value 9 does not fit constant BIG (3 bits)");
+        assert_eq!(err("signal FETCH : bit;", ""), "duplicate name FETCH");
+        assert_eq!(err("signal s : color_t;", ""), "unknown type color_t");
+        let bad_choice = fsm("", "").replace("when IDLE =>", "when b\"00\" =>");
+        assert_eq!(elab(bad_choice).unwrap_err().message, "case on state_t needs its literals as choices");
+        let bad_choice = fsm("", "").replace("when IDLE =>", "when LAST =>");
+        assert_eq!(elab(bad_choice).unwrap_err().message, "LAST is not a literal of state_t");
+    }
+
+    #[test]
+    fn test_array_types() {
+        let rf = |decls: &str, body: &str| {
+            format!(
+                r#"
+entity Rf is
+  port(clk : in bit; a : in bits(1 downto 0); d : in bits(3 downto 0); q : out bits(3 downto 0));
+end entity;
+
+architecture rtl of Rf is
+  constant N : integer := 4;
+  type mem_t is array (N - 1 downto 0) of bits(3 downto 0);
+  type flags_t is array (1 to 8) of bit;
+  signal mem : mem_t;
+  signal flags : flags_t;
+  {}
+begin
+  {}
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      mem(a) <= d;
+      flags(3) <= '1';
+    end if;
+  end process;
+end architecture;
+"#,
+                decls, body
+            )
+        };
+        let elab = |src: String| elaborate(&parse_str(&src).unwrap(), "Rf");
+
+        let netlist = elab(rf("", "q <= mem(a);")).unwrap();
+        let mem = &netlist.signals[netlist.name_to_id["mem"]];
+        assert_eq!((mem.width, mem.ty.clone()), (16, SignalType::Array { low: 0, high: 3, elem_width: 4 }));
+        assert_eq!(mem.ty.element_offset(2), Some(8));
+        assert_eq!(netlist.signals[netlist.name_to_id["flags"]].width, 8);
+        assert!(matches!(&netlist.assigns[0].expr, ExprRef::Element { .. }));
+        assert!(matches!(netlist.processes[0].stmts[0], SeqStmtRef::AssignElement { .. }));
+
+        // A constant index is a plain slice
+        let netlist = elab(rf("", "q <= mem(N - 2);")).unwrap();
+        let ExprRef::Target(t) = &netlist.assigns[0].expr else {
+            panic!("expected a slice");
+        };
+        assert!(matches!(t.sel, Some(Selector::Range { msb: 11, lsb: 8, .. })));
+
+        let err = |decls: &str, body: &str| elab(rf(decls, body)).unwrap_err().message;
+        assert_eq!(err("", "q <= mem(4);"), "index 4 out of range 0 to 3 for mem");
+        assert_eq!(err("", "q <= mem(1 downto 0);"), "cannot slice array mem");
+        assert_eq!(
+            err("signal b : bits(3 downto 0);", "b <= d; mem(a) <= b;"),
+            "run-time index of mem only allowed in expressions and process assignments"
+        );
+        assert_eq!(err("", "q(a) <= '0';"), "run-time index only allowed on arrays");
+        assert_eq!(err("type grid_t is array (0 to 1) of mem_t;", ""), "array type grid_t of arrays is not supported");
+        assert_eq!(err("type e_t is array (2 to 1) of bit;", ""), "null range for array type e_t");
+    }
+
+    #[test]
+    fn test_packages_and_use_clauses() {
+        let src = |uses: &str, decls: &str| {
+            format!(
+                r#"
+package cpu_pkg is
+  constant XLEN : integer := 8;
+  constant OP_ADD : bits(3 downto 0) := b"0011";
+  type mode_t is (RUN, HALT);
+  component Alu port(a : in bits(7 downto 0); y : out bits(7 downto 0)); end component;
+end package;
+
+use work.cpu_pkg.all;
+package soc_pkg is
+  constant WORDS : integer := XLEN * 2;
+end package;
+
+library work;
+{}
+entity Core is
+  port(clk : in bit; mode : in mode_t; d : in bits(XLEN - 1 downto 0); op : out bits(3 downto 0));
+end entity;
+
+use work.soc_pkg.WORDS;
+architecture rtl of Core is
+  signal buf : bits(WORDS - 1 downto 0);
+  signal state : mode_t := HALT;
+  {}
+begin
+  op <= OP_ADD;
+  buf <= d & d;
+end architecture;
+"#,
+                uses, decls
+            )
+        };
+        let elab = |uses: &str, decls: &str| elaborate(&parse_str(&src(uses, decls)).unwrap(), "Core");
+
+        let netlist = elab("use work.cpu_pkg.all;", "").unwrap();
+        let width = |name: &str| netlist.signals[netlist.name_to_id[name]].width;
+        assert_eq!((width("d"), width("buf"), width("mode")), (8, 16, 1));
+        let state = &netlist.signals[netlist.name_to_id["state"]];
+        assert_eq!(state.ty.display(&state.value).as_deref(), Some("HALT"));
+        let widths = declared_widths(&parse_str(&src("use work.cpu_pkg.all;", "")).unwrap(), "Core").unwrap();
+        let widths: Vec<(&str, usize)> = widths.iter().map(|(n, w)| (n.as_str(), *w)).collect();
+        assert_eq!(widths, [("clk", 1), ("mode", 1), ("d", 8), ("op", 4), ("buf", 16), ("state", 1)]);
+        // Using the same package twice, or one name of it, is harmless
+        assert!(elab("use work.cpu_pkg.all; use work.cpu_pkg.XLEN; use work.cpu_pkg.Alu;", "").is_ok());
+
+        let err = |uses: &str, decls: &str| elab(uses, decls).unwrap_err().message;
+        assert_eq!(err("use work.cpu_pkg.XLEN;", ""), "unknown type mode_t");
+        assert_eq!(err("use work.cpu_pkg.all;", "signal XLEN : bit;"), "duplicate name XLEN");
+        assert_eq!(err("use work.gpu_pkg.all;", ""), "unknown package gpu_pkg");
+        assert_eq!(err("use work.cpu_pkg.YLEN;", ""), "package cpu_pkg has no YLEN");
+        let err = parse_str(&src("use lib.cpu_pkg.all;", "")).unwrap_err();
+        assert_eq!(err.message, "library lib is not declared");
+        let cycle = src("use work.cpu_pkg.all;", "").replace("package cpu_pkg is", "use work.soc_pkg.all;\npackage cpu_pkg is");
+        let err = elaborate(&parse_str(&cycle).unwrap(), "Core").unwrap_err();
+        assert_eq!(err.message, "circular use of package cpu_pkg");
+    }
+
+    #[test]
+    fn test_when_else_aggregates_and_open() {
+        let src = |body: &str| {
+            format!(
+                r#"
+entity And is
+  port(a : in bit; b : in bit; y : out bit; n : out bit);
+end entity;
+
+architecture rtl of And is
+begin
+  y <= a and b;
+  n <= not (a and b);
+end architecture;
+
+entity Top is
+  port(s : in bit; a : in bits(3 downto 0); y : out bits(3 downto 0); z : out bit);
+end entity;
+
+architecture rtl of Top is
+begin
+  u_and: And port map (a => s, b => a(0), y => z, n => open);
+  {}
+end architecture;
+"#,
+                body
+            )
+        };
+        let elab = |body: &str| elaborate(&parse_str(&src(body)).unwrap(), "Top");
+
+        let netlist = elab("y <= a when s = '1' else (others => '0');").unwrap();
+        // The unmapped output still gets a signal of its own
+        assert!(netlist.name_to_id.contains_key("u_and/n"));
+        let select = netlist.assigns.iter().find(|a| matches!(a.expr, ExprRef::Select { .. }));
+        let Some(ExprRef::Select { otherwise, .. }) = select.map(|a| &a.expr) else {
+            panic!("expected a select");
+        };
+        assert!(matches!(&**otherwise, ExprRef::Literal(v) if v.bits == BitVec::from_u64(4, 0)));
+        assert!(elab("y <= (3 downto 2 => s) & a(1 downto 0);").is_ok());
+        assert!(elab("y <= a when s = '1' else not a when a(0) = '1' else x\"F\";").is_ok());
+
+        let err = |body: &str| elab(body).unwrap_err().message;
+        assert_eq!(err("y <= (3 downto 0 => a);"), "aggregate value must be a single bit");
+        assert_eq!(err("y <= (3 downto 0 => b\"01\");"), "aggregate value must be a single bit");
+        assert_eq!(err("y <= (others => '0') & a;"), "(others => ...) only allowed as the whole value of an assignment");
+        let err = elaborate(&parse_str(&src("y <= a;").replace("y => z,", "y => open,")).unwrap(), "Top").unwrap_err();
+        assert_eq!(err.message, "out port z must be driven exactly once");
+    }
+}
@@ -0,0 +1,527 @@
+//! Vérification d'équivalence combinatoire entre deux entités.
+//!
+//! Les deux entités doivent avoir les mêmes ports (noms, directions,
+//! largeurs). Jusqu'à 16 bits d'entrée, toutes les combinaisons sont
+//! simulées ; au-delà, chaque sortie est construite comme un BDD (diagramme
+//! de décision binaire) sur les bits d'entrée et le BDD de « au moins une
+//! sortie diffère » est comparé à la constante fausse. Un contre-exemple
+//! trouvé par BDD est rejoué sur le simulateur.
+
+use crate::ast::{Design, Direction};
+use crate::bitblast::{BitLogic, SymEval, FALSE, TRUE};
+use crate::elab::{elaborate, Netlist, PrimitiveNet};
+use crate::error::Error;
+use crate::graph::CombGraph;
+use crate::sim::Simulator;
+use crate::value::BitVec;
+use std::collections::HashMap;
+
+/// Nombre maximal de bits d'entrée vérifiés par simulation exhaustive
+pub const EXHAUSTIVE_MAX_BITS: usize = 16;
+
+/// Taille maximale des BDD avant abandon
+const BDD_MAX_NODES: usize = 2_000_000;
+
+/// Méthode qui a établi l'équivalence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EquivMethod {
+    Exhaustive,
+    Bdd,
+}
+
+impl EquivMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EquivMethod::Exhaustive => "exhaustive simulation",
+            EquivMethod::Bdd => "BDD",
+        }
+    }
+}
+
+/// Résultat d'une vérification d'équivalence
+#[derive(Clone, Debug)]
+pub enum Equivalence {
+    /// Mêmes sorties pour toutes les entrées
+    Equivalent { method: EquivMethod, input_bits: usize },
+    /// Entrées qui distinguent les deux entités
+    Different(Counterexample),
+}
+
+/// Entrées distinguant deux entités et les sorties qui diffèrent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    pub inputs: Vec<(String, BitVec)>,
+    /// Nom de la sortie, valeur de l'entité de gauche, de celle de droite
+    pub outputs: Vec<(String, BitVec, BitVec)>,
+}
+
+/// Port du top : nom, direction, largeur et net
+struct TopPort {
+    name: String,
+    input: bool,
+    width: usize,
+    signal: usize,
+}
+
+fn top_ports(netlist: &Netlist) -> Vec<TopPort> {
+    netlist.instances[0]
+        .ports
+        .iter()
+        .map(|p| TopPort {
+            name: p.name.clone(),
+            input: matches!(p.dir, Direction::In),
+            width: netlist.signals[p.signal].width,
+            signal: p.signal,
+        })
+        .collect()
+}
+
+/// Vérifie que `left` et `right` calculent les mêmes sorties pour toutes
+/// les entrées
+pub fn check_equivalence(design: &Design, left: &str, right: &str) -> Result<Equivalence, Error> {
+    let nets = [elaborate(design, left)?, elaborate(design, right)?];
+    for (net, name) in nets.iter().zip([left, right]) {
+        ensure_combinational(net, name)?;
+    }
+
+    let (lp, rp) = (top_ports(&nets[0]), top_ports(&nets[1]));
+    for (a, b, a_name, b_name) in [(&lp, &rp, left, right), (&rp, &lp, right, left)] {
+        for port in a.iter() {
+            let other = b
+                .iter()
+                .find(|p| p.name == port.name)
+                .ok_or_else(|| Error::new(format!("{} has no port {} of {}", b_name, port.name, a_name)))?;
+            if other.input != port.input || other.width != port.width {
+                return Err(Error::new(format!(
+                    "port {} is {}[{}] in {} but {}[{}] in {}",
+                    port.name,
+                    dir_str(port.input),
+                    port.width,
+                    a_name,
+                    dir_str(other.input),
+                    other.width,
+                    b_name
+                )));
+            }
+        }
+    }
+    let inputs: Vec<(String, usize)> = lp
+        .iter()
+        .filter(|p| p.input)
+        .map(|p| (p.name.clone(), p.width))
+        .collect();
+    let outputs: Vec<String> = lp.iter().filter(|p| !p.input).map(|p| p.name.clone()).collect();
+    let input_bits: usize = inputs.iter().map(|(_, w)| w).sum();
+
+    let mut sims = [Simulator::new(nets[0].clone()), Simulator::new(nets[1].clone())];
+    if input_bits <= EXHAUSTIVE_MAX_BITS {
+        for k in 0..1u64 << input_bits {
+            let mut offset = 0;
+            let vector: Vec<(String, BitVec)> = inputs
+                .iter()
+                .map(|(name, width)| {
+                    let value = BitVec::from_u64(*width, k >> offset);
+                    offset += width;
+                    (name.clone(), value)
+                })
+                .collect();
+            if let Some(cex) = compare_outputs(&mut sims, &vector, &outputs)? {
+                return Ok(Equivalence::Different(cex));
+            }
+        }
+        return Ok(Equivalence::Equivalent { method: EquivMethod::Exhaustive, input_bits });
+    }
+
+    // Bits d'entrée entrelacés (a0 b0 a1 b1 ...), bon ordre pour les chemins de données
+    let max_width = inputs.iter().map(|(_, w)| *w).max().unwrap_or(0);
+    let mut var_of: HashMap<(String, usize), u32> = HashMap::new();
+    for bit in 0..max_width {
+        for (name, width) in &inputs {
+            if bit < *width {
+                let var = var_of.len() as u32;
+                var_of.insert((name.clone(), bit), var);
+            }
+        }
+    }
+
+    // L'évaluation symbolique calcule chaque noeud une seule fois, dans
+    // l'ordre topologique des noeuds
+    for (net, name) in nets.iter().zip([left, right]) {
+        if CombGraph::build(net).find_node_cycle().is_some() {
+            return Err(Error::new(format!(
+                "{} feeds bits of a signal back into the same signal (more than {} input bits)",
+                name, EXHAUSTIVE_MAX_BITS
+            )));
+        }
+    }
+    let mut bdd = Bdd::new();
+    let mut outs: Vec<Vec<Vec<u32>>> = Vec::new();
+    for netlist in &nets {
+        let mut eval = SymEval::new(netlist, &mut bdd);
+        for port in top_ports(netlist).iter().filter(|p| p.input) {
+            for bit in 0..port.width {
+                let var = eval.logic.var(var_of[&(port.name.clone(), bit)])?;
+                eval.bits[port.signal][bit] = Some(var);
+            }
+        }
+        eval.run()?;
+        let ports = top_ports(netlist);
+        let values = outputs
+            .iter()
+            .map(|name| {
+                let port = ports.iter().find(|p| &p.name == name).expect("port checked above");
+                eval.signal(port.signal)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        outs.push(values);
+    }
+
+    let mut miter = FALSE;
+    for (l, r) in outs[0].iter().zip(&outs[1]) {
+        for (&a, &b) in l.iter().zip(r) {
+            let diff = bdd.xor(a, b)?;
+            miter = bdd.or(miter, diff)?;
+        }
+    }
+    if miter == FALSE {
+        return Ok(Equivalence::Equivalent { method: EquivMethod::Bdd, input_bits });
+    }
+
+    let assignment = bdd.satisfy(miter);
+    let vector: Vec<(String, BitVec)> = inputs
+        .iter()
+        .map(|(name, width)| {
+            let bits = (0..*width)
+                .map(|bit| assignment.get(&var_of[&(name.clone(), bit)]).copied().unwrap_or(0))
+                .collect();
+            (name.clone(), BitVec::from_bits_lsb(bits))
+        })
+        .collect();
+    match compare_outputs(&mut sims, &vector, &outputs)? {
+        Some(cex) => Ok(Equivalence::Different(cex)),
+        None => Err(Error::new("equivalence: BDD counterexample not confirmed by simulation")),
+    }
+}
+
+fn dir_str(input: bool) -> &'static str {
+    if input {
+        "in"
+    } else {
+        "out"
+    }
+}
+
+/// Refuse les registres, mémoires et boucles combinatoires
+fn ensure_combinational(netlist: &Netlist, name: &str) -> Result<(), Error> {
+    let sequential = |what: &str| Error::new(format!("{} is not combinational ({})", name, what));
+    if !netlist.processes.is_empty() {
+        return Err(sequential("process"));
+    }
+    for prim in &netlist.primitives {
+        match prim {
+            PrimitiveNet::Dff { .. } => return Err(sequential("dff")),
+            PrimitiveNet::Ram { .. } => return Err(sequential("ram")),
+            PrimitiveNet::Rom { .. } => return Err(sequential("rom")),
+            _ => {}
+        }
+    }
+    if CombGraph::build(netlist).find_cycle(netlist).is_some() {
+        return Err(Error::new(format!("{} has a combinational loop", name)));
+    }
+    Ok(())
+}
+
+/// Applique le même vecteur aux deux simulateurs ; Some si une sortie diffère
+fn compare_outputs(
+    sims: &mut [Simulator; 2],
+    vector: &[(String, BitVec)],
+    outputs: &[String],
+) -> Result<Option<Counterexample>, Error> {
+    for sim in sims.iter_mut() {
+        for (name, value) in vector {
+            sim.set_signal(name, value.clone())?;
+        }
+        sim.eval_comb()?;
+    }
+    let mut diffs = Vec::new();
+    for name in outputs {
+        let (l, r) = (sims[0].get_signal(name)?, sims[1].get_signal(name)?);
+        if l != r {
+            diffs.push((name.clone(), l, r));
+        }
+    }
+    Ok((!diffs.is_empty()).then(|| Counterexample {
+        inputs: vector.to_vec(),
+        outputs: diffs,
+    }))
+}
+
+/// BDD réduit et ordonné ; les noeuds 0 et 1 sont les constantes
+struct Bdd {
+    /// (variable, fils faux, fils vrai)
+    nodes: Vec<(u32, u32, u32)>,
+    unique: HashMap<(u32, u32, u32), u32>,
+    ite_cache: HashMap<(u32, u32, u32), u32>,
+}
+
+impl Bdd {
+    fn new() -> Self {
+        Bdd {
+            nodes: vec![(u32::MAX, FALSE, FALSE), (u32::MAX, TRUE, TRUE)],
+            unique: HashMap::new(),
+            ite_cache: HashMap::new(),
+        }
+    }
+
+    fn mk(&mut self, var: u32, lo: u32, hi: u32) -> Result<u32, Error> {
+        if lo == hi {
+            return Ok(lo);
+        }
+        if let Some(&node) = self.unique.get(&(var, lo, hi)) {
+            return Ok(node);
+        }
+        if self.nodes.len() >= BDD_MAX_NODES {
+            return Err(Error::new(format!(
+                "equivalence: BDD exceeds {} nodes, the design is too large to prove",
+                BDD_MAX_NODES
+            )));
+        }
+        let node = self.nodes.len() as u32;
+        self.nodes.push((var, lo, hi));
+        self.unique.insert((var, lo, hi), node);
+        Ok(node)
+    }
+
+    fn var(&mut self, var: u32) -> Result<u32, Error> {
+        self.mk(var, FALSE, TRUE)
+    }
+
+    /// Une affectation des variables qui rend `f` vrai (f ≠ 0) ; les
+    /// variables absentes valent 0
+    fn satisfy(&self, mut f: u32) -> HashMap<u32, u8> {
+        let mut assignment = HashMap::new();
+        while f > TRUE {
+            let (var, lo, hi) = self.nodes[f as usize];
+            if lo != FALSE {
+                assignment.insert(var, 0);
+                f = lo;
+            } else {
+                assignment.insert(var, 1);
+                f = hi;
+            }
+        }
+        assignment
+    }
+}
+
+impl BitLogic for Bdd {
+    fn ite(&mut self, f: u32, g: u32, h: u32) -> Result<u32, Error> {
+        if f == TRUE || g == h {
+            return Ok(g);
+        }
+        if f == FALSE {
+            return Ok(h);
+        }
+        if g == TRUE && h == FALSE {
+            return Ok(f);
+        }
+        if let Some(&node) = self.ite_cache.get(&(f, g, h)) {
+            return Ok(node);
+        }
+        let var = [f, g, h].iter().map(|&n| self.nodes[n as usize].0).min().unwrap_or(u32::MAX);
+        let cofactor = |bdd: &Self, n: u32, value: bool| {
+            let (v, lo, hi) = bdd.nodes[n as usize];
+            match (v == var, value) {
+                (true, false) => lo,
+                (true, true) => hi,
+                _ => n,
+            }
+        };
+        let (f0, g0, h0) = (cofactor(self, f, false), cofactor(self, g, false), cofactor(self, h, false));
+        let (f1, g1, h1) = (cofactor(self, f, true), cofactor(self, g, true), cofactor(self, h, true));
+        let lo = self.ite(f0, g0, h0)?;
+        let hi = self.ite(f1, g1, h1)?;
+        let node = self.mk(var, lo, hi)?;
+        self.ite_cache.insert((f, g, h), node);
+        Ok(node)
+    }
+
+    fn not(&mut self, f: u32) -> Result<u32, Error> {
+        self.ite(f, FALSE, TRUE)
+    }
+
+    fn and(&mut self, f: u32, g: u32) -> Result<u32, Error> {
+        self.ite(f, g, FALSE)
+    }
+
+    fn or(&mut self, f: u32, g: u32) -> Result<u32, Error> {
+        self.ite(f, TRUE, g)
+    }
+
+    fn xor(&mut self, f: u32, g: u32) -> Result<u32, Error> {
+        let ng = self.not(g)?;
+        self.ite(f, ng, g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+
+    const DESIGN: &str = r#"
+entity Mux is
+  port(a : in bits(3 downto 0); b : in bits(3 downto 0); sel : in bit; y : out bits(3 downto 0));
+end entity;
+
+architecture rtl of Mux is
+begin
+  y <= (a and not (sel & sel & sel & sel)) or (b and (sel & sel & sel & sel));
+end architecture;
+
+entity MuxGates is
+  port(a : in bits(3 downto 0); b : in bits(3 downto 0); sel : in bit; y : out bits(3 downto 0));
+end entity;
+
+architecture rtl of MuxGates is
+  signal ns : bit;
+  signal t, u : bits(3 downto 0);
+begin
+  n: nand2 port map (a => sel, b => sel, y => ns);
+  g: for i in 0 to 3 generate
+    t(i) <= not (a(i) and ns);
+    u(i) <= not (b(i) and sel);
+    y(i) <= not (t(i) and u(i));
+  end generate;
+end architecture;
+
+entity Add24 is
+  port(a : in bits(23 downto 0); b : in bits(23 downto 0); y : out bits(23 downto 0); c : out bit);
+end entity;
+
+architecture rtl of Add24 is
+  signal s : bits(24 downto 0);
+begin
+  s <= ('0' & a) + ('0' & b);
+  y <= s(23 downto 0);
+  c <= s(24);
+end architecture;
+
+entity Ripple24 is
+  port(a : in bits(23 downto 0); b : in bits(23 downto 0); y : out bits(23 downto 0); c : out bit);
+end entity;
+
+architecture rtl of Ripple24 is
+  signal carry : bits(24 downto 0);
+begin
+  carry(0) <= '0';
+  g: for i in 0 to 23 generate
+    y(i) <= a(i) xor b(i) xor carry(i);
+    carry(i + 1) <= (a(i) and b(i)) or (carry(i) and (a(i) xor b(i)));
+  end generate;
+  c <= carry(24);
+end architecture;
+
+entity BadRipple24 is
+  port(a : in bits(23 downto 0); b : in bits(23 downto 0); y : out bits(23 downto 0); c : out bit);
+end entity;
+
+architecture rtl of BadRipple24 is
+  signal carry : bits(24 downto 0);
+begin
+  carry(0) <= '0';
+  g: for i in 0 to 23 generate
+    y(i) <= a(i) xor b(i) xor carry(i);
+    ok: if i /= 17 generate
+      carry(i + 1) <= (a(i) and b(i)) or (carry(i) and (a(i) xor b(i)));
+    end generate;
+    bad: if i = 17 generate
+      carry(i + 1) <= a(i) and b(i);
+    end generate;
+  end generate;
+  c <= carry(24);
+end architecture;
+"#;
+
+    fn design() -> Design {
+        parse_str(DESIGN).unwrap()
+    }
+
+    #[test]
+    fn test_exhaustive_equivalence() {
+        match check_equivalence(&design(), "Mux", "MuxGates").unwrap() {
+            Equivalence::Equivalent { method, input_bits } => {
+                assert_eq!(method, EquivMethod::Exhaustive);
+                assert_eq!(input_bits, 9);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bdd_equivalence_and_counterexample() {
+        let d = design();
+        assert!(matches!(
+            check_equivalence(&d, "Add24", "Ripple24").unwrap(),
+            Equivalence::Equivalent { method: EquivMethod::Bdd, input_bits: 48 }
+        ));
+        let Equivalence::Different(cex) = check_equivalence(&d, "Ripple24", "BadRipple24").unwrap() else {
+            panic!("the carry chain is broken at bit 17");
+        };
+        // Le contre-exemple propage une retenue à travers le bit 17
+        let value = |name: &str| cex.inputs.iter().find(|(n, _)| n == name).unwrap().1.to_u64_trunc();
+        let (a, b) = (value("a"), value("b"));
+        assert_eq!(((a ^ b) >> 17) & 1, 1);
+        assert_ne!(cex.outputs[0].1, cex.outputs[0].2);
+    }
+
+    #[test]
+    fn test_self_feeding_carry_chain() {
+        let chain = |width: usize| {
+            format!(
+                "entity Chain is
+  port(cin : in bit; g : in bits({0} downto 0); p : in bits({0} downto 0); cout : out bit);
+end entity;
+
+architecture rtl of Chain is
+  signal c : bits({1} downto 0);
+begin
+  c(0) <= cin;
+  c({1} downto 1) <= g or (p and c({0} downto 0));
+  cout <= c({1});
+end architecture;
+
+entity Lookahead is
+  port(cin : in bit; g : in bits({0} downto 0); p : in bits({0} downto 0); cout : out bit);
+end entity;
+
+architecture rtl of Lookahead is
+  signal c : bits({1} downto 0);
+begin
+  c(0) <= cin;
+  gen: for i in 0 to {0} generate
+    c(i + 1) <= g(i) or (p(i) and c(i));
+  end generate;
+  cout <= c({1});
+end architecture;
+",
+                width - 1,
+                width
+            )
+        };
+        let small = parse_str(&chain(4)).unwrap();
+        assert!(matches!(
+            check_equivalence(&small, "Chain", "Lookahead").unwrap(),
+            Equivalence::Equivalent { method: EquivMethod::Exhaustive, .. }
+        ));
+        let wide = parse_str(&chain(16)).unwrap();
+        let err = check_equivalence(&wide, "Chain", "Lookahead").unwrap_err();
+        assert_eq!(err.message, "Chain feeds bits of a signal back into the same signal (more than 16 input bits)");
+    }
+
+    #[test]
+    fn test_port_mismatch() {
+        let err = check_equivalence(&design(), "Mux", "Add24").unwrap_err();
+        assert_eq!(err.message, "port a is in[4] in Mux but in[24] in Add24");
+    }
+}
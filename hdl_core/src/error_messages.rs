@@ -52,6 +52,7 @@ pub enum ErrorCode {
     E501, // out port must map to signal
     E502, // out port must be driven once
     E503, // cannot drive input port
+    E504, // combinational loop
 
     // Erreurs de process (E6xx)
     E601, // process has no statements
@@ -59,6 +60,7 @@ pub enum ErrorCode {
     E603, // rising_edge expects 1 arg
     E604, // rising_edge arg must be signal
     E605, // rising_edge clock mismatch
+    E606, // process mixes edges
 
     // Erreurs de fonction (E7xx)
     E701, // resize expects 2 args
@@ -71,6 +73,16 @@ pub enum ErrorCode {
     R802, // index out of range
     R803, // combinational logic did not converge
     R804, // unknown primitive
+
+    // Avertissements du lint (W9xx)
+    W901, // signal never read
+    W902, // input port never read
+    W903, // output port never driven
+    W904, // signal read but never driven
+    W905, // instance input left unconnected
+    W906, // width truncation in assignment
+    W907, // case without others
+    W908, // async reset branch reads a signal (latch)
 }
 
 impl ErrorCode {
@@ -88,11 +100,13 @@ impl ErrorCode {
             E309 => "E309", E310 => "E310",
             E401 => "E401", E402 => "E402", E403 => "E403", E404 => "E404",
             E405 => "E405", E406 => "E406", E407 => "E407",
-            E501 => "E501", E502 => "E502", E503 => "E503",
+            E501 => "E501", E502 => "E502", E503 => "E503", E504 => "E504",
             E601 => "E601", E602 => "E602", E603 => "E603", E604 => "E604",
-            E605 => "E605",
+            E605 => "E605", E606 => "E606",
             E701 => "E701", E702 => "E702", E703 => "E703", E704 => "E704",
             R801 => "R801", R802 => "R802", R803 => "R803", R804 => "R804",
+            W901 => "W901", W902 => "W902", W903 => "W903", W904 => "W904",
+            W905 => "W905", W906 => "W906", W907 => "W907", W908 => "W908",
         }
     }
 }
@@ -147,13 +161,15 @@ pub fn msg(code: ErrorCode) -> &'static str {
         E501 => "Un port de sortie doit être connecté à un signal, pas à une expression.",
         E502 => "Ce port de sortie doit être piloté exactement une fois.",
         E503 => "Impossible de piloter un port d'entrée. Les entrées sont en lecture seule.",
+        E504 => "Boucle combinatoire: un signal dépend de sa propre valeur sans passer par un registre. Insérez un registre (dff ou process) ou cassez la boucle.",
 
         // Process errors
         E601 => "Le process est vide. Ajoutez des instructions à l'intérieur.",
-        E602 => "Le process doit commencer par 'if rising_edge(clk) then'. C'est obligatoire pour la logique synchrone.",
+        E602 => "Le process doit commencer par 'if rising_edge(clk) then' (ou 'if falling_edge(clk) then'). C'est obligatoire pour la logique synchrone.",
         E603 => "La fonction 'rising_edge' attend exactement 1 argument: le signal d'horloge.",
         E604 => "L'argument de 'rising_edge' doit être un signal (généralement 'clk').",
         E605 => "Le signal d'horloge dans rising_edge ne correspond pas à celui du process.",
+        E606 => "Un process ne teste qu'un seul front d'horloge, dans sa condition de tête. Séparez la logique du front montant et celle du front descendant en deux process.",
 
         // Function errors
         E701 => "La fonction 'resize' attend 2 arguments: resize(signal, nouvelle_largeur).",
@@ -166,9 +182,90 @@ pub fn msg(code: ErrorCode) -> &'static str {
         R802 => "Index de bit hors limites. Vérifiez la plage du signal.",
         R803 => "La logique combinatoire n'a pas convergé. Vérifiez qu'il n'y a pas de boucle de rétroaction.",
         R804 => "Primitive inconnue.",
+
+        // Lint warnings
+        W901 => "Signal jamais lu. Supprimez-le ou utilisez sa valeur.",
+        W902 => "Port d'entrée jamais lu. Vérifiez qu'il ne manque pas une connexion.",
+        W903 => "Port de sortie jamais piloté: il reste à 0. Ajoutez une affectation ou connectez-le à une instance.",
+        W904 => "Signal lu mais jamais piloté: il garde sa valeur initiale (0). Ajoutez une affectation.",
+        W905 => "Entrée d'instance non connectée: le signal qui lui est associé n'est jamais piloté.",
+        W906 => "Affectation tronquée: la valeur a plus de bits que la cible, les bits de poids fort sont perdus. Utilisez resize() si c'est voulu.",
+        W907 => "Case incomplet: des valeurs du sélecteur ne sont couvertes par aucun choix. Ajoutez 'when others =>'.",
+        W908 => "Branche de reset asynchrone qui dépend d'un signal: tant que le reset est actif, le registre recopie ce signal comme un verrou (latch). Donnez-lui une valeur constante.",
     }
 }
 
+/// Début des messages (anglais) du lexer, du parser et de l'élaboration,
+/// du plus précis au plus général
+const PREFIXES: &[(&str, ErrorCode)] = &[
+    ("invalid bit literal", ErrorCode::L101),
+    ("invalid hex literal", ErrorCode::L102),
+    ("invalid binary literal", ErrorCode::L103),
+    ("invalid int literal", ErrorCode::L104),
+    ("unexpected character", ErrorCode::L105),
+    ("expected quote", ErrorCode::L106),
+    ("unterminated string", ErrorCode::L107),
+    ("expected entity, architecture", ErrorCode::P201),
+    ("expected port direction", ErrorCode::P202),
+    ("expected type", ErrorCode::P203),
+    ("expected range direction", ErrorCode::P204),
+    ("expected signal, constant", ErrorCode::P205),
+    ("expected constant, type or component", ErrorCode::P205),
+    ("unexpected token in expression", ErrorCode::P206),
+    ("expected identifier", ErrorCode::P207),
+    ("expected literal", ErrorCode::P208),
+    ("expected integer", ErrorCode::P209),
+    ("unexpected token", ErrorCode::P210),
+    ("duplicate entity", ErrorCode::E301),
+    ("duplicate signal", ErrorCode::E302),
+    ("duplicate name", ErrorCode::E302),
+    ("unknown entity", ErrorCode::E304),
+    ("missing architecture", ErrorCode::E305),
+    ("missing port mapping", ErrorCode::E306),
+    ("unknown port", ErrorCode::E307),
+    ("unknown signal", ErrorCode::E308),
+    ("multiple drivers", ErrorCode::E309),
+    ("multiple architectures", ErrorCode::E310),
+    ("port width mismatch", ErrorCode::E401),
+    ("mux2 sel must be 1 bit", ErrorCode::E403),
+    ("dff clk must be 1 bit", ErrorCode::E404),
+    ("ram clk must be 1 bit", ErrorCode::E405),
+    ("ram we must be 1 bit", ErrorCode::E406),
+    ("ram addr width", ErrorCode::E407),
+    ("rom addr width", ErrorCode::E407),
+    ("output port must map to a signal", ErrorCode::E501),
+    ("cannot drive input port", ErrorCode::E503),
+    ("[E504]", ErrorCode::E504),
+    ("process has no statements", ErrorCode::E601),
+    ("process must start with", ErrorCode::E602),
+    ("process guard must be", ErrorCode::E602),
+    ("resize expects 2 args", ErrorCode::E701),
+    ("resize width must be positive", ErrorCode::E702),
+    ("resize width must be a constant", ErrorCode::E703),
+    ("unsupported function", ErrorCode::E704),
+    ("unknown primitive", ErrorCode::R804),
+];
+
+/// Fragments reconnus n'importe où dans le message
+const FRAGMENTS: &[(&str, ErrorCode)] = &[
+    ("must be driven exactly once", ErrorCode::E502),
+    ("mixes rising_edge and falling_edge", ErrorCode::E606),
+    ("edge expects 1 arg", ErrorCode::E603),
+    ("edge arg must be a signal", ErrorCode::E604),
+    ("clock name mismatch", ErrorCode::E605),
+    ("width mismatch", ErrorCode::E402),
+];
+
+/// Code de l'erreur dont `message` est le texte produit par le lexer, le
+/// parser ou l'élaboration ; `None` pour les messages sans code
+pub fn classify(message: &str) -> Option<ErrorCode> {
+    PREFIXES
+        .iter()
+        .find(|(prefix, _)| message.starts_with(prefix))
+        .or_else(|| FRAGMENTS.iter().find(|(fragment, _)| message.contains(fragment)))
+        .map(|&(_, code)| code)
+}
+
 /// Génère un message d'erreur formaté avec le code et des arguments
 pub fn fmt_msg(code: ErrorCode, args: &[&str]) -> String {
     let base = msg(code);
@@ -186,6 +283,8 @@ pub fn fmt_msg(code: ErrorCode, args: &[&str]) -> String {
 
 // Messages avec placeholders pour les noms
 pub mod detailed {
+    use crate::error::Span;
+
     pub fn duplicate_entity(name: &str) -> String {
         format!("[E301] L'entité '{}' est déjà définie. Chaque entité doit avoir un nom unique.", name)
     }
@@ -237,6 +336,26 @@ pub mod detailed {
     pub fn out_port_must_map_signal(name: &str) -> String {
         format!("[E501] Le port de sortie '{}' doit être connecté à un signal, pas à une expression ou constante.", name)
     }
+
+    /// `steps`: signaux de la boucle dans l'ordre de propagation, avec la
+    /// position de l'affectation ou de l'instance qui pilote chacun
+    pub fn combinational_loop(steps: &[(String, Option<Span>)]) -> String {
+        let mut path: Vec<&str> = steps.iter().map(|(name, _)| name.as_str()).collect();
+        if let Some(first) = path.first().copied() {
+            path.push(first);
+        }
+        let mut out = format!(
+            "[E504] Boucle combinatoire: {}. Chaque signal de la boucle dépend de sa propre valeur sans passer par un registre, la logique ne peut donc pas se stabiliser (elle oscille ou se verrouille). Insérez un registre (dff ou process rising_edge) ou cassez la boucle.",
+            path.join(" -> ")
+        );
+        for (name, span) in steps {
+            match span {
+                Some(span) => out.push_str(&format!("\n  {} piloté ligne {}, colonne {}", name, span.line, span.col)),
+                None => out.push_str(&format!("\n  {} piloté (position inconnue)", name)),
+            }
+        }
+        out
+    }
 }
 
 #[cfg(test)]
@@ -247,6 +366,7 @@ mod tests {
     fn test_error_codes() {
         assert_eq!(ErrorCode::L101.as_str(), "L101");
         assert_eq!(ErrorCode::E301.as_str(), "E301");
+        assert_eq!(ErrorCode::W906.as_str(), "W906");
     }
 
     #[test]
@@ -256,10 +376,34 @@ mod tests {
         assert!(msg.contains("'0'"));
     }
 
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("unknown signal foo"), Some(ErrorCode::E308));
+        assert_eq!(classify("unexpected token in expression"), Some(ErrorCode::P206));
+        assert_eq!(classify("unexpected token"), Some(ErrorCode::P210));
+        assert_eq!(classify("port width mismatch for a (expected 4, got 8)"), Some(ErrorCode::E401));
+        assert_eq!(classify("nand2 width mismatch"), Some(ErrorCode::E402));
+        assert_eq!(classify("falling_edge clock name mismatch"), Some(ErrorCode::E605));
+        assert_eq!(classify("out port y must be driven exactly once"), Some(ErrorCode::E502));
+        assert_eq!(classify("unknown package p"), None);
+    }
+
     #[test]
     fn test_detailed_messages() {
         let msg = detailed::unknown_signal("counter");
         assert!(msg.contains("counter"));
         assert!(msg.contains("E308"));
     }
+
+    #[test]
+    fn test_combinational_loop_message() {
+        let steps = vec![
+            ("x".to_string(), Some(crate::error::Span { line: 3, col: 5 })),
+            ("u0/a".to_string(), None),
+        ];
+        let msg = detailed::combinational_loop(&steps);
+        assert!(msg.starts_with("[E504] Boucle combinatoire: x -> u0/a -> x."));
+        assert!(msg.contains("x piloté ligne 3, colonne 5"));
+        assert_eq!(ErrorCode::E504.as_str(), "E504");
+    }
 }
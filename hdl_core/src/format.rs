@@ -0,0 +1,874 @@
+//! Mise en forme canonique des sources HDL.
+//!
+//! Le texte est analysé par le parser puis réécrit : mots-clés en
+//! minuscules, indentation de deux espaces, un port par ligne avec les `:`
+//! alignés, unités séparées par une ligne vide. Les clauses `generic` et
+//! les `port map` restent sur une ligne s'ils tiennent en `MAX_WIDTH`
+//! colonnes sans commentaire, sinon un élément par ligne, `=>` alignés. Les identificateurs gardent leur casse, les déclarations leur
+//! ordre et les lignes vides isolées sont conservées.
+//!
+//! Les commentaires ne sont pas dans l'AST : ceux du lexer sont replacés
+//! par position. Un commentaire en fin de ligne suit la ligne produite
+//! pour le nœud (ou le mot-clé `end`, `else`, `when`...) qui commence sa
+//! ligne ; un commentaire seul sur sa ligne précède le nœud qui le suit.
+//! La mise en forme d'un texte déjà mis en forme le laisse inchangé.
+
+use crate::ast::*;
+use crate::error::{Error, Span};
+use crate::lexer::{Comment, Lexer, Token, TokenKind};
+use crate::parser::parse_str;
+
+/// Texte mis en forme de `src` ; erreur si `src` ne s'analyse pas
+pub fn format_source(src: &str) -> Result<String, Error> {
+    let mut lexer = Lexer::new(src);
+    let tokens = lexer.lex()?;
+    let design = parse_str(src)?;
+    let comments = lexer
+        .comments()
+        .iter()
+        .map(|c| {
+            let trailing = tokens.iter().any(|t| t.span.line == c.span.line && t.span.col < c.span.col);
+            (c.clone(), trailing)
+        })
+        .collect();
+    let mut printer = Printer {
+        src_lines: src.lines().collect(),
+        tokens,
+        comments,
+        next_comment: 0,
+        cursor: 0,
+        out: Vec::new(),
+        indent: 0,
+        trailing: Vec::new(),
+        block_start: true,
+        last_line: 0,
+    };
+    printer.design(&design);
+    Ok(printer.finish())
+}
+
+struct Printer<'a> {
+    src_lines: Vec<&'a str>,
+    tokens: Vec<Token>,
+    /// Commentaires, en fin de ligne de code ou non
+    comments: Vec<(Comment, bool)>,
+    next_comment: usize,
+    /// Jetons déjà atteints, pour retrouver le prochain `end`
+    cursor: usize,
+    out: Vec<String>,
+    indent: usize,
+    /// Commentaires de fin de ligne à ajouter à la prochaine ligne produite
+    trailing: Vec<String>,
+    /// Rien n'a encore été écrit dans le bloc ouvert
+    block_start: bool,
+    /// Ligne source du dernier nœud atteint
+    last_line: usize,
+}
+
+/// Déclaration d'une architecture ou d'un paquetage, pour les remettre
+/// dans l'ordre du source
+enum Decl<'d> {
+    Type(&'d TypeDecl),
+    Constant(&'d ConstantDecl),
+    Signal(&'d SignalDecl),
+    Component(&'d ComponentDecl),
+}
+
+impl Decl<'_> {
+    fn span(&self) -> Option<Span> {
+        match self {
+            Decl::Type(d) => d.span,
+            Decl::Constant(d) => d.span,
+            Decl::Signal(d) => d.span,
+            Decl::Component(d) => d.span,
+        }
+    }
+}
+
+enum Unit<'d> {
+    Entity(&'d Entity),
+    Architecture(&'d Architecture),
+    Package(&'d Package),
+}
+
+/// Largeur au-delà de laquelle une clause `generic` ou un `port map` est
+/// écrit un élément par ligne
+const MAX_WIDTH: usize = 100;
+
+fn position(span: Option<Span>) -> (usize, usize) {
+    span.map_or((usize::MAX, 0), |s| (s.line, s.col))
+}
+
+impl Printer<'_> {
+    fn finish(mut self) -> String {
+        self.leading((usize::MAX, 0));
+        while self.out.last().is_some_and(|l| l.is_empty()) {
+            self.out.pop();
+        }
+        let mut text = self.out.join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        self.row(text.as_ref());
+    }
+
+    /// Écrit une ligne ; rend son index et la fin du code si elle porte un
+    /// commentaire de fin de ligne
+    fn row(&mut self, text: &str) -> Option<(usize, usize)> {
+        let mut line = format!("{}{}", "  ".repeat(self.indent), text);
+        let code_end = line.len();
+        for comment in self.trailing.drain(..) {
+            line.push(' ');
+            line.push_str(&comment);
+        }
+        let commented = line.len() > code_end;
+        self.out.push(line);
+        self.block_start = false;
+        commented.then_some((self.out.len() - 1, code_end))
+    }
+
+    /// Aligne les commentaires de fin de ligne d'une liste
+    fn align_comments(&mut self, rows: &[(usize, usize)]) {
+        let column = rows.iter().map(|&(_, end)| end).max().unwrap_or(0);
+        for &(idx, end) in rows {
+            self.out[idx].insert_str(end, &" ".repeat(column - end));
+        }
+    }
+
+    fn blank(&mut self) {
+        if self.out.last().is_some_and(|l| !l.is_empty()) {
+            self.out.push(String::new());
+        }
+    }
+
+    fn open(&mut self) {
+        self.indent += 1;
+        self.block_start = true;
+    }
+
+    fn close(&mut self) {
+        self.indent -= 1;
+        self.block_start = false;
+    }
+
+    /// Garde la ligne vide qui précède `line` dans le source, sauf en tête
+    /// de bloc
+    fn keep_blank(&mut self, line: usize) {
+        let after_blank = line > 1 && self.src_lines.get(line - 2).is_some_and(|l| l.trim().is_empty());
+        if after_blank && !self.block_start {
+            self.blank();
+        }
+    }
+
+    /// Écrit les commentaires situés avant `pos`
+    fn leading(&mut self, pos: (usize, usize)) {
+        while let Some((comment, _)) = self.comments.get(self.next_comment) {
+            if (comment.span.line, comment.span.col) >= pos {
+                break;
+            }
+            let (line, text) = (comment.span.line, comment.text.clone());
+            self.next_comment += 1;
+            self.keep_blank(line);
+            self.out.push(format!("{}{}", "  ".repeat(self.indent), text));
+            self.block_start = false;
+        }
+    }
+
+    /// Se place sur le nœud ou le jeton à `span` : commentaires qui le
+    /// précèdent, ligne vide, commentaire en fin de sa ligne
+    fn at(&mut self, span: Option<Span>) {
+        let Some(span) = span else {
+            return;
+        };
+        self.leading((span.line, span.col));
+        if span.line != self.last_line {
+            self.keep_blank(span.line);
+            self.last_line = span.line;
+        }
+        if let Some(i) = (self.cursor..self.tokens.len()).find(|&i| self.tokens[i].span == span) {
+            self.cursor = i + 1;
+        }
+        while let Some((comment, true)) = self.comments.get(self.next_comment) {
+            if comment.span.line != span.line {
+                break;
+            }
+            self.trailing.push(comment.text.clone());
+            self.next_comment += 1;
+        }
+    }
+
+    /// Se place sur le prochain jeton `kind`, s'il précède `limit`
+    fn at_token(&mut self, kind: TokenKind, limit: (usize, usize)) {
+        let span = self.tokens[self.cursor..]
+            .iter()
+            .take_while(|t| (t.span.line, t.span.col) < limit)
+            .find(|t| t.kind == kind)
+            .map(|t| t.span);
+        self.at(span);
+    }
+
+    /// Se place sur le mot-clé qui ferme ou partage le bloc courant
+    fn at_keyword(&mut self, kind: TokenKind) {
+        self.at_token(kind, (usize::MAX, 0));
+    }
+
+    fn design(&mut self, design: &Design) {
+        let mut units: Vec<Unit> = design.entities.iter().map(Unit::Entity).collect();
+        units.extend(design.architectures.iter().map(Unit::Architecture));
+        units.extend(design.packages.iter().map(Unit::Package));
+        units.sort_by_key(|unit| {
+            position(match unit {
+                Unit::Entity(e) => e.span,
+                Unit::Architecture(a) => a.span,
+                Unit::Package(p) => p.span,
+            })
+        });
+        for unit in units {
+            self.blank();
+            match unit {
+                Unit::Entity(e) => {
+                    self.context(&e.uses, e.span);
+                    self.entity(e);
+                }
+                Unit::Architecture(a) => {
+                    self.context(&a.uses, a.span);
+                    self.architecture(a);
+                }
+                Unit::Package(p) => {
+                    self.context(&p.uses, p.span);
+                    self.package(p);
+                }
+            }
+        }
+    }
+
+    /// Clauses `library` et `use` d'une unité ; `work` n'est pas déclarée
+    fn context(&mut self, uses: &[UseClause], unit: Option<Span>) {
+        let first = uses.first().and_then(|u| u.span).or(unit);
+        self.at_token(TokenKind::KwLibrary, position(first));
+        let mut libraries: Vec<&str> = Vec::new();
+        for clause in uses {
+            let known = libraries.iter().any(|l| l.eq_ignore_ascii_case(&clause.library));
+            if !known && !clause.library.eq_ignore_ascii_case("work") {
+                libraries.push(&clause.library);
+            }
+        }
+        for library in libraries {
+            self.line(format!("library {};", library));
+        }
+        for clause in uses {
+            self.at(clause.span);
+            let item = clause.item.as_deref().unwrap_or("all");
+            self.line(format!("use {}.{}.{};", clause.library, clause.package, item));
+        }
+    }
+
+    fn entity(&mut self, ent: &Entity) {
+        self.at(ent.span);
+        self.line(format!("entity {} is", ent.name));
+        self.open();
+        self.generics(&ent.generics);
+        self.ports(&ent.ports);
+        self.close();
+        self.at_keyword(TokenKind::KwEnd);
+        self.line("end entity;");
+    }
+
+    fn component(&mut self, comp: &ComponentDecl) {
+        self.at(comp.span);
+        self.line(format!("component {}", comp.name));
+        self.open();
+        self.generics(&comp.generics);
+        self.ports(&comp.ports);
+        self.close();
+        self.at_keyword(TokenKind::KwEnd);
+        self.line("end component;");
+    }
+
+    fn generics(&mut self, generics: &[Generic]) {
+        if generics.is_empty() {
+            return;
+        }
+        let groups: Vec<&[Generic]> = generics.chunk_by(|a, b| a.span == b.span).collect();
+        let names: Vec<String> = groups.iter().map(|g| names(g.iter().map(|x| &x.name))).collect();
+        let items: Vec<(String, String)> = groups
+            .iter()
+            .zip(names)
+            .map(|(group, names)| {
+                let default = group[0].default.as_ref().map(|d| format!(" := {}", int_text(d, 0))).unwrap_or_default();
+                (names, format!("integer{}", default))
+            })
+            .collect();
+        let inline: Vec<String> = items.iter().map(|(names, ty)| format!("{} : {}", names, ty)).collect();
+        let inline = format!("generic({});", inline.join("; "));
+        if self.fits_inline(&inline, TokenKind::KwPort) {
+            self.line(inline);
+            return;
+        }
+        let width = items.iter().map(|(names, _)| names.len()).max().unwrap_or(0);
+        self.line("generic(");
+        self.open();
+        let mut rows = Vec::new();
+        for (i, (group, (names, ty))) in groups.iter().zip(&items).enumerate() {
+            self.at(group[0].span);
+            let sep = if i + 1 < groups.len() { ";" } else { "" };
+            rows.extend(self.row(&format!("{:width$} : {}{}", names, ty, sep)));
+        }
+        self.align_comments(&rows);
+        self.close();
+        self.line(");");
+    }
+
+    /// Une clause tient sur une ligne si elle ne dépasse pas `MAX_WIDTH` et
+    /// ne contient aucun commentaire avant le prochain jeton `end`
+    fn fits_inline(&self, text: &str, end: TokenKind) -> bool {
+        let Some(end) = self.tokens[self.cursor..].iter().find(|t| t.kind == end) else {
+            return false;
+        };
+        let comment_inside = self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|(c, _)| (c.span.line, c.span.col) < (end.span.line, end.span.col));
+        !comment_inside && 2 * self.indent + text.len() <= MAX_WIDTH
+    }
+
+    fn ports(&mut self, ports: &[Port]) {
+        let groups: Vec<&[Port]> = ports.chunk_by(|a, b| a.span == b.span).collect();
+        let names: Vec<String> = groups.iter().map(|g| names(g.iter().map(|x| &x.name))).collect();
+        let width = names.iter().map(String::len).max().unwrap_or(0);
+        self.line("port(");
+        self.open();
+        let mut rows = Vec::new();
+        for (i, (group, names)) in groups.iter().zip(&names).enumerate() {
+            self.at(group[0].span);
+            let dir = match group[0].dir {
+                Direction::In => "in ",
+                Direction::Out => "out",
+            };
+            let sep = if i + 1 < groups.len() { ";" } else { "" };
+            rows.extend(self.row(&format!("{:width$} : {} {}{}", names, dir, type_text(&group[0].ty), sep)));
+        }
+        self.align_comments(&rows);
+        self.close();
+        self.line(");");
+    }
+
+    fn architecture(&mut self, arch: &Architecture) {
+        self.at(arch.span);
+        self.line(format!("architecture {} of {} is", arch.name, arch.entity));
+        self.open();
+        let mut decls: Vec<Decl> = arch.types.iter().map(Decl::Type).collect();
+        decls.extend(arch.constants.iter().map(Decl::Constant));
+        decls.extend(arch.signals.iter().map(Decl::Signal));
+        decls.extend(arch.components.iter().map(Decl::Component));
+        self.decls(decls);
+        self.close();
+        self.at_keyword(TokenKind::KwBegin);
+        self.line("begin");
+        self.open();
+        self.concurrent(&arch.stmts);
+        self.close();
+        self.at_keyword(TokenKind::KwEnd);
+        self.line("end architecture;");
+    }
+
+    fn package(&mut self, pkg: &Package) {
+        self.at(pkg.span);
+        self.line(format!("package {} is", pkg.name));
+        self.open();
+        let mut decls: Vec<Decl> = pkg.types.iter().map(Decl::Type).collect();
+        decls.extend(pkg.constants.iter().map(Decl::Constant));
+        decls.extend(pkg.components.iter().map(Decl::Component));
+        self.decls(decls);
+        self.close();
+        self.at_keyword(TokenKind::KwEnd);
+        self.line("end package;");
+    }
+
+    fn decls(&mut self, mut decls: Vec<Decl>) {
+        decls.sort_by_key(|d| position(d.span()));
+        for decl in decls {
+            match decl {
+                Decl::Type(t) => {
+                    self.at(t.span);
+                    let def = match &t.def {
+                        TypeDef::Enum(literals) => format!("({})", literals.join(", ")),
+                        TypeDef::Array { left, right, dir, elem } => format!(
+                            "array ({} {} {}) of {}",
+                            int_text(left, 0),
+                            dir_text(dir),
+                            int_text(right, 0),
+                            type_text(elem)
+                        ),
+                    };
+                    self.line(format!("type {} is {};", t.name, def));
+                }
+                Decl::Constant(c) => {
+                    self.at(c.span);
+                    let value = match &c.value {
+                        ConstantValue::Int(v) => int_text(v, 0),
+                        ConstantValue::Expr(e) => expr_text(e, 0),
+                    };
+                    self.line(format!("constant {} : {} := {};", c.name, type_text(&c.ty), value));
+                }
+                Decl::Signal(s) => {
+                    self.at(s.span);
+                    let init = s.init.as_ref().map(|e| format!(" := {}", expr_text(e, 0))).unwrap_or_default();
+                    self.line(format!("signal {} : {}{};", s.names.join(", "), type_text(&s.ty), init));
+                }
+                Decl::Component(c) => self.component(c),
+            }
+        }
+    }
+
+    fn concurrent(&mut self, stmts: &[ConcurrentStmt]) {
+        for stmt in stmts {
+            match stmt {
+                ConcurrentStmt::Assign(assign) => self.assign(assign),
+                ConcurrentStmt::Process(proc) => {
+                    self.at(proc.span);
+                    self.line(format!("process({})", proc.sensitivity.join(", ")));
+                    self.at_keyword(TokenKind::KwBegin);
+                    self.line("begin");
+                    self.open();
+                    self.sequential(&proc.stmts);
+                    self.close();
+                    self.at_keyword(TokenKind::KwEnd);
+                    self.line("end process;");
+                }
+                ConcurrentStmt::Instance(inst) => self.instance(inst),
+                ConcurrentStmt::Generate(generate) => {
+                    self.at(generate.span);
+                    let scheme = match &generate.scheme {
+                        GenerateScheme::For { var, left, right, dir } => {
+                            format!("for {} in {} {} {}", var, int_text(left, 0), dir_text(dir), int_text(right, 0))
+                        }
+                        GenerateScheme::If { op, left, right } => {
+                            format!("if {} {} {}", int_text(left, 0), binary_op(*op).0, int_text(right, 0))
+                        }
+                    };
+                    self.line(format!("{}: {} generate", generate.label, scheme));
+                    self.open();
+                    self.concurrent(&generate.stmts);
+                    self.close();
+                    self.at_keyword(TokenKind::KwEnd);
+                    self.line("end generate;");
+                }
+            }
+        }
+    }
+
+    fn instance(&mut self, inst: &InstanceStmt) {
+        self.at(inst.span);
+        let mut header = format!("{}: {}", inst.name, inst.entity);
+        if !inst.generic_map.is_empty() {
+            let generics: Vec<String> = inst
+                .generic_map
+                .iter()
+                .map(|g| format!("{} => {}", g.name, int_text(&g.value, 0)))
+                .collect();
+            header.push_str(&format!(" generic map ({})", generics.join(", ")));
+        }
+        let assocs: Vec<String> = inst
+            .port_map
+            .iter()
+            .map(|a| format!("{} => {}", a.port, expr_text(&a.expr, 0)))
+            .collect();
+        let inline = format!("{} port map ({});", header, assocs.join(", "));
+        if self.fits_inline(&inline, TokenKind::Semicolon) {
+            self.line(inline);
+            return;
+        }
+        self.line(format!("{} port map (", header));
+        self.open();
+        let width = inst.port_map.iter().map(|a| a.port.len()).max().unwrap_or(0);
+        let mut rows = Vec::new();
+        for (i, assoc) in inst.port_map.iter().enumerate() {
+            self.at(assoc.span);
+            let sep = if i + 1 < inst.port_map.len() { "," } else { "" };
+            rows.extend(self.row(&format!("{:width$} => {}{}", assoc.port, expr_text(&assoc.expr, 0), sep)));
+        }
+        self.align_comments(&rows);
+        self.close();
+        self.line(");");
+    }
+
+    fn assign(&mut self, assign: &AssignStmt) {
+        self.at(assign.span);
+        let head = format!("{} <= ", target_text(&assign.target));
+        // Plusieurs `when` : un choix par ligne, alignés sous le premier
+        let mut choices = Vec::new();
+        let mut rest = &assign.expr;
+        while let Expr::When { value, cond, otherwise } = rest {
+            choices.push(format!("{} when {} else", expr_text(value, 0), expr_text(cond, 0)));
+            rest = otherwise;
+        }
+        if choices.len() < 2 {
+            self.line(format!("{}{};", head, expr_text(&assign.expr, 0)));
+            return;
+        }
+        choices.push(format!("{};", expr_text(rest, 0)));
+        for (i, choice) in choices.iter().enumerate() {
+            let pad = if i == 0 { head.clone() } else { " ".repeat(head.len()) };
+            self.line(format!("{}{}", pad, choice));
+        }
+    }
+
+    fn sequential(&mut self, stmts: &[SeqStmt]) {
+        for stmt in stmts {
+            match stmt {
+                SeqStmt::Assign(assign) => self.assign(assign),
+                SeqStmt::If(stmt) => {
+                    self.at(stmt.span);
+                    self.line(format!("if {} then", expr_text(&stmt.cond, 0)));
+                    self.block(&stmt.then_stmts);
+                    for (cond, stmts) in &stmt.elsif {
+                        self.at_keyword(TokenKind::KwElsif);
+                        self.line(format!("elsif {} then", expr_text(cond, 0)));
+                        self.block(stmts);
+                    }
+                    if !stmt.else_stmts.is_empty() {
+                        self.at_keyword(TokenKind::KwElse);
+                        self.line("else");
+                        self.block(&stmt.else_stmts);
+                    }
+                    self.at_keyword(TokenKind::KwEnd);
+                    self.line("end if;");
+                }
+                SeqStmt::Case(stmt) => {
+                    self.at(stmt.span);
+                    self.line(format!("case {} is", expr_text(&stmt.expr, 0)));
+                    self.open();
+                    for (choice, stmts) in &stmt.arms {
+                        self.at_keyword(TokenKind::KwWhen);
+                        let choice = match choice {
+                            CaseChoice::Literal(lit) => literal_text(lit),
+                            CaseChoice::Ident(name) => name.clone(),
+                            CaseChoice::Others => "others".to_string(),
+                        };
+                        self.line(format!("when {} =>", choice));
+                        self.block(stmts);
+                    }
+                    self.close();
+                    self.at_keyword(TokenKind::KwEnd);
+                    self.line("end case;");
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, stmts: &[SeqStmt]) {
+        self.open();
+        self.sequential(stmts);
+        self.inner_comments();
+        self.close();
+    }
+
+    /// Écrit dans le bloc les commentaires qui suivent sa dernière
+    /// instruction, s'ils sont plus indentés que le mot-clé qui le ferme
+    fn inner_comments(&mut self) {
+        let closing = [TokenKind::KwEnd, TokenKind::KwElse, TokenKind::KwElsif, TokenKind::KwWhen];
+        let Some(end) = self.tokens[self.cursor..].iter().find(|t| closing.contains(&t.kind)).map(|t| t.span) else {
+            return;
+        };
+        while let Some((comment, false)) = self.comments.get(self.next_comment) {
+            if (comment.span.line, comment.span.col) >= (end.line, end.col) || comment.span.col <= end.col {
+                break;
+            }
+            self.leading((comment.span.line, comment.span.col + 1));
+        }
+    }
+}
+
+fn names<'n>(names: impl Iterator<Item = &'n String>) -> String {
+    names.map(String::as_str).collect::<Vec<_>>().join(", ")
+}
+
+fn dir_text(dir: &RangeDir) -> &'static str {
+    match dir {
+        RangeDir::Downto => "downto",
+        RangeDir::To => "to",
+    }
+}
+
+/// Type tel qu'il s'écrit ; `integer` seul pour la plage 32 bits par défaut
+pub(crate) fn type_text(ty: &Type) -> String {
+    match ty {
+        Type::Bit => "bit".to_string(),
+        Type::Bits { msb, lsb, dir } => format!("bits({} {} {})", int_text(msb, 0), dir_text(dir), int_text(lsb, 0)),
+        Type::Integer {
+            low: IntExpr::Lit(low),
+            high: IntExpr::Lit(high),
+        } if *low == i32::MIN as i64 && *high == i32::MAX as i64 => "integer".to_string(),
+        Type::Integer { low, high } => format!("integer range {} to {}", int_text(low, 0), int_text(high, 0)),
+        Type::Named(name) => name.clone(),
+    }
+}
+
+/// Texte d'une expression entière, entre parenthèses si son opérateur lie
+/// moins que `min`
+pub(crate) fn int_text(expr: &IntExpr, min: u8) -> String {
+    match expr {
+        IntExpr::Lit(v) if *v < 0 => format!("({})", v),
+        IntExpr::Lit(v) => v.to_string(),
+        IntExpr::Name(name) => name.clone(),
+        IntExpr::Neg(inner) => negate(int_text(inner, 3)),
+        IntExpr::Binary { op, left, right } => {
+            let (symbol, prec) = match op {
+                IntOp::Add => ("+", 1),
+                IntOp::Sub => ("-", 1),
+                IntOp::Mul => ("*", 2),
+                IntOp::Div => ("/", 2),
+            };
+            let text = format!("{} {} {}", int_text(left, prec), symbol, int_text(right, prec + 1));
+            if prec < min {
+                format!("({})", text)
+            } else {
+                text
+            }
+        }
+    }
+}
+
+/// `-x`, sans former de commentaire `--`
+fn negate(operand: String) -> String {
+    if operand.starts_with('-') {
+        format!("-({})", operand)
+    } else {
+        format!("-{}", operand)
+    }
+}
+
+/// Symbole et priorité d'un opérateur, dans l'ordre du parser : comparaison,
+/// `+ -`, `&`, décalages, `and or`, `xor`
+fn binary_op(op: BinaryOp) -> (&'static str, u8) {
+    match op {
+        BinaryOp::Eq => ("=", 1),
+        BinaryOp::Ne => ("/=", 1),
+        BinaryOp::Lt => ("<", 1),
+        BinaryOp::Le => ("<=", 1),
+        BinaryOp::Gt => (">", 1),
+        BinaryOp::Ge => (">=", 1),
+        BinaryOp::Add => ("+", 2),
+        BinaryOp::Sub => ("-", 2),
+        BinaryOp::Concat => ("&", 3),
+        BinaryOp::Shl => ("<<", 4),
+        BinaryOp::Shr => (">>", 4),
+        BinaryOp::And => ("and", 5),
+        BinaryOp::Or => ("or", 5),
+        BinaryOp::Xor => ("xor", 6),
+    }
+}
+
+fn expr_text(expr: &Expr, min: u8) -> String {
+    match expr {
+        Expr::Literal(lit) => literal_text(lit),
+        Expr::Target(target) => target_text(target),
+        Expr::Unary { op, expr } => {
+            let operand = expr_text(expr, 7);
+            match op {
+                UnaryOp::Not => format!("not {}", operand),
+                UnaryOp::Neg => negate(operand),
+            }
+        }
+        Expr::Binary { op, left, right } => {
+            let (symbol, prec) = binary_op(*op);
+            // Une seule comparaison, entre deux sommes
+            let (left_min, right_min) = if prec == 1 { (2, 2) } else { (prec, prec + 1) };
+            let text = format!("{} {} {}", expr_text(left, left_min), symbol, expr_text(right, right_min));
+            if prec < min {
+                format!("({})", text)
+            } else {
+                text
+            }
+        }
+        Expr::Call { name, args } => {
+            let args: Vec<String> = args.iter().map(|a| expr_text(a, 0)).collect();
+            format!("{}({})", name.to_ascii_lowercase(), args.join(", "))
+        }
+        Expr::When { value, cond, otherwise } => {
+            format!("{} when {} else {}", expr_text(value, 0), expr_text(cond, 0), expr_text(otherwise, 0))
+        }
+        Expr::Aggregate { range, value } => {
+            let choice = match range {
+                Some((left, right, dir)) => format!("{} {} {}", int_text(left, 0), dir_text(dir), int_text(right, 0)),
+                None => "others".to_string(),
+            };
+            format!("({} => {})", choice, expr_text(value, 0))
+        }
+    }
+}
+
+fn literal_text(lit: &Literal) -> String {
+    match lit {
+        Literal::Bit(b) => format!("'{}'", u8::from(*b)),
+        Literal::Bits(digits, LiteralBase::Bin) => format!("b\"{}\"", digits),
+        Literal::Bits(digits, LiteralBase::Hex) => format!("x\"{}\"", digits),
+        Literal::Int(v) => v.to_string(),
+    }
+}
+
+fn target_text(target: &Target) -> String {
+    let sel = match &target.sel {
+        None => String::new(),
+        Some(SelectorExpr::Index(index)) => format!("({})", int_text(index, 0)),
+        Some(SelectorExpr::Expr(index)) => format!("({})", expr_text(index, 0)),
+        Some(SelectorExpr::Range { msb, lsb, dir }) => {
+            format!("({} {} {})", int_text(msb, 0), dir_text(dir), int_text(lsb, 0))
+        }
+    };
+    format!("{}{}", target.name, sel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    const MESSY: &str = r#"-- Compteur
+LIBRARY work;
+USE work.cfg.ALL;
+ENTITY Counter IS
+GENERIC(N : INTEGER := 4);
+  Port(clk, rst : IN bit; -- horloge et reset
+  en:in BIT;
+     q : OUT Bits(N-1 DOWNTO 0)); -- compte
+END ENTITY Counter;
+architecture RTL of Counter is
+signal cnt : bits(N-1 downto 0) := X"0";
+  -- état
+  type state_t is (IDLE, RUN);
+  signal state : state_t;
+BEGIN
+  PROCESS(clk, rst) BEGIN
+    IF rst = '1' THEN cnt <= X"0";
+    ELSIF Rising_Edge(clk) THEN
+      CASE state IS
+        WHEN IDLE => -- attente
+          IF en = '1' THEN state <= RUN; END IF;
+        WHEN OTHERS =>
+          cnt <= cnt + 1;
+          -- rien d'autre
+      END CASE;
+    END IF;
+  END PROCESS;
+
+  u_one : Inc PORT MAP (a=>cnt, y=>q);
+  u_inc : Inc GENERIC MAP (W => N*(2+1)) PORT MAP (a=>cnt,
+     -- retenue ignorée
+     carry_out=>open_c, y=>q); -- sortie
+END ARCHITECTURE;
+-- fin
+"#;
+
+    const FORMATTED: &str = r#"-- Compteur
+use work.cfg.all;
+entity Counter is
+  generic(N : integer := 4);
+  port(
+    clk, rst : in  bit;                 -- horloge et reset
+    en       : in  bit;
+    q        : out bits(N - 1 downto 0) -- compte
+  );
+end entity;
+
+architecture RTL of Counter is
+  signal cnt : bits(N - 1 downto 0) := x"0";
+  -- état
+  type state_t is (IDLE, RUN);
+  signal state : state_t;
+begin
+  process(clk, rst)
+  begin
+    if rst = '1' then
+      cnt <= x"0";
+    elsif rising_edge(clk) then
+      case state is
+        when IDLE => -- attente
+          if en = '1' then
+            state <= RUN;
+          end if;
+        when others =>
+          cnt <= cnt + 1;
+          -- rien d'autre
+      end case;
+    end if;
+  end process;
+
+  u_one: Inc port map (a => cnt, y => q);
+  u_inc: Inc generic map (W => N * (2 + 1)) port map (
+    a         => cnt,
+    -- retenue ignorée
+    carry_out => open_c, -- sortie
+    y         => q
+  );
+end architecture;
+-- fin
+"#;
+
+    #[test]
+    fn test_format_layout() {
+        assert_eq!(format_source(MESSY).unwrap(), FORMATTED);
+        assert_eq!(format_source(FORMATTED).unwrap(), FORMATTED);
+        assert!(format_source("entity X is").is_err());
+    }
+
+    #[test]
+    fn test_format_expressions() {
+        let src = "entity E is port(a : in bits(3 downto 0); y : out bits(3 downto 0)); end entity;
+architecture rtl of E is begin
+  y <= (a and (b or c)) xor not (a + 1) & (a - (b - c));
+  y(1 downto 0) <= - (-a) & a(i) & regs(ir(1 downto 0));
+  y <= resize(a, 4) < (b << 1);
+  y <= (others=>'0') when a = 0 else (3 downto 1 => a(0)) & '1';
+  y <= a when s = '1' else b when t = '1' else x\"0\";
+end architecture;";
+        let out = format_source(src).unwrap();
+        assert!(out.contains("  y <= (a and (b or c)) xor not (a + 1) & (a - (b - c));\n"), "{}", out);
+        assert!(out.contains("  y(1 downto 0) <= -(-a) & a(i) & regs(ir(1 downto 0));\n"), "{}", out);
+        assert!(out.contains("  y <= resize(a, 4) < b << 1;\n"), "{}", out);
+        assert!(out.contains("  y <= (others => '0') when a = 0 else (3 downto 1 => a(0)) & '1';\n"), "{}", out);
+        assert!(out.contains("  y <= a when s = '1' else\n       b when t = '1' else\n       x\"0\";\n"), "{}", out);
+        assert_eq!(format_source(&out).unwrap(), out);
+        let n_minus_1 = IntExpr::Binary { op: IntOp::Sub, left: Box::new(IntExpr::Name("N".into())), right: Box::new(IntExpr::Lit(1)) };
+        let times_2 = IntExpr::Binary { op: IntOp::Mul, left: Box::new(n_minus_1), right: Box::new(IntExpr::Lit(2)) };
+        assert_eq!(int_text(&times_2, 0), "(N - 1) * 2");
+        assert_eq!(type_text(&Type::Integer { low: IntExpr::Lit(i32::MIN as i64), high: IntExpr::Lit(i32::MAX as i64) }), "integer");
+    }
+
+    /// Toute la bibliothèque analysable : idempotent, commentaires conservés
+    #[test]
+    fn test_format_library_is_stable() {
+        fn visit(dir: &Path, files: &mut Vec<std::path::PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap().filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if path.is_dir() {
+                    visit(&path, files);
+                } else if path.extension().is_some_and(|e| e == "hdl") {
+                    files.push(path);
+                }
+            }
+        }
+        let mut files = Vec::new();
+        visit(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../hdl_lib"), &mut files);
+        let mut formatted = 0;
+        for file in files {
+            let src = fs::read_to_string(&file).unwrap();
+            let Ok(out) = format_source(&src) else {
+                continue;
+            };
+            let count = |text: &str| {
+                let mut lexer = Lexer::new(text);
+                lexer.lex().unwrap();
+                lexer.comments().len()
+            };
+            assert_eq!(count(&out), count(&src), "{}", file.display());
+            assert_eq!(format_source(&out).unwrap(), out, "{}", file.display());
+            formatted += 1;
+        }
+        assert!(formatted > 10);
+    }
+}
@@ -0,0 +1,512 @@
+//! Graphe de dépendances combinatoires
+//! Construit à partir du Netlist: quels noeuds (affectations, primitives)
+//! lisent et écrivent quels bits de quels signaux.
+//!
+//! Une arête relie deux noeuds dès qu'un bit écrit par l'un est lu par
+//! l'autre : un noeud qui lit une partie de sa propre cible
+//! (`a <= a(1 downto 0) & x;`) forme une boucle au niveau des noeuds sans
+//! qu'aucun bit ne dépende de lui-même. `find_cycle` revérifie donc ces
+//! boucles bit par bit.
+
+use crate::ast::{BinaryOp, Selector, UnaryOp};
+use crate::elab::{ExprRef, Netlist, PrimitiveNet, Signal, SignalType, TargetRef};
+use crate::value::ValueKind;
+use std::collections::VecDeque;
+
+/// Un noeud combinatoire du netlist
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CombNode {
+    /// Index dans `Netlist::assigns`
+    Assign(usize),
+    /// Index dans `Netlist::primitives`
+    Primitive(usize),
+}
+
+/// Plage de bits (positions, lsb = 0) d'un signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitSpan {
+    pub signal: usize,
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl BitSpan {
+    fn overlaps(&self, other: &BitSpan) -> bool {
+        self.signal == other.signal && self.lo <= other.hi && other.lo <= self.hi
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CombGraph {
+    pub nodes: Vec<CombNode>,
+    /// Bits écrits par chaque noeud
+    pub writes: Vec<BitSpan>,
+    /// Bits lus par chaque noeud
+    pub reads: Vec<Vec<BitSpan>>,
+    /// Noeuds dont l'entrée dépend de la sortie du noeud (au bit près)
+    pub succ: Vec<Vec<usize>>,
+    /// Pour chaque signal, les noeuds qui en lisent au moins un bit
+    pub readers: Vec<Vec<usize>>,
+    /// Pour chaque signal, les noeuds qui en écrivent au moins un bit
+    pub drivers: Vec<Vec<usize>>,
+    /// Ordre topologique des noeuds; les noeuds pris dans une boucle
+    /// (ou en aval d'une boucle) sont placés à la fin dans l'ordre du netlist
+    pub order: Vec<usize>,
+    /// Nombre de noeuds en tête d'`order` qui ne sont ni dans une boucle ni
+    /// en aval d'une boucle
+    pub acyclic: usize,
+    /// Position de chaque noeud dans `order`
+    pub rank: Vec<usize>,
+    /// Noeud associé à chaque primitive combinatoire (None pour Dff)
+    pub prim_node: Vec<Option<usize>>,
+}
+
+impl CombGraph {
+    pub fn build(netlist: &Netlist) -> Self {
+        let mut nodes = Vec::new();
+        let mut writes = Vec::new();
+        let mut reads = Vec::new();
+        for (i, assign) in netlist.assigns.iter().enumerate() {
+            let mut r = Vec::new();
+            collect_reads(&assign.expr, &netlist.signals, &mut r);
+            nodes.push(CombNode::Assign(i));
+            writes.push(target_span(&assign.target, &netlist.signals));
+            reads.push(r);
+        }
+        let mut prim_node = Vec::with_capacity(netlist.primitives.len());
+        for (i, prim) in netlist.primitives.iter().enumerate() {
+            let mut r = Vec::new();
+            let out = match prim {
+                PrimitiveNet::Nand2 { a, b, y }
+                | PrimitiveNet::And2 { a, b, y }
+                | PrimitiveNet::Or2 { a, b, y }
+                | PrimitiveNet::Xor2 { a, b, y } => {
+                    collect_reads(a, &netlist.signals, &mut r);
+                    collect_reads(b, &netlist.signals, &mut r);
+                    y
+                }
+                PrimitiveNet::Not1 { a, y } => {
+                    collect_reads(a, &netlist.signals, &mut r);
+                    y
+                }
+                PrimitiveNet::Mux2 { a, b, sel, y } => {
+                    collect_reads(a, &netlist.signals, &mut r);
+                    collect_reads(b, &netlist.signals, &mut r);
+                    collect_reads(sel, &netlist.signals, &mut r);
+                    y
+                }
+                PrimitiveNet::Ram { addr, dout, .. } | PrimitiveNet::Rom { addr, dout, .. } => {
+                    collect_reads(addr, &netlist.signals, &mut r);
+                    dout
+                }
+                PrimitiveNet::Dff { .. } => {
+                    prim_node.push(None);
+                    continue;
+                }
+            };
+            prim_node.push(Some(nodes.len()));
+            nodes.push(CombNode::Primitive(i));
+            writes.push(target_span(out, &netlist.signals));
+            reads.push(r);
+        }
+
+        let sig_count = netlist.signals.len();
+        let mut readers = vec![Vec::new(); sig_count];
+        let mut drivers = vec![Vec::new(); sig_count];
+        for (n, spans) in reads.iter().enumerate() {
+            for span in spans {
+                if readers[span.signal].last() != Some(&n) {
+                    readers[span.signal].push(n);
+                }
+            }
+        }
+        for (n, span) in writes.iter().enumerate() {
+            drivers[span.signal].push(n);
+        }
+
+        let mut succ = vec![Vec::new(); nodes.len()];
+        for (n, w) in writes.iter().enumerate() {
+            for &m in &readers[w.signal] {
+                if reads[m].iter().any(|r| r.overlaps(w)) {
+                    succ[n].push(m);
+                }
+            }
+        }
+
+        let (order, acyclic) = levelize(&succ);
+        let mut rank = vec![0; nodes.len()];
+        for (pos, &n) in order.iter().enumerate() {
+            rank[n] = pos;
+        }
+
+        Self {
+            nodes,
+            writes,
+            reads,
+            succ,
+            readers,
+            drivers,
+            order,
+            acyclic,
+            rank,
+            prim_node,
+        }
+    }
+
+    /// Cherche un cycle combinatoire au bit près; renvoie ses noeuds dans
+    /// l'ordre de propagation (chaque noeud est un prédécesseur du suivant,
+    /// le dernier reboucle sur le premier)
+    pub fn find_cycle(&self, netlist: &Netlist) -> Option<Vec<usize>> {
+        self.find_node_cycle()?;
+        // Sommets : les bits écrits par les noeuds suspects
+        let suspects = &self.order[self.acyclic..];
+        let mut base = vec![usize::MAX; self.nodes.len()];
+        let mut owner = Vec::new();
+        for &n in suspects {
+            base[n] = owner.len();
+            owner.extend(std::iter::repeat_n(n, self.writes[n].hi - self.writes[n].lo + 1));
+        }
+        let mut succ = vec![Vec::new(); owner.len()];
+        for &m in suspects {
+            for (bit, deps) in self.bit_deps(m, netlist).iter().enumerate() {
+                for dep in deps {
+                    for &n in &self.drivers[dep.signal] {
+                        let w = self.writes[n];
+                        if base[n] == usize::MAX || !w.overlaps(dep) {
+                            continue;
+                        }
+                        for pos in w.lo.max(dep.lo)..=w.hi.min(dep.hi) {
+                            succ[base[n] + pos - w.lo].push(base[m] + bit);
+                        }
+                    }
+                }
+            }
+        }
+        let mut cycle: Vec<usize> = find_cycle_in(&succ)?.into_iter().map(|v| owner[v]).collect();
+        cycle.dedup();
+        if cycle.len() > 1 && cycle.first() == cycle.last() {
+            cycle.pop();
+        }
+        Some(cycle)
+    }
+
+    /// Cycle au niveau des noeuds, qui peut ne relier aucun bit à lui-même
+    pub fn find_node_cycle(&self) -> Option<Vec<usize>> {
+        find_cycle_in(&self.succ)
+    }
+
+    /// Bits lus par chaque bit écrit par le noeud (lsb = 0)
+    fn bit_deps(&self, node: usize, netlist: &Netlist) -> Vec<Vec<BitSpan>> {
+        let w = self.writes[node];
+        let width = w.hi - w.lo + 1;
+        let exact = match self.nodes[node] {
+            CombNode::Assign(i) => expr_bits(&netlist.assigns[i].expr, &netlist.signals),
+            CombNode::Primitive(_) => None,
+        };
+        match exact {
+            // Valeur étendue à la largeur de la cible comme le simulateur
+            Some((bits, signed)) => extend(bits, width, signed),
+            None => vec![merge(self.reads[node].clone()); width],
+        }
+    }
+
+}
+
+/// Cycle d'un graphe donné par ses successeurs, dans l'ordre de propagation
+fn find_cycle_in(succ: &[Vec<usize>]) -> Option<Vec<usize>> {
+    // 0 = non visité, 1 = sur la pile, 2 = terminé
+    let mut state = vec![0u8; succ.len()];
+    for start in 0..succ.len() {
+        if state[start] != 0 {
+            continue;
+        }
+        let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+        state[start] = 1;
+        while let Some(&mut (node, ref mut next)) = stack.last_mut() {
+            if let Some(&m) = succ[node].get(*next) {
+                *next += 1;
+                match state[m] {
+                    0 => {
+                        state[m] = 1;
+                        stack.push((m, 0));
+                    }
+                    1 => {
+                        let pos = stack.iter().position(|&(n, _)| n == m).unwrap_or(0);
+                        return Some(stack[pos..].iter().map(|&(n, _)| n).collect());
+                    }
+                    _ => {}
+                }
+            } else {
+                state[node] = 2;
+                stack.pop();
+            }
+        }
+    }
+    None
+}
+
+/// Tri topologique (Kahn); les noeuds restants appartiennent à une boucle
+/// ou sont en aval d'une boucle. Rend l'ordre et le nombre de noeuds triés.
+fn levelize(succ: &[Vec<usize>]) -> (Vec<usize>, usize) {
+    let n = succ.len();
+    let mut indegree = vec![0usize; n];
+    for edges in succ {
+        for &m in edges {
+            indegree[m] += 1;
+        }
+    }
+    let mut queue: VecDeque<usize> = (0..n).filter(|&i| indegree[i] == 0).collect();
+    let mut order = Vec::with_capacity(n);
+    let mut placed = vec![false; n];
+    while let Some(node) = queue.pop_front() {
+        order.push(node);
+        placed[node] = true;
+        for &m in &succ[node] {
+            indegree[m] -= 1;
+            if indegree[m] == 0 {
+                queue.push_back(m);
+            }
+        }
+    }
+    let sorted = order.len();
+    order.extend((0..n).filter(|&i| !placed[i]));
+    (order, sorted)
+}
+
+/// Plage de bits désignée par une cible (signal entier ou sélection)
+pub fn target_span(target: &TargetRef, signals: &[Signal]) -> BitSpan {
+    let sig = &signals[target.signal];
+    let min = sig.lsb.min(sig.msb);
+    let clamp = |idx: i64| ((idx - min).max(0) as usize).min(sig.width.saturating_sub(1));
+    let (lo, hi) = match &target.sel {
+        None => (0, sig.width.saturating_sub(1)),
+        Some(Selector::Index(i)) => (clamp(*i), clamp(*i)),
+        Some(Selector::Range { msb, lsb, .. }) => (clamp((*msb).min(*lsb)), clamp((*msb).max(*lsb))),
+    };
+    BitSpan {
+        signal: target.signal,
+        lo,
+        hi,
+    }
+}
+
+/// Collecte les bits de signaux lus par une expression
+pub fn collect_reads(expr: &ExprRef, signals: &[Signal], out: &mut Vec<BitSpan>) {
+    match expr {
+        ExprRef::Literal(_) => {}
+        ExprRef::Target(t) => out.push(target_span(t, signals)),
+        ExprRef::Unary { expr, .. } => collect_reads(expr, signals, out),
+        ExprRef::Binary { left, right, .. } => {
+            collect_reads(left, signals, out);
+            collect_reads(right, signals, out);
+        }
+        ExprRef::Call { args, .. } => {
+            for a in args {
+                collect_reads(a, signals, out);
+            }
+        }
+        // Indice connu à l'exécution seulement : tout le tableau est lu
+        ExprRef::Element { array, index } => {
+            out.push(BitSpan {
+                signal: *array,
+                lo: 0,
+                hi: signals[*array].width.saturating_sub(1),
+            });
+            collect_reads(index, signals, out);
+        }
+        ExprRef::Select { cond, then, otherwise } => {
+            collect_reads(cond, signals, out);
+            collect_reads(then, signals, out);
+            collect_reads(otherwise, signals, out);
+        }
+    }
+}
+
+/// Bits lus par chaque bit d'une expression (lsb = 0), et si la valeur est
+/// étendue avec son signe; None si la largeur n'est connue qu'à l'exécution
+fn expr_bits(expr: &ExprRef, signals: &[Signal]) -> Option<(Vec<Vec<BitSpan>>, bool)> {
+    match expr {
+        ExprRef::Literal(v) => Some((vec![Vec::new(); v.bits.width()], v.kind == ValueKind::Arithmetic)),
+        ExprRef::Target(t) => {
+            let span = target_span(t, signals);
+            let bits = (span.lo..=span.hi)
+                .map(|pos| vec![BitSpan { signal: span.signal, lo: pos, hi: pos }])
+                .collect();
+            Some((bits, false))
+        }
+        ExprRef::Unary { op: UnaryOp::Not, expr } => Some((expr_bits(expr, signals)?.0, false)),
+        ExprRef::Unary { op: UnaryOp::Neg, expr } => Some((carry_chain(&expr_bits(expr, signals)?.0, &[]), true)),
+        ExprRef::Binary { op, left, right } => {
+            let (l, l_signed) = expr_bits(left, signals)?;
+            let (r, r_signed) = expr_bits(right, signals)?;
+            let width = l.len().max(r.len());
+            let all = || merge(l.iter().chain(&r).flatten().copied().collect());
+            Some(match op {
+                BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => {
+                    let (l, r) = (extend(l, width, false), extend(r, width, false));
+                    (l.into_iter().zip(r).map(|(a, b)| merge([a, b].concat())).collect(), false)
+                }
+                // Opérandes étendus avec leur signe, chaque bit dépend des
+                // bits de poids inférieur par la retenue
+                BinaryOp::Add | BinaryOp::Sub => {
+                    let (l, r) = (extend(l, width, l_signed), extend(r, width, r_signed));
+                    (carry_chain(&l, &r), true)
+                }
+                BinaryOp::Concat => (r.into_iter().chain(l).collect(), false),
+                BinaryOp::Shl | BinaryOp::Shr => match right.as_ref() {
+                    ExprRef::Literal(count) => {
+                        let count = count.bits.to_u64_trunc() as usize;
+                        let bits = (0..l.len())
+                            .map(|i| match op {
+                                BinaryOp::Shl => i.checked_sub(count).map(|j| l[j].clone()).unwrap_or_default(),
+                                _ => l.get(i.saturating_add(count)).cloned().unwrap_or_default(),
+                            })
+                            .collect();
+                        (bits, false)
+                    }
+                    _ => (vec![all(); l.len()], false),
+                },
+                BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                    (vec![all()], false)
+                }
+            })
+        }
+        ExprRef::Call { name, args } => {
+            let signed = name.eq_ignore_ascii_case("sresize");
+            match args.as_slice() {
+                [value, ExprRef::Literal(size)] if signed || name.eq_ignore_ascii_case("resize") => {
+                    let (bits, _) = expr_bits(value, signals)?;
+                    let width = size.bits.to_u64_trunc() as usize;
+                    Some((extend(bits, width, signed), signed))
+                }
+                _ => None,
+            }
+        }
+        ExprRef::Element { array, .. } => {
+            let SignalType::Array { elem_width, .. } = &signals[*array].ty else {
+                return None;
+            };
+            let mut reads = Vec::new();
+            collect_reads(expr, signals, &mut reads);
+            Some((vec![merge(reads); *elem_width], false))
+        }
+        // Chaque bit : la condition entière et le même bit des deux choix
+        ExprRef::Select { cond, then, otherwise } => {
+            let mut reads = Vec::new();
+            collect_reads(cond, signals, &mut reads);
+            let (a, a_signed) = expr_bits(then, signals)?;
+            let (b, b_signed) = expr_bits(otherwise, signals)?;
+            let width = a.len().max(b.len());
+            let (a, b) = (extend(a, width, a_signed), extend(b, width, b_signed));
+            let bits = a.into_iter().zip(b).map(|(x, y)| merge([x, y, reads.clone()].concat())).collect();
+            Some((bits, a_signed && b_signed))
+        }
+    }
+}
+
+/// Bit i de la somme : bits 0..=i des deux opérandes
+fn carry_chain(a: &[Vec<BitSpan>], b: &[Vec<BitSpan>]) -> Vec<Vec<BitSpan>> {
+    let mut acc = Vec::new();
+    (0..a.len().max(b.len()))
+        .map(|i| {
+            acc.extend(a.get(i).into_iter().chain(b.get(i)).flatten().copied());
+            acc = merge(std::mem::take(&mut acc));
+            acc.clone()
+        })
+        .collect()
+}
+
+/// Tronque ou étend à `width` bits; l'extension de signe recopie le bit de
+/// poids fort, l'extension par zéros ne lit rien
+fn extend(mut bits: Vec<Vec<BitSpan>>, width: usize, signed: bool) -> Vec<Vec<BitSpan>> {
+    let top = if signed { bits.last().cloned().unwrap_or_default() } else { Vec::new() };
+    bits.resize(width, top);
+    bits
+}
+
+/// Plages triées, les plages qui se touchent fusionnées
+fn merge(mut spans: Vec<BitSpan>) -> Vec<BitSpan> {
+    spans.sort_by_key(|s| (s.signal, s.lo));
+    let mut out: Vec<BitSpan> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match out.last_mut() {
+            Some(last) if last.signal == span.signal && span.lo <= last.hi + 1 => last.hi = last.hi.max(span.hi),
+            _ => out.push(span),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elab::elaborate;
+    use crate::parser::parse_str;
+
+    /// Graphe d'une architecture qui déclare `decl` ; les ports sont
+    /// communs aux tests
+    fn build(decl: &str, body: &str) -> (Netlist, CombGraph) {
+        let hdl = format!(
+            "entity T is
+  port(x : in bit; cin : in bit; g : in bits(3 downto 0); p : in bits(3 downto 0); y : out bits(2 downto 0));
+end entity;
+
+architecture rtl of T is
+  {}
+begin
+{}
+end architecture;
+",
+            decl, body
+        );
+        let netlist = elaborate(&parse_str(&hdl).unwrap(), "T").unwrap();
+        let graph = CombGraph::build(&netlist);
+        (netlist, graph)
+    }
+
+    #[test]
+    fn test_shift_into_own_target_is_not_a_loop() {
+        let (netlist, graph) = build("signal a : bits(2 downto 0);", "  a <= a(1 downto 0) & x;\n  y <= a;");
+        assert_eq!(graph.find_node_cycle(), Some(vec![0]));
+        assert_eq!(graph.acyclic, 0);
+        assert_eq!(graph.find_cycle(&netlist), None);
+    }
+
+    #[test]
+    fn test_carry_chain_is_not_a_loop() {
+        let (netlist, graph) = build(
+            "signal c : bits(4 downto 0);",
+            "  c(0) <= cin;\n  c(4 downto 1) <= g or (p and c(3 downto 0));\n  y <= c(4 downto 2);",
+        );
+        assert_eq!(graph.find_node_cycle(), Some(vec![1]));
+        assert_eq!(graph.find_cycle(&netlist), None);
+    }
+
+    #[test]
+    fn test_carry_only_flows_upward() {
+        // Bit i de la somme lit les bits 0..=i : a(1) lit a(0), a(2) lit a(1)
+        let (netlist, graph) = build(
+            "signal a : bits(2 downto 0);",
+            "  a(2 downto 1) <= a(1 downto 0) + 1;\n  a(0) <= x;\n  y <= a;",
+        );
+        assert!(graph.find_node_cycle().is_some());
+        assert_eq!(graph.find_cycle(&netlist), None);
+    }
+
+    #[test]
+    fn test_bit_deps() {
+        let (netlist, graph) = build(
+            "signal a : bits(2 downto 0);",
+            "  a <= (g(1 downto 0) & x) xor (p(2 downto 0) << 1);\n  y <= a;",
+        );
+        let bit = |signal: &str, pos: usize| BitSpan {
+            signal: netlist.name_to_id[signal],
+            lo: pos,
+            hi: pos,
+        };
+        assert_eq!(
+            graph.bit_deps(0, &netlist),
+            vec![vec![bit("x", 0)], vec![bit("g", 0), bit("p", 0)], vec![bit("g", 1), bit("p", 1)]]
+        );
+    }
+}
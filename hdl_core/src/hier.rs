@@ -0,0 +1,221 @@
+//! Navigation dans la hiérarchie d'instances issue de l'élaboration.
+//!
+//! Les chemins utilisent `/` comme séparateur : `""` (ou `/`) désigne
+//! l'entité de plus haut niveau, `cpu/alu` une instance, `gen(3)` une
+//! itération de `generate`. Un chemin relatif peut remonter avec `..`.
+
+use crate::ast::Direction;
+use crate::elab::{InstanceNode, Netlist};
+use crate::error::Error;
+
+/// Rôle d'un signal vu depuis une portée
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeSignalKind {
+    In,
+    Out,
+    Local,
+}
+
+impl ScopeSignalKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScopeSignalKind::In => "in",
+            ScopeSignalKind::Out => "out",
+            ScopeSignalKind::Local => "local",
+        }
+    }
+}
+
+/// Signal visible dans une portée, sous son nom local
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScopeSignal {
+    /// Nom dans la portée (`y`)
+    pub name: String,
+    /// Nom du net dans la netlist (`cpu/alu_y`), utilisable avec `get_signal`
+    pub net: String,
+    pub width: usize,
+    pub kind: ScopeSignalKind,
+}
+
+impl Netlist {
+    /// Index de l'instance désignée par un chemin absolu
+    pub fn instance(&self, path: &str) -> Option<usize> {
+        let path = path.trim_matches('/');
+        self.instances.iter().position(|node| node.path == path)
+    }
+
+    fn instance_or_err(&self, path: &str) -> Result<usize, Error> {
+        self.instance(path)
+            .ok_or_else(|| Error::new(format!("unknown instance {}", path)))
+    }
+
+    /// Sous-instances directes d'une instance, dans l'ordre du source
+    pub fn instance_children(&self, path: &str) -> Result<Vec<&InstanceNode>, Error> {
+        let id = self.instance_or_err(path)?;
+        Ok(self.instances[id]
+            .children
+            .iter()
+            .map(|&child| &self.instances[child])
+            .collect())
+    }
+
+    /// Ports puis signaux locaux d'une instance
+    pub fn scope_signals(&self, path: &str) -> Result<Vec<ScopeSignal>, Error> {
+        let node = &self.instances[self.instance_or_err(path)?];
+        let ports = node.ports.iter().map(|p| {
+            let kind = match p.dir {
+                Direction::In => ScopeSignalKind::In,
+                Direction::Out => ScopeSignalKind::Out,
+            };
+            (p.name.as_str(), p.signal, kind)
+        });
+        let locals = node
+            .locals
+            .iter()
+            .map(|(name, id)| (name.as_str(), *id, ScopeSignalKind::Local));
+        Ok(ports
+            .chain(locals)
+            .map(|(name, id, kind)| ScopeSignal {
+                name: name.to_string(),
+                net: self.signals[id].name.clone(),
+                width: self.signals[id].width,
+                kind,
+            })
+            .collect())
+    }
+
+    /// Résout `path` relativement à l'instance `scope` et renvoie l'index du
+    /// net. Le dernier segment est un port ou un signal local ; les
+    /// précédents descendent dans les sous-instances ou remontent (`..`).
+    /// Un chemin commençant par `/` part de l'entité de plus haut niveau.
+    pub fn resolve_signal(&self, scope: &str, path: &str) -> Result<usize, Error> {
+        let unknown = || Error::new(format!("unknown signal {}", path));
+        let mut node = if path.starts_with('/') {
+            self.instance_or_err("")?
+        } else {
+            self.instance_or_err(scope)?
+        };
+        let mut segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty() && *s != ".").collect();
+        let name = segments.pop().ok_or_else(unknown)?;
+        for segment in segments {
+            let current = &self.instances[node];
+            node = if segment == ".." {
+                current.parent.ok_or_else(unknown)?
+            } else {
+                current
+                    .children
+                    .iter()
+                    .copied()
+                    .find(|&child| self.instances[child].name == segment)
+                    .ok_or_else(unknown)?
+            };
+        }
+        let node = &self.instances[node];
+        node.ports
+            .iter()
+            .map(|p| (p.name.as_str(), p.signal))
+            .chain(node.locals.iter().map(|(n, id)| (n.as_str(), *id)))
+            .find(|(n, _)| *n == name)
+            .map(|(_, id)| id)
+            .ok_or_else(unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elab::elaborate;
+    use crate::parser::parse_str;
+
+    const DESIGN: &str = r#"
+entity Inv is
+  port(a : in bit; y : out bit);
+end entity;
+
+architecture rtl of Inv is
+begin
+  y <= not a;
+end architecture;
+
+entity Buf is
+  port(a : in bit; y : out bit);
+end entity;
+
+architecture rtl of Buf is
+  signal t : bit;
+begin
+  i0: Inv port map (a => a, y => t);
+  i1: Inv port map (a => t, y => y);
+end architecture;
+
+entity Top is
+  port(a : in bits(1 downto 0); y : out bits(1 downto 0));
+end entity;
+
+architecture rtl of Top is
+  signal m, z : bit;
+begin
+  b: Buf port map (a => a(0), y => m);
+  y(0) <= m;
+  g: for i in 1 to 1 generate
+    n: Not1 port map (a => a(i), y => z);
+  end generate;
+  y(1) <= z;
+end architecture;
+"#;
+
+    fn netlist() -> Netlist {
+        elaborate(&parse_str(DESIGN).unwrap(), "Top").unwrap()
+    }
+
+    #[test]
+    fn test_instance_tree() {
+        let n = netlist();
+        let top: Vec<_> = n
+            .instance_children("")
+            .unwrap()
+            .iter()
+            .map(|c| (c.path.clone(), c.entity.clone()))
+            .collect();
+        assert_eq!(
+            top,
+            vec![("b".to_string(), Some("Buf".to_string())), ("g(1)".to_string(), None)]
+        );
+        let names: Vec<_> = n.instance_children("b").unwrap().iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, vec!["i0", "i1"]);
+        let gen = n.instance_children("/g(1)/").unwrap();
+        assert_eq!(gen[0].path, "g(1)/n");
+        assert_eq!(gen[0].entity.as_deref(), Some("not1"));
+        assert!(n.instance_children("b/i0").unwrap().is_empty());
+        assert!(n.instance_children("nope").is_err());
+    }
+
+    #[test]
+    fn test_scope_signals() {
+        let n = netlist();
+        let sigs = n.scope_signals("b").unwrap();
+        let summary: Vec<_> = sigs.iter().map(|s| (s.name.as_str(), s.kind, s.net.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", ScopeSignalKind::In, "b/a"),
+                ("y", ScopeSignalKind::Out, "m"),
+                ("t", ScopeSignalKind::Local, "b/t"),
+            ]
+        );
+        let prim = n.scope_signals("g(1)/n").unwrap();
+        assert_eq!(prim.len(), 1);
+        assert_eq!((prim[0].name.as_str(), prim[0].kind), ("y", ScopeSignalKind::Out));
+    }
+
+    #[test]
+    fn test_resolve_relative_paths() {
+        let n = netlist();
+        let name = |id: usize| n.signals[id].name.clone();
+        assert_eq!(name(n.resolve_signal("", "b/i1/y").unwrap()), "m");
+        assert_eq!(name(n.resolve_signal("b/i0", "../t").unwrap()), "b/t");
+        assert_eq!(name(n.resolve_signal("b/i0", "/b/i1/a").unwrap()), "b/i1/a");
+        assert!(n.resolve_signal("b", "../../m").is_err());
+        assert!(n.resolve_signal("b", "i0/zz").is_err());
+    }
+}
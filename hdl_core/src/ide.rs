@@ -0,0 +1,473 @@
+//! Services d'éditeur du serveur de langage : diagnostics, survol,
+//! définition et complétion des ports d'un `port map`.
+//!
+//! Les positions sont celles des `Span` (ligne et colonne à partir de 1,
+//! en caractères). Le document analysé est le texte en cours d'édition ;
+//! les unités qu'il ne définit pas viennent des sources du projet, par la
+//! même résolution que `hdl_cli --lib`.
+
+use crate::ast::{Architecture, Design, Direction, Entity, Port};
+use crate::elab::{declared_widths, elaborate};
+use crate::error::{Error, Span};
+use crate::error_messages::{classify, ErrorCode};
+use crate::format::type_text;
+use crate::lexer::{Lexer, TokenKind};
+use crate::library::{find_unit, resolve, SourceProvider};
+use crate::parser::parse_str;
+use std::collections::HashMap;
+
+/// Erreur à afficher dans l'éditeur
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Premier caractère signalé
+    pub span: Span,
+    /// Nombre de caractères signalés (le mot à cette position)
+    pub len: usize,
+    pub message: String,
+    pub code: Option<ErrorCode>,
+}
+
+/// Position d'une définition ; `file` vaut `None` dans le document analysé
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub file: Option<String>,
+    pub span: Span,
+}
+
+/// Port proposé dans un `port map`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    /// Direction, type et largeur
+    pub detail: String,
+}
+
+/// Erreurs du document : celle de l'analyse syntaxique, sinon celle de
+/// l'élaboration de chacune de ses entités. Une erreur qui vient d'une
+/// entité d'un autre fichier n'est pas répétée ici ; une erreur sans
+/// position est placée sur l'architecture (ou l'entité) élaborée.
+pub fn diagnostics(src: &str, provider: &mut dyn SourceProvider) -> Vec<Diagnostic> {
+    let design = match parse_str(src) {
+        Ok(design) => design,
+        Err(err) => return vec![diagnostic(src, err, Span { line: 1, col: 1 })],
+    };
+    let mut tops: Vec<&str> = design.entities.iter().map(|e| e.name.as_str()).collect();
+    for arch in &design.architectures {
+        if !tops.contains(&arch.entity.as_str()) {
+            tops.push(&arch.entity);
+        }
+    }
+    let first = unit_span(&design, tops.first().copied().unwrap_or_default());
+    let mut full = design.clone();
+    if let Err(err) = resolve(&mut full, &tops, provider) {
+        return vec![diagnostic(src, err, first)];
+    }
+
+    // Erreur de chaque entité des autres fichiers, calculée à la demande
+    let mut foreign: HashMap<String, Option<String>> = HashMap::new();
+    let mut out = Vec::new();
+    for top in &tops {
+        let Some(ent) = full.entities.iter().find(|e| e.name == *top) else {
+            continue;
+        };
+        // Sans valeur pour un generic, l'entité n'est élaborée que par ses instances
+        if ent.generics.iter().any(|g| g.default.is_none()) {
+            continue;
+        }
+        let Err(err) = elaborate(&full, top) else {
+            continue;
+        };
+        let elsewhere = full
+            .entities
+            .iter()
+            .filter(|e| !design.entities.iter().any(|d| d.name == e.name))
+            .filter(|e| e.generics.iter().all(|g| g.default.is_some()))
+            .any(|e| {
+                let message = foreign
+                    .entry(e.name.clone())
+                    .or_insert_with(|| elaborate(&full, &e.name).err().map(|err| err.message));
+                message.as_deref() == Some(err.message.as_str())
+            });
+        if elsewhere {
+            continue;
+        }
+        let diag = diagnostic(src, err, unit_span(&design, top));
+        if !out.contains(&diag) {
+            out.push(diag);
+        }
+    }
+    out
+}
+
+/// Description du port, du signal ou de l'entité sous le curseur
+pub fn hover(src: &str, pos: Span, provider: &mut dyn SourceProvider) -> Option<String> {
+    let (word, start) = word_at(src, pos)?;
+    let design = parse_str(src).ok();
+    if let Some(entity) = formal_of(src, start, &word) {
+        let (ent, widths) = lookup_entity(&entity, design.as_ref(), provider)?;
+        let port = ent.ports.iter().find(|p| p.name == word)?;
+        return Some(describe_port(port, &widths));
+    }
+    if let Some(design) = &design {
+        if let Some(entity) = enclosing_entity(design, pos) {
+            if let Some((ent, widths)) = lookup_entity(entity, Some(design), provider) {
+                if let Some(port) = ent.ports.iter().find(|p| p.name == word) {
+                    return Some(describe_port(port, &widths));
+                }
+                let signal = architecture(design, entity)
+                    .and_then(|arch| arch.signals.iter().find(|s| s.names.contains(&word)));
+                if let Some(signal) = signal {
+                    return Some(format!("signal {} : {}{}", word, type_text(&signal.ty), width_text(&widths, &word)));
+                }
+            }
+        }
+    }
+    let (ent, widths) = lookup_entity(&word, design.as_ref(), provider)?;
+    let mut text = format!("entity {}", ent.name);
+    for port in &ent.ports {
+        text.push_str(&format!("\n  {}", describe_port(port, &widths)));
+    }
+    Some(text)
+}
+
+/// Déclaration du nom sous le curseur : port ou signal de l'unité
+/// courante, port formel d'un `port map`, entité ou paquetage du projet
+pub fn definition(src: &str, pos: Span, provider: &mut dyn SourceProvider) -> Option<Location> {
+    let (word, start) = word_at(src, pos)?;
+    let design = parse_str(src).ok();
+    if let Some(entity) = formal_of(src, start, &word) {
+        let (file, design) = unit_source(&entity, design.as_ref(), provider)?;
+        let port = design.entities.iter().find(|e| e.name == entity)?.ports.iter().find(|p| p.name == word)?;
+        return Some(Location { file, span: port.span? });
+    }
+    if let Some(design) = &design {
+        if let Some(entity) = enclosing_entity(design, pos) {
+            let port = design
+                .entities
+                .iter()
+                .find(|e| e.name == entity)
+                .and_then(|e| e.ports.iter().find(|p| p.name == word))
+                .and_then(|p| p.span);
+            let signal = architecture(design, entity)
+                .and_then(|arch| arch.signals.iter().find(|s| s.names.contains(&word)))
+                .and_then(|s| s.span);
+            if let Some(span) = port.or(signal) {
+                return Some(Location { file: None, span });
+            }
+        }
+    }
+    let (file, design) = unit_source(&word, design.as_ref(), provider)?;
+    let entity = design.entities.iter().find(|e| e.name == word).and_then(|e| e.span);
+    let package = design.packages.iter().find(|p| p.name == word).and_then(|p| p.span);
+    Some(Location {
+        file,
+        span: entity.or(package)?,
+    })
+}
+
+/// Ports de l'entité du `port map` ouvert avant le curseur qui n'y sont
+/// pas encore associés ; le document n'a pas besoin de s'analyser
+pub fn completions(src: &str, pos: Span, provider: &mut dyn SourceProvider) -> Vec<Completion> {
+    let Some((entity, mapped)) = open_port_map(src, pos) else {
+        return Vec::new();
+    };
+    let design = parse_str(src).ok();
+    let Some((ent, widths)) = lookup_entity(&entity, design.as_ref(), provider) else {
+        return Vec::new();
+    };
+    ent.ports
+        .iter()
+        .filter(|p| !mapped.contains(&p.name))
+        .map(|p| Completion {
+            label: p.name.clone(),
+            detail: describe_port(p, &widths)[p.name.len() + 3..].to_string(),
+        })
+        .collect()
+}
+
+fn diagnostic(src: &str, err: Error, fallback: Span) -> Diagnostic {
+    let span = err.span.unwrap_or(fallback);
+    let len = word_at(src, span)
+        .filter(|(_, start)| *start == span)
+        .map_or(1, |(word, _)| word.chars().count());
+    Diagnostic {
+        span,
+        len,
+        code: classify(&err.message),
+        message: err.message,
+    }
+}
+
+/// Architecture de `entity` dans le document, sinon l'entité elle-même
+fn unit_span(design: &Design, entity: &str) -> Span {
+    let arch = architecture(design, entity).and_then(|a| a.span);
+    let ent = design.entities.iter().find(|e| e.name == entity).and_then(|e| e.span);
+    arch.or(ent).unwrap_or(Span { line: 1, col: 1 })
+}
+
+fn architecture<'a>(design: &'a Design, entity: &str) -> Option<&'a Architecture> {
+    design.architectures.iter().find(|a| a.entity == entity)
+}
+
+/// Entité de la dernière entité ou architecture commencée avant `pos`
+fn enclosing_entity(design: &Design, pos: Span) -> Option<&str> {
+    let entities = design.entities.iter().map(|e| (e.span, e.name.as_str()));
+    let archs = design.architectures.iter().map(|a| (a.span, a.entity.as_str()));
+    let packages = design.packages.iter().map(|p| (p.span, ""));
+    entities
+        .chain(archs)
+        .chain(packages)
+        .filter_map(|(span, name)| Some(((span?.line, span?.col), name)))
+        .filter(|(start, _)| *start <= (pos.line, pos.col))
+        .max_by_key(|(start, _)| *start)
+        .map(|(_, name)| name)
+        .filter(|name| !name.is_empty())
+}
+
+/// Entité `name` du document ou du projet, avec la largeur de ses ports
+/// quand elle se calcule
+fn lookup_entity(name: &str, local: Option<&Design>, provider: &mut dyn SourceProvider) -> Option<(Entity, HashMap<String, usize>)> {
+    let mut full = local.cloned().unwrap_or_default();
+    // Une source illisible laisse seulement les largeurs inconnues
+    let _ = resolve(&mut full, &[name], provider);
+    let ent = full.entities.iter().find(|e| e.name == name)?.clone();
+    let widths = declared_widths(&full, name).unwrap_or_default().into_iter().collect();
+    Some((ent, widths))
+}
+
+/// Fichier (`None` pour le document) et contenu qui déclarent l'unité `name`
+fn unit_source(name: &str, local: Option<&Design>, provider: &mut dyn SourceProvider) -> Option<(Option<String>, Design)> {
+    if let Some(design) = local {
+        let here = design.entities.iter().any(|e| e.name == name) || design.packages.iter().any(|p| p.name == name);
+        if here {
+            return Some((None, design.clone()));
+        }
+    }
+    let (file, design) = find_unit(name, provider).ok()??;
+    Some((Some(file), design))
+}
+
+fn describe_port(port: &Port, widths: &HashMap<String, usize>) -> String {
+    let dir = match port.dir {
+        Direction::In => "in",
+        Direction::Out => "out",
+    };
+    format!("{} : {} {}{}", port.name, dir, type_text(&port.ty), width_text(widths, &port.name))
+}
+
+fn width_text(widths: &HashMap<String, usize>, name: &str) -> String {
+    match widths.get(name) {
+        Some(1) => " -- 1 bit".to_string(),
+        Some(width) => format!(" -- {} bits", width),
+        None => String::new(),
+    }
+}
+
+/// Identificateur sous le curseur (ou qui finit juste avant) et sa position
+fn word_at(src: &str, pos: Span) -> Option<(String, Span)> {
+    let line: Vec<char> = src.lines().nth(pos.line.checked_sub(1)?)?.chars().collect();
+    let is_word = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    let mut idx = pos.col.checked_sub(1)?;
+    if !line.get(idx).is_some_and(is_word) {
+        idx = idx.checked_sub(1).filter(|&i| line.get(i).is_some_and(is_word))?;
+    }
+    let start = (0..=idx).rev().take_while(|&i| is_word(&line[i])).last()?;
+    let end = (idx..line.len()).take_while(|&i| is_word(&line[i])).last()? + 1;
+    if line[start].is_ascii_digit() {
+        return None;
+    }
+    let word: String = line[start..end].iter().collect();
+    Some((word, Span { line: pos.line, col: start + 1 }))
+}
+
+/// Octet de `src` à la position `pos`
+fn offset(src: &str, pos: Span) -> usize {
+    let mut at = 0;
+    for (n, line) in src.split_inclusive('\n').enumerate() {
+        if n + 1 == pos.line {
+            return at + line.char_indices().nth(pos.col - 1).map_or(line.trim_end_matches('\n').len(), |(i, _)| i);
+        }
+        at += line.len();
+    }
+    src.len()
+}
+
+/// Entité instanciée quand `word`, à `start`, est un port formel (`word =>`
+/// dans un `port map`)
+fn formal_of(src: &str, start: Span, word: &str) -> Option<String> {
+    let after = &src[offset(src, start) + word.len()..];
+    if !after.trim_start().starts_with("=>") {
+        return None;
+    }
+    open_port_map(src, start).map(|(entity, _)| entity)
+}
+
+/// Entité du `port map (` ouvert avant `pos` et ports déjà associés dans
+/// la liste ; `None` hors d'un `port map` ou si le début ne se lexe pas
+fn open_port_map(src: &str, pos: Span) -> Option<(String, Vec<String>)> {
+    let tokens = Lexer::new(&src[..offset(src, pos)]).lex().ok()?;
+    let kinds: Vec<&TokenKind> = tokens.iter().map(|t| &t.kind).filter(|k| **k != TokenKind::Eof).collect();
+    let mut depth = 0;
+    let mut open = None;
+    for i in (0..kinds.len()).rev() {
+        match kinds[i] {
+            TokenKind::RParen => depth += 1,
+            TokenKind::LParen if depth > 0 => depth -= 1,
+            TokenKind::LParen => {
+                open = Some(i);
+                break;
+            }
+            TokenKind::Semicolon => return None,
+            _ => {}
+        }
+    }
+    let open = open?;
+    if open < 3 || *kinds[open - 1] != TokenKind::KwMap || *kinds[open - 2] != TokenKind::KwPort {
+        return None;
+    }
+    // `label: Entity [generic map (...)] port map (`
+    let mut before = open - 3;
+    if *kinds[before] == TokenKind::RParen {
+        let mut depth = 0;
+        let close = before;
+        let generic_open = (0..=close).rev().find(|&i| {
+            match kinds[i] {
+                TokenKind::RParen => depth += 1,
+                TokenKind::LParen => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })?;
+        if generic_open < 3 || *kinds[generic_open - 1] != TokenKind::KwMap || *kinds[generic_open - 2] != TokenKind::KwGeneric {
+            return None;
+        }
+        before = generic_open - 3;
+    }
+    let TokenKind::Ident(entity) = kinds[before] else {
+        return None;
+    };
+
+    let mut mapped = Vec::new();
+    let mut depth = 0;
+    for pair in kinds[open + 1..].windows(2) {
+        match pair {
+            [TokenKind::LParen, _] => depth += 1,
+            [TokenKind::RParen, _] => depth -= 1,
+            [TokenKind::Ident(name), TokenKind::Arrow] if depth == 0 => mapped.push(name.clone()),
+            _ => {}
+        }
+    }
+    Some((entity.clone(), mapped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::MemorySources;
+
+    const ADDER: &str = "entity Adder is
+  generic(N : integer := 8);
+  port(a, b : in bits(N - 1 downto 0); sum : out bits(N - 1 downto 0));
+end entity;
+architecture rtl of Adder is begin sum <= a + b; end architecture;";
+
+    const BROKEN: &str = "entity Broken is port(a : in bit; y : out bit); end entity;
+architecture rtl of Broken is begin y <= nope; end architecture;";
+
+    const TOP: &str = "entity Top is
+  port(x : in bits(7 downto 0); s : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Top is
+  signal t : bits(3 downto 0);
+begin
+  t <= x(3 downto 0);
+  u_add: Adder port map (a => x, b => x, sum => s);
+end architecture;";
+
+    fn project() -> MemorySources {
+        MemorySources::new(vec![
+            ("lib/adder.hdl".to_string(), ADDER.to_string()),
+            ("lib/Broken.hdl".to_string(), BROKEN.to_string()),
+        ])
+    }
+
+    fn at(line: usize, col: usize) -> Span {
+        Span { line, col }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        assert!(diagnostics(TOP, &mut project()).is_empty());
+
+        // Erreur de syntaxe : position et code du parser
+        let diags = diagnostics("entity Top is port(a : inout bit); end entity;", &mut project());
+        assert_eq!(diags.len(), 1);
+        assert_eq!((diags[0].span, diags[0].code), (at(1, 30), Some(ErrorCode::P202)));
+
+        // Erreur d'élaboration sans position : sur l'architecture, le mot entier
+        let src = TOP.replace("t <= x(3 downto 0);", "t <= y;");
+        let diags = diagnostics(&src, &mut project());
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "unknown signal y");
+        assert_eq!((diags[0].span, diags[0].len, diags[0].code), (at(5, 1), 12, Some(ErrorCode::E308)));
+
+        // L'erreur interne de Broken appartient à son fichier
+        let src = TOP.replace("t <= x(3 downto 0);", "u_b: Broken port map (a => x(0), y => t(0));");
+        assert!(diagnostics(&src, &mut project()).is_empty());
+        let diags = diagnostics(&src.replace("y => t(0)", "z => t(0)"), &mut project());
+        assert_eq!(diags[0].code, Some(ErrorCode::E307));
+
+        // Erreur d'une instance : soulignée sur sa ligne
+        assert_eq!(diags[0].span, at(8, 36));
+        let diags = diagnostics(&TOP.replace("b => x,", "b => t,"), &mut project());
+        assert_eq!(diags[0].span, at(9, 34));
+        let diags = diagnostics(&src.replace("Broken", "Nope"), &mut project());
+        assert_eq!((diags[0].span, diags[0].code), (at(8, 3), Some(ErrorCode::E304)));
+    }
+
+    #[test]
+    fn test_hover() {
+        let mut lib = project();
+        // Port formel d'une entité d'un autre fichier, generic par défaut
+        assert_eq!(
+            hover(TOP, at(9, 27), &mut lib).as_deref(),
+            Some("a : in bits(N - 1 downto 0) -- 8 bits")
+        );
+        assert_eq!(hover(TOP, at(2, 8), &mut lib).as_deref(), Some("x : in bits(7 downto 0) -- 8 bits"));
+        assert_eq!(hover(TOP, at(8, 3), &mut lib).as_deref(), Some("signal t : bits(3 downto 0) -- 4 bits"));
+        let entity = hover(TOP, at(9, 10), &mut lib).unwrap();
+        assert!(entity.starts_with("entity Adder\n  a : in"), "{}", entity);
+        assert_eq!(hover(TOP, at(4, 1), &mut lib), None);
+    }
+
+    #[test]
+    fn test_definition() {
+        let mut lib = project();
+        let adder = definition(TOP, at(9, 12), &mut lib).unwrap();
+        assert_eq!((adder.file.as_deref(), adder.span), (Some("lib/adder.hdl"), at(1, 1)));
+        let sum = definition(TOP, at(9, 42), &mut lib).unwrap();
+        assert_eq!((sum.file.as_deref(), sum.span), (Some("lib/adder.hdl"), at(3, 40)));
+        let t = definition(TOP, at(8, 3), &mut lib).unwrap();
+        assert_eq!((t.file, t.span), (None, at(6, 3)));
+        // Signal associé au port : le port de Top
+        assert_eq!(definition(TOP, at(9, 40), &mut lib).unwrap(), Location { file: None, span: at(2, 8) });
+        assert_eq!(definition(TOP, at(9, 49), &mut lib).unwrap().span, at(2, 33));
+    }
+
+    #[test]
+    fn test_port_map_completion() {
+        // Instance en cours de saisie : le document ne s'analyse pas
+        let src = "architecture rtl of Top is
+begin
+  u_add: Adder generic map (N => 4) port map (a => x(0), ";
+        let labels = |src: &str, pos: Span| -> Vec<String> {
+            completions(src, pos, &mut project()).into_iter().map(|c| c.label).collect()
+        };
+        assert_eq!(labels(src, at(3, 58)), ["b", "sum"]);
+        let items = completions(src, at(3, 58), &mut project());
+        assert_eq!(items[1].detail, "out bits(N - 1 downto 0) -- 8 bits");
+        assert!(labels(src, at(3, 20)).is_empty());
+        assert!(labels("u: Nope port map (", at(1, 19)).is_empty());
+    }
+}
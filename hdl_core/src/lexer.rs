@@ -30,10 +30,23 @@ pub enum TokenKind {
     KwOut,
     KwDownto,
     KwTo,
+    KwGeneric,
+    KwGenerate,
+    KwFor,
+    KwInteger,
+    KwRange,
+    KwConstant,
+    KwType,
+    KwArray,
+    KwLibrary,
+    KwUse,
+    KwPackage,
+    KwAll,
 
     KwBit,
     KwBits,
     KwRisingEdge,
+    KwFallingEdge,
     KwAnd,
     KwOr,
     KwXor,
@@ -51,6 +64,8 @@ pub enum TokenKind {
 
     Plus,
     Minus,
+    Star,
+    Slash,
     Amp,
     Shl,
     Shr,
@@ -71,11 +86,20 @@ pub struct Token {
     pub span: Span,
 }
 
+/// `-- text` comment, kept aside for the formatter
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    pub span: Span,
+    /// From the `--` to the end of the line, trailing blanks removed
+    pub text: String,
+}
+
 pub struct Lexer<'a> {
     src: &'a str,
     idx: usize,
     line: usize,
     col: usize,
+    comments: Vec<Comment>,
 }
 
 impl<'a> Lexer<'a> {
@@ -85,9 +109,15 @@ impl<'a> Lexer<'a> {
             idx: 0,
             line: 1,
             col: 1,
+            comments: Vec::new(),
         }
     }
 
+    /// Comments skipped so far, in source order
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
     pub fn lex(&mut self) -> Result<Vec<Token>, Error> {
         let mut tokens = Vec::new();
         loop {
@@ -252,9 +282,13 @@ impl<'a> Lexer<'a> {
                     self.bump();
                     Ok(Token { kind: TokenKind::Ne, span })
                 } else {
-                    Err(Error::with_span("unexpected '/'", span))
+                    Ok(Token { kind: TokenKind::Slash, span })
                 }
             }
+            '*' => {
+                self.bump();
+                Ok(Token { kind: TokenKind::Star, span })
+            }
             '+' => {
                 self.bump();
                 Ok(Token { kind: TokenKind::Plus, span })
@@ -295,9 +329,22 @@ impl<'a> Lexer<'a> {
             "out" => TokenKind::KwOut,
             "downto" => TokenKind::KwDownto,
             "to" => TokenKind::KwTo,
+            "generic" => TokenKind::KwGeneric,
+            "generate" => TokenKind::KwGenerate,
+            "for" => TokenKind::KwFor,
+            "integer" => TokenKind::KwInteger,
+            "range" => TokenKind::KwRange,
+            "constant" => TokenKind::KwConstant,
+            "type" => TokenKind::KwType,
+            "array" => TokenKind::KwArray,
+            "library" => TokenKind::KwLibrary,
+            "use" => TokenKind::KwUse,
+            "package" => TokenKind::KwPackage,
+            "all" => TokenKind::KwAll,
             "bit" => TokenKind::KwBit,
             "bits" => TokenKind::KwBits,
             "rising_edge" => TokenKind::KwRisingEdge,
+            "falling_edge" => TokenKind::KwFallingEdge,
             "and" => TokenKind::KwAnd,
             "or" => TokenKind::KwOr,
             "xor" => TokenKind::KwXor,
@@ -310,9 +357,15 @@ impl<'a> Lexer<'a> {
         loop {
             self.consume_while(|c| c.is_whitespace());
             if self.peek() == Some('-') && self.peek_next() == Some('-') {
-                self.bump();
-                self.bump();
-                self.consume_while(|c| c != '\n');
+                let span = Span {
+                    line: self.line,
+                    col: self.col,
+                };
+                let text = self.consume_while(|c| c != '\n');
+                self.comments.push(Comment {
+                    span,
+                    text: text.trim_end().to_string(),
+                });
                 continue;
             }
             break;
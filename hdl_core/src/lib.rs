@@ -1,13 +1,27 @@
 pub mod ast;
+mod bitblast;
+pub mod dot;
 pub mod elab;
+pub mod equiv;
+pub mod export;
+pub mod format;
 pub mod error;
 pub mod error_messages;
+pub mod graph;
+pub mod hier;
+pub mod ide;
 pub mod lexer;
+pub mod library;
+pub mod lint;
+pub mod mem;
 pub mod parser;
 pub mod sim;
+pub mod stats;
 pub mod test_runner;
 pub mod value;
+pub mod vcd;
+pub mod watch;
 
 pub use error::{Error, Span};
 pub use error_messages::{ErrorCode, msg, detailed};
-pub use test_runner::{run_test, run_test_file, TestResult, TestFailure};
+pub use test_runner::{run_test, run_test_file, run_test_with_options, TestOptions, TestResult, TestFailure};
@@ -0,0 +1,311 @@
+//! Chemin de recherche des sources HDL.
+//!
+//! Un design part des fichiers nommés explicitement ; tant qu'une instance
+//! désigne une entité inconnue ou qu'un `use` nomme un paquetage absent,
+//! le fichier `<Nom>.hdl` (casse ignorée) est cherché dans les sources du
+//! chemin, dans l'ordre. Sinon, les sources sont analysées une à une pour
+//! trouver celle qui déclare l'unité (plusieurs entités par fichier) ; les
+//! fichiers qui ne s'analysent pas sont alors ignorés. Une primitive
+//! (`and2`, `dff`...) n'est cherchée que par le nom de fichier. Un fichier
+//! chargé n'apporte que les unités pas encore définies.
+
+use crate::ast::{ConcurrentStmt, Design, UseClause};
+use crate::elab::is_primitive_name;
+use crate::error::Error;
+use crate::parser::parse_str;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sources consultées par la résolution
+pub trait SourceProvider {
+    /// Noms des fichiers `.hdl` disponibles, dans l'ordre de recherche
+    fn files(&mut self) -> Result<Vec<String>, Error>;
+    /// Texte d'un des fichiers de `files`
+    fn read(&mut self, file: &str) -> Result<String, Error>;
+}
+
+/// Fichiers `.hdl` sous des répertoires, récursivement, le premier
+/// répertoire d'abord
+pub struct SearchPath {
+    dirs: Vec<PathBuf>,
+    recursive: bool,
+}
+
+impl SearchPath {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        SearchPath { dirs, recursive: true }
+    }
+
+    /// Fichiers `.hdl` des répertoires eux-mêmes, sans leurs
+    /// sous-répertoires
+    pub fn flat(dirs: Vec<PathBuf>) -> Self {
+        SearchPath { dirs, recursive: false }
+    }
+}
+
+impl SourceProvider for SearchPath {
+    fn files(&mut self) -> Result<Vec<String>, Error> {
+        let mut files = Vec::new();
+        for dir in &self.dirs {
+            collect_hdl_files(dir, self.recursive, &mut files)?;
+        }
+        Ok(files.iter().map(|p| p.display().to_string()).collect())
+    }
+
+    fn read(&mut self, file: &str) -> Result<String, Error> {
+        fs::read_to_string(file).map_err(|e| Error::new(format!("{}: {}", file, e)))
+    }
+}
+
+fn collect_hdl_files(dir: &Path, recursive: bool, out: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::new(format!("{}: {}", dir.display(), e)))?;
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            if recursive {
+                collect_hdl_files(&path, recursive, out)?;
+            }
+        } else if path.extension().is_some_and(|e| e == "hdl") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Sources en mémoire, `(nom de fichier, texte)` (bibliothèque du web)
+pub struct MemorySources {
+    files: Vec<(String, String)>,
+}
+
+impl MemorySources {
+    pub fn new(files: Vec<(String, String)>) -> Self {
+        MemorySources { files }
+    }
+}
+
+impl SourceProvider for MemorySources {
+    fn files(&mut self) -> Result<Vec<String>, Error> {
+        Ok(self.files.iter().map(|(name, _)| name.clone()).collect())
+    }
+
+    fn read(&mut self, file: &str) -> Result<String, Error> {
+        self.files
+            .iter()
+            .find(|(name, _)| name == file)
+            .map(|(_, src)| src.clone())
+            .ok_or_else(|| Error::new(format!("{}: no such source", file)))
+    }
+}
+
+/// Complète `design` avec les sources qui définissent les entités
+/// `roots`, les entités instanciées et les paquetages utilisés qui lui
+/// manquent ; rend les fichiers chargés, dans l'ordre. Ce qui reste
+/// introuvable est signalé par l'élaboration.
+pub fn resolve(design: &mut Design, roots: &[&str], provider: &mut dyn SourceProvider) -> Result<Vec<String>, Error> {
+    let files = provider.files()?;
+    let mut loaded: Vec<String> = Vec::new();
+    // Unité -> fichier qui la déclare, construit au premier échec par le nom
+    let mut scanned: Option<HashMap<String, String>> = None;
+    loop {
+        let mut progress = false;
+        for name in missing_units(design, roots) {
+            // Un fichier chargé juste avant a pu la définir
+            if design.entities.iter().any(|e| e.name == name) || design.packages.iter().any(|p| p.name == name) {
+                continue;
+            }
+            let by_name = files.iter().find(|f| {
+                let stem = Path::new(f.as_str()).file_stem().map(|s| s.to_string_lossy().to_lowercase());
+                stem.as_deref() == Some(name.to_lowercase().as_str()) && !loaded.contains(f)
+            });
+            let file = match by_name {
+                Some(file) => Some(file.clone()),
+                // Une primitive n'est remplacée que par un fichier à son nom
+                None if is_primitive_name(&name.to_ascii_lowercase()) => None,
+                None => {
+                    if scanned.is_none() {
+                        scanned = Some(scan(&files, provider)?);
+                    }
+                    scanned.as_ref().and_then(|units| units.get(&name)).filter(|f| !loaded.contains(f)).cloned()
+                }
+            };
+            let Some(file) = file else {
+                continue;
+            };
+            let src = provider.read(&file)?;
+            let unit = parse_str(&src).map_err(|e| Error::new(format!("{}: {}", file, e)))?;
+            add_new_units(design, unit);
+            loaded.push(file);
+            progress = true;
+        }
+        if !progress {
+            return Ok(loaded);
+        }
+    }
+}
+
+/// Fichier qui déclare l'entité ou le paquetage `name`, avec son contenu
+/// analysé ; même ordre de recherche que `resolve`, mais les fichiers qui
+/// ne s'analysent pas sont toujours ignorés
+pub fn find_unit(name: &str, provider: &mut dyn SourceProvider) -> Result<Option<(String, Design)>, Error> {
+    let mut files = provider.files()?;
+    // Tri stable : les fichiers au nom de l'unité d'abord
+    files.sort_by_key(|f| {
+        let stem = Path::new(f.as_str()).file_stem().map(|s| s.to_string_lossy().to_lowercase());
+        stem.as_deref() != Some(name.to_lowercase().as_str())
+    });
+    for file in files {
+        let Ok(design) = parse_str(&provider.read(&file)?) else {
+            continue;
+        };
+        if design.entities.iter().any(|e| e.name == name) || design.packages.iter().any(|p| p.name == name) {
+            return Ok(Some((file, design)));
+        }
+    }
+    Ok(None)
+}
+
+/// Unités déclarées par chaque fichier qui s'analyse ; le premier fichier
+/// l'emporte
+fn scan(files: &[String], provider: &mut dyn SourceProvider) -> Result<HashMap<String, String>, Error> {
+    let mut units = HashMap::new();
+    for file in files {
+        let Ok(design) = parse_str(&provider.read(file)?) else {
+            continue;
+        };
+        let names = design.entities.iter().map(|e| &e.name).chain(design.packages.iter().map(|p| &p.name));
+        for name in names {
+            units.entry(name.clone()).or_insert_with(|| file.clone());
+        }
+    }
+    Ok(units)
+}
+
+/// Entités et paquetages référencés mais pas définis, dans l'ordre de
+/// première référence
+fn missing_units(design: &Design, roots: &[&str]) -> Vec<String> {
+    let entities: HashSet<&str> = design.entities.iter().map(|e| e.name.as_str()).collect();
+    let packages: HashSet<&str> = design.packages.iter().map(|p| p.name.as_str()).collect();
+    let mut wanted: Vec<String> = roots.iter().map(|r| r.to_string()).collect();
+    for arch in &design.architectures {
+        instantiated(&arch.stmts, &mut wanted);
+    }
+    wanted.retain(|name| !entities.contains(name.as_str()));
+
+    let uses: Vec<&UseClause> = design
+        .entities
+        .iter()
+        .flat_map(|e| &e.uses)
+        .chain(design.architectures.iter().flat_map(|a| &a.uses))
+        .chain(design.packages.iter().flat_map(|p| &p.uses))
+        .collect();
+    wanted.extend(
+        uses.into_iter()
+            .filter(|u| !packages.contains(u.package.as_str()))
+            .map(|u| u.package.clone()),
+    );
+    let mut seen = HashSet::new();
+    wanted.retain(|name| seen.insert(name.clone()));
+    wanted
+}
+
+fn instantiated(stmts: &[ConcurrentStmt], out: &mut Vec<String>) {
+    for stmt in stmts {
+        match stmt {
+            ConcurrentStmt::Instance(inst) => out.push(inst.entity.clone()),
+            ConcurrentStmt::Generate(generate) => instantiated(&generate.stmts, out),
+            ConcurrentStmt::Assign(_) | ConcurrentStmt::Process(_) => {}
+        }
+    }
+}
+
+/// Ajoute les entités (avec leur architecture) et les paquetages de `unit`
+/// que `design` ne définit pas encore
+fn add_new_units(design: &mut Design, unit: Design) {
+    let entities: HashSet<String> = design.entities.iter().map(|e| e.name.clone()).collect();
+    let packages: HashSet<String> = design.packages.iter().map(|p| p.name.clone()).collect();
+    design
+        .architectures
+        .extend(unit.architectures.into_iter().filter(|a| !entities.contains(&a.entity)));
+    design
+        .entities
+        .extend(unit.entities.into_iter().filter(|e| !entities.contains(&e.name)));
+    design
+        .packages
+        .extend(unit.packages.into_iter().filter(|p| !packages.contains(&p.name)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elab::elaborate;
+
+    fn sources(files: &[(&str, &str)]) -> MemorySources {
+        MemorySources::new(files.iter().map(|(n, s)| (n.to_string(), s.to_string())).collect())
+    }
+
+    const INV: &str = "entity Inv is port(a : in bit; y : out bit); end entity;
+architecture rtl of Inv is begin y <= not a; end architecture;";
+
+    const GATES: &str = "library work;
+use work.widths.all;
+entity Buf is port(a : in bits(W - 1 downto 0); y : out bits(W - 1 downto 0)); end entity;
+architecture rtl of Buf is begin y <= a; end architecture;
+entity Inv is port(a : in bit; y : out bit); end entity;
+architecture rtl of Inv is begin y <= a; end architecture;";
+
+    const WIDTHS: &str = "package widths is
+  constant W : integer := 4;
+end package;";
+
+    #[test]
+    fn test_resolve_by_file_name_then_by_scan() {
+        let top = "entity Top is port(a : in bits(3 downto 0); y : out bits(3 downto 0); z : out bit); end entity;
+architecture rtl of Top is
+begin
+  g: for i in 0 to 0 generate
+    u_inv: Inv port map (a => a(0), y => z);
+  end generate;
+  u_buf: Buf port map (a => a, y => y);
+end architecture;";
+        let mut design = parse_str(top).unwrap();
+        let mut provider = sources(&[
+            ("lib/broken.hdl", "entity ???"),
+            ("lib/gates.hdl", GATES),
+            ("lib/pkg/Widths.hdl", WIDTHS),
+            ("lib/INV.hdl", INV),
+        ]);
+        let loaded = resolve(&mut design, &["Top"], &mut provider).unwrap();
+        // Inv par son nom, Buf par balayage, puis le paquetage qu'il utilise ;
+        // l'Inv de gates.hdl n'est pas pris une seconde fois
+        assert_eq!(loaded, ["lib/INV.hdl", "lib/gates.hdl", "lib/pkg/Widths.hdl"]);
+        assert_eq!((design.entities.len(), design.architectures.len()), (3, 3));
+        let netlist = elaborate(&design, "Top").unwrap();
+        assert_eq!(netlist.signals[netlist.name_to_id["u_buf/a"]].width, 4);
+
+        // Rien ne manque : rien n'est chargé
+        assert!(resolve(&mut design, &["Top"], &mut provider).unwrap().is_empty());
+
+        let found = |name: &str, provider: &mut MemorySources| find_unit(name, provider).unwrap().map(|(f, _)| f);
+        assert_eq!(found("Inv", &mut provider).as_deref(), Some("lib/INV.hdl"));
+        assert_eq!(found("Buf", &mut provider).as_deref(), Some("lib/gates.hdl"));
+        assert_eq!(found("widths", &mut provider).as_deref(), Some("lib/pkg/Widths.hdl"));
+        assert_eq!(found("Top", &mut provider), None);
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        // Un fichier nécessaire qui ne s'analyse pas est une erreur
+        let mut design = parse_str("entity Top is port(a : in bit); end entity;
+architecture rtl of Top is begin u: Inv port map (a => a, y => open_y); end architecture;")
+        .unwrap();
+        let err = resolve(&mut design, &[], &mut sources(&[("Inv.hdl", "entity Inv")])).unwrap_err();
+        assert!(err.message.starts_with("Inv.hdl: "), "{}", err.message);
+
+        // Une unité introuvable est laissée à l'élaboration
+        let mut design = Design::default();
+        assert!(resolve(&mut design, &["Missing"], &mut sources(&[("Inv.hdl", INV)])).unwrap().is_empty());
+        assert_eq!(elaborate(&design, "Missing").unwrap_err().message, "unknown entity Missing");
+    }
+}
@@ -0,0 +1,587 @@
+//! Analyse statique (lint) des sources HDL.
+//!
+//! Repère ce que l'élaboration accepte mais qui trahit souvent une erreur :
+//! signaux et ports inutilisés, sorties jamais pilotées, entrées d'instance
+//! reliées à un signal que rien ne pilote, affectations tronquées, `case`
+//! sans `others` qui ne couvre pas toutes les valeurs, et branches de reset
+//! asynchrone qui recopient un signal comme un verrou (latch). Chaque
+//! avertissement a un code W9xx d'`error_messages` et la position du nœud
+//! concerné.
+//!
+//! Le lint lit l'AST d'une architecture ; les largeurs et les types viennent
+//! de ses déclarations, avec les generics par défaut. Les instances ne sont
+//! pas élaborées.
+
+use crate::ast::*;
+use crate::elab::{ArchScope, SignalType};
+use crate::error::Span;
+use crate::error_messages::ErrorCode;
+use std::collections::HashSet;
+
+/// Avertissement du lint
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+    pub code: ErrorCode,
+    pub message: String,
+    pub span: Span,
+}
+
+/// Avertissements de toutes les architectures de `design`
+pub fn lint_design(design: &Design) -> Vec<Lint> {
+    design
+        .architectures
+        .iter()
+        .flat_map(|arch| lint_architecture(design, arch))
+        .collect()
+}
+
+/// Avertissements d'une architecture de `design`, par position ; les
+/// entités et paquetages qu'elle utilise doivent être dans `design`
+pub fn lint_architecture(design: &Design, arch: &Architecture) -> Vec<Lint> {
+    let Some(ent) = design.entities.iter().find(|e| e.name == arch.entity) else {
+        return Vec::new();
+    };
+    let mut linter = Linter {
+        design,
+        arch,
+        ent,
+        scope: ArchScope::new(design, &arch.entity).ok(),
+        fallback: arch.span.or(ent.span).unwrap_or(Span { line: 1, col: 1 }),
+        read: HashSet::new(),
+        read_in_logic: HashSet::new(),
+        driven: HashSet::new(),
+        inputs: Vec::new(),
+        lints: Vec::new(),
+    };
+    linter.concurrent(&arch.stmts);
+    linter.unused();
+    let mut lints = linter.lints;
+    lints.sort_by_key(|l| (l.span.line, l.span.col));
+    lints
+}
+
+struct Linter<'d> {
+    design: &'d Design,
+    arch: &'d Architecture,
+    ent: &'d Entity,
+    /// `None` si les déclarations ne s'élaborent pas : pas de contrôle de largeur
+    scope: Option<ArchScope>,
+    /// Position des avertissements d'un nœud sans position
+    fallback: Span,
+    /// Noms lus, y compris par une entrée d'instance
+    read: HashSet<String>,
+    /// Noms lus par une expression ou un process
+    read_in_logic: HashSet<String>,
+    driven: HashSet<String>,
+    /// Entrées d'instance reliées à un signal entier : (instance, port, signal, position)
+    inputs: Vec<(String, String, String, Option<Span>)>,
+    lints: Vec<Lint>,
+}
+
+impl<'d> Linter<'d> {
+    fn warn(&mut self, code: ErrorCode, message: String, span: Option<Span>) {
+        let span = span.unwrap_or(self.fallback);
+        self.lints.push(Lint { code, message, span });
+    }
+
+    fn is_signal(&self, name: &str) -> bool {
+        self.ent.ports.iter().any(|p| p.name == name) || self.arch.signals.iter().any(|s| s.names.iter().any(|n| n == name))
+    }
+
+    fn concurrent(&mut self, stmts: &[ConcurrentStmt]) {
+        for stmt in stmts {
+            match stmt {
+                ConcurrentStmt::Assign(assign) => self.assign(assign),
+                ConcurrentStmt::Process(proc) => {
+                    for name in &proc.sensitivity {
+                        self.read.insert(name.clone());
+                        self.read_in_logic.insert(name.clone());
+                    }
+                    self.latch(proc);
+                    self.sequential(&proc.stmts);
+                }
+                ConcurrentStmt::Instance(inst) => self.instance(inst),
+                ConcurrentStmt::Generate(generate) => {
+                    let saved = self.scope.clone();
+                    if let (GenerateScheme::For { var, left, .. }, Some(scope)) = (&generate.scheme, &mut self.scope) {
+                        match scope.eval(left) {
+                            Some(value) => scope.set_const(var, value),
+                            None => self.scope = None,
+                        }
+                    }
+                    self.concurrent(&generate.stmts);
+                    self.scope = saved;
+                }
+            }
+        }
+    }
+
+    fn sequential(&mut self, stmts: &[SeqStmt]) {
+        for stmt in stmts {
+            match stmt {
+                SeqStmt::Assign(assign) => self.assign(assign),
+                SeqStmt::If(stmt) => {
+                    self.read_expr(&stmt.cond, true);
+                    self.sequential(&stmt.then_stmts);
+                    for (cond, stmts) in &stmt.elsif {
+                        self.read_expr(cond, true);
+                        self.sequential(stmts);
+                    }
+                    self.sequential(&stmt.else_stmts);
+                }
+                SeqStmt::Case(stmt) => {
+                    self.read_expr(&stmt.expr, true);
+                    self.case(stmt);
+                    for (_, stmts) in &stmt.arms {
+                        self.sequential(stmts);
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_expr(&mut self, expr: &Expr, in_logic: bool) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Target(target) => {
+                self.read.insert(target.name.clone());
+                if in_logic {
+                    self.read_in_logic.insert(target.name.clone());
+                }
+                self.read_selector(target);
+            }
+            Expr::Unary { expr, .. } => self.read_expr(expr, in_logic),
+            Expr::Binary { left, right, .. } => {
+                self.read_expr(left, in_logic);
+                self.read_expr(right, in_logic);
+            }
+            Expr::Call { args, .. } => {
+                for arg in args {
+                    self.read_expr(arg, in_logic);
+                }
+            }
+            Expr::When { value, cond, otherwise } => {
+                self.read_expr(value, in_logic);
+                self.read_expr(cond, true);
+                self.read_expr(otherwise, in_logic);
+            }
+            Expr::Aggregate { value, .. } => self.read_expr(value, true),
+        }
+    }
+
+    /// Index calculé à l'exécution : `regs(ra + 1)`, ou `regs(ra)` que le
+    /// parser lit comme une expression entière
+    fn read_selector(&mut self, target: &Target) {
+        match &target.sel {
+            Some(SelectorExpr::Expr(index)) => self.read_expr(index, true),
+            Some(SelectorExpr::Index(index)) => {
+                let mut names = Vec::new();
+                int_names(index, &mut names);
+                for name in names {
+                    self.read.insert(name.to_string());
+                    self.read_in_logic.insert(name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn assign(&mut self, assign: &AssignStmt) {
+        self.driven.insert(assign.target.name.clone());
+        self.read_selector(&assign.target);
+        self.read_expr(&assign.expr, true);
+        self.truncation(assign);
+    }
+
+    fn instance(&mut self, inst: &InstanceStmt) {
+        let ports = self.ports_of(&inst.entity);
+        for assoc in &inst.port_map {
+            let is_out = match &ports {
+                Some(ports) => ports.iter().any(|p| p.name == assoc.port && matches!(p.dir, Direction::Out)),
+                None => matches!(assoc.port.as_str(), "y" | "q" | "dout"),
+            };
+            match (&assoc.expr, is_out) {
+                (Expr::Target(target), true) => {
+                    self.driven.insert(target.name.clone());
+                    self.read_selector(target);
+                }
+                (expr, true) => self.read_expr(expr, true),
+                (expr, false) => {
+                    if let Expr::Target(Target { name, sel: None, .. }) = expr {
+                        let span = assoc.span.or(inst.span);
+                        self.inputs.push((inst.name.clone(), assoc.port.clone(), name.clone(), span));
+                    }
+                    self.read_expr(expr, false);
+                }
+            }
+        }
+    }
+
+    /// Ports de l'entité ou du composant instancié ; `None` pour une primitive
+    fn ports_of(&self, name: &str) -> Option<&'d [Port]> {
+        let (design, arch) = (self.design, self.arch);
+        let entity = design.entities.iter().find(|e| e.name == name).map(|e| &e.ports);
+        let component = || {
+            arch.components
+                .iter()
+                .chain(design.packages.iter().flat_map(|p| &p.components))
+                .find(|c| c.name == name)
+                .map(|c| &c.ports)
+        };
+        entity.or_else(component).map(Vec::as_slice)
+    }
+
+    /// Signaux et ports jamais lus, jamais pilotés, entrées en l'air
+    fn unused(&mut self) {
+        let (arch, ent) = (self.arch, self.ent);
+        let undriven = |linter: &Self, name: &str| {
+            !linter.driven.contains(name)
+                && arch.signals.iter().any(|s| s.init.is_none() && s.names.iter().any(|n| n == name))
+        };
+        for port in &ent.ports {
+            match port.dir {
+                Direction::In if !self.read.contains(&port.name) => {
+                    self.warn(ErrorCode::W902, format!("input port {} is never read", port.name), port.span)
+                }
+                Direction::Out if !self.driven.contains(&port.name) => {
+                    self.warn(ErrorCode::W903, format!("output port {} is never driven", port.name), port.span)
+                }
+                _ => {}
+            }
+        }
+        for decl in &arch.signals {
+            for name in &decl.names {
+                if !self.read.contains(name) {
+                    let message = if self.driven.contains(name) {
+                        format!("signal {} is assigned but never read", name)
+                    } else {
+                        format!("signal {} is never used", name)
+                    };
+                    self.warn(ErrorCode::W901, message, decl.span);
+                } else if self.read_in_logic.contains(name) && undriven(self, name) {
+                    self.warn(ErrorCode::W904, format!("signal {} is read but never driven", name), decl.span);
+                }
+            }
+        }
+        for (inst, port, signal, span) in std::mem::take(&mut self.inputs) {
+            if undriven(self, &signal) {
+                let message = format!("input {} of {} is connected to {}, which is never driven", port, inst, signal);
+                self.warn(ErrorCode::W905, message, span);
+            }
+        }
+    }
+
+    /// Valeur plus large que sa cible : les bits de poids fort sont perdus
+    fn truncation(&mut self, assign: &AssignStmt) {
+        let Some(scope) = &self.scope else {
+            return;
+        };
+        let (Some(target), Some(value)) = (scope.target_width(&assign.target), value_width(scope, &assign.expr)) else {
+            return;
+        };
+        if value > target {
+            let message = format!(
+                "assignment to {} truncates a {}-bit value to {} bits",
+                assign.target.name, value, target
+            );
+            self.warn(ErrorCode::W906, message, assign.span);
+        }
+    }
+
+    /// `case` sans `others` dont les choix ne couvrent pas toutes les
+    /// valeurs du sélecteur
+    fn case(&mut self, stmt: &CaseStmt) {
+        if stmt.arms.iter().any(|(choice, _)| matches!(choice, CaseChoice::Others)) {
+            return;
+        }
+        let choices: HashSet<String> = stmt
+            .arms
+            .iter()
+            .map(|(choice, _)| match choice {
+                CaseChoice::Literal(lit) => literal_value(lit).map_or_else(|| format!("{:?}", lit), |v| v.to_string()),
+                CaseChoice::Ident(name) => name.clone(),
+                CaseChoice::Others => String::new(),
+            })
+            .collect();
+        let complete = self.scope.as_ref().is_some_and(|scope| match scope.expr_type(&stmt.expr) {
+            Some(SignalType::Enum { literals, .. }) => literals.iter().all(|l| choices.contains(l)),
+            Some(SignalType::Integer { low, high }) => {
+                high - low < 1 << 16 && (*low..=*high).all(|v| choices.contains(&v.to_string()))
+            }
+            _ => scope
+                .expr_width(&stmt.expr)
+                .is_some_and(|width| width <= 16 && choices.len() >= 1 << width),
+        });
+        if !complete {
+            self.warn(ErrorCode::W907, "case has no others choice and does not cover every value".to_string(), stmt.span);
+        }
+    }
+
+    /// Process à reset asynchrone dont la branche de reset lit un signal :
+    /// pendant le reset, le registre suit ce signal sans horloge. C'est le
+    /// seul verrou possible : un process commence toujours par un front
+    /// d'horloge, donc un `if` sans `else` ou un `case` incomplet y garde
+    /// la valeur du registre et n'est pas signalé.
+    fn latch(&mut self, proc: &ProcessStmt) {
+        let Some(SeqStmt::If(top)) = proc.stmts.first() else {
+            return;
+        };
+        let is_edge = |e: &Expr| {
+            matches!(e, Expr::Call { name, .. }
+                if name.eq_ignore_ascii_case("rising_edge") || name.eq_ignore_ascii_case("falling_edge"))
+        };
+        if is_edge(&top.cond) || !top.elsif.first().is_some_and(|(cond, _)| is_edge(cond)) {
+            return;
+        }
+        let mut assigns = Vec::new();
+        collect_assigns(&top.then_stmts, &mut assigns);
+        for assign in assigns {
+            let mut names = Vec::new();
+            expr_names(&assign.expr, &mut names);
+            if let Some(source) = names.into_iter().find(|n| self.is_signal(n)) {
+                let message = format!(
+                    "asynchronous reset value of {} depends on {}: it follows {} while the reset is active, like a latch",
+                    assign.target.name, source, source
+                );
+                self.warn(ErrorCode::W908, message, assign.span);
+            }
+        }
+    }
+}
+
+fn collect_assigns<'s>(stmts: &'s [SeqStmt], out: &mut Vec<&'s AssignStmt>) {
+    for stmt in stmts {
+        match stmt {
+            SeqStmt::Assign(assign) => out.push(assign),
+            SeqStmt::If(stmt) => {
+                collect_assigns(&stmt.then_stmts, out);
+                for (_, stmts) in &stmt.elsif {
+                    collect_assigns(stmts, out);
+                }
+                collect_assigns(&stmt.else_stmts, out);
+            }
+            SeqStmt::Case(stmt) => {
+                for (_, stmts) in &stmt.arms {
+                    collect_assigns(stmts, out);
+                }
+            }
+        }
+    }
+}
+
+fn expr_names<'e>(expr: &'e Expr, out: &mut Vec<&'e str>) {
+    match expr {
+        Expr::Literal(_) => {}
+        Expr::Target(target) => {
+            out.push(&target.name);
+            match &target.sel {
+                Some(SelectorExpr::Expr(index)) => expr_names(index, out),
+                Some(SelectorExpr::Index(index)) => int_names(index, out),
+                _ => {}
+            }
+        }
+        Expr::Unary { expr, .. } => expr_names(expr, out),
+        Expr::Binary { left, right, .. } => {
+            expr_names(left, out);
+            expr_names(right, out);
+        }
+        Expr::Call { args, .. } => {
+            for arg in args {
+                expr_names(arg, out);
+            }
+        }
+        Expr::When { value, cond, otherwise } => {
+            expr_names(value, out);
+            expr_names(cond, out);
+            expr_names(otherwise, out);
+        }
+        Expr::Aggregate { range, value } => {
+            if let Some((left, right, _)) = range {
+                int_names(left, out);
+                int_names(right, out);
+            }
+            expr_names(value, out);
+        }
+    }
+}
+
+fn int_names<'e>(expr: &'e IntExpr, out: &mut Vec<&'e str>) {
+    match expr {
+        IntExpr::Lit(_) => {}
+        IntExpr::Name(name) => out.push(name),
+        IntExpr::Neg(inner) => int_names(inner, out),
+        IntExpr::Binary { left, right, .. } => {
+            int_names(left, out);
+            int_names(right, out);
+        }
+    }
+}
+
+/// Largeur utile d'une valeur : celle de l'élaboration, sauf pour les
+/// littéraux et constantes entières hors concaténation, qui ne comptent
+/// que leurs bits significatifs (`cnt + 1` n'a pas 32 bits)
+fn value_width(scope: &ArchScope, expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Literal(lit) => literal_value(lit).map(|v| int_width(v as i64)).or_else(|| scope.expr_width(expr)),
+        Expr::Target(target) => match scope.constant(target) {
+            Some(value) => Some(int_width(value)),
+            None => scope.expr_width(expr),
+        },
+        Expr::Unary { op: UnaryOp::Neg, expr: inner } => match inner.as_ref() {
+            Expr::Literal(Literal::Int(v)) => Some(int_width(-v)),
+            _ => value_width(scope, inner),
+        },
+        Expr::Unary { expr, .. } => value_width(scope, expr),
+        Expr::Binary { op, left, right } => Some(match op {
+            BinaryOp::Concat => scope.expr_width(left)? + scope.expr_width(right)?,
+            BinaryOp::Shl | BinaryOp::Shr => value_width(scope, left)?,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 1,
+            _ => value_width(scope, left)?.max(value_width(scope, right)?),
+        }),
+        Expr::Call { .. } | Expr::Aggregate { .. } => scope.expr_width(expr),
+        // `(others => x)` n'a pas de largeur propre : celle de l'autre choix
+        Expr::When { value, otherwise, .. } => match (value_width(scope, value), value_width(scope, otherwise)) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        },
+    }
+}
+
+/// Bits d'un entier : non signé s'il est positif, en complément à deux sinon
+fn int_width(value: i64) -> usize {
+    if value >= 0 {
+        (64 - value.leading_zeros() as usize).max(1)
+    } else {
+        65 - (!value).leading_zeros() as usize
+    }
+}
+
+fn literal_value(lit: &Literal) -> Option<u64> {
+    match lit {
+        Literal::Bit(b) => Some(u64::from(*b)),
+        Literal::Int(v) => u64::try_from(*v).ok(),
+        Literal::Bits(digits, base) => {
+            let digits: String = digits.chars().filter(|c| *c != '_').collect();
+            let radix = if *base == LiteralBase::Bin { 2 } else { 16 };
+            u64::from_str_radix(&digits, radix).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_str;
+
+    const SMELLY: &str = r#"entity Top is
+  port(
+    clk   : in  bit;
+    rst   : in  bit;
+    spare : in  bit;
+    d     : in  bits(7 downto 0);
+    q     : out bits(7 downto 0);
+    flag  : out bit
+  );
+end entity;
+
+architecture rtl of Top is
+  type state_t is (IDLE, RUN, DONE);
+  signal state : state_t;
+  signal dead : bit;
+  signal floating : bit;
+  signal scratch : bits(3 downto 0);
+  signal sum : bits(3 downto 0);
+  signal wide : bits(8 downto 0);
+  signal nib : bits(1 downto 0);
+begin
+  scratch <= d(3 downto 0);
+  sum <= d(3 downto 0) + 1;
+  wide <= '0' & d;
+  u0: Inv port map (a => floating, y => nib(0));
+  process(clk, rst)
+  begin
+    if rst = '1' then
+      q <= d;
+    elsif rising_edge(clk) then
+      q <= wide;
+      case state is
+        when IDLE => state <= RUN;
+        when RUN => state <= DONE;
+      end case;
+      case nib is
+        when b"00" => q <= x"00";
+        when b"01" => q <= x"01";
+        when b"10" => q <= x"02";
+        when b"11" => q <= x"FF";
+      end case;
+      q(3 downto 0) <= sum + x"3";
+    end if;
+  end process;
+end architecture;
+
+entity Inv is
+  port(a : in bit; y : out bit);
+end entity;
+
+architecture rtl of Inv is
+begin
+  y <= not a;
+end architecture;
+"#;
+
+    #[test]
+    fn test_lint_findings() {
+        let design = parse_str(SMELLY).unwrap();
+        let lints = lint_design(&design);
+        let found: Vec<(&str, usize, &str)> =
+            lints.iter().map(|l| (l.code.as_str(), l.span.line, l.message.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("W902", 5, "input port spare is never read"),
+                ("W903", 8, "output port flag is never driven"),
+                ("W901", 15, "signal dead is never used"),
+                ("W901", 17, "signal scratch is assigned but never read"),
+                ("W905", 25, "input a of u0 is connected to floating, which is never driven"),
+                ("W908", 29, "asynchronous reset value of q depends on d: it follows d while the reset is active, like a latch"),
+                ("W906", 31, "assignment to q truncates a 9-bit value to 8 bits"),
+                ("W907", 32, "case has no others choice and does not cover every value"),
+            ]
+        );
+        assert!(lints.iter().all(|l| l.code.as_str().starts_with('W')));
+    }
+
+    #[test]
+    fn test_lint_clean_design() {
+        let src = "entity Counter is
+  generic(N : integer := 4);
+  port(clk : in bit; en : in bit; idx : in bits(1 downto 0); q : out bits(N - 1 downto 0));
+end entity;
+
+architecture rtl of Counter is
+  type regs_t is array (0 to 3) of bit;
+  signal regs : regs_t;
+  signal cnt : integer range 0 to 9;
+  signal bus_v : bits(N - 1 downto 0);
+begin
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      if en = '1' then
+        cnt <= cnt + 1;
+        regs(idx) <= en;
+      end if;
+      case cnt is
+        when 9 => cnt <= 0;
+        when others => bus_v <= resize(b\"1\", N);
+      end case;
+    end if;
+  end process;
+  g: for i in 0 to N - 1 generate
+    q(i) <= bus_v(i) xor bus_v(N - 1 - i) xor regs(idx);
+  end generate;
+end architecture;";
+        let design = parse_str(src).unwrap();
+        assert_eq!(lint_design(&design), Vec::new());
+    }
+}
@@ -0,0 +1,418 @@
+//! Contenu des primitives `ram` et `rom`.
+//!
+//! Les petites mémoires (16 bits d'adresse au plus) sont un tableau ; les
+//! plus grandes ne stockent que les mots écrits, si bien qu'une RAM de
+//! 32 bits d'adresse ne coûte que ce qu'on y met. Un mot jamais écrit vaut 0.
+//!
+//! Formats de fichiers :
+//! - hexadécimal façon `$readmemh` : un mot par jeton (`0x` facultatif),
+//!   `@adresse` repositionne l'écriture, `//` commente la fin de ligne ;
+//! - binaire : mots petit-boutistes de `ceil(largeur / 8)` octets ;
+//! - image A32B : segments chargés à leur adresse octet divisée par la
+//!   taille d'un mot.
+
+use crate::error::Error;
+use crate::value::BitVec;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Largeur d'adresse au-delà de laquelle le stockage devient creux
+const DENSE_MAX_ADDR_WIDTH: usize = 16;
+
+/// Primitive qui porte une mémoire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryKind {
+    Ram,
+    Rom,
+}
+
+impl MemoryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemoryKind::Ram => "ram",
+            MemoryKind::Rom => "rom",
+        }
+    }
+}
+
+/// Mémoire d'un design, désignée par le chemin de son instance
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryInfo {
+    /// Chemin de l'instance (`cpu/imem`)
+    pub path: String,
+    pub kind: MemoryKind,
+    pub addr_width: usize,
+    pub data_width: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Storage {
+    Dense(Vec<BitVec>),
+    /// Mots non nuls uniquement
+    Sparse(BTreeMap<u64, BitVec>),
+}
+
+/// Mémoire de `2^addr_width` mots de `data_width` bits
+#[derive(Clone, Debug)]
+pub struct Memory {
+    addr_width: usize,
+    data_width: usize,
+    words: Storage,
+}
+
+impl Memory {
+    pub fn new(addr_width: usize, data_width: usize) -> Self {
+        let words = if addr_width <= DENSE_MAX_ADDR_WIDTH {
+            Storage::Dense(vec![BitVec::new(data_width, 0); 1 << addr_width])
+        } else {
+            Storage::Sparse(BTreeMap::new())
+        };
+        Memory { addr_width, data_width, words }
+    }
+
+    pub fn addr_width(&self) -> usize {
+        self.addr_width
+    }
+
+    pub fn data_width(&self) -> usize {
+        self.data_width
+    }
+
+    /// Octets d'un mot dans les fichiers binaires
+    pub fn word_bytes(&self) -> usize {
+        self.data_width.div_ceil(8)
+    }
+
+    /// Dernière adresse valide
+    pub fn last_addr(&self) -> u64 {
+        match self.addr_width {
+            w if w >= 64 => u64::MAX,
+            w => (1u64 << w) - 1,
+        }
+    }
+
+    fn check_addr(&self, addr: u64) -> Result<(), Error> {
+        if addr > self.last_addr() {
+            return Err(Error::new(format!(
+                "address 0x{:X} out of range ({} address bits)",
+                addr, self.addr_width
+            )));
+        }
+        Ok(())
+    }
+
+    /// Mot à `addr` ; 0 hors de la mémoire
+    pub fn read(&self, addr: u64) -> BitVec {
+        let word = match &self.words {
+            Storage::Dense(words) => usize::try_from(addr).ok().and_then(|i| words.get(i)),
+            Storage::Sparse(words) => words.get(&addr),
+        };
+        word.cloned().unwrap_or_else(|| BitVec::new(self.data_width, 0))
+    }
+
+    /// Écrit `value`, ramené à la largeur des mots
+    pub fn write(&mut self, addr: u64, value: &BitVec) -> Result<(), Error> {
+        self.check_addr(addr)?;
+        let value = value.resize_zero(self.data_width);
+        match &mut self.words {
+            Storage::Dense(words) => words[addr as usize] = value,
+            Storage::Sparse(words) => {
+                if value == BitVec::new(self.data_width, 0) {
+                    words.remove(&addr);
+                } else {
+                    words.insert(addr, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Mots non nuls de `range`, par adresse croissante
+    pub fn nonzero(&self, range: Range<u64>) -> Vec<(u64, BitVec)> {
+        let zero = BitVec::new(self.data_width, 0);
+        match &self.words {
+            Storage::Dense(words) => {
+                let end = range.end.min(words.len() as u64);
+                (range.start..end)
+                    .filter(|&a| words[a as usize] != zero)
+                    .map(|a| (a, words[a as usize].clone()))
+                    .collect()
+            }
+            Storage::Sparse(words) => words.range(range).map(|(&a, w)| (a, w.clone())).collect(),
+        }
+    }
+
+    /// Charge un texte hexadécimal à partir de l'adresse 0 ; retourne le
+    /// nombre de mots écrits
+    pub fn load_hex(&mut self, text: &str) -> Result<usize, Error> {
+        let mut addr = 0u64;
+        let mut count = 0;
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or("");
+            for token in line.split_whitespace() {
+                let err = |what: &str| Error::new(format!("line {}: invalid {} {}", line_no + 1, what, token));
+                if let Some(target) = token.strip_prefix('@') {
+                    addr = u64::from_str_radix(target, 16).map_err(|_| err("address"))?;
+                    continue;
+                }
+                let digits = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")).unwrap_or(token);
+                let digits = digits.replace('_', "");
+                if digits.is_empty() {
+                    return Err(err("word"));
+                }
+                let value = BitVec::from_hex_msb(&digits).map_err(|_| err("word"))?;
+                if value.resize_zero(self.data_width).resize_zero(value.width()) != value {
+                    return Err(Error::new(format!(
+                        "line {}: word {} does not fit in {} bits",
+                        line_no + 1,
+                        token,
+                        self.data_width
+                    )));
+                }
+                self.write(addr, &value)?;
+                addr = addr.wrapping_add(1);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Charge des mots petit-boutistes à partir du mot `start` ; un dernier
+    /// mot incomplet est complété par des zéros
+    pub fn load_bytes(&mut self, start: u64, bytes: &[u8]) -> Result<usize, Error> {
+        let word_bytes = self.word_bytes().max(1);
+        let mut count = 0;
+        for (i, chunk) in bytes.chunks(word_bytes).enumerate() {
+            let mut word = BitVec::new(word_bytes * 8, 0);
+            for (j, &byte) in chunk.iter().enumerate() {
+                word.set_slice(j * 8, &BitVec::from_u64(8, byte as u64));
+            }
+            self.write(start.wrapping_add(i as u64), &word)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Charge les segments d'une image A32B (format de a32_asm) ; retourne
+    /// le nombre de mots écrits
+    pub fn load_a32b(&mut self, bytes: &[u8]) -> Result<usize, Error> {
+        let word_bytes = self.word_bytes().max(1) as u64;
+        let mut count = 0;
+        for (vaddr, data) in a32b_segments(bytes)? {
+            if !(vaddr as u64).is_multiple_of(word_bytes) {
+                return Err(Error::new(format!(
+                    "segment at 0x{:X} is not aligned on {}-byte words",
+                    vaddr, word_bytes
+                )));
+            }
+            count += self.load_bytes(vaddr as u64 / word_bytes, data)?;
+        }
+        Ok(count)
+    }
+
+    /// Charge un fichier selon son extension : `.a32b`, `.bin`, sinon
+    /// hexadécimal
+    pub fn load_file(&mut self, name: &str, bytes: &[u8]) -> Result<usize, Error> {
+        match file_format(name) {
+            FileFormat::A32b => self.load_a32b(bytes),
+            FileFormat::Bin => self.load_bytes(0, bytes),
+            FileFormat::Hex => {
+                let text = std::str::from_utf8(bytes).map_err(|_| Error::new(format!("{}: not a hex text file", name)))?;
+                self.load_hex(text)
+            }
+        }
+    }
+
+    /// Contenu de `range` au format du fichier `name` (`.bin` ou hexadécimal)
+    pub fn dump_file(&self, name: &str, range: Range<u64>) -> Result<Vec<u8>, Error> {
+        match file_format(name) {
+            FileFormat::A32b => Err(Error::new(format!("{}: cannot dump to an A32B image", name))),
+            FileFormat::Bin => {
+                if range.end.saturating_sub(range.start) > BIN_DUMP_MAX_WORDS {
+                    return Err(Error::new(format!(
+                        "{}: binary dump of more than {} words, give a range",
+                        name, BIN_DUMP_MAX_WORDS
+                    )));
+                }
+                self.dump_bytes(range)
+            }
+            FileFormat::Hex => Ok(self.dump_hex(range).into_bytes()),
+        }
+    }
+
+    /// Mots non nuls de `range` au format hexadécimal, relisible par
+    /// `load_hex` ; une ligne `@adresse` précède chaque suite contiguë
+    pub fn dump_hex(&self, range: Range<u64>) -> String {
+        let digits = self.data_width.div_ceil(4).max(1);
+        let addr_digits = self.addr_width.div_ceil(4).max(1);
+        let mut out = String::new();
+        let mut next = None;
+        for (addr, word) in self.nonzero(range) {
+            if next != Some(addr) {
+                out += &format!("@{:0w$X}\n", addr, w = addr_digits);
+            }
+            out += &hex_word(&word, digits);
+            out.push('\n');
+            next = Some(addr.wrapping_add(1));
+        }
+        out
+    }
+
+    /// Mots de `range` en binaire petit-boutiste, zéros compris
+    pub fn dump_bytes(&self, range: Range<u64>) -> Result<Vec<u8>, Error> {
+        if range.end > range.start {
+            self.check_addr(range.end - 1)?;
+        }
+        let word_bytes = self.word_bytes();
+        let mut out = Vec::new();
+        for addr in range {
+            let word = self.read(addr);
+            out.extend((0..word_bytes).map(|j| word.slice(j * 8, 8).to_u64_trunc() as u8));
+        }
+        Ok(out)
+    }
+}
+
+/// Taille maximale d'un dump binaire, qui écrit aussi les mots nuls
+const BIN_DUMP_MAX_WORDS: u64 = 1 << 20;
+
+enum FileFormat {
+    A32b,
+    Bin,
+    Hex,
+}
+
+fn file_format(name: &str) -> FileFormat {
+    let lower = name.to_ascii_lowercase();
+    if lower.ends_with(".a32b") {
+        FileFormat::A32b
+    } else if lower.ends_with(".bin") {
+        FileFormat::Bin
+    } else {
+        FileFormat::Hex
+    }
+}
+
+/// Nom de fichier mémoire dans un script (`prog.a32b`, `data.hex`), par
+/// opposition à un mot hexadécimal
+pub fn is_memory_file(token: &str) -> bool {
+    token.contains('.')
+}
+
+fn hex_word(word: &BitVec, digits: usize) -> String {
+    (0..digits)
+        .rev()
+        .map(|i| char::from_digit(word.slice(i * 4, 4).to_u64_trunc() as u32, 16).unwrap_or('0').to_ascii_uppercase())
+        .collect()
+}
+
+fn read_u16(bytes: &[u8], off: usize) -> Result<u16, Error> {
+    bytes
+        .get(off..off + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| Error::new("truncated A32B image"))
+}
+
+fn read_u32(bytes: &[u8], off: usize) -> Result<u32, Error> {
+    bytes
+        .get(off..off + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| Error::new("truncated A32B image"))
+}
+
+/// Segments chargés d'une image A32B : adresse octet et contenu. Les
+/// segments BSS valent 0 et n'ont rien à écrire.
+pub fn a32b_segments(bytes: &[u8]) -> Result<Vec<(u32, &[u8])>, Error> {
+    if bytes.len() < 32 || &bytes[0..4] != b"A32B" {
+        return Err(Error::new("not an A32B image"));
+    }
+    if read_u16(bytes, 4)? != 1 {
+        return Err(Error::new("unsupported A32B version"));
+    }
+    let ph_count = read_u16(bytes, 12)? as usize;
+    let ph_size = read_u16(bytes, 14)? as usize;
+    let ph_offset = read_u32(bytes, 16)? as usize;
+    if ph_size != 24 {
+        return Err(Error::new("invalid A32B program header size"));
+    }
+    let mut segments = Vec::new();
+    for i in 0..ph_count {
+        let off = ph_offset + i * ph_size;
+        let seg_type = read_u32(bytes, off)?;
+        let vaddr = read_u32(bytes, off + 8)?;
+        let file_off = read_u32(bytes, off + 12)? as usize;
+        let file_size = read_u32(bytes, off + 16)? as usize;
+        if seg_type != 1 || file_size == 0 {
+            continue;
+        }
+        let data = bytes
+            .get(file_off..file_off + file_size)
+            .ok_or_else(|| Error::new("A32B segment data out of range"))?;
+        segments.push((vaddr, data));
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Image A32B à un segment chargé à `vaddr`
+    fn a32b(vaddr: u32, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(b"A32B");
+        out.extend(1u16.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(vaddr.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(24u16.to_le_bytes());
+        out.extend(32u32.to_le_bytes());
+        let size = (32 + 24 + data.len()) as u32;
+        out.extend(size.to_le_bytes());
+        out.resize(32, 0);
+        for field in [1, 0b101, vaddr, 56, data.len() as u32, data.len() as u32] {
+            out.extend(field.to_le_bytes());
+        }
+        out.extend(data);
+        out
+    }
+
+    #[test]
+    fn test_sparse_memory_roundtrip() {
+        let mut mem = Memory::new(30, 32);
+        assert!(matches!(mem.words, Storage::Sparse(_)));
+        mem.write(0x3FFF_FFFF, &BitVec::from_u64(32, 0xDEAD_BEEF)).unwrap();
+        assert!(mem.write(0x4000_0000, &BitVec::from_u64(32, 1)).is_err());
+        let loaded = mem.load_hex("// programme\n@10 0x1 2\n  0003_0000 // fin\n@20\nFF").unwrap();
+        assert_eq!(loaded, 4);
+        assert_eq!(mem.read(0x12).to_u64_trunc(), 0x3_0000);
+        assert_eq!(mem.read(0x13).to_u64_trunc(), 0);
+
+        let dump = mem.dump_hex(0..mem.last_addr() + 1);
+        assert_eq!(dump, "@00000010\n00000001\n00000002\n00030000\n@00000020\n000000FF\n@3FFFFFFF\nDEADBEEF\n");
+        let mut copy = Memory::new(30, 32);
+        copy.load_hex(&dump).unwrap();
+        assert_eq!(copy.nonzero(0..u64::MAX), mem.nonzero(0..u64::MAX));
+
+        assert!(mem.load_hex("1_0000_0000").is_err());
+        assert!(mem.load_hex("@zz").is_err());
+    }
+
+    #[test]
+    fn test_binary_and_a32b_images() {
+        let mut mem = Memory::new(8, 16);
+        assert_eq!(mem.load_bytes(2, &[0x34, 0x12, 0x78]).unwrap(), 2);
+        assert_eq!(mem.read(2).to_u64_trunc(), 0x1234);
+        assert_eq!(mem.read(3).to_u64_trunc(), 0x78);
+        assert_eq!(mem.dump_bytes(1..4).unwrap(), vec![0, 0, 0x34, 0x12, 0x78, 0]);
+        assert!(mem.dump_bytes(250..257).is_err());
+
+        let mut rom = Memory::new(10, 32);
+        let image = a32b(0x20, &[0x01, 0x00, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(rom.load_a32b(&image).unwrap(), 2);
+        assert_eq!(rom.read(8).to_u64_trunc(), 0xE000_0001);
+        assert_eq!(rom.read(9).to_u64_trunc(), 0xFFFF_FFFF);
+        assert!(rom.load_a32b(&a32b(0x22, &[1, 2, 3, 4])).is_err());
+        assert!(rom.load_a32b(b"ELF").is_err());
+    }
+}
@@ -5,57 +5,171 @@ use crate::lexer::{Lexer, Token, TokenKind};
 pub fn parse_str(src: &str) -> Result<Design, Error> {
     let mut lexer = Lexer::new(src);
     let tokens = lexer.lex()?;
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(src, tokens);
     parser.parse_design()
 }
 
-struct Parser {
+struct Parser<'a> {
+    src: &'a str,
     tokens: Vec<Token>,
     idx: usize,
 }
 
-impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, idx: 0 }
+impl<'a> Parser<'a> {
+    fn new(src: &'a str, tokens: Vec<Token>) -> Self {
+        Self { src, tokens, idx: 0 }
     }
 
     fn parse_design(&mut self) -> Result<Design, Error> {
-        let mut entities = Vec::new();
-        let mut architectures = Vec::new();
+        let mut design = Design::default();
+        // `work` is always visible; `library` clauses declare the others
+        let mut libraries = vec!["work".to_string()];
+        let mut uses = Vec::new();
         while !self.check(TokenKind::Eof) {
-            if self.check(TokenKind::KwEntity) {
-                entities.push(self.parse_entity()?);
+            if self.check(TokenKind::KwLibrary) {
+                self.bump();
+                libraries.extend(self.parse_ident_list()?);
+                self.expect(TokenKind::Semicolon)?;
+            } else if self.check(TokenKind::KwUse) {
+                uses.push(self.parse_use_clause(&libraries)?);
+            } else if self.check(TokenKind::KwEntity) {
+                design.entities.push(self.parse_entity(std::mem::take(&mut uses))?);
             } else if self.check(TokenKind::KwArchitecture) {
-                architectures.push(self.parse_architecture()?);
+                design.architectures.push(self.parse_architecture(std::mem::take(&mut uses))?);
+            } else if self.check(TokenKind::KwPackage) {
+                design.packages.push(self.parse_package(std::mem::take(&mut uses))?);
             } else {
-                return Err(self.err_here("expected entity or architecture"));
+                return Err(self.err_here("expected entity, architecture or package"));
             }
         }
-        Ok(Design {
-            entities,
-            architectures,
+        Ok(design)
+    }
+
+    /// `use lib.pkg.all;` or `use lib.pkg.NAME;`, `lib` declared by a
+    /// `library` clause (or `work`)
+    fn parse_use_clause(&mut self, libraries: &[String]) -> Result<UseClause, Error> {
+        let span = self.current_span();
+        self.expect(TokenKind::KwUse)?;
+        let library_span = self.current_span();
+        let library = self.expect_ident()?;
+        if !libraries.iter().any(|l| l.eq_ignore_ascii_case(&library)) {
+            return Err(Error::with_span(format!("library {} is not declared", library), library_span));
+        }
+        self.expect(TokenKind::Dot)?;
+        let package = self.expect_ident()?;
+        self.expect(TokenKind::Dot)?;
+        let item = if self.check(TokenKind::KwAll) {
+            self.bump();
+            None
+        } else {
+            Some(self.expect_ident()?)
+        };
+        self.expect(TokenKind::Semicolon)?;
+        Ok(UseClause {
+            library,
+            package,
+            item,
+            span: Some(span),
         })
     }
 
-    fn parse_entity(&mut self) -> Result<Entity, Error> {
+    fn parse_package(&mut self, uses: Vec<UseClause>) -> Result<Package, Error> {
         let span = self.current_span();
-        self.expect(TokenKind::KwEntity)?;
+        self.expect(TokenKind::KwPackage)?;
         let name = self.expect_ident()?;
         self.expect(TokenKind::KwIs)?;
+        let mut types = Vec::new();
+        let mut constants = Vec::new();
+        let mut components = Vec::new();
+        while !self.check(TokenKind::KwEnd) {
+            if self.check(TokenKind::KwConstant) {
+                constants.push(self.parse_constant_decl()?);
+            } else if self.check(TokenKind::KwType) {
+                types.push(self.parse_type_decl()?);
+            } else if self.check(TokenKind::KwComponent) {
+                components.push(self.parse_component_decl()?);
+            } else {
+                return Err(self.err_here("expected constant, type or component declaration"));
+            }
+        }
+        self.expect(TokenKind::KwEnd)?;
+        self.expect(TokenKind::KwPackage)?;
+        if self.peek_ident() {
+            self.bump();
+        }
+        self.expect(TokenKind::Semicolon)?;
+        Ok(Package {
+            name,
+            uses,
+            types,
+            constants,
+            components,
+            span: Some(span),
+        })
+    }
+
+    fn parse_entity(&mut self, uses: Vec<UseClause>) -> Result<Entity, Error> {
+        let span = self.current_span();
+        self.expect(TokenKind::KwEntity)?;
+        let name = self.expect_unit_name()?;
+        self.expect(TokenKind::KwIs)?;
+        let generics = self.parse_generic_clause()?;
         let ports = self.parse_port_clause()?;
         self.expect(TokenKind::KwEnd)?;
         self.expect(TokenKind::KwEntity)?;
-        if self.peek_ident() {
+        if self.peek_ident() || self.peek_keyword_name() {
             self.bump();
         }
         self.expect(TokenKind::Semicolon)?;
         Ok(Entity {
             name,
+            uses,
+            generics,
             ports,
             span: Some(span),
         })
     }
 
+    /// Optional `generic (N : integer := 8; ...);`
+    fn parse_generic_clause(&mut self) -> Result<Vec<Generic>, Error> {
+        let mut generics = Vec::new();
+        if !self.check(TokenKind::KwGeneric) {
+            return Ok(generics);
+        }
+        self.bump();
+        self.expect(TokenKind::LParen)?;
+        loop {
+            let span = self.current_span();
+            let names = self.parse_ident_list()?;
+            self.expect(TokenKind::Colon)?;
+            self.expect(TokenKind::KwInteger)?;
+            let default = if self.check(TokenKind::ColonEq) {
+                self.bump();
+                Some(self.parse_int_expr()?)
+            } else {
+                None
+            };
+            for name in names {
+                generics.push(Generic {
+                    name,
+                    default: default.clone(),
+                    span: Some(span),
+                });
+            }
+            if self.check(TokenKind::Semicolon) {
+                self.bump();
+                if self.check(TokenKind::RParen) {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        self.expect(TokenKind::Semicolon)?;
+        Ok(generics)
+    }
+
     fn parse_port_clause(&mut self) -> Result<Vec<Port>, Error> {
         self.expect(TokenKind::KwPort)?;
         self.expect(TokenKind::LParen)?;
@@ -111,11 +225,30 @@ impl Parser {
             self.expect(TokenKind::RParen)?;
             return Ok(Type::Bits { msb, lsb, dir });
         }
+        if self.check(TokenKind::KwInteger) {
+            self.bump();
+            if !self.check(TokenKind::KwRange) {
+                return Ok(Type::Integer {
+                    low: IntExpr::Lit(i32::MIN as i64),
+                    high: IntExpr::Lit(i32::MAX as i64),
+                });
+            }
+            self.bump();
+            let (left, right, dir) = self.parse_range()?;
+            let (low, high) = match dir {
+                RangeDir::To => (left, right),
+                RangeDir::Downto => (right, left),
+            };
+            return Ok(Type::Integer { low, high });
+        }
+        if self.peek_ident() {
+            return Ok(Type::Named(self.expect_ident()?));
+        }
         Err(self.err_here("expected type"))
     }
 
-    fn parse_range(&mut self) -> Result<(i64, i64, RangeDir), Error> {
-        let left = self.parse_int_lit()?;
+    fn parse_range(&mut self) -> Result<(IntExpr, IntExpr, RangeDir), Error> {
+        let left = self.parse_int_expr()?;
         let dir = if self.check(TokenKind::KwDownto) {
             self.bump();
             RangeDir::Downto
@@ -125,26 +258,32 @@ impl Parser {
         } else {
             return Err(self.err_here("expected range direction"));
         };
-        let right = self.parse_int_lit()?;
+        let right = self.parse_int_expr()?;
         Ok((left, right, dir))
     }
 
-    fn parse_architecture(&mut self) -> Result<Architecture, Error> {
+    fn parse_architecture(&mut self, uses: Vec<UseClause>) -> Result<Architecture, Error> {
         let span = self.current_span();
         self.expect(TokenKind::KwArchitecture)?;
         let name = self.expect_ident()?;
         self.expect(TokenKind::KwOf)?;
-        let entity = self.expect_ident()?;
+        let entity = self.expect_unit_name()?;
         self.expect(TokenKind::KwIs)?;
+        let mut types = Vec::new();
+        let mut constants = Vec::new();
         let mut signals = Vec::new();
         let mut components = Vec::new();
         while !self.check(TokenKind::KwBegin) {
             if self.check(TokenKind::KwSignal) {
                 signals.push(self.parse_signal_decl()?);
+            } else if self.check(TokenKind::KwConstant) {
+                constants.push(self.parse_constant_decl()?);
+            } else if self.check(TokenKind::KwType) {
+                types.push(self.parse_type_decl()?);
             } else if self.check(TokenKind::KwComponent) {
                 components.push(self.parse_component_decl()?);
             } else {
-                return Err(self.err_here("expected signal or component declaration"));
+                return Err(self.err_here("expected signal, constant, type or component declaration"));
             }
         }
         self.expect(TokenKind::KwBegin)?;
@@ -161,6 +300,9 @@ impl Parser {
         Ok(Architecture {
             name,
             entity,
+            uses,
+            types,
+            constants,
             signals,
             components,
             stmts,
@@ -189,16 +331,66 @@ impl Parser {
         })
     }
 
+    fn parse_constant_decl(&mut self) -> Result<ConstantDecl, Error> {
+        let span = self.current_span();
+        self.expect(TokenKind::KwConstant)?;
+        let name = self.expect_ident()?;
+        self.expect(TokenKind::Colon)?;
+        let ty = self.parse_type()?;
+        self.expect(TokenKind::ColonEq)?;
+        let value = match ty {
+            Type::Integer { .. } => ConstantValue::Int(self.parse_int_expr()?),
+            _ => ConstantValue::Expr(self.parse_expr()?),
+        };
+        self.expect(TokenKind::Semicolon)?;
+        Ok(ConstantDecl {
+            name,
+            ty,
+            value,
+            span: Some(span),
+        })
+    }
+
+    /// `type name is (A, B, ...);` or `type name is array (0 to N) of <type>;`
+    fn parse_type_decl(&mut self) -> Result<TypeDecl, Error> {
+        let span = self.current_span();
+        self.expect(TokenKind::KwType)?;
+        let name = self.expect_ident()?;
+        self.expect(TokenKind::KwIs)?;
+        let def = if self.check(TokenKind::KwArray) {
+            self.bump();
+            self.expect(TokenKind::LParen)?;
+            let (left, right, dir) = self.parse_range()?;
+            self.expect(TokenKind::RParen)?;
+            self.expect(TokenKind::KwOf)?;
+            let elem = Box::new(self.parse_type()?);
+            TypeDef::Array { left, right, dir, elem }
+        } else {
+            self.expect(TokenKind::LParen)?;
+            let literals = self.parse_ident_list()?;
+            self.expect(TokenKind::RParen)?;
+            TypeDef::Enum(literals)
+        };
+        self.expect(TokenKind::Semicolon)?;
+        Ok(TypeDecl {
+            name,
+            def,
+            span: Some(span),
+        })
+    }
+
     fn parse_component_decl(&mut self) -> Result<ComponentDecl, Error> {
         let span = self.current_span();
         self.expect(TokenKind::KwComponent)?;
-        let name = self.expect_ident()?;
+        let name = self.expect_unit_name()?;
+        let generics = self.parse_generic_clause()?;
         let ports = self.parse_port_clause()?;
         self.expect(TokenKind::KwEnd)?;
         self.expect(TokenKind::KwComponent)?;
         self.expect(TokenKind::Semicolon)?;
         Ok(ComponentDecl {
             name,
+            generics,
             ports,
             span: Some(span),
         })
@@ -209,6 +401,9 @@ impl Parser {
             return Ok(ConcurrentStmt::Process(self.parse_process_stmt()?));
         }
         if self.peek_ident() && self.peek_next_is(TokenKind::Colon) {
+            if self.peek_at_is(2, TokenKind::KwFor) || self.peek_at_is(2, TokenKind::KwIf) {
+                return Ok(ConcurrentStmt::Generate(self.parse_generate_stmt()?));
+            }
             return Ok(ConcurrentStmt::Instance(self.parse_instance_stmt()?));
         }
         Ok(ConcurrentStmt::Assign(self.parse_assign_stmt()?))
@@ -218,7 +413,30 @@ impl Parser {
         let span = self.current_span();
         let name = self.expect_ident()?;
         self.expect(TokenKind::Colon)?;
-        let entity = self.expect_ident()?;
+        let entity = self.expect_unit_name()?;
+        let mut generic_map = Vec::new();
+        if self.check(TokenKind::KwGeneric) {
+            self.bump();
+            self.expect(TokenKind::KwMap)?;
+            self.expect(TokenKind::LParen)?;
+            loop {
+                let span = self.current_span();
+                let name = self.expect_ident()?;
+                self.expect(TokenKind::Arrow)?;
+                let value = self.parse_int_expr()?;
+                generic_map.push(GenericAssoc {
+                    name,
+                    value,
+                    span: Some(span),
+                });
+                if self.check(TokenKind::Comma) {
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+            self.expect(TokenKind::RParen)?;
+        }
         self.expect(TokenKind::KwPort)?;
         self.expect(TokenKind::KwMap)?;
         self.expect(TokenKind::LParen)?;
@@ -240,11 +458,58 @@ impl Parser {
         Ok(InstanceStmt {
             name,
             entity,
+            generic_map,
             port_map,
             span: Some(span),
         })
     }
 
+    fn parse_generate_stmt(&mut self) -> Result<GenerateStmt, Error> {
+        let span = self.current_span();
+        let label = self.expect_ident()?;
+        self.expect(TokenKind::Colon)?;
+        let scheme = if self.check(TokenKind::KwFor) {
+            self.bump();
+            let var = self.expect_ident()?;
+            self.expect(TokenKind::KwIn)?;
+            let (left, right, dir) = self.parse_range()?;
+            GenerateScheme::For { var, left, right, dir }
+        } else {
+            self.expect(TokenKind::KwIf)?;
+            let left = self.parse_int_expr()?;
+            if !self.is_rel_op() {
+                return Err(self.err_here("expected comparison in generate condition"));
+            }
+            let op = match self.bump().kind {
+                TokenKind::Eq => BinaryOp::Eq,
+                TokenKind::Ne => BinaryOp::Ne,
+                TokenKind::Lt => BinaryOp::Lt,
+                TokenKind::Le => BinaryOp::Le,
+                TokenKind::Gt => BinaryOp::Gt,
+                _ => BinaryOp::Ge,
+            };
+            let right = self.parse_int_expr()?;
+            GenerateScheme::If { op, left, right }
+        };
+        self.expect(TokenKind::KwGenerate)?;
+        let mut stmts = Vec::new();
+        while !self.check(TokenKind::KwEnd) {
+            stmts.push(self.parse_concurrent_stmt()?);
+        }
+        self.expect(TokenKind::KwEnd)?;
+        self.expect(TokenKind::KwGenerate)?;
+        if self.peek_ident() {
+            self.bump();
+        }
+        self.expect(TokenKind::Semicolon)?;
+        Ok(GenerateStmt {
+            label,
+            scheme,
+            stmts,
+            span: Some(span),
+        })
+    }
+
     fn parse_assoc(&mut self) -> Result<Assoc, Error> {
         let span = self.current_span();
         let port = self.expect_ident()?;
@@ -261,7 +526,11 @@ impl Parser {
         let span = self.current_span();
         self.expect(TokenKind::KwProcess)?;
         self.expect(TokenKind::LParen)?;
-        let clk = self.expect_ident()?;
+        let mut sensitivity = vec![self.expect_ident()?];
+        while self.check(TokenKind::Comma) {
+            self.bump();
+            sensitivity.push(self.expect_ident()?);
+        }
         self.expect(TokenKind::RParen)?;
         self.expect(TokenKind::KwBegin)?;
         let mut stmts = Vec::new();
@@ -272,7 +541,7 @@ impl Parser {
         self.expect(TokenKind::KwProcess)?;
         self.expect(TokenKind::Semicolon)?;
         Ok(ProcessStmt {
-            clk,
+            sensitivity,
             stmts,
             span: Some(span),
         })
@@ -360,7 +629,7 @@ impl Parser {
         let span = self.current_span();
         let target = self.parse_target()?;
         self.expect(TokenKind::Le)?; // <=
-        let expr = self.parse_expr()?;
+        let expr = self.parse_when_expr()?;
         self.expect(TokenKind::Semicolon)?;
         Ok(AssignStmt {
             target,
@@ -373,27 +642,7 @@ impl Parser {
         let span = self.current_span();
         let name = self.expect_ident()?;
         let sel = if self.check(TokenKind::LParen) {
-            self.bump();
-            let first = self.parse_int_lit()?;
-            if self.check(TokenKind::KwDownto) || self.check(TokenKind::KwTo) {
-                let dir = if self.check(TokenKind::KwDownto) {
-                    self.bump();
-                    RangeDir::Downto
-                } else {
-                    self.bump();
-                    RangeDir::To
-                };
-                let second = self.parse_int_lit()?;
-                self.expect(TokenKind::RParen)?;
-                Some(Selector::Range {
-                    msb: first,
-                    lsb: second,
-                    dir,
-                })
-            } else {
-                self.expect(TokenKind::RParen)?;
-                Some(Selector::Index(first))
-            }
+            self.parse_selector()?
         } else {
             None
         };
@@ -404,6 +653,23 @@ impl Parser {
         })
     }
 
+    /// `a when c1 else b when c2 else d`
+    fn parse_when_expr(&mut self) -> Result<Expr, Error> {
+        let value = self.parse_expr()?;
+        if !self.check(TokenKind::KwWhen) {
+            return Ok(value);
+        }
+        self.bump();
+        let cond = self.parse_expr()?;
+        self.expect(TokenKind::KwElse)?;
+        let otherwise = self.parse_when_expr()?;
+        Ok(Expr::When {
+            value: Box::new(value),
+            cond: Box::new(cond),
+            otherwise: Box::new(otherwise),
+        })
+    }
+
     fn parse_expr(&mut self) -> Result<Expr, Error> {
         self.parse_rel_expr()
     }
@@ -543,6 +809,9 @@ impl Parser {
 
     fn parse_primary(&mut self) -> Result<Expr, Error> {
         if self.check(TokenKind::LParen) {
+            if let Some(aggregate) = self.parse_aggregate()? {
+                return Ok(aggregate);
+            }
             self.bump();
             let expr = self.parse_expr()?;
             self.expect(TokenKind::RParen)?;
@@ -555,6 +824,9 @@ impl Parser {
         if self.check(TokenKind::KwRisingEdge) {
             return self.parse_call("rising_edge");
         }
+        if self.check(TokenKind::KwFallingEdge) {
+            return self.parse_call("falling_edge");
+        }
         if self.peek_ident() {
             let ident = self.expect_ident()?;
             if self.check(TokenKind::LParen) {
@@ -577,9 +849,37 @@ impl Parser {
         Err(self.err_here("unexpected token in expression"))
     }
 
+    /// `(others => x)` or `(19 downto 0 => x)`; `None`, with nothing consumed,
+    /// for a parenthesized expression
+    fn parse_aggregate(&mut self) -> Result<Option<Expr>, Error> {
+        let start = self.idx;
+        self.expect(TokenKind::LParen)?;
+        let range = if self.check(TokenKind::KwOthers) {
+            self.bump();
+            None
+        } else {
+            match self.parse_range() {
+                Ok(range) if self.check(TokenKind::Arrow) => Some(range),
+                _ => {
+                    self.idx = start;
+                    return Ok(None);
+                }
+            }
+        };
+        self.expect(TokenKind::Arrow)?;
+        let value = self.parse_expr()?;
+        self.expect(TokenKind::RParen)?;
+        Ok(Some(Expr::Aggregate {
+            range,
+            value: Box::new(value),
+        }))
+    }
+
     fn parse_call(&mut self, name: &str) -> Result<Expr, Error> {
         if name.eq_ignore_ascii_case("rising_edge") {
             self.expect(TokenKind::KwRisingEdge)?;
+        } else if name.eq_ignore_ascii_case("falling_edge") {
+            self.expect(TokenKind::KwFallingEdge)?;
         }
         self.expect(TokenKind::LParen)?;
         let mut args = Vec::new();
@@ -600,9 +900,19 @@ impl Parser {
         })
     }
 
-    fn parse_selector(&mut self) -> Result<Option<Selector>, Error> {
+    fn parse_selector(&mut self) -> Result<Option<SelectorExpr>, Error> {
         self.expect(TokenKind::LParen)?;
-        let first = self.parse_int_lit()?;
+        let start = self.idx;
+        let first = match self.parse_int_expr() {
+            Ok(first) if self.check(TokenKind::KwDownto) || self.check(TokenKind::KwTo) || self.check(TokenKind::RParen) => first,
+            // Not a constant expression (`regs(ir(3 downto 0))`): a run-time index
+            _ => {
+                self.idx = start;
+                let expr = self.parse_expr()?;
+                self.expect(TokenKind::RParen)?;
+                return Ok(Some(SelectorExpr::Expr(Box::new(expr))));
+            }
+        };
         let sel = if self.check(TokenKind::KwDownto) || self.check(TokenKind::KwTo) {
             let dir = if self.check(TokenKind::KwDownto) {
                 self.bump();
@@ -611,20 +921,80 @@ impl Parser {
                 self.bump();
                 RangeDir::To
             };
-            let second = self.parse_int_lit()?;
+            let second = self.parse_int_expr()?;
             self.expect(TokenKind::RParen)?;
-            Some(Selector::Range {
+            Some(SelectorExpr::Range {
                 msb: first,
                 lsb: second,
                 dir,
             })
         } else {
             self.expect(TokenKind::RParen)?;
-            Some(Selector::Index(first))
+            Some(SelectorExpr::Index(first))
         };
         Ok(sel)
     }
 
+    /// Constant integer expression: `+ -` over `* /` over unary minus
+    fn parse_int_expr(&mut self) -> Result<IntExpr, Error> {
+        let mut expr = self.parse_int_term()?;
+        loop {
+            let op = if self.check(TokenKind::Plus) {
+                IntOp::Add
+            } else if self.check(TokenKind::Minus) {
+                IntOp::Sub
+            } else {
+                break;
+            };
+            self.bump();
+            let rhs = self.parse_int_term()?;
+            expr = IntExpr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(rhs),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_int_term(&mut self) -> Result<IntExpr, Error> {
+        let mut expr = self.parse_int_factor()?;
+        loop {
+            let op = if self.check(TokenKind::Star) {
+                IntOp::Mul
+            } else if self.check(TokenKind::Slash) {
+                IntOp::Div
+            } else {
+                break;
+            };
+            self.bump();
+            let rhs = self.parse_int_factor()?;
+            expr = IntExpr::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(rhs),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_int_factor(&mut self) -> Result<IntExpr, Error> {
+        if self.check(TokenKind::Minus) {
+            self.bump();
+            return Ok(IntExpr::Neg(Box::new(self.parse_int_factor()?)));
+        }
+        if self.check(TokenKind::LParen) {
+            self.bump();
+            let expr = self.parse_int_expr()?;
+            self.expect(TokenKind::RParen)?;
+            return Ok(expr);
+        }
+        if self.peek_ident() {
+            return Ok(IntExpr::Name(self.expect_ident()?));
+        }
+        Ok(IntExpr::Lit(self.parse_int_lit()?))
+    }
+
     fn parse_literal(&mut self) -> Result<Literal, Error> {
         let span = self.current_span();
         let tok = self.bump();
@@ -704,15 +1074,40 @@ impl Parser {
         }
     }
 
+    /// Entity or component name; the gates `And`, `Or`, `Xor`, `Not` and the
+    /// register `Bit` are named after keywords, kept as written
+    fn expect_unit_name(&mut self) -> Result<String, Error> {
+        if !self.peek_keyword_name() {
+            return self.expect_ident();
+        }
+        let span = self.bump().span;
+        let line = self.src.lines().nth(span.line - 1).unwrap_or_default();
+        Ok(line
+            .chars()
+            .skip(span.col - 1)
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect())
+    }
+
+    fn peek_keyword_name(&self) -> bool {
+        matches!(
+            self.peek().kind,
+            TokenKind::KwAnd | TokenKind::KwOr | TokenKind::KwXor | TokenKind::KwNot | TokenKind::KwBit
+        )
+    }
+
     fn peek_ident(&self) -> bool {
         matches!(self.peek().kind, TokenKind::Ident(_))
     }
 
     fn peek_next_is(&self, kind: TokenKind) -> bool {
-        if self.idx + 1 >= self.tokens.len() {
-            return false;
-        }
-        self.tokens[self.idx + 1].kind == kind
+        self.peek_at_is(1, kind)
+    }
+
+    fn peek_at_is(&self, offset: usize, kind: TokenKind) -> bool {
+        self.tokens
+            .get(self.idx + offset)
+            .is_some_and(|tok| tok.kind == kind)
     }
 
     fn check(&self, kind: TokenKind) -> bool {
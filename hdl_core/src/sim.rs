@@ -1,143 +1,485 @@
-use crate::elab::{CaseChoiceRef, ExprRef, Netlist, PrimitiveNet, SeqStmtRef, TargetRef};
+use crate::elab::{CaseChoiceRef, Edge, ExprRef, InstanceNode, Netlist, PrimitiveNet, SeqStmtRef, SignalType, TargetRef};
 use crate::error::Error;
+use crate::graph::{CombGraph, CombNode};
+use crate::hier::ScopeSignal;
+use crate::mem::{Memory, MemoryInfo, MemoryKind};
+use crate::dot::DotView;
+use crate::stats::DesignStats;
 use crate::value::{BitVec, Value, ValueKind};
+use crate::vcd::VcdTrace;
+use crate::watch::{Breakpoint, Condition, RunOutcome};
 use crate::ast::{BinaryOp, Selector, UnaryOp};
-use std::collections::HashMap;
-
-struct RamState {
-    mem: Vec<BitVec>,
-    data_width: usize,
-}
-
-struct RomState {
-    mem: Vec<BitVec>,
-    data_width: usize,
-    addr_width: usize,
-}
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 
 pub struct Simulator {
     netlist: Netlist,
     max_comb_iters: usize,
-    ram_state: Vec<RamState>,
-    rom_state: Vec<RomState>,
+    ram_state: Vec<Memory>,
+    rom_state: Vec<Memory>,
+    /// RAM state index for each primitive (only meaningful for Ram)
+    ram_slot: Vec<usize>,
+    graph: CombGraph,
+    /// Clock each signal is a plain copy of (itself when driven otherwise)
+    clock_root: Vec<usize>,
+    /// Clock domain (root clock signal) of each process
+    proc_clock: Vec<usize>,
+    /// Clock domain of each Dff/Ram; None when clocked by an expression
+    prim_clock: Vec<Option<usize>>,
+    /// Processes with an asynchronous reset
+    reset_procs: Vec<usize>,
+    /// Pending nodes, ordered by topological rank
+    worklist: BinaryHeap<Reverse<usize>>,
+    queued: Vec<bool>,
+    comb_evals: u64,
+    trace: Option<VcdTrace>,
+    /// Value each forced signal is held at, whatever drives it
+    forced: Vec<Option<BitVec>>,
+    /// Breakpoints by id; removed ones leave a hole so ids stay stable
+    breakpoints: Vec<Option<Breakpoint>>,
 }
 
 impl Simulator {
     pub fn new(netlist: Netlist) -> Self {
         let mut ram_state = Vec::new();
         let mut rom_state = Vec::new();
+        let mut ram_slot = Vec::with_capacity(netlist.primitives.len());
         for prim in &netlist.primitives {
+            ram_slot.push(ram_state.len());
             match prim {
                 PrimitiveNet::Ram {
                     addr_width,
                     data_width,
                     ..
-                } => {
-                    let depth = 1usize << (*addr_width as u32);
-                    let mut mem = Vec::with_capacity(depth);
-                    for _ in 0..depth {
-                        mem.push(BitVec::new(*data_width, 0));
-                    }
-                    ram_state.push(RamState {
-                        mem,
-                        data_width: *data_width,
-                    });
-                }
+                } => ram_state.push(Memory::new(*addr_width, *data_width)),
                 PrimitiveNet::Rom {
                     addr_width,
                     data_width,
                     ..
-                } => {
-                    let depth = 1usize << (*addr_width as u32);
-                    let mut mem = Vec::with_capacity(depth);
-                    for _ in 0..depth {
-                        mem.push(BitVec::new(*data_width, 0));
-                    }
-                    rom_state.push(RomState {
-                        mem,
-                        data_width: *data_width,
-                        addr_width: *addr_width,
-                    });
-                }
+                } => rom_state.push(Memory::new(*addr_width, *data_width)),
                 _ => {}
             }
         }
+        let graph = CombGraph::build(&netlist);
+        let node_count = graph.nodes.len();
+        let clock_root = clock_roots(&netlist);
+        let proc_clock = netlist.processes.iter().map(|p| clock_root[p.clk]).collect();
+        let reset_procs = (0..netlist.processes.len())
+            .filter(|&i| netlist.processes[i].reset.is_some())
+            .collect();
+        let prim_clock = netlist
+            .primitives
+            .iter()
+            .map(|prim| match prim {
+                PrimitiveNet::Dff { clk, .. } | PrimitiveNet::Ram { clk, .. } => match clk {
+                    ExprRef::Target(TargetRef { signal, sel: None }) => Some(clock_root[*signal]),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        let forced = vec![None; netlist.signals.len()];
         Self {
             netlist,
             max_comb_iters: 1000,
             ram_state,
             rom_state,
+            ram_slot,
+            graph,
+            clock_root,
+            proc_clock,
+            prim_clock,
+            reset_procs,
+            // Everything is evaluated once on the first settle
+            worklist: (0..node_count).map(Reverse).collect(),
+            queued: vec![true; node_count],
+            comb_evals: 0,
+            trace: None,
+            forced,
+            breakpoints: Vec::new(),
         }
     }
 
-    /// Load binary data into ROM at the given index
-    /// Data is loaded as 16-bit words (little-endian)
-    pub fn load_rom(&mut self, rom_index: usize, data: &[u8]) -> Result<(), Error> {
-        let state = self.rom_state.get_mut(rom_index)
-            .ok_or_else(|| Error::new(format!("ROM index {} out of range", rom_index)))?;
+    /// Start recording signal changes as a VCD waveform.
+    /// Each eval/tick/tock advances the trace by one time unit.
+    pub fn start_vcd(&mut self, top: &str) {
+        self.trace = Some(VcdTrace::new(top, &self.netlist.signals));
+    }
 
-        // Load data as words
-        let word_size = (state.data_width + 7) / 8;
-        for (i, chunk) in data.chunks(word_size).enumerate() {
-            if i >= state.mem.len() {
-                break; // ROM is full
-            }
-            let mut val: u64 = 0;
-            for (j, &byte) in chunk.iter().enumerate() {
-                val |= (byte as u64) << (j * 8);
-            }
-            state.mem[i] = BitVec::from_i64(state.data_width, val as i64);
+    /// Render the recorded waveform, if recording was started
+    pub fn vcd(&self) -> Option<String> {
+        self.trace
+            .as_ref()
+            .map(|t| t.render(&self.netlist.signals))
+    }
+
+    fn record(&mut self) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.sample(&self.netlist.signals);
         }
+    }
+
+    /// Load binary data into ROM at the given index, as little-endian
+    /// words of `ceil(data_width / 8)` bytes from address 0
+    pub fn load_rom(&mut self, rom_index: usize, data: &[u8]) -> Result<(), Error> {
+        let path = self.rom_path(rom_index)?;
+        let depth = self.memory(&path)?.last_addr().saturating_add(1);
+        let word_bytes = self.memory(&path)?.word_bytes() as u64;
+        // Whatever does not fit is dropped, like a ROM that is full
+        let len = depth.saturating_mul(word_bytes).min(data.len() as u64) as usize;
+        self.update_memory(&path, |mem| mem.load_bytes(0, &data[..len]))?;
         Ok(())
     }
 
-    /// Load hex string into ROM (each line is one word)
+    /// Load hex words into ROM (`$readmemh` syntax, see `mem`)
     pub fn load_rom_hex(&mut self, rom_index: usize, hex: &str) -> Result<(), Error> {
-        let state = self.rom_state.get_mut(rom_index)
-            .ok_or_else(|| Error::new(format!("ROM index {} out of range", rom_index)))?;
+        let path = self.rom_path(rom_index)?;
+        self.update_memory(&path, |mem| mem.load_hex(hex))?;
+        Ok(())
+    }
 
-        for (i, line) in hex.lines().enumerate() {
-            let trimmed = line.trim();
-            if trimmed.is_empty() || trimmed.starts_with("//") {
-                continue;
-            }
-            if i >= state.mem.len() {
-                break;
-            }
-            let val = u64::from_str_radix(trimmed.trim_start_matches("0x"), 16)
-                .map_err(|e| Error::new(format!("invalid hex at line {}: {}", i, e)))?;
-            state.mem[i] = BitVec::from_i64(state.data_width, val as i64);
+    /// Instance path of the ROM at `rom_index`
+    pub fn rom_path(&self, rom_index: usize) -> Result<String, Error> {
+        self.netlist
+            .primitives
+            .iter()
+            .position(|prim| matches!(prim, PrimitiveNet::Rom { rom_index: idx, .. } if *idx == rom_index))
+            .map(|prim| self.netlist.instances[self.netlist.primitive_instances[prim]].path.clone())
+            .ok_or_else(|| Error::new(format!("ROM index {} out of range", rom_index)))
+    }
+
+    /// RAM and ROM primitives, in netlist order
+    pub fn memories(&self) -> Vec<MemoryInfo> {
+        (0..self.netlist.primitives.len())
+            .filter_map(|prim| {
+                let (kind, mem) = self.prim_memory(prim)?;
+                Some(MemoryInfo {
+                    path: self.netlist.instances[self.netlist.primitive_instances[prim]].path.clone(),
+                    kind,
+                    addr_width: mem.addr_width(),
+                    data_width: mem.data_width(),
+                })
+            })
+            .collect()
+    }
+
+    fn prim_memory(&self, prim: usize) -> Option<(MemoryKind, &Memory)> {
+        match &self.netlist.primitives[prim] {
+            PrimitiveNet::Ram { .. } => Some((MemoryKind::Ram, &self.ram_state[self.ram_slot[prim]])),
+            PrimitiveNet::Rom { rom_index, .. } => Some((MemoryKind::Rom, &self.rom_state[*rom_index])),
+            _ => None,
         }
-        Ok(())
+    }
+
+    /// Primitive index of the memory instance at `path` (`cpu/imem`)
+    fn memory_prim(&self, path: &str) -> Result<usize, Error> {
+        let path = path.trim_matches('/');
+        (0..self.netlist.primitives.len())
+            .find(|&prim| {
+                self.prim_memory(prim).is_some()
+                    && self.netlist.instances[self.netlist.primitive_instances[prim]].path == path
+            })
+            .ok_or_else(|| {
+                let known: Vec<String> = self.memories().into_iter().map(|m| m.path).collect();
+                Error::new(format!("unknown memory {} (memories: {})", path, known.join(", ")))
+            })
+    }
+
+    /// Contents of the memory instance at `path`
+    pub fn memory(&self, path: &str) -> Result<&Memory, Error> {
+        let prim = self.memory_prim(path)?;
+        Ok(self.prim_memory(prim).map(|(_, mem)| mem).expect("memory primitive"))
+    }
+
+    /// Changes the memory at `path` and updates its read port
+    pub fn update_memory<T>(&mut self, path: &str, f: impl FnOnce(&mut Memory) -> Result<T, Error>) -> Result<T, Error> {
+        let prim = self.memory_prim(path)?;
+        let mem = match &self.netlist.primitives[prim] {
+            PrimitiveNet::Rom { rom_index, .. } => &mut self.rom_state[*rom_index],
+            _ => &mut self.ram_state[self.ram_slot[prim]],
+        };
+        let out = f(mem)?;
+        if let Some(node) = self.graph.prim_node[prim] {
+            self.schedule(node);
+        }
+        Ok(out)
+    }
+
+    pub fn read_memory(&self, path: &str, addr: u64) -> Result<BitVec, Error> {
+        let mem = self.memory(path)?;
+        if addr > mem.last_addr() {
+            return Err(Error::new(format!("address 0x{:X} out of range for {}", addr, path)));
+        }
+        Ok(mem.read(addr))
+    }
+
+    pub fn write_memory(&mut self, path: &str, addr: u64, value: &BitVec) -> Result<(), Error> {
+        self.update_memory(path, |mem| mem.write(addr, value))
     }
 
     pub fn set_max_comb_iters(&mut self, max: usize) {
         self.max_comb_iters = max;
     }
 
+    /// Net id for a flat net name or a hierarchical path (`cpu/alu/y`)
+    fn signal_id(&self, name: &str) -> Result<usize, Error> {
+        match self.netlist.name_to_id.get(name) {
+            Some(&id) => Ok(id),
+            None => self.netlist.resolve_signal("", name),
+        }
+    }
+
+    /// Net id, bit position and width of an array element path
+    /// (`u_rf/regs(3)`); `None` when `name` is not an element path
+    fn element_id(&self, name: &str) -> Result<Option<(usize, usize, usize)>, Error> {
+        let Some((base, index)) = name.strip_suffix(')').and_then(|n| n.rsplit_once('(')) else {
+            return Ok(None);
+        };
+        let Ok(index) = index.trim().parse::<i64>() else {
+            return Ok(None);
+        };
+        let id = self.signal_id(base)?;
+        let sig = &self.netlist.signals[id];
+        let SignalType::Array { low, high, elem_width } = &sig.ty else {
+            return Err(Error::new(format!("{} is not an array", base)));
+        };
+        match sig.ty.element_offset(index) {
+            Some(pos) => Ok(Some((id, pos, *elem_width))),
+            None => Err(Error::new(format!(
+                "index {} out of range {} to {} for {}",
+                index, low, high, base
+            ))),
+        }
+    }
+
+    /// Sets a signal, or one element of an array signal (`regs(3)`)
     pub fn set_signal(&mut self, name: &str, value: BitVec) -> Result<(), Error> {
-        let id = self
-            .netlist
-            .name_to_id
-            .get(name)
-            .copied()
-            .ok_or_else(|| Error::new(format!("unknown signal {}", name)))?;
-        let width = self.netlist.signals[id].width;
-        let resized = value.resize_zero(width);
-        self.netlist.signals[id].value = resized;
+        let (id, resized) = match self.element_id(name)? {
+            Some((id, pos, width)) => {
+                let mut bits = self.netlist.signals[id].value.clone();
+                bits.set_slice(pos, &value.resize_zero(width));
+                (id, bits)
+            }
+            None => {
+                let id = self.signal_id(name)?;
+                (id, value.resize_zero(self.netlist.signals[id].width))
+            }
+        };
+        self.netlist.signals[id].value = self.forced[id].clone().unwrap_or(resized);
+        self.signal_changed(id);
+        // A driven signal gets its driver's value back on the next eval
+        for i in 0..self.graph.drivers[id].len() {
+            let node = self.graph.drivers[id][i];
+            self.schedule(node);
+        }
         Ok(())
     }
 
     pub fn get_signal(&self, name: &str) -> Result<BitVec, Error> {
-        let id = self
-            .netlist
-            .name_to_id
-            .get(name)
-            .copied()
-            .ok_or_else(|| Error::new(format!("unknown signal {}", name)))?;
+        if let Some((id, pos, width)) = self.element_id(name)? {
+            return Ok(self.netlist.signals[id].value.slice(pos, width));
+        }
+        let id = self.signal_id(name)?;
         Ok(self.netlist.signals[id].value.clone())
     }
 
+    /// Declared type of a signal (enumeration, integer range or plain bits)
+    pub fn signal_type(&self, name: &str) -> Result<&SignalType, Error> {
+        let id = self.signal_id(name)?;
+        Ok(&self.netlist.signals[id].ty)
+    }
+
+    /// Current value of an enumeration or integer signal as written in the
+    /// source (`FETCH`, `-3`); `None` for plain vectors
+    pub fn display_signal(&self, name: &str) -> Result<Option<String>, Error> {
+        let id = self.signal_id(name)?;
+        let sig = &self.netlist.signals[id];
+        Ok(sig.ty.display(&sig.value))
+    }
+
+    /// Design hierarchy, the top entity first
+    pub fn instances(&self) -> &[InstanceNode] {
+        &self.netlist.instances
+    }
+
+    /// Direct sub-instances (and generate blocks) of the instance at `path`
+    pub fn instance_children(&self, path: &str) -> Result<Vec<&InstanceNode>, Error> {
+        self.netlist.instance_children(path)
+    }
+
+    /// Ports and local signals of the instance at `path`, by local name
+    pub fn scope_signals(&self, path: &str) -> Result<Vec<ScopeSignal>, Error> {
+        self.netlist.scope_signals(path)
+    }
+
+    /// Resolves `path` relative to the instance `scope` and returns the
+    /// flat net name it is bound to
+    pub fn resolve_signal(&self, scope: &str, path: &str) -> Result<String, Error> {
+        let id = self.netlist.resolve_signal(scope, path)?;
+        Ok(self.netlist.signals[id].name.clone())
+    }
+
+    /// Gate counts and critical path of the simulated design
+    pub fn design_stats(&self) -> DesignStats {
+        self.netlist.design_stats()
+    }
+
+    /// Graphviz schematic of the instance at `path` (`""` for the top)
+    pub fn to_dot(&self, path: &str, view: DotView) -> Result<String, Error> {
+        self.netlist.to_dot(path, view)
+    }
+
+    /// Holds a signal at `value` until it is released: its drivers, process
+    /// assignments and `set_signal` no longer change it. The signal takes
+    /// the value at once; the logic reading it follows on the next eval or
+    /// clock edge.
+    pub fn force_signal(&mut self, name: &str, value: BitVec) -> Result<(), Error> {
+        let id = self.signal_id(name)?;
+        let value = value.resize_zero(self.netlist.signals[id].width);
+        self.netlist.signals[id].value = value.clone();
+        self.forced[id] = Some(value);
+        self.signal_changed(id);
+        Ok(())
+    }
+
+    /// Gives a forced signal back to its drivers. A combinational signal
+    /// gets its driven value on the next eval; a register keeps the forced
+    /// value until its next clock edge.
+    pub fn release_signal(&mut self, name: &str) -> Result<(), Error> {
+        let id = self.signal_id(name)?;
+        if self.forced[id].take().is_none() {
+            return Err(Error::new(format!("{} is not forced", name)));
+        }
+        for i in 0..self.graph.drivers[id].len() {
+            let node = self.graph.drivers[id][i];
+            self.schedule(node);
+        }
+        Ok(())
+    }
+
+    pub fn release_all(&mut self) {
+        for id in 0..self.forced.len() {
+            if self.forced[id].take().is_some() {
+                for i in 0..self.graph.drivers[id].len() {
+                    let node = self.graph.drivers[id][i];
+                    self.schedule(node);
+                }
+            }
+        }
+    }
+
+    /// Names of the forced signals
+    pub fn forced_signals(&self) -> Vec<String> {
+        (0..self.forced.len())
+            .filter(|&id| self.forced[id].is_some())
+            .map(|id| self.netlist.signals[id].name.clone())
+            .collect()
+    }
+
+    /// Stops `run_until_break` after a cycle where `condition` holds, e.g.
+    /// `pc = 0x40` (syntax in `watch`). Returns the breakpoint id.
+    pub fn add_breakpoint(&mut self, condition: &str) -> Result<usize, Error> {
+        let condition = Condition::parse(condition, &self.netlist.signals, &|name| self.signal_id(name))?;
+        self.breakpoints.push(Some(Breakpoint::Condition(condition)));
+        Ok(self.breakpoints.len())
+    }
+
+    /// Stops `run_until_break` after a cycle that changed `name`
+    pub fn add_watchpoint(&mut self, name: &str) -> Result<usize, Error> {
+        let signal = self.signal_id(name)?;
+        let last = self.netlist.signals[signal].value.clone();
+        self.breakpoints.push(Some(Breakpoint::Watch { signal, last }));
+        Ok(self.breakpoints.len())
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> Result<(), Error> {
+        match id.checked_sub(1).and_then(|i| self.breakpoints.get_mut(i)) {
+            Some(slot @ Some(_)) => {
+                *slot = None;
+                Ok(())
+            }
+            _ => Err(Error::new(format!("no breakpoint {}", id))),
+        }
+    }
+
+    /// Removes every breakpoint; ids are not reused
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.iter_mut().for_each(|bp| *bp = None);
+    }
+
+    /// Breakpoints as (id, description): the condition, or `watch <signal>`
+    pub fn breakpoints(&self) -> Vec<(usize, String)> {
+        self.breakpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(i, bp)| {
+                let text = match bp.as_ref()? {
+                    Breakpoint::Condition(c) => c.text.clone(),
+                    Breakpoint::Watch { signal, .. } => format!("watch {}", self.netlist.signals[*signal].name),
+                };
+                Some((i + 1, text))
+            })
+            .collect()
+    }
+
+    /// Ids of the breakpoints that fire on the current state; watchpoints
+    /// compare against the state of the previous call
+    pub fn check_breakpoints(&mut self) -> Vec<usize> {
+        let signals = &self.netlist.signals;
+        let mut hits = Vec::new();
+        for (i, bp) in self.breakpoints.iter_mut().enumerate() {
+            let hit = match bp {
+                Some(Breakpoint::Condition(c)) => c.holds(signals),
+                Some(Breakpoint::Watch { signal, last }) => {
+                    let changed = signals[*signal].value != *last;
+                    *last = signals[*signal].value.clone();
+                    changed
+                }
+                None => false,
+            };
+            if hit {
+                hits.push(i + 1);
+            }
+        }
+        hits
+    }
+
+    /// Runs clock cycles (rising then falling edge of `clock`, or of every
+    /// clock) until a breakpoint fires or `max_cycles` have run
+    pub fn run_until_break(&mut self, clock: Option<&str>, max_cycles: u64) -> Result<RunOutcome, Error> {
+        self.run_until_break_with(max_cycles, |sim| match clock {
+            Some(clk) => {
+                sim.tick_clock(clk)?;
+                sim.tock_clock(clk)
+            }
+            None => {
+                sim.tick()?;
+                sim.tock()
+            }
+        })
+    }
+
+    /// Like `run_until_break`, with `cycle` running one clock cycle
+    pub fn run_until_break_with(
+        &mut self,
+        max_cycles: u64,
+        mut cycle: impl FnMut(&mut Self) -> Result<(), Error>,
+    ) -> Result<RunOutcome, Error> {
+        // Watchpoints start from the current state
+        self.check_breakpoints();
+        for n in 1..=max_cycles {
+            cycle(self)?;
+            let hits = self.check_breakpoints();
+            if !hits.is_empty() {
+                return Ok(RunOutcome { cycles: n, hits });
+            }
+        }
+        Ok(RunOutcome {
+            cycles: max_cycles,
+            hits: Vec::new(),
+        })
+    }
+
     /// Returns all signal names in the circuit
     pub fn signal_names(&self) -> Vec<String> {
         self.netlist.signals.iter().map(|s| s.name.clone()).collect()
@@ -163,180 +505,259 @@ impl Simulator {
     }
 
     pub fn eval_comb(&mut self) -> Result<(), Error> {
-        for _ in 0..self.max_comb_iters {
-            let mut changed = false;
-            let assigns = self.netlist.assigns.clone();
-            for assign in assigns {
-                let value = self.eval_expr(&assign.expr)?;
-                let was_changed = self.apply_target(&assign.target, value)?;
-                if was_changed {
-                    changed = true;
+        self.settle()?;
+        self.record();
+        Ok(())
+    }
+
+    /// Number of combinational node evaluations performed so far
+    pub fn comb_evals(&self) -> u64 {
+        self.comb_evals
+    }
+
+    /// Re-evaluate pending combinational nodes in topological order.
+    /// Only the fanout of signals that actually changed is scheduled, so an
+    /// acyclic netlist evaluates each node at most once; true loops are
+    /// iterated until stable, bounded by max_comb_iters.
+    fn settle(&mut self) -> Result<(), Error> {
+        // An asserted asynchronous reset drives its registers like a
+        // combinational source: apply it, then settle what it changed
+        for _ in 0..=self.max_comb_iters {
+            self.settle_comb()?;
+            if !self.apply_async_resets()? {
+                return Ok(());
+            }
+        }
+        Err(Error::new("asynchronous reset did not converge"))
+    }
+
+    fn settle_comb(&mut self) -> Result<(), Error> {
+        let budget = self
+            .max_comb_iters
+            .saturating_mul(self.graph.nodes.len().max(1));
+        let mut evals = 0usize;
+        while let Some(Reverse(rank)) = self.worklist.pop() {
+            let node = self.graph.order[rank];
+            self.queued[node] = false;
+            evals += 1;
+            if evals > budget {
+                self.worklist.clear();
+                self.queued.iter_mut().for_each(|q| *q = false);
+                return Err(Error::new("combinational logic did not converge"));
+            }
+            self.comb_evals += 1;
+            if self.eval_node(node)? {
+                for i in 0..self.graph.succ[node].len() {
+                    let next = self.graph.succ[node][i];
+                    self.schedule(next);
                 }
             }
-            let primitives = self.netlist.primitives.clone();
-            let mut ram_idx = 0usize;
-            let mut rom_idx = 0usize;
-            for prim in primitives {
-                match prim {
-                    PrimitiveNet::Nand2 { a, b, y } => {
-                        let av = self.eval_expr(&a)?;
-                        let bv = self.eval_expr(&b)?;
-                        let value = Value {
-                            bits: BitVec::and(&av.bits, &bv.bits).not(),
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&y, value)? {
-                            changed = true;
-                        }
-                    }
-                    PrimitiveNet::Not1 { a, y } => {
-                        let v = self.eval_expr(&a)?;
-                        let value = Value {
-                            bits: v.bits.not(),
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&y, value)? {
-                            changed = true;
-                        }
-                    }
-                    PrimitiveNet::And2 { a, b, y } => {
-                        let av = self.eval_expr(&a)?;
-                        let bv = self.eval_expr(&b)?;
-                        let value = Value {
-                            bits: BitVec::and(&av.bits, &bv.bits),
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&y, value)? {
-                            changed = true;
-                        }
-                    }
-                    PrimitiveNet::Or2 { a, b, y } => {
-                        let av = self.eval_expr(&a)?;
-                        let bv = self.eval_expr(&b)?;
-                        let value = Value {
-                            bits: BitVec::or(&av.bits, &bv.bits),
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&y, value)? {
-                            changed = true;
-                        }
-                    }
-                    PrimitiveNet::Xor2 { a, b, y } => {
-                        let av = self.eval_expr(&a)?;
-                        let bv = self.eval_expr(&b)?;
-                        let value = Value {
-                            bits: BitVec::xor(&av.bits, &bv.bits),
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&y, value)? {
-                            changed = true;
-                        }
-                    }
-                    PrimitiveNet::Mux2 { a, b, sel, y } => {
-                        let sel_v = self.eval_expr(&sel)?;
-                        let pick_b = self.value_is_true(&sel_v);
-                        let chosen = if pick_b {
-                            self.eval_expr(&b)?
-                        } else {
-                            self.eval_expr(&a)?
-                        };
-                        let value = Value {
-                            bits: chosen.bits,
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&y, value)? {
-                            changed = true;
-                        }
-                    }
-                    PrimitiveNet::Ram {
-                        addr,
-                        dout,
-                        addr_width,
-                        ..
-                    } => {
-                        let addr_v = self.eval_expr(&addr)?;
-                        let addr_bits = addr_v.bits.resize_zero(addr_width);
-                        let idx = addr_bits.to_u64_trunc() as usize;
-                        let data = {
-                            let state = self
-                                .ram_state
-                                .get(ram_idx)
-                                .ok_or_else(|| Error::new("ram state missing"))?;
-                            if idx >= state.mem.len() {
-                                return Err(Error::new("ram address out of range"));
-                            }
-                            state.mem[idx].clone()
-                        };
-                        let value = Value {
-                            bits: data,
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&dout, value)? {
-                            changed = true;
-                        }
-                        ram_idx += 1;
-                    }
-                    PrimitiveNet::Rom {
-                        addr,
-                        dout,
-                        addr_width,
-                        rom_index,
-                        ..
-                    } => {
-                        // ROM is purely combinatorial - read only
-                        let addr_v = self.eval_expr(&addr)?;
-                        let addr_bits = addr_v.bits.resize_zero(addr_width);
-                        let idx = addr_bits.to_u64_trunc() as usize;
-                        let data = {
-                            let state = self
-                                .rom_state
-                                .get(rom_index)
-                                .ok_or_else(|| Error::new("rom state missing"))?;
-                            if idx >= state.mem.len() {
-                                // Return 0 for out-of-range addresses
-                                BitVec::new(state.data_width, 0)
-                            } else {
-                                state.mem[idx].clone()
-                            }
-                        };
-                        let value = Value {
-                            bits: data,
-                            kind: ValueKind::Bitwise,
-                        };
-                        if self.apply_target(&dout, value)? {
-                            changed = true;
-                        }
-                        rom_idx += 1;
-                    }
-                    PrimitiveNet::Dff { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Applies the reset branch of every process whose reset is asserted,
+    /// returns true if a register changed
+    fn apply_async_resets(&mut self) -> Result<bool, Error> {
+        let mut updates: HashMap<usize, BitVec> = HashMap::new();
+        for &i in &self.reset_procs {
+            if let Some(reset) = &self.netlist.processes[i].reset {
+                if self.value_is_true(&self.eval_expr(&reset.cond)?) {
+                    self.eval_seq_block(&reset.stmts, &mut updates)?;
                 }
             }
-            // Silence unused variable warning
-            let _ = rom_idx;
-            if !changed {
-                return Ok(());
+        }
+        let mut changed = false;
+        for (sig, val) in updates {
+            changed |= self.store_register(sig, val);
+        }
+        Ok(changed)
+    }
+
+    /// Writes the new value of a register unless the signal is forced,
+    /// returns true if it changed
+    fn store_register(&mut self, sig: usize, val: BitVec) -> bool {
+        if self.forced[sig].is_some() || self.netlist.signals[sig].value == val {
+            return false;
+        }
+        self.netlist.signals[sig].value = val;
+        self.signal_changed(sig);
+        true
+    }
+
+    fn schedule(&mut self, node: usize) {
+        if !self.queued[node] {
+            self.queued[node] = true;
+            self.worklist.push(Reverse(self.graph.rank[node]));
+        }
+    }
+
+    /// Schedule every node reading the given signal
+    fn signal_changed(&mut self, sig: usize) {
+        for i in 0..self.graph.readers[sig].len() {
+            let node = self.graph.readers[sig][i];
+            self.schedule(node);
+        }
+    }
+
+    /// Evaluate one combinational node, returns true if its output changed
+    fn eval_node(&mut self, node: usize) -> Result<bool, Error> {
+        match self.graph.nodes[node] {
+            CombNode::Assign(i) => {
+                let value = self.eval_expr(&self.netlist.assigns[i].expr)?;
+                let target = self.netlist.assigns[i].target.clone();
+                self.apply_target(&target, value)
+            }
+            CombNode::Primitive(i) => {
+                let (target, value) = self.eval_primitive(i)?;
+                self.apply_target(&target, value)
             }
         }
-        Err(Error::new("combinational logic did not converge"))
     }
 
+    fn eval_primitive(&self, prim: usize) -> Result<(TargetRef, Value), Error> {
+        let bitwise = |bits| Value {
+            bits,
+            kind: ValueKind::Bitwise,
+        };
+        Ok(match &self.netlist.primitives[prim] {
+            PrimitiveNet::Nand2 { a, b, y } => {
+                let av = self.eval_expr(a)?;
+                let bv = self.eval_expr(b)?;
+                (y.clone(), bitwise(BitVec::and(&av.bits, &bv.bits).not()))
+            }
+            PrimitiveNet::Not1 { a, y } => {
+                let v = self.eval_expr(a)?;
+                (y.clone(), bitwise(v.bits.not()))
+            }
+            PrimitiveNet::And2 { a, b, y } => {
+                let av = self.eval_expr(a)?;
+                let bv = self.eval_expr(b)?;
+                (y.clone(), bitwise(BitVec::and(&av.bits, &bv.bits)))
+            }
+            PrimitiveNet::Or2 { a, b, y } => {
+                let av = self.eval_expr(a)?;
+                let bv = self.eval_expr(b)?;
+                (y.clone(), bitwise(BitVec::or(&av.bits, &bv.bits)))
+            }
+            PrimitiveNet::Xor2 { a, b, y } => {
+                let av = self.eval_expr(a)?;
+                let bv = self.eval_expr(b)?;
+                (y.clone(), bitwise(BitVec::xor(&av.bits, &bv.bits)))
+            }
+            PrimitiveNet::Mux2 { a, b, sel, y } => {
+                let sel_v = self.eval_expr(sel)?;
+                let chosen = if self.value_is_true(&sel_v) {
+                    self.eval_expr(b)?
+                } else {
+                    self.eval_expr(a)?
+                };
+                (y.clone(), bitwise(chosen.bits))
+            }
+            PrimitiveNet::Ram {
+                addr,
+                dout,
+                addr_width,
+                ..
+            } => {
+                let addr_v = self.eval_expr(addr)?;
+                let idx = addr_v.bits.resize_zero(*addr_width).to_u64_trunc();
+                (dout.clone(), bitwise(self.ram_state[self.ram_slot[prim]].read(idx)))
+            }
+            PrimitiveNet::Rom {
+                addr,
+                dout,
+                addr_width,
+                rom_index,
+                ..
+            } => {
+                // ROM is purely combinatorial - read only
+                let addr_v = self.eval_expr(addr)?;
+                let idx = addr_v.bits.resize_zero(*addr_width).to_u64_trunc();
+                (dout.clone(), bitwise(self.rom_state[*rom_index].read(idx)))
+            }
+            PrimitiveNet::Dff { .. } => return Err(Error::new("dff is not combinational")),
+        })
+    }
+
+    /// Rising edge on every clock: fires all rising_edge processes, Dffs and RAM writes
     pub fn tick(&mut self) -> Result<(), Error> {
+        self.clock_edge(None, Edge::Rising)
+    }
+
+    /// Falling edge on every clock: fires all falling_edge processes
+    pub fn tock(&mut self) -> Result<(), Error> {
+        self.clock_edge(None, Edge::Falling)
+    }
+
+    /// Drives `clk` high and fires only the elements clocked by it
+    pub fn tick_clock(&mut self, clk: &str) -> Result<(), Error> {
+        let root = self.drive_clock(clk, 1)?;
+        self.clock_edge(Some(root), Edge::Rising)
+    }
+
+    /// Drives `clk` low and fires only the falling_edge processes clocked by it
+    pub fn tock_clock(&mut self, clk: &str) -> Result<(), Error> {
+        let root = self.drive_clock(clk, 0)?;
+        self.clock_edge(Some(root), Edge::Falling)
+    }
+
+    /// Names of the clock signals driving processes, Dffs and RAMs
+    pub fn clocks(&self) -> Vec<String> {
+        let mut roots: Vec<usize> = self
+            .proc_clock
+            .iter()
+            .copied()
+            .chain(self.prim_clock.iter().flatten().copied())
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+        roots
+            .into_iter()
+            .map(|id| self.netlist.signals[id].name.clone())
+            .collect()
+    }
+
+    fn drive_clock(&mut self, clk: &str, level: u8) -> Result<usize, Error> {
+        let id = self.signal_id(clk)?;
+        if self.netlist.signals[id].width != 1 {
+            return Err(Error::new(format!("clock {} must be 1 bit", clk)));
+        }
+        self.set_signal(clk, BitVec::new(1, level))?;
+        Ok(self.clock_root[id])
+    }
+
+    /// Fires the sequential elements sensitive to `edge` of `domain`
+    /// (every domain when None), then settles the combinational logic
+    fn clock_edge(&mut self, domain: Option<usize>, edge: Edge) -> Result<(), Error> {
         // First evaluate combinational logic to get stable inputs
-        self.eval_comb()?;
+        self.settle()?;
 
+        let in_domain = |clock: Option<usize>| domain.is_none() || clock == domain;
         let mut updates: HashMap<usize, BitVec> = HashMap::new();
-        for proc in &self.netlist.processes {
+        for (proc, clock) in self.netlist.processes.iter().zip(&self.proc_clock) {
+            if proc.edge != edge || !in_domain(Some(*clock)) {
+                continue;
+            }
+            // A held reset wins over the clock edge
+            if let Some(reset) = &proc.reset {
+                if self.value_is_true(&self.eval_expr(&reset.cond)?) {
+                    continue;
+                }
+            }
             let mut local: HashMap<usize, BitVec> = HashMap::new();
             self.eval_seq_block(&proc.stmts, &mut local)?;
             for (sig, val) in local {
                 updates.insert(sig, val);
             }
         }
-        let mut ram_idx = 0usize;
-        for prim in &self.netlist.primitives {
+        let mut written_rams = Vec::new();
+        for (prim_idx, prim) in self.netlist.primitives.iter().enumerate() {
+            if edge != Edge::Rising || !in_domain(self.prim_clock[prim_idx]) {
+                continue;
+            }
             match prim {
                 PrimitiveNet::Dff { d, q, .. } => {
-                    // tick() represents the rising clock edge - always latch
                     let val = self.eval_expr(d)?;
                     self.apply_to_updates(q, val, &mut updates)?;
                 }
@@ -347,44 +768,34 @@ impl Simulator {
                     addr_width,
                     ..
                 } => {
-                    // tick() represents the rising clock edge - check write enable only
+                    // Only write enable is sampled: the edge itself is the clock
                     let we_v = self.eval_expr(we)?;
                     if self.value_is_true(&we_v) {
                         let addr_v = self.eval_expr(addr)?;
-                        let addr_bits = addr_v.bits.resize_zero(*addr_width);
-                        let idx = addr_bits.to_u64_trunc() as usize;
+                        let idx = addr_v.bits.resize_zero(*addr_width).to_u64_trunc();
                         let din_v = self.eval_expr(din)?;
-                        let data_width = self
-                            .ram_state
-                            .get(ram_idx)
-                            .ok_or_else(|| Error::new("ram state missing"))?
-                            .data_width;
-                        let data = Self::value_to_width(&din_v, data_width);
-                        let state = self
-                            .ram_state
-                            .get_mut(ram_idx)
-                            .ok_or_else(|| Error::new("ram state missing"))?;
-                        if idx >= state.mem.len() {
-                            return Err(Error::new("ram address out of range"));
-                        }
-                        state.mem[idx] = data;
+                        let mem = &mut self.ram_state[self.ram_slot[prim_idx]];
+                        let data = Self::value_to_width(&din_v, mem.data_width());
+                        mem.write(idx, &data)?;
+                        written_rams.push(prim_idx);
                     }
-                    ram_idx += 1;
                 }
                 _ => {}
             }
         }
         for (sig, val) in updates {
-            self.netlist.signals[sig].value = val;
+            self.store_register(sig, val);
+        }
+        for prim_idx in written_rams {
+            if let Some(node) = self.graph.prim_node[prim_idx] {
+                self.schedule(node);
+            }
         }
-        self.eval_comb()?;
+        self.settle()?;
+        self.record();
         Ok(())
     }
 
-    pub fn tock(&mut self) -> Result<(), Error> {
-        self.eval_comb()
-    }
-
     fn eval_seq_block(
         &self,
         stmts: &[SeqStmtRef],
@@ -396,6 +807,14 @@ impl Simulator {
                     let value = self.eval_expr(expr)?;
                     self.apply_to_updates(target, value, updates)?;
                 }
+                SeqStmtRef::AssignElement { array, index, expr } => {
+                    let (pos, width) = self.element_pos(*array, index)?;
+                    let value = Self::value_to_width(&self.eval_expr(expr)?, width);
+                    let out = updates
+                        .entry(*array)
+                        .or_insert_with(|| self.netlist.signals[*array].value.clone());
+                    out.set_slice(pos, &value);
+                }
                 SeqStmtRef::If(ifstmt) => {
                     if self.value_is_true(&self.eval_expr(&ifstmt.cond)?) {
                         self.eval_seq_block(&ifstmt.then_stmts, updates)?;
@@ -570,27 +989,65 @@ impl Simulator {
                 }
                 Err(Error::new("unsupported function call"))
             }
+            ExprRef::Element { array, index } => {
+                let (pos, width) = self.element_pos(*array, index)?;
+                Ok(Value {
+                    bits: self.netlist.signals[*array].value.slice(pos, width),
+                    kind: ValueKind::Bitwise,
+                })
+            }
+            ExprRef::Select { cond, then, otherwise } => {
+                let cond = self.eval_expr(cond)?;
+                let (then, otherwise) = (self.eval_expr(then)?, self.eval_expr(otherwise)?);
+                // Same width and kind whichever choice is taken
+                let width = then.bits.width().max(otherwise.bits.width());
+                let kind = if then.kind == otherwise.kind { then.kind } else { ValueKind::Bitwise };
+                let chosen = if self.value_is_true(&cond) { then } else { otherwise };
+                Ok(Value {
+                    bits: Self::value_to_width(&chosen, width),
+                    kind,
+                })
+            }
+        }
+    }
+
+    /// Bit position and width of the array element selected by `index`
+    fn element_pos(&self, array: usize, index: &ExprRef) -> Result<(usize, usize), Error> {
+        let sig = &self.netlist.signals[array];
+        let SignalType::Array { low, high, elem_width } = &sig.ty else {
+            return Err(Error::new(format!("{} is not an array", sig.name)));
+        };
+        let value = self.eval_expr(index)?;
+        let index = match value.kind {
+            ValueKind::Arithmetic => value.bits.resize_sign(64).to_u64_trunc() as i64,
+            _ => value.bits.to_u64_trunc() as i64,
+        };
+        match sig.ty.element_offset(index) {
+            Some(pos) => Ok((pos, *elem_width)),
+            None => Err(Error::new(format!(
+                "index {} out of range {} to {} for {}",
+                index, low, high, sig.name
+            ))),
         }
     }
 
     fn apply_target(&mut self, target: &TargetRef, value: Value) -> Result<bool, Error> {
-        let sig = self.netlist.signals[target.signal].clone();
+        let sig = &self.netlist.signals[target.signal];
         let new_bits = Self::value_to_width(&value, sig.width);
-        let mut changed = false;
-        if let Some(sel) = &target.sel {
+        let updated = if let Some(forced) = &self.forced[target.signal] {
+            forced.clone()
+        } else if let Some(sel) = &target.sel {
             let mut base = sig.value.clone();
-            self.write_slice(&sig, &mut base, sel, &new_bits)?;
-            if base != sig.value {
-                changed = true;
-                self.netlist.signals[target.signal].value = base;
-            }
+            self.write_slice(sig, &mut base, sel, &new_bits)?;
+            base
         } else {
-            if new_bits != sig.value {
-                changed = true;
-                self.netlist.signals[target.signal].value = new_bits.clone();
-            }
+            new_bits
+        };
+        if updated == sig.value {
+            return Ok(false);
         }
-        Ok(changed)
+        self.netlist.signals[target.signal].value = updated;
+        Ok(true)
     }
 
     fn apply_to_updates(
@@ -630,14 +1087,9 @@ impl Simulator {
                 Ok(BitVec::new(1, sig.value.get(pos)))
             }
             Selector::Range { msb, lsb, .. } => {
-                let lo = (*msb).min(*lsb);
-                let hi = (*msb).max(*lsb);
-                let mut bits = Vec::new();
-                for idx in lo..=hi {
-                    let pos = self.index_to_pos(sig, idx)?;
-                    bits.push(sig.value.get(pos));
-                }
-                Ok(BitVec::from_bits_lsb(bits))
+                let lo = self.index_to_pos(sig, (*msb).min(*lsb))?;
+                let hi = self.index_to_pos(sig, (*msb).max(*lsb))?;
+                Ok(sig.value.slice(lo, hi - lo + 1))
             }
         }
     }
@@ -655,14 +1107,9 @@ impl Simulator {
                 sig_bits.set(pos, value.get(0));
             }
             Selector::Range { msb, lsb, .. } => {
-                let lo = (*msb).min(*lsb);
-                let hi = (*msb).max(*lsb);
-                let width = (hi - lo + 1) as usize;
-                let v = value.resize_zero(width);
-                for (offset, idx) in (lo..=hi).enumerate() {
-                    let pos = self.index_to_pos(sig, idx)?;
-                    sig_bits.set(pos, v.get(offset));
-                }
+                let lo = self.index_to_pos(sig, (*msb).min(*lsb))?;
+                let hi = self.index_to_pos(sig, (*msb).max(*lsb))?;
+                sig_bits.set_slice(lo, &value.resize_zero(hi - lo + 1));
             }
         }
         Ok(())
@@ -677,3 +1124,462 @@ impl Simulator {
         Ok((idx - min) as usize)
     }
 }
+
+#[cfg(test)]
+impl Simulator {
+    /// Reference implementation: sweep every node until nothing changes
+    /// (the pre-levelization algorithm). Returns the number of evaluations.
+    fn settle_sweep(&mut self) -> Result<u64, Error> {
+        let mut evals = 0u64;
+        for _ in 0..self.max_comb_iters {
+            let mut changed = false;
+            for node in 0..self.graph.nodes.len() {
+                evals += 1;
+                if self.eval_node(node)? {
+                    changed = true;
+                }
+            }
+            if !changed {
+                self.worklist.clear();
+                self.queued.iter_mut().for_each(|q| *q = false);
+                return Ok(evals);
+            }
+        }
+        Err(Error::new("combinational logic did not converge"))
+    }
+}
+
+/// Maps each signal to the clock it is a plain copy of, following the
+/// port assigns that carry a clock down the hierarchy
+fn clock_roots(netlist: &Netlist) -> Vec<usize> {
+    let mut source: Vec<usize> = (0..netlist.signals.len()).collect();
+    for assign in &netlist.assigns {
+        if let (None, ExprRef::Target(TargetRef { signal, sel: None })) = (&assign.target.sel, &assign.expr) {
+            source[assign.target.signal] = *signal;
+        }
+    }
+    (0..source.len())
+        .map(|mut id| {
+            // Bounded walk: alias cycles are comb loops, rejected at elaboration
+            for _ in 0..source.len() {
+                if source[id] == id {
+                    break;
+                }
+                id = source[id];
+            }
+            id
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Design;
+    use crate::elab::elaborate;
+    use crate::library::{resolve, SearchPath};
+    use crate::parser::parse_str;
+
+    /// 32-bit ripple-carry adder built only from nand2 gates
+    fn ripple_adder_hdl() -> String {
+        let mut hdl = String::from(
+            r#"
+entity FullAdder is
+  port(a : in bit; b : in bit; cin : in bit; s : out bit; cout : out bit);
+end entity;
+
+architecture rtl of FullAdder is
+  signal t1, t2, t3, t4, t5, t6, t7 : bit;
+begin
+  g1: nand2 port map (a => a, b => b, y => t1);
+  g2: nand2 port map (a => a, b => t1, y => t2);
+  g3: nand2 port map (a => b, b => t1, y => t3);
+  g4: nand2 port map (a => t2, b => t3, y => t4);
+  g5: nand2 port map (a => t4, b => cin, y => t5);
+  g6: nand2 port map (a => t4, b => t5, y => t6);
+  g7: nand2 port map (a => cin, b => t5, y => t7);
+  g8: nand2 port map (a => t6, b => t7, y => s);
+  g9: nand2 port map (a => t1, b => t5, y => cout);
+end architecture;
+
+entity Ripple32 is
+  port(a : in bits(31 downto 0); b : in bits(31 downto 0); cin : in bit; s : out bits(31 downto 0); cout : out bit);
+end entity;
+
+architecture rtl of Ripple32 is
+  signal c : bits(32 downto 1);
+begin
+  cout <= c(32);
+"#,
+        );
+        // Declared MSB first: the worst case for a netlist-order sweep
+        for i in (0..32).rev() {
+            let cin = if i == 0 { "cin".to_string() } else { format!("c({})", i) };
+            hdl.push_str(&format!(
+                "  fa{i}: FullAdder port map (a => a({i}), b => b({i}), cin => {cin}, s => s({i}), cout => c({n}));\n",
+                i = i,
+                cin = cin,
+                n = i + 1
+            ));
+        }
+        hdl.push_str("end architecture;\n");
+        hdl
+    }
+
+    fn alu32_design() -> Design {
+        let sources = [
+            include_str!("../../hdl_lib/gates/Nand2.hdl"),
+            include_str!("../../hdl_lib/gates/Not1.hdl"),
+            include_str!("../../hdl_lib/gates/And2.hdl"),
+            include_str!("../../hdl_lib/gates/Or2.hdl"),
+            include_str!("../../hdl_lib/gates/Xor2.hdl"),
+            include_str!("../../hdl_lib/gates/Mux2.hdl"),
+            include_str!("../../hdl_lib/arith/Alu32.hdl"),
+        ];
+        let mut design = Design::default();
+        for src in sources {
+            design.append(parse_str(src).unwrap());
+        }
+        design
+    }
+
+    /// Drive the same stimulus through the event-driven and the sweeping
+    /// simulator, check they agree, and return (event evals, sweep evals).
+    fn compare(design: &Design, top: &str, stimulus: &[Vec<(&str, u64)>]) -> (u64, u64) {
+        let netlist = elaborate(design, top).unwrap();
+        let mut fast = Simulator::new(netlist.clone());
+        let mut slow = Simulator::new(netlist);
+        fast.eval_comb().unwrap();
+        slow.settle_sweep().unwrap();
+        let start_fast = fast.comb_evals();
+        let mut sweep_evals = 0;
+        for step in stimulus {
+            for (name, value) in step {
+                fast.set_signal(name, BitVec::from_u64(64, *value)).unwrap();
+                slow.set_signal(name, BitVec::from_u64(64, *value)).unwrap();
+            }
+            fast.eval_comb().unwrap();
+            sweep_evals += slow.settle_sweep().unwrap();
+            assert_eq!(fast.dump_signals(), slow.dump_signals());
+        }
+        (fast.comb_evals() - start_fast, sweep_evals)
+    }
+
+    #[test]
+    fn test_levelized_ripple_adder_matches_sweep() {
+        let design = parse_str(&ripple_adder_hdl()).unwrap();
+        let stimulus = vec![
+            vec![("a", 0xFFFF_FFFF), ("b", 0)],
+            vec![("b", 1)],
+            vec![("a", 0x1234_5678), ("b", 0x0FED_CBA9)],
+            vec![("a", 0x8000_0000)],
+        ];
+        let (fast, slow) = compare(&design, "Ripple32", &stimulus);
+        let netlist = elaborate(&design, "Ripple32").unwrap();
+        let nodes = (netlist.assigns.len() + netlist.primitives.len()) as u64;
+        // Each step re-evaluates at most the fanout cone, i.e. each node once
+        assert!(fast <= nodes * stimulus.len() as u64);
+        assert!(fast * 10 < slow, "event-driven {} vs sweep {}", fast, slow);
+    }
+
+    #[test]
+    fn test_levelized_alu32_matches_sweep() {
+        let design = alu32_design();
+        let stimulus = vec![
+            vec![("a", 7), ("b", 5), ("op", 0)],
+            vec![("op", 1)],
+            vec![("a", 0xFFFF_FFFF), ("b", 1), ("op", 0)],
+            vec![("op", 2)],
+            vec![("op", 3)],
+            vec![("a", 0x8000_0000)],
+        ];
+        let (fast, slow) = compare(&design, "Alu32", &stimulus);
+        assert!(fast * 10 < slow, "event-driven {} vs sweep {}", fast, slow);
+    }
+
+    #[test]
+    fn test_unstable_loop_hits_the_budget() {
+        let hdl = r#"
+entity Osc is
+  port(x : in bit; y : out bit);
+end entity;
+
+architecture rtl of Osc is
+  signal a : bit;
+begin
+  a <= x;
+  y <= a;
+end architecture;
+"#;
+        // Netlists built without `elaborate` may hold a loop: a <= not a
+        let mut netlist = elaborate(&parse_str(hdl).unwrap(), "Osc").unwrap();
+        let target = netlist.assigns[0].target.clone();
+        netlist.assigns[0].expr = ExprRef::Unary {
+            op: crate::ast::UnaryOp::Not,
+            expr: Box::new(ExprRef::Target(target)),
+        };
+        let mut sim = Simulator::new(netlist);
+        sim.set_max_comb_iters(10);
+        let err = sim.eval_comb().unwrap_err();
+        assert_eq!(err.message, "combinational logic did not converge");
+        assert!(sim.comb_evals() <= 10 * 2);
+        // The worklist is left empty: the next evaluation starts over
+        assert!(sim.eval_comb().is_ok());
+    }
+
+    #[test]
+    fn test_hierarchical_signal_paths() {
+        let design = parse_str(&ripple_adder_hdl()).unwrap();
+        let mut sim = Simulator::new(elaborate(&design, "Ripple32").unwrap());
+        sim.set_signal("a", BitVec::from_u64(32, 1)).unwrap();
+        sim.set_signal("b", BitVec::from_u64(32, 1)).unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.instances().len(), 1 + 32 * 10);
+        assert_eq!(sim.instance_children("").unwrap()[0].name, "fa31");
+        assert_eq!(sim.resolve_signal("fa0", "cout").unwrap(), "fa0/cout_out");
+        assert_eq!(sim.get_signal("fa0/cout").unwrap().to_u64_trunc(), 1);
+        assert_eq!(sim.get_signal("fa1/cin").unwrap().to_u64_trunc(), 1);
+        assert_eq!(sim.get_signal("c").unwrap().to_u64_trunc(), 1);
+        assert!(sim.get_signal("fa0/nope").is_err());
+    }
+
+    #[test]
+    fn test_force_release_and_breakpoints() {
+        let design = parse_str(
+            r#"
+entity Pc is
+  port(clk : in bit; pc : out bits(7 downto 0); odd : out bit);
+end entity;
+
+architecture rtl of Pc is
+  signal count, next_pc : bits(7 downto 0);
+begin
+  next_pc <= count + x"01";
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      count <= next_pc;
+    end if;
+  end process;
+  pc <= count;
+  odd <= count(0);
+end architecture;
+"#,
+        )
+        .unwrap();
+        let mut sim = Simulator::new(elaborate(&design, "Pc").unwrap());
+
+        let bp = sim.add_breakpoint("pc = 0x40").unwrap();
+        let outcome = sim.run_until_break(Some("clk"), 1000).unwrap();
+        assert_eq!(outcome, RunOutcome { cycles: 64, hits: vec![bp] });
+        sim.remove_breakpoint(bp).unwrap();
+        assert!(sim.remove_breakpoint(bp).is_err());
+
+        // A forced net overrides its driver, the register follows it
+        sim.force_signal("next_pc", BitVec::from_u64(8, 0xF0)).unwrap();
+        sim.tick_clock("clk").unwrap();
+        sim.tock_clock("clk").unwrap();
+        assert_eq!(sim.get_signal("pc").unwrap().to_u64_trunc(), 0xF0);
+        assert_eq!(sim.forced_signals(), vec!["next_pc".to_string()]);
+        sim.release_signal("next_pc").unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.get_signal("next_pc").unwrap().to_u64_trunc(), 0xF1);
+        assert!(sim.release_signal("next_pc").is_err());
+
+        // A forced register ignores its clock edges until released
+        sim.force_signal("count", BitVec::from_u64(8, 3)).unwrap();
+        sim.tick_clock("clk").unwrap();
+        assert_eq!(sim.get_signal("pc").unwrap().to_u64_trunc(), 3);
+        sim.release_all();
+        sim.tick_clock("clk").unwrap();
+        sim.tock_clock("clk").unwrap();
+        assert_eq!(sim.get_signal("pc").unwrap().to_u64_trunc(), 4);
+
+        let watch = sim.add_watchpoint("odd").unwrap();
+        let both = sim.add_breakpoint("pc(7 downto 4) == 0 and not odd").unwrap();
+        let outcome = sim.run_until_break(None, 10).unwrap();
+        assert_eq!(outcome, RunOutcome { cycles: 1, hits: vec![watch] });
+        assert_eq!(
+            sim.breakpoints(),
+            vec![
+                (watch, "watch odd".to_string()),
+                (both, "pc(7 downto 4) == 0 and not odd".to_string())
+            ]
+        );
+        sim.remove_breakpoint(watch).unwrap();
+        assert_eq!(sim.run_until_break(None, 10).unwrap(), RunOutcome { cycles: 1, hits: vec![both] });
+        sim.clear_breakpoints();
+        assert_eq!(sim.run_until_break(None, 5).unwrap(), RunOutcome { cycles: 5, hits: vec![] });
+        assert!(sim.breakpoints().is_empty());
+        assert_eq!(sim.add_watchpoint("odd").unwrap(), both + 1);
+        assert!(sim.remove_breakpoint(both).is_err());
+        assert!(sim.add_breakpoint("nope = 1").is_err());
+        assert!(sim.add_breakpoint("pc(8) = 1").is_err());
+    }
+
+    #[test]
+    fn test_sparse_memories_and_inspection() {
+        let design = parse_str(
+            r#"
+entity Soc is
+  port(clk : in bit; we : in bit; addr : in bits(31 downto 0); din : in bits(31 downto 0);
+       dout : out bits(31 downto 0); pc : in bits(7 downto 0); instr : out bits(15 downto 0));
+end entity;
+
+architecture rtl of Soc is
+begin
+  dmem: ram port map (clk => clk, we => we, addr => addr, din => din, dout => dout);
+  imem: rom port map (addr => pc, dout => instr);
+end architecture;
+"#,
+        )
+        .unwrap();
+        let mut sim = Simulator::new(elaborate(&design, "Soc").unwrap());
+        let memories = sim.memories();
+        assert_eq!(memories.len(), 2);
+        assert_eq!((memories[0].path.as_str(), memories[0].kind, memories[0].addr_width), ("dmem", MemoryKind::Ram, 32));
+        assert_eq!((memories[1].path.as_str(), memories[1].kind, memories[1].data_width), ("imem", MemoryKind::Rom, 16));
+
+        // 2^32 words, only the written ones are stored
+        sim.set_signal("we", BitVec::from_u64(1, 1)).unwrap();
+        sim.set_signal("addr", BitVec::from_u64(32, 0xFFFF_FFF0)).unwrap();
+        sim.set_signal("din", BitVec::from_u64(32, 0x1234_5678)).unwrap();
+        sim.tick_clock("clk").unwrap();
+        assert_eq!(sim.get_signal("dout").unwrap().to_u64_trunc(), 0x1234_5678);
+        assert_eq!(sim.read_memory("dmem", 0xFFFF_FFF0).unwrap().to_u64_trunc(), 0x1234_5678);
+        assert_eq!(sim.memory("dmem").unwrap().nonzero(0..u64::MAX).len(), 1);
+
+        // Writes from outside show up on the read port
+        sim.write_memory("dmem", 0xFFFF_FFF0, &BitVec::from_u64(32, 7)).unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.get_signal("dout").unwrap().to_u64_trunc(), 7);
+
+        sim.load_rom(0, &[0x34, 0x12, 0xCD, 0xAB]).unwrap();
+        sim.set_signal("pc", BitVec::from_u64(8, 1)).unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.get_signal("instr").unwrap().to_u64_trunc(), 0xABCD);
+        sim.load_rom_hex(0, "@1 0x00FF 0x0001").unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.get_signal("instr").unwrap().to_u64_trunc(), 0xFF);
+        assert_eq!(sim.memory("imem").unwrap().dump_hex(0..4), "@00\n1234\n00FF\n0001\n");
+
+        assert!(sim.read_memory("imem", 0x100).is_err());
+        assert!(sim.write_memory("dmem", 1 << 32, &BitVec::from_u64(32, 1)).is_err());
+        let err = sim.memory("cache").unwrap_err();
+        assert!(err.message.contains("memories: dmem, imem"));
+        assert!(sim.load_rom(1, &[]).is_err());
+    }
+
+    #[test]
+    fn test_array_register_file() {
+        let design = parse_str(
+            r#"
+entity Rf is
+  port(clk : in bit; we : in bit; wa : in bits(2 downto 0); wd : in bits(7 downto 0);
+       ra : in bits(2 downto 0); qa : out bits(7 downto 0); q0 : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Rf is
+  type regs_t is array (0 to 5) of bits(7 downto 0);
+  signal regs : regs_t;
+begin
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      if we = '1' then
+        regs(wa) <= wd;
+      end if;
+    end if;
+  end process;
+  qa <= regs(ra + 1);
+  q0 <= regs(0);
+end architecture;
+"#,
+        )
+        .unwrap();
+        let mut sim = Simulator::new(elaborate(&design, "Rf").unwrap());
+        assert_eq!(sim.get_signal("regs").unwrap().width(), 48);
+        sim.set_signal("we", BitVec::from_u64(1, 1)).unwrap();
+        for (addr, data) in [(0, 0x11), (3, 0x33), (5, 0x55)] {
+            sim.set_signal("wa", BitVec::from_u64(3, addr)).unwrap();
+            sim.set_signal("wd", BitVec::from_u64(8, data)).unwrap();
+            sim.tick_clock("clk").unwrap();
+        }
+        assert_eq!(sim.get_signal("regs").unwrap().to_u64_trunc(), 0x55_00_33_00_00_11);
+        assert_eq!(sim.get_signal("q0").unwrap().to_u64_trunc(), 0x11);
+        sim.set_signal("ra", BitVec::from_u64(3, 2)).unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.get_signal("qa").unwrap().to_u64_trunc(), 0x33);
+
+        // Elements by path, from outside
+        assert_eq!(sim.get_signal("regs(5)").unwrap().to_u64_trunc(), 0x55);
+        sim.set_signal("regs(4)", BitVec::from_u64(8, 0x44)).unwrap();
+        sim.set_signal("ra", BitVec::from_u64(3, 3)).unwrap();
+        sim.eval_comb().unwrap();
+        assert_eq!(sim.get_signal("qa").unwrap().to_u64_trunc(), 0x44);
+        assert_eq!(sim.get_signal("regs(6)").unwrap_err().message, "index 6 out of range 0 to 5 for regs");
+        assert_eq!(sim.get_signal("qa(0)").unwrap_err().message, "qa is not an array");
+
+        sim.set_signal("wa", BitVec::from_u64(3, 7)).unwrap();
+        assert_eq!(sim.tick_clock("clk").unwrap_err().message, "index 7 out of range 0 to 5 for regs");
+    }
+
+    #[test]
+    fn test_when_else_and_aggregates() {
+        let design = parse_str(
+            r#"
+entity Sel is
+  port(clk : in bit; op : in bits(1 downto 0); a : in bits(7 downto 0); s : in bit;
+       y : out bits(7 downto 0); ext : out bits(7 downto 0); q : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Sel is
+begin
+  y <= a when op = b"00" else
+       not a when op = b"01" else
+       (others => '1') when op = b"10" else
+       (others => '0');
+  ext <= (7 downto 4 => s) & a(3 downto 0);
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      q <= (others => a(7));
+    end if;
+  end process;
+end architecture;
+"#,
+        )
+        .unwrap();
+        let mut sim = Simulator::new(elaborate(&design, "Sel").unwrap());
+        sim.set_signal("a", BitVec::from_u64(8, 0x85)).unwrap();
+        sim.set_signal("s", BitVec::from_u64(1, 1)).unwrap();
+        for (op, y) in [(0, 0x85), (1, 0x7A), (2, 0xFF), (3, 0x00)] {
+            sim.set_signal("op", BitVec::from_u64(2, op)).unwrap();
+            sim.eval_comb().unwrap();
+            assert_eq!(sim.get_signal("y").unwrap().to_u64_trunc(), y, "op = {}", op);
+        }
+        assert_eq!(sim.get_signal("ext").unwrap().to_u64_trunc(), 0xF5);
+        sim.tick_clock("clk").unwrap();
+        assert_eq!(sim.get_signal("q").unwrap().to_u64_trunc(), 0xFF);
+    }
+
+    /// The single-cycle CPU of hdl_lib decoding a few instruction words:
+    /// each one only re-evaluates the logic it reaches
+    #[test]
+    fn test_levelized_hdl_lib_cpu_matches_sweep() {
+        let lib = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../hdl_lib");
+        let mut design = Design::default();
+        resolve(&mut design, &["CPU"], &mut SearchPath::new(vec![lib])).unwrap();
+        let stimulus = vec![
+            // MOV R0, #1; MOV R1, #1; MOV R0, #7 (from tests/T01, T02, T04)
+            vec![("instr_data", 0xE2A0_0001)],
+            vec![("instr_data", 0xE2A1_0001)],
+            vec![("instr_data", 0xE2A0_0007)],
+            // LDR R0, =0x20000 (tests/T03), then the loaded word changes
+            vec![("instr_data", 0xE521_E01C), ("mem_rdata", 0x2_0000)],
+            vec![("mem_rdata", 0x1234)],
+        ];
+        let (fast, slow) = compare(&design, "CPU", &stimulus);
+        assert!(fast * 10 < slow, "event-driven {} vs sweep {}", fast, slow);
+    }
+}
@@ -0,0 +1,581 @@
+//! Statistiques d'un design élaboré : nombre de portes par type et par
+//! sous-arbre d'instances, surface en équivalent NAND et chemin
+//! combinatoire le plus long entre registres.
+//!
+//! Les primitives sont comptées bit par bit (un `And2` de 8 bits compte 8
+//! portes). Une affectation est ramenée à des portes quand elle n'utilise
+//! que `not`, `and`, `or` et `xor` ; les autres opérateurs (`+`, `<`,
+//! `<<`...) et les process sont comptés à part comme logique
+//! comportementale, sans surface, et chaque opérateur compte pour un
+//! niveau dans le chemin critique.
+
+use crate::ast::{BinaryOp, Selector, UnaryOp};
+use crate::elab::{ExprRef, Netlist, PrimitiveNet, SignalType};
+use crate::graph::{target_span, BitSpan, CombGraph, CombNode};
+
+/// Type de porte élémentaire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GateKind {
+    Nand2,
+    Not1,
+    And2,
+    Or2,
+    Xor2,
+    Mux2,
+    Dff,
+}
+
+impl GateKind {
+    pub const ALL: [GateKind; 7] = [
+        GateKind::Nand2,
+        GateKind::Not1,
+        GateKind::And2,
+        GateKind::Or2,
+        GateKind::Xor2,
+        GateKind::Mux2,
+        GateKind::Dff,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GateKind::Nand2 => "nand2",
+            GateKind::Not1 => "not1",
+            GateKind::And2 => "and2",
+            GateKind::Or2 => "or2",
+            GateKind::Xor2 => "xor2",
+            GateKind::Mux2 => "mux2",
+            GateKind::Dff => "dff",
+        }
+    }
+
+    /// Nombre de NAND2 de la construction classique de la porte
+    pub fn nand_area(self) -> usize {
+        match self {
+            GateKind::Nand2 | GateKind::Not1 => 1,
+            GateKind::And2 => 2,
+            GateKind::Or2 => 3,
+            GateKind::Xor2 | GateKind::Mux2 => 4,
+            GateKind::Dff => 6,
+        }
+    }
+}
+
+/// Compteurs de portes d'un design ou d'un sous-arbre
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GateCounts {
+    gates: [usize; 7],
+    /// Bits des RAM et ROM (hors surface)
+    pub memory_bits: usize,
+    /// Opérateurs non logiques et process, non comptés en portes
+    pub behavioural: usize,
+}
+
+impl GateCounts {
+    pub fn get(&self, kind: GateKind) -> usize {
+        self.gates[kind as usize]
+    }
+
+    pub fn total(&self) -> usize {
+        self.gates.iter().sum()
+    }
+
+    /// Surface en équivalent NAND2
+    pub fn nand_area(&self) -> usize {
+        GateKind::ALL.iter().map(|&k| self.get(k) * k.nand_area()).sum()
+    }
+
+    fn add_gates(&mut self, kind: GateKind, count: usize) {
+        self.gates[kind as usize] += count;
+    }
+
+    fn add(&mut self, other: &GateCounts) {
+        for (mine, theirs) in self.gates.iter_mut().zip(other.gates) {
+            *mine += theirs;
+        }
+        self.memory_bits += other.memory_bits;
+        self.behavioural += other.behavioural;
+    }
+}
+
+/// Portes d'une instance et de toutes ses sous-instances
+#[derive(Clone, Debug)]
+pub struct InstanceStats {
+    pub path: String,
+    /// Entité ou primitive instanciée ; None pour un bloc generate
+    pub entity: Option<String>,
+    /// Profondeur dans la hiérarchie, 0 pour le top
+    pub level: usize,
+    /// Instance d'une primitive (feuille de la hiérarchie)
+    pub primitive: bool,
+    pub gates: GateCounts,
+}
+
+/// Étape du chemin critique : le noeud traversé et le signal qu'il écrit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathStep {
+    /// `chemin (primitive)` ou `assign chemin` pour une affectation
+    pub through: String,
+    pub signal: String,
+    /// Niveaux cumulés depuis le début du chemin
+    pub depth: usize,
+}
+
+/// Chemin combinatoire le plus long, en niveaux de portes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CriticalPath {
+    pub depth: usize,
+    /// Entrée ou sortie de registre où commence le chemin
+    pub from: Option<String>,
+    /// Sortie ou entrée de registre où il aboutit
+    pub to: Option<String>,
+    /// Une étape par niveau : le premier noeud qui atteint la profondeur
+    pub steps: Vec<PathStep>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DesignStats {
+    /// Une entrée par noeud de `Netlist::instances`, le top en premier
+    pub instances: Vec<InstanceStats>,
+    pub critical_path: CriticalPath,
+}
+
+impl DesignStats {
+    /// Compteurs du design entier
+    pub fn gates(&self) -> &GateCounts {
+        &self.instances[0].gates
+    }
+
+    /// Rapport texte : tableau des portes par instance jusqu'à `max_level`
+    /// (les primitives ne sont pas détaillées), puis le chemin critique
+    pub fn report(&self, max_level: usize) -> String {
+        let rows: Vec<&InstanceStats> = self
+            .instances
+            .iter()
+            .filter(|i| i.level <= max_level && !i.primitive)
+            .collect();
+        let label = |i: &InstanceStats| {
+            let name = if i.level == 0 { "(top)" } else { i.path.rsplit('/').next().unwrap_or("") };
+            format!("{}{}", "  ".repeat(i.level), name)
+        };
+        let name_width = rows.iter().map(|i| label(i).len()).max().unwrap_or(0).max("instance".len());
+        let entity_width = rows
+            .iter()
+            .map(|i| i.entity.as_deref().unwrap_or("-").len())
+            .max()
+            .unwrap_or(0)
+            .max("entity".len());
+
+        let mut out = format!("{:<nw$}  {:<ew$}", "instance", "entity", nw = name_width, ew = entity_width);
+        for kind in GateKind::ALL {
+            out.push_str(&format!(" {:>6}", kind.as_str()));
+        }
+        out.push_str(&format!(" {:>7} {:>7}\n", "total", "area"));
+        for i in rows {
+            out.push_str(&format!(
+                "{:<nw$}  {:<ew$}",
+                label(i),
+                i.entity.as_deref().unwrap_or("-"),
+                nw = name_width,
+                ew = entity_width
+            ));
+            for kind in GateKind::ALL {
+                out.push_str(&format!(" {:>6}", i.gates.get(kind)));
+            }
+            out.push_str(&format!(" {:>7} {:>7}\n", i.gates.total(), i.gates.nand_area()));
+        }
+
+        let gates = self.gates();
+        if gates.memory_bits > 0 {
+            out.push_str(&format!("memory: {} bits (not in area)\n", gates.memory_bits));
+        }
+        if gates.behavioural > 0 {
+            out.push_str(&format!(
+                "behavioural: {} operators/processes not counted as gates\n",
+                gates.behavioural
+            ));
+        }
+        let path = &self.critical_path;
+        out.push_str(&format!("critical path: {} levels", path.depth));
+        if let (Some(from), Some(to)) = (&path.from, &path.to) {
+            out.push_str(&format!(", {} -> {}", from, to));
+        }
+        out.push('\n');
+        for step in &path.steps {
+            out.push_str(&format!("  {:>4}  {}  via {}\n", step.depth, step.signal, step.through));
+        }
+        out
+    }
+}
+
+impl Netlist {
+    /// Compte les portes du design et cherche son chemin critique
+    pub fn design_stats(&self) -> DesignStats {
+        let mut own = vec![GateCounts::default(); self.instances.len()];
+        let mut primitive = vec![false; self.instances.len()];
+        for (i, prim) in self.primitives.iter().enumerate() {
+            primitive[self.primitive_instances[i]] = true;
+            let counts = &mut own[self.primitive_instances[i]];
+            let width = |t| target_span(t, &self.signals);
+            let bits = |span: BitSpan| span.hi - span.lo + 1;
+            match prim {
+                PrimitiveNet::Nand2 { y, .. } => counts.add_gates(GateKind::Nand2, bits(width(y))),
+                PrimitiveNet::Not1 { y, .. } => counts.add_gates(GateKind::Not1, bits(width(y))),
+                PrimitiveNet::And2 { y, .. } => counts.add_gates(GateKind::And2, bits(width(y))),
+                PrimitiveNet::Or2 { y, .. } => counts.add_gates(GateKind::Or2, bits(width(y))),
+                PrimitiveNet::Xor2 { y, .. } => counts.add_gates(GateKind::Xor2, bits(width(y))),
+                PrimitiveNet::Mux2 { y, .. } => counts.add_gates(GateKind::Mux2, bits(width(y))),
+                PrimitiveNet::Dff { q, .. } => counts.add_gates(GateKind::Dff, bits(width(q))),
+                PrimitiveNet::Ram { addr_width, data_width, .. }
+                | PrimitiveNet::Rom { addr_width, data_width, .. } => {
+                    let words = 1usize.checked_shl(*addr_width as u32).unwrap_or(usize::MAX);
+                    counts.memory_bits = counts.memory_bits.saturating_add(words.saturating_mul(*data_width));
+                }
+            }
+        }
+        for assign in &self.assigns {
+            self.count_expr(&assign.expr, &mut own[assign.scope]);
+        }
+        for process in &self.processes {
+            own[process.scope].behavioural += 1;
+        }
+
+        // Les enfants sont toujours créés après leur parent
+        let mut totals = own;
+        for id in (1..self.instances.len()).rev() {
+            if let Some(parent) = self.instances[id].parent {
+                let child = totals[id].clone();
+                totals[parent].add(&child);
+            }
+        }
+        let instances = self
+            .instances
+            .iter()
+            .zip(totals)
+            .enumerate()
+            .map(|(id, (node, gates))| InstanceStats {
+                path: node.path.clone(),
+                entity: node.entity.clone(),
+                level: if node.path.is_empty() { 0 } else { node.path.split('/').count() },
+                primitive: primitive[id],
+                gates,
+            })
+            .collect();
+        DesignStats {
+            instances,
+            critical_path: self.critical_path(),
+        }
+    }
+
+    /// Portes et opérateurs comportementaux d'une expression
+    fn count_expr(&self, expr: &ExprRef, counts: &mut GateCounts) {
+        match expr {
+            ExprRef::Literal(_) | ExprRef::Target(_) => {}
+            ExprRef::Unary { op, expr: inner } => {
+                match op {
+                    UnaryOp::Not => counts.add_gates(GateKind::Not1, self.expr_width(inner)),
+                    UnaryOp::Neg => counts.behavioural += 1,
+                }
+                self.count_expr(inner, counts);
+            }
+            ExprRef::Binary { op, left, right } => {
+                let width = self.expr_width(expr);
+                match op {
+                    BinaryOp::And => counts.add_gates(GateKind::And2, width),
+                    BinaryOp::Or => counts.add_gates(GateKind::Or2, width),
+                    BinaryOp::Xor => counts.add_gates(GateKind::Xor2, width),
+                    BinaryOp::Concat => {}
+                    _ => counts.behavioural += 1,
+                }
+                self.count_expr(left, counts);
+                self.count_expr(right, counts);
+            }
+            ExprRef::Call { name, args } => {
+                if !is_resize(name) {
+                    counts.behavioural += 1;
+                }
+                for arg in args {
+                    self.count_expr(arg, counts);
+                }
+            }
+            // Lecture d'un tableau : un multiplexeur d'éléments
+            ExprRef::Element { index, .. } => {
+                counts.behavioural += 1;
+                self.count_expr(index, counts);
+            }
+            // `when ... else` : un multiplexeur par bit
+            ExprRef::Select { cond, then, otherwise } => {
+                counts.add_gates(GateKind::Mux2, self.expr_width(expr));
+                self.count_expr(cond, counts);
+                self.count_expr(then, counts);
+                self.count_expr(otherwise, counts);
+            }
+        }
+    }
+
+    /// Largeur d'une expression, avec les règles du simulateur
+    fn expr_width(&self, expr: &ExprRef) -> usize {
+        match expr {
+            ExprRef::Literal(value) => value.bits.width(),
+            ExprRef::Target(target) => match &target.sel {
+                None => self.signals[target.signal].width,
+                Some(Selector::Index(_)) => 1,
+                Some(Selector::Range { msb, lsb, .. }) => (msb - lsb).unsigned_abs() as usize + 1,
+            },
+            ExprRef::Unary { expr, .. } => self.expr_width(expr),
+            ExprRef::Binary { op, left, right } => match op {
+                BinaryOp::Concat => self.expr_width(left) + self.expr_width(right),
+                BinaryOp::Shl | BinaryOp::Shr => self.expr_width(left),
+                BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 1,
+                BinaryOp::And | BinaryOp::Or | BinaryOp::Xor | BinaryOp::Add | BinaryOp::Sub => {
+                    self.expr_width(left).max(self.expr_width(right))
+                }
+            },
+            ExprRef::Call { name, args } => match (is_resize(name), args.get(1)) {
+                (true, Some(ExprRef::Literal(size))) => size.bits.to_u64_trunc() as usize,
+                _ => args.iter().map(|a| self.expr_width(a)).max().unwrap_or(0),
+            },
+            ExprRef::Element { array, .. } => match self.signals[*array].ty {
+                SignalType::Array { elem_width, .. } => elem_width,
+                _ => self.signals[*array].width,
+            },
+            ExprRef::Select { then, otherwise, .. } => self.expr_width(then).max(self.expr_width(otherwise)),
+        }
+    }
+
+    /// Chemin le plus long du graphe combinatoire ; un noeud pèse un niveau
+    /// par primitive, et par opérateur traversé pour une affectation
+    fn critical_path(&self) -> CriticalPath {
+        let graph = CombGraph::build(self);
+        let weight: Vec<usize> = graph
+            .nodes
+            .iter()
+            .map(|node| match node {
+                CombNode::Assign(i) => expr_levels(&self.assigns[*i].expr),
+                CombNode::Primitive(_) => 1,
+            })
+            .collect();
+        // Profondeur en entrée puis en sortie de chaque noeud, et son
+        // prédécesseur le plus profond
+        let mut depth = vec![0; graph.nodes.len()];
+        let mut pred: Vec<Option<usize>> = vec![None; graph.nodes.len()];
+        for &node in &graph.order {
+            depth[node] += weight[node];
+            for &next in &graph.succ[node] {
+                if pred[next].is_none() || depth[node] > depth[next] {
+                    depth[next] = depth[node];
+                    pred[next] = Some(node);
+                }
+            }
+        }
+        let Some(end) = (0..graph.nodes.len()).max_by_key(|&n| (depth[n], graph.rank[n])) else {
+            return CriticalPath::default();
+        };
+
+        let mut chain = vec![end];
+        while let Some(p) = pred[*chain.last().unwrap_or(&end)] {
+            chain.push(p);
+        }
+        chain.reverse();
+        let first = chain[0];
+        let from = graph.reads[first]
+            .iter()
+            .find(|span| graph.drivers[span.signal].is_empty())
+            .or_else(|| graph.reads[first].first())
+            .map(|span| self.span_name(span));
+        let steps = chain
+            .iter()
+            .map(|&node| PathStep {
+                through: match graph.nodes[node] {
+                    CombNode::Assign(i) => {
+                        let scope = &self.instances[self.assigns[i].scope].path;
+                        if scope.is_empty() {
+                            "assign".to_string()
+                        } else {
+                            format!("assign in {}", scope)
+                        }
+                    }
+                    CombNode::Primitive(i) => {
+                        let inst = &self.instances[self.primitive_instances[i]];
+                        format!("{} ({})", inst.path, inst.entity.as_deref().unwrap_or(""))
+                    }
+                },
+                signal: self.span_name(&graph.writes[node]),
+                depth: depth[node],
+            })
+            .collect::<Vec<_>>();
+        let to = steps.last().map(|step| step.signal.clone());
+        let mut kept: Vec<PathStep> = Vec::new();
+        // Les fils (profondeur inchangée) n'apportent rien au rapport
+        for step in steps {
+            if step.depth > kept.last().map_or(0, |last| last.depth) {
+                kept.push(step);
+            }
+        }
+        CriticalPath {
+            depth: depth[end],
+            from,
+            to,
+            steps: kept,
+        }
+    }
+
+    /// `a`, `a(3)` ou `a(7 downto 4)` selon les bits désignés
+    fn span_name(&self, span: &BitSpan) -> String {
+        let sig = &self.signals[span.signal];
+        if span.lo == 0 && span.hi + 1 == sig.width {
+            return sig.name.clone();
+        }
+        let base = sig.lsb.min(sig.msb);
+        let (lo, hi) = (base + span.lo as i64, base + span.hi as i64);
+        if lo == hi {
+            format!("{}({})", sig.name, lo)
+        } else {
+            format!("{}({} downto {})", sig.name, hi, lo)
+        }
+    }
+}
+
+fn is_resize(name: &str) -> bool {
+    name.eq_ignore_ascii_case("resize") || name.eq_ignore_ascii_case("sresize")
+}
+
+/// Niveaux de portes d'une expression (0 pour un fil ou une concaténation)
+fn expr_levels(expr: &ExprRef) -> usize {
+    match expr {
+        ExprRef::Literal(_) | ExprRef::Target(_) => 0,
+        ExprRef::Unary { expr, .. } => 1 + expr_levels(expr),
+        ExprRef::Binary { op: BinaryOp::Concat, left, right } => expr_levels(left).max(expr_levels(right)),
+        ExprRef::Binary { left, right, .. } => 1 + expr_levels(left).max(expr_levels(right)),
+        ExprRef::Call { name, args } => {
+            let inner = args.iter().map(expr_levels).max().unwrap_or(0);
+            if is_resize(name) {
+                inner
+            } else {
+                1 + inner
+            }
+        }
+        ExprRef::Element { index, .. } => 1 + expr_levels(index),
+        ExprRef::Select { cond, then, otherwise } => {
+            1 + expr_levels(cond).max(expr_levels(then)).max(expr_levels(otherwise))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elab::elaborate;
+    use crate::parser::parse_str;
+
+    const ADDERS: &str = r#"
+entity FullAdder is
+  port(a : in bit; b : in bit; cin : in bit; s : out bit; cout : out bit);
+end entity;
+
+architecture rtl of FullAdder is
+  signal p, g, t : bit;
+begin
+  xp: Xor2 port map (a => a, b => b, y => p);
+  xs: Xor2 port map (a => p, b => cin, y => s);
+  ag: And2 port map (a => a, b => b, y => g);
+  at: And2 port map (a => p, b => cin, y => t);
+  oc: Or2 port map (a => g, b => t, y => cout);
+end architecture;
+
+entity Ripple4 is
+  port(a : in bits(3 downto 0); b : in bits(3 downto 0); cin : in bit;
+       s : out bits(3 downto 0); cout : out bit);
+end entity;
+
+architecture rtl of Ripple4 is
+  signal c : bits(4 downto 0);
+begin
+  c(0) <= cin;
+  g: for i in 0 to 3 generate
+    fa: FullAdder port map (a => a(i), b => b(i), cin => c(i), s => s(i), cout => c(i + 1));
+  end generate;
+  cout <= c(4);
+end architecture;
+
+entity Lookahead4 is
+  port(a : in bits(3 downto 0); b : in bits(3 downto 0); cin : in bit;
+       s : out bits(3 downto 0); cout : out bit);
+end entity;
+
+architecture rtl of Lookahead4 is
+  signal p, g : bits(3 downto 0);
+  signal c : bits(4 downto 0);
+begin
+  p <= a xor b;
+  g <= a and b;
+  c(0) <= cin;
+  c(1) <= g(0) or (p(0) and cin);
+  c(2) <= (g(1) or (p(1) and g(0))) or (p(1) and p(0) and cin);
+  c(3) <= (g(2) or (p(2) and g(1))) or ((p(2) and p(1) and g(0)) or (p(2) and p(1) and p(0) and cin));
+  c(4) <= (g(3) or (p(3) and g(2))) or ((p(3) and p(2) and g(1)) or (p(3) and p(2) and p(1) and (g(0) or (p(0) and cin))));
+  s <= p xor c(3 downto 0);
+  cout <= c(4);
+end architecture;
+
+entity Acc is
+  port(clk : in bit; d : in bits(7 downto 0); q : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Acc is
+  signal r, n : bits(7 downto 0);
+begin
+  n <= r + d;
+  reg: Dff port map (clk => clk, d => n, q => r);
+  q <= not r;
+end architecture;
+"#;
+
+    fn stats(top: &str) -> DesignStats {
+        elaborate(&parse_str(ADDERS).unwrap(), top).unwrap().design_stats()
+    }
+
+    #[test]
+    fn test_gate_counts_per_subtree() {
+        let s = stats("Ripple4");
+        let total = s.gates();
+        assert_eq!(
+            GateKind::ALL.map(|k| total.get(k)),
+            [0, 0, 8, 4, 8, 0, 0]
+        );
+        assert_eq!(total.nand_area(), 8 * 2 + 4 * 3 + 8 * 4);
+        assert_eq!(total.behavioural, 0);
+
+        let fa = s.instances.iter().find(|i| i.path == "g(2)/fa").unwrap();
+        assert_eq!((fa.entity.as_deref(), fa.level, fa.gates.total()), (Some("FullAdder"), 2, 5));
+        let gen = s.instances.iter().find(|i| i.path == "g(2)").unwrap();
+        assert_eq!(gen.gates, fa.gates);
+        let report = s.report(1);
+        assert!(report.contains("\n  g(0)"), "{}", report);
+        assert!(!report.contains("FullAdder"), "{}", report);
+    }
+
+    #[test]
+    fn test_critical_path_ripple_vs_lookahead() {
+        let ripple = stats("Ripple4").critical_path;
+        // p0, puis and + or par étage de retenue
+        assert_eq!(ripple.depth, 9);
+        assert_eq!(ripple.from.as_deref(), Some("a(0)"));
+        assert_eq!(ripple.to.as_deref(), Some("cout"));
+        assert_eq!(ripple.steps.len(), 9);
+        assert_eq!(ripple.steps[0].through, "g(0)/fa/xp (xor2)");
+
+        let lookahead = stats("Lookahead4");
+        assert!(lookahead.critical_path.depth < ripple.depth);
+        assert!(lookahead.gates().total() > 20);
+        assert_eq!(lookahead.gates().behavioural, 0);
+    }
+
+    #[test]
+    fn test_registers_cut_paths() {
+        let s = stats("Acc");
+        assert_eq!(s.gates().get(GateKind::Dff), 8);
+        assert_eq!(s.gates().get(GateKind::Not1), 8);
+        assert_eq!(s.gates().behavioural, 1);
+        assert_eq!(s.critical_path.depth, 1);
+    }
+}
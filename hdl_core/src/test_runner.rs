@@ -2,8 +2,10 @@
 //! Parse et exécute les fichiers de test .tst pour les circuits HDL
 
 use crate::ast::Design;
-use crate::elab::elaborate;
+use crate::elab::{elaborate, SignalType};
 use crate::error::Error;
+use crate::hier::ScopeSignalKind;
+use crate::mem::is_memory_file;
 use crate::parser::parse_str;
 use crate::sim::Simulator;
 use crate::value::BitVec;
@@ -17,6 +19,52 @@ pub struct TestResult {
     pub passed_checks: usize,
     pub errors: Vec<String>,
     pub failures: Vec<TestFailure>,
+    /// Trace VCD de la simulation (si demandée dans les options ou par `vcd`)
+    pub vcd: Option<String>,
+    /// Fichier demandé par la commande `vcd <fichier>` du script
+    pub vcd_file: Option<String>,
+    /// Table produite par `output-list` et `output` (contenu du fichier `.out`)
+    pub output: Option<String>,
+    /// Fichiers produits par `memdump`, dans l'ordre des commandes
+    pub dumps: Vec<(String, Vec<u8>)>,
+}
+
+/// Options d'exécution d'un test
+#[derive(Debug, Clone, Default)]
+pub struct TestOptions {
+    /// Enregistre une trace VCD (visualisable dans GTKWave)
+    pub vcd: bool,
+    /// Contenu de la table `.cmp` nommée par `compare-to`
+    pub compare: Option<String>,
+    /// Contenu des fichiers lus par `romload` et `memload`, par nom
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+/// Fichiers référencés par un script de test
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptFiles {
+    /// Entité testée (`load And` ou `load And.hdl`)
+    pub top: String,
+    /// Sources HDL à charger
+    pub load: Vec<String>,
+    /// Table attendue (`compare-to`)
+    pub compare_to: Option<String>,
+    /// Table produite (`output-file`)
+    pub output_file: Option<String>,
+    /// Images mémoire lues par `romload` et `memload`
+    pub inputs: Vec<String>,
+}
+
+/// Colonne d'un `output-list` : `nom%B1.8.1` (format, marge gauche,
+/// longueur, marge droite), comme les outils nand2tetris
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputColumn {
+    signal: String,
+    format: char,
+    pad_left: usize,
+    /// Largeur de la valeur ; None : largeur du signal
+    len: Option<usize>,
+    pad_right: usize,
 }
 
 /// Détail d'un échec de test
@@ -48,7 +96,7 @@ impl TestFailure {
 
     /// Formate l'échec pour affichage web (HTML)
     pub fn format_html(&self) -> String {
-        let mut s = format!("<div class=\"test-failure\">\n");
+        let mut s = String::from("<div class=\"test-failure\">\n");
         s += &format!("  <div class=\"failure-header\">❌ Ligne {} : Signal <code>{}</code></div>\n",
                      self.line_number, self.signal);
         s += &format!("  <div class=\"failure-expected\">Attendu : <code>{}</code></div>\n", self.expected);
@@ -67,102 +115,456 @@ impl TestFailure {
     }
 }
 
+/// Mémoire visée par `romload` ou `memload`
+#[derive(Debug, Clone)]
+enum MemoryRef {
+    /// ROM par numéro, dans l'ordre d'élaboration
+    Rom(usize),
+    /// Chemin de l'instance `ram` ou `rom` (`cpu/imem`)
+    Path(String),
+}
+
 /// Une commande de test
 #[derive(Debug, Clone)]
 enum TestCmd {
     Set { signal: String, value: String },
     Eval,
-    Tick,
-    Tock,
+    /// Déclare les horloges pilotées par un `tick`/`tock` sans argument
+    Clock { names: Vec<String> },
+    /// Front montant d'une horloge, ou de toutes les horloges déclarées
+    Tick { clock: Option<String> },
+    /// Front descendant d'une horloge, ou de toutes les horloges déclarées
+    Tock { clock: Option<String> },
+    /// tick puis tock
+    Step { clock: Option<String> },
+    /// Enregistre une trace VCD à écrire dans ce fichier
+    Vcd { path: String },
     Expect { signal: String, value: String },
     /// Load hex data into ROM (rom_index, hex_data)
     RomLoad { rom_index: usize, hex_data: String },
-    /// Repeat commands N times
-    Repeat { count: usize, commands: Vec<(usize, TestCmd)> },
+    /// Charge un fichier dans une mémoire (`romload <fichier>`, `memload`)
+    MemLoad { memory: MemoryRef, file: String },
+    /// Écrit le contenu d'une mémoire (adresses `start..start+count`)
+    MemDump { memory: String, file: String, range: Option<(String, String)> },
+    /// Affecte une variable du script
+    Let { name: String, value: String },
+    /// Répète un bloc `count` fois ; le compteur optionnel vaut 0..count-1
+    Repeat { count: String, counter: Option<String>, body: Vec<(usize, TestCmd)> },
+    /// Déclare les colonnes de la table de sortie et écrit son en-tête
+    OutputList { columns: Vec<OutputColumn> },
+    /// Ajoute une ligne à la table de sortie
+    Output,
+    /// Choisit les vecteurs d'entrée des `check`
+    Vectors { mode: VectorMode },
+    /// Pilote les entrées et compare les sorties à une référence
+    Check { outputs: Vec<String>, reference: CheckRef },
+    /// Maintient un signal à une valeur, quels que soient ses pilotes
+    Force { signal: String, value: String },
+    /// Rend un signal forcé (tous sans argument) à ses pilotes
+    Release { signal: Option<String> },
+    /// Point d'arrêt sur une condition (`pc = 0x40`)
+    Break { condition: String },
+    /// Point d'arrêt sur tout changement d'un signal
+    Watch { signal: String },
+    /// Cycles d'horloge jusqu'à un point d'arrêt, au plus `max_cycles`
+    Run { max_cycles: String },
+}
+
+/// Cycles d'un `run` sans limite explicite
+const RUN_MAX_CYCLES: &str = "10000";
+
+/// Vecteurs d'entrée d'un `check`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VectorMode {
+    /// Exhaustif jusqu'à 16 bits d'entrée, sinon 1000 vecteurs aléatoires
+    Auto,
+    Exhaustive,
+    Random { count: u64, seed: u64 },
+}
+
+/// Nombre maximal de bits d'entrée d'un `vectors exhaustive`
+const EXHAUSTIVE_MAX_BITS: usize = 24;
+
+/// Référence d'un `check`
+#[derive(Debug, Clone)]
+enum CheckRef {
+    /// `check y = a + b` : les entrées nommées dans l'expression sont pilotées
+    Expr(String),
+    /// `check y against Ref` : les entrées de l'entité de référence sont pilotées
+    Entity(String),
+}
+
+/// xorshift64*, déterministe pour que les contre-exemples se reproduisent
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn bits(&mut self, width: usize) -> BitVec {
+        let mut limb = 0;
+        BitVec::from_bits_lsb(
+            (0..width)
+                .map(|i| {
+                    if i % 64 == 0 {
+                        limb = self.next();
+                    }
+                    ((limb >> (i % 64)) & 1) as u8
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Fichiers nommés par les commandes `load`, `compare-to` et `output-file`
+pub fn script_files(script: &str) -> Result<ScriptFiles, Error> {
+    Ok(parse_test_script(script)?.0)
 }
 
 /// Parse un script de test
-fn parse_test_script(script: &str) -> Result<(String, Vec<(usize, TestCmd)>), Error> {
-    let mut commands = Vec::new();
-    let mut chip_name = String::new();
+fn parse_test_script(script: &str) -> Result<(ScriptFiles, Vec<(usize, TestCmd)>), Error> {
+    let mut files = ScriptFiles::default();
+    let mut statements = script
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !(line.starts_with('#') || line.starts_with("--"))
+        })
+        .flat_map(|(idx, line)| split_statements(line).into_iter().map(move |stmt| (idx + 1, stmt)));
+    let commands = parse_block(&mut statements, &mut files, None)?;
+    Ok((files, commands))
+}
 
-    for (line_idx, line) in script.lines().enumerate() {
-        let line_number = line_idx + 1;
-        let line = line.trim();
-        if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
-            continue;
+/// Découpe une ligne en ordres : `,` et `;` les séparent (syntaxe
+/// nand2tetris), `{` termine un ordre et `}` en est un à lui seul
+fn split_statements(line: &str) -> Vec<&str> {
+    let line = line.split("//").next().unwrap_or("");
+    let mut statements = Vec::new();
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            ',' | ';' => {
+                statements.push(&line[start..i]);
+                start = i + 1;
+            }
+            '{' => {
+                statements.push(&line[start..=i]);
+                start = i + 1;
+            }
+            '}' => {
+                statements.push(&line[start..i]);
+                statements.push("}");
+                start = i + 1;
+            }
+            _ => {}
         }
+    }
+    statements.push(&line[start..]);
+    statements.into_iter().map(str::trim).filter(|s| !s.is_empty()).collect()
+}
 
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.is_empty() {
-            continue;
-        }
+/// Parse les commandes jusqu'à la fin du script, ou jusqu'au `}` qui ferme
+/// le bloc `repeat` ouvert à la ligne `open`
+fn parse_block<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    files: &mut ScriptFiles,
+    open: Option<usize>,
+) -> Result<Vec<(usize, TestCmd)>, Error> {
+    let mut commands = Vec::new();
 
-        match parts[0].to_lowercase().as_str() {
-            "load" => {
-                if parts.len() >= 2 {
-                    chip_name = parts[1].to_string();
-                }
+    while let Some((line_number, line)) = lines.next() {
+        let err = |msg: &str| Error::new(format!("line {}: {}", line_number, msg));
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let cmd = match parts[0].to_lowercase().as_str() {
+            "}" if parts.len() == 1 => {
+                return match open {
+                    Some(_) => Ok(commands),
+                    None => Err(err("unmatched }")),
+                };
             }
-            "set" => {
-                if parts.len() >= 3 {
-                    commands.push((line_number, TestCmd::Set {
-                        signal: parts[1].to_string(),
-                        value: parts[2].to_string(),
-                    }));
+            "load" if parts.len() >= 2 => {
+                // load <Top> [fichiers...] ou load <Top.hdl>
+                if let Some(top) = parts[1].strip_suffix(".hdl") {
+                    files.top = top.to_string();
+                    files.load = parts[1..].iter().map(|s| s.to_string()).collect();
+                } else {
+                    files.top = parts[1].to_string();
+                    files.load = parts[2..].iter().map(|s| s.to_string()).collect();
                 }
+                continue;
+            }
+            "compare-to" if parts.len() == 2 => {
+                files.compare_to = Some(parts[1].to_string());
+                continue;
             }
-            "eval" => {
-                commands.push((line_number, TestCmd::Eval));
+            "output-file" if parts.len() == 2 => {
+                files.output_file = Some(parts[1].to_string());
+                continue;
             }
-            "tick" => {
-                commands.push((line_number, TestCmd::Tick));
+            "output-list" if parts.len() >= 2 => {
+                let columns = parts[1..]
+                    .iter()
+                    .map(|spec| parse_output_column(spec).ok_or_else(|| err(&format!("invalid output column {}", spec))))
+                    .collect::<Result<_, _>>()?;
+                TestCmd::OutputList { columns }
             }
-            "tock" => {
-                commands.push((line_number, TestCmd::Tock));
+            "output" if parts.len() == 1 => TestCmd::Output,
+            "vectors" if parts.len() >= 2 => {
+                let number = |s: &str| s.parse::<u64>().map_err(|_| err(&format!("invalid number {}", s)));
+                let mode = match parts[1..] {
+                    ["auto"] => VectorMode::Auto,
+                    ["exhaustive"] => VectorMode::Exhaustive,
+                    ["random", count] => VectorMode::Random { count: number(count)?, seed: 1 },
+                    ["random", count, seed] => VectorMode::Random { count: number(count)?, seed: number(seed)? },
+                    _ => return Err(err("usage: vectors auto | exhaustive | random <count> [seed]")),
+                };
+                TestCmd::Vectors { mode }
             }
-            "expect" => {
-                if parts.len() >= 3 {
-                    commands.push((line_number, TestCmd::Expect {
-                        signal: parts[1].to_string(),
-                        value: parts[2].to_string(),
-                    }));
+            "check" if parts.len() >= 4 => {
+                // check <sortie> = <expression> ou check <sorties...> against <Entité>
+                let usage = || err("usage: check <output> = <expression> | check <outputs...> against <entity>");
+                match parts.iter().position(|p| *p == "=" || *p == "against") {
+                    Some(2) if parts[2] == "=" => TestCmd::Check {
+                        outputs: vec![parts[1].to_string()],
+                        reference: CheckRef::Expr(parts[3..].join(" ")),
+                    },
+                    Some(at) if parts[at] == "against" && at > 1 && parts.len() == at + 2 => TestCmd::Check {
+                        outputs: parts[1..at].iter().map(|s| s.to_string()).collect(),
+                        reference: CheckRef::Entity(parts[at + 1].to_string()),
+                    },
+                    _ => return Err(usage()),
                 }
             }
-            "romload" => {
+            "set" if parts.len() >= 3 => TestCmd::Set {
+                signal: parts[1].to_string(),
+                value: parts[2..].join(" "),
+            },
+            "force" if parts.len() >= 3 => TestCmd::Force {
+                signal: parts[1].to_string(),
+                value: parts[2..].join(" "),
+            },
+            "release" if parts.len() <= 2 => TestCmd::Release { signal: parts.get(1).map(|s| s.to_string()) },
+            "break" if parts.len() >= 2 => TestCmd::Break { condition: parts[1..].join(" ") },
+            "watch" if parts.len() == 2 => TestCmd::Watch { signal: parts[1].to_string() },
+            "run" => TestCmd::Run {
+                max_cycles: match parts.len() {
+                    1 => RUN_MAX_CYCLES.to_string(),
+                    _ => parts[1..].join(" "),
+                },
+            },
+            "eval" => TestCmd::Eval,
+            "clock" if parts.len() >= 2 => TestCmd::Clock {
+                names: parts[1..].iter().map(|s| s.to_string()).collect(),
+            },
+            "tick" => TestCmd::Tick { clock: parts.get(1).map(|s| s.to_string()) },
+            "tock" => TestCmd::Tock { clock: parts.get(1).map(|s| s.to_string()) },
+            "step" => TestCmd::Step { clock: parts.get(1).map(|s| s.to_string()) },
+            "vcd" if parts.len() >= 2 => TestCmd::Vcd { path: parts[1].to_string() },
+            "expect" if parts.len() >= 3 => TestCmd::Expect {
+                signal: parts[1].to_string(),
+                value: parts[2..].join(" "),
+            },
+            "romload" if parts.len() <= 3 && parts.last().is_some_and(|p| is_memory_file(p)) => {
+                // romload [rom_index | instance] <file.a32b | file.hex | file.bin>
+                let memory = match parts.len() {
+                    2 => MemoryRef::Rom(0),
+                    _ => match parts[1].parse::<usize>() {
+                        Ok(index) => MemoryRef::Rom(index),
+                        Err(_) => MemoryRef::Path(parts[1].to_string()),
+                    },
+                };
+                let file = parts[parts.len() - 1].to_string();
+                files.inputs.push(file.clone());
+                TestCmd::MemLoad { memory, file }
+            }
+            "romload" if parts.len() >= 2 => {
                 // romload <rom_index> <hex_values...>
                 // or romload <hex_values...> (defaults to rom 0)
-                if parts.len() >= 2 {
-                    let (rom_index, hex_start) = if parts[1].parse::<usize>().is_ok() && parts.len() > 2 {
-                        (parts[1].parse::<usize>().unwrap_or(0), 2)
-                    } else {
-                        (0, 1)
-                    };
-                    let hex_data = parts[hex_start..].join("\n");
-                    commands.push((line_number, TestCmd::RomLoad { rom_index, hex_data }));
+                let (rom_index, hex_start) = if parts[1].parse::<usize>().is_ok() && parts.len() > 2 {
+                    (parts[1].parse::<usize>().unwrap_or(0), 2)
+                } else {
+                    (0, 1)
+                };
+                let hex_data = parts[hex_start..].join("\n");
+                TestCmd::RomLoad { rom_index, hex_data }
+            }
+            "memload" if parts.len() == 3 => {
+                files.inputs.push(parts[2].to_string());
+                TestCmd::MemLoad {
+                    memory: MemoryRef::Path(parts[1].to_string()),
+                    file: parts[2].to_string(),
                 }
             }
-            "repeat" => {
-                // repeat <count> { commands... }
-                // For simplicity, just parse "repeat N" and execute next commands N times
-                if parts.len() >= 2 {
-                    if let Ok(count) = parts[1].parse::<usize>() {
-                        commands.push((line_number, TestCmd::Repeat { count, commands: Vec::new() }));
-                    }
+            "memload" => return Err(err("usage: memload <instance> <file>")),
+            "memdump" if parts.len() == 3 || parts.len() == 5 => TestCmd::MemDump {
+                memory: parts[1].to_string(),
+                file: parts[2].to_string(),
+                range: (parts.len() == 5).then(|| (parts[3].to_string(), parts[4].to_string())),
+            },
+            "let" if parts.len() >= 3 => {
+                // let <nom> [=] <expression>
+                let rest = if parts[2] == "=" { &parts[3..] } else { &parts[2..] };
+                if !is_identifier(parts[1]) || rest.is_empty() {
+                    return Err(err("usage: let <name> = <expression>"));
                 }
+                TestCmd::Let { name: parts[1].to_string(), value: rest.join(" ") }
+            }
+            "repeat" if parts.len() >= 2 => {
+                // repeat <n> [compteur] { ... } ; sans bloc : n cycles tick/tock
+                let block = parts.last() == Some(&"{");
+                let args = &parts[1..parts.len() - block as usize];
+                let counter = match args {
+                    [_] => None,
+                    [_, name] if block && is_identifier(name) => Some(name.to_string()),
+                    _ => return Err(err("usage: repeat <count> [counter] { ... }")),
+                };
+                let body = if block {
+                    parse_block(lines, files, Some(line_number))?
+                } else {
+                    vec![(line_number, TestCmd::Step { clock: None })]
+                };
+                TestCmd::Repeat { count: args[0].to_string(), counter, body }
+            }
+            "load" | "set" | "clock" | "vcd" | "expect" | "romload" | "let" | "repeat" | "output-list" | "vectors"
+            | "check" | "force" | "break" => {
+                return Err(err(&format!("missing argument for {}", parts[0])));
             }
-            _ => {
-                // Ignorer les commandes inconnues
+            "compare-to" | "output-file" | "output" | "release" | "watch" | "memdump" => {
+                return Err(err(&format!("wrong number of arguments for {}", parts[0])));
             }
+            _ => return Err(err(&format!("unknown command {}", parts[0]))),
+        };
+        commands.push((line_number, cmd));
+    }
+
+    match open {
+        Some(line_number) => Err(Error::new(format!("line {}: repeat block is never closed", line_number))),
+        None => Ok(commands),
+    }
+}
+
+/// `nom`, `nom%X` ou `nom%X<gauche>.<longueur>.<droite>` avec X = B, D, X
+/// ou S (symbolique : littéral d'énumération, entier en décimal)
+fn parse_output_column(spec: &str) -> Option<OutputColumn> {
+    let (signal, format) = spec.split_once('%').unwrap_or((spec, "B"));
+    let mut chars = format.chars();
+    let kind = chars.next()?.to_ascii_uppercase();
+    if signal.is_empty() || !matches!(kind, 'B' | 'D' | 'X' | 'S') {
+        return None;
+    }
+    let sizes = chars.as_str();
+    let (pad_left, len, pad_right) = if sizes.is_empty() {
+        (1, None, 1)
+    } else {
+        let nums: Vec<usize> = sizes.split('.').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+        match nums[..] {
+            [left, len, right] if len > 0 => (left, Some(len), right),
+            _ => return None,
         }
+    };
+    Some(OutputColumn { signal: signal.to_string(), format: kind, pad_left, len, pad_right })
+}
+
+impl OutputColumn {
+    fn width(&self, signal_width: usize, ty: &SignalType) -> usize {
+        let len = self.len.unwrap_or(match (self.format, ty) {
+            ('S', SignalType::Enum { literals, .. }) => literals.iter().map(String::len).max().unwrap_or(1),
+            ('S', SignalType::Integer { low, high }) => low.to_string().len().max(high.to_string().len()),
+            ('X', _) => signal_width.div_ceil(4),
+            ('D', _) => {
+                // Assez large pour la valeur la plus négative
+                let mut min = BitVec::new(signal_width, 0);
+                min.set(signal_width.saturating_sub(1), 1);
+                format_decimal(&min).len()
+            }
+            _ => signal_width,
+        });
+        self.pad_left + len + self.pad_right
+    }
+
+    /// Nom centré dans la colonne (l'espace en trop va à droite)
+    fn header(&self, signal_width: usize, ty: &SignalType) -> String {
+        let width = self.width(signal_width, ty);
+        let name: String = self.signal.chars().take(width).collect();
+        let left = (width - name.chars().count()) / 2;
+        format!("{}{:<w$}", " ".repeat(left), name, w = width - left)
+    }
+
+    fn cell(&self, value: &BitVec, ty: &SignalType) -> String {
+        let len = self.width(value.width(), ty) - self.pad_left - self.pad_right;
+        let text = match (self.format, ty.display(value)) {
+            ('S', Some(symbol)) => format!("{:<len$}", symbol),
+            ('B' | 'S', _) => (0..len).rev().map(|i| if i < value.width() && value.get(i) == 1 { '1' } else { '0' }).collect(),
+            ('X', _) => hex_digits(value, len),
+            _ => format!("{:>len$}", format_decimal(value)),
+        };
+        format!("{}{}{}", " ".repeat(self.pad_left), text, " ".repeat(self.pad_right))
     }
+}
+
+/// Les `len` chiffres hexadécimaux de poids faible, en majuscules
+fn hex_digits(value: &BitVec, len: usize) -> String {
+    (0..len)
+        .rev()
+        .map(|digit| {
+            let nibble = (0..4)
+                .map(|b| digit * 4 + b)
+                .filter(|&i| i < value.width())
+                .fold(0, |acc, i| acc | (value.get(i) as u32) << (i % 4));
+            char::from_digit(nibble, 16).unwrap_or('0').to_ascii_uppercase()
+        })
+        .collect()
+}
+
+/// `0x...` sur toute la largeur de la valeur
+fn hex(value: &BitVec) -> String {
+    format!("0x{}", hex_digits(value, value.width().div_ceil(4).max(1)))
+}
+
+/// Décimal signé (complément à 2) comme nand2tetris ; un bit seul vaut 0 ou 1
+fn format_decimal(value: &BitVec) -> String {
+    if value.width() <= 1 {
+        return value.to_u64_trunc().to_string();
+    }
+    (value.resize_sign(64).to_u64_trunc() as i64).to_string()
+}
+
+/// Cellules d'une ligne de table `| a | b |`, sans les espaces autour
+fn table_cells(line: &str) -> Vec<&str> {
+    let line = line.trim();
+    let inner = line.strip_prefix('|').unwrap_or(line);
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    inner.split('|').map(str::trim).collect()
+}
 
-    Ok((chip_name, commands))
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 /// Parse une valeur en BitVec
 fn parse_value(input: &str) -> Result<BitVec, Error> {
     let t = input.trim();
+    // Littéraux HDL: b"1010" et x"2A"
+    if t.len() >= 3 && t.ends_with('"') {
+        if let Some(inner) = t.strip_prefix("b\"") {
+            return BitVec::from_bits_msb(&inner[..inner.len() - 1]).map_err(|e| Error::new(e.to_string()));
+        }
+        if let Some(inner) = t.strip_prefix("x\"") {
+            return BitVec::from_hex_msb(&inner[..inner.len() - 1]).map_err(|e| Error::new(e.to_string()));
+        }
+    }
     if t.starts_with("0b") || t.starts_with("0B") {
         let inner = &t[2..];
         return BitVec::from_bits_msb(inner).map_err(|e| Error::new(e.to_string()));
@@ -179,101 +581,701 @@ fn parse_value(input: &str) -> Result<BitVec, Error> {
     Ok(BitVec::from_i64(width.max(1), val))
 }
 
+/// Évalue une expression entière du script : nombres (décimal, `0x`, `0b`),
+/// variables, opérateurs du C (`+ - * / % << >> & | ^ ~`, comparaisons
+/// valant 0 ou 1) et parenthèses
+fn eval_expr(input: &str, vars: &HashMap<String, i64>) -> Result<i64, Error> {
+    let tokens = tokenize_expr(input)?;
+    let mut pos = 0;
+    let value = expr_binary(&tokens, &mut pos, vars, 0)?;
+    if pos < tokens.len() {
+        return Err(Error::new(format!("invalid expression {}", input)));
+    }
+    Ok(value)
+}
+
+/// Noms utilisés par une expression, dans l'ordre, sans doublon
+fn expr_identifiers(input: &str) -> Result<Vec<String>, Error> {
+    let mut names: Vec<String> = Vec::new();
+    for token in tokenize_expr(input)? {
+        if let ExprToken::Var(name) = token {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ExprToken {
+    Num(i64),
+    Var(String),
+    Op(&'static str),
+}
+
+/// Opérateurs, les plus longs d'abord
+const EXPR_OPS: [&str; 19] = [
+    "<<", ">>", "==", "!=", "<=", ">=", "+", "-", "*", "/", "%", "&", "|", "^", "~", "<", ">", "(", ")",
+];
+
+/// Opérateurs binaires par priorité croissante
+const BINARY_OPS: [&[&str]; 8] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+fn tokenize_expr(input: &str) -> Result<Vec<ExprToken>, Error> {
+    let invalid = || Error::new(format!("invalid expression {}", input));
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(op) = EXPR_OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(ExprToken::Op(op));
+            rest = &rest[op.len()..];
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            let token = if c.is_ascii_digit() {
+                let lower = word.to_lowercase();
+                let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+                    u64::from_str_radix(hex, 16).map(|v| v as i64)
+                } else if let Some(bin) = lower.strip_prefix("0b") {
+                    u64::from_str_radix(bin, 2).map(|v| v as i64)
+                } else {
+                    lower.parse()
+                };
+                ExprToken::Num(parsed.map_err(|_| invalid())?)
+            } else {
+                ExprToken::Var(word.to_string())
+            };
+            tokens.push(token);
+            rest = &rest[end..];
+        } else {
+            return Err(invalid());
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn expr_binary(tokens: &[ExprToken], pos: &mut usize, vars: &HashMap<String, i64>, level: usize) -> Result<i64, Error> {
+    let Some(ops) = BINARY_OPS.get(level) else {
+        return expr_unary(tokens, pos, vars);
+    };
+    let mut value = expr_binary(tokens, pos, vars, level + 1)?;
+    while let Some(ExprToken::Op(op)) = tokens.get(*pos) {
+        if !ops.contains(op) {
+            break;
+        }
+        *pos += 1;
+        let rhs = expr_binary(tokens, pos, vars, level + 1)?;
+        if matches!(*op, "/" | "%") && rhs == 0 {
+            return Err(Error::new("division by zero"));
+        }
+        // Décalages logiques ; au-delà de 63 bits le résultat est nul
+        let shift = u32::try_from(rhs).ok().filter(|&s| s < 64);
+        value = match *op {
+            "+" => value.wrapping_add(rhs),
+            "-" => value.wrapping_sub(rhs),
+            "*" => value.wrapping_mul(rhs),
+            "/" => value.wrapping_div(rhs),
+            "%" => value.wrapping_rem(rhs),
+            "<<" => shift.map_or(0, |s| value << s),
+            ">>" => shift.map_or(0, |s| ((value as u64) >> s) as i64),
+            "&" => value & rhs,
+            "|" => value | rhs,
+            "^" => value ^ rhs,
+            "==" => (value == rhs) as i64,
+            "!=" => (value != rhs) as i64,
+            "<" => (value < rhs) as i64,
+            "<=" => (value <= rhs) as i64,
+            ">" => (value > rhs) as i64,
+            _ => (value >= rhs) as i64,
+        };
+    }
+    Ok(value)
+}
+
+fn expr_unary(tokens: &[ExprToken], pos: &mut usize, vars: &HashMap<String, i64>) -> Result<i64, Error> {
+    let token = tokens.get(*pos).cloned();
+    *pos += 1;
+    match token {
+        Some(ExprToken::Num(n)) => Ok(n),
+        Some(ExprToken::Var(name)) => vars
+            .get(&name)
+            .copied()
+            .ok_or_else(|| Error::new(format!("unknown variable {}", name))),
+        Some(ExprToken::Op("-")) => Ok(expr_unary(tokens, pos, vars)?.wrapping_neg()),
+        Some(ExprToken::Op("~")) => Ok(!expr_unary(tokens, pos, vars)?),
+        Some(ExprToken::Op("(")) => {
+            let value = expr_binary(tokens, pos, vars, 0)?;
+            if tokens.get(*pos) != Some(&ExprToken::Op(")")) {
+                return Err(Error::new("missing )"));
+            }
+            *pos += 1;
+            Ok(value)
+        }
+        _ => Err(Error::new("invalid expression")),
+    }
+}
+
+/// Valeur d'un `set`/`expect` : un littéral, sinon une expression sur les
+/// variables. Renvoie aussi le texte affiché en cas d'échec et le signe.
+fn eval_value(input: &str, vars: &HashMap<String, i64>) -> Result<(BitVec, String, bool), Error> {
+    if let Ok(bv) = parse_value(input) {
+        return Ok((bv, input.to_string(), input.starts_with('-')));
+    }
+    let v = eval_expr(input, vars)?;
+    Ok((BitVec::from_i64(64, v), v.to_string(), v < 0))
+}
+
+/// Front montant (`tick`) ou descendant (`tock`) d'une horloge nommée ;
+/// sans nom, toutes les horloges déclarées changent de niveau ensemble
+fn clock_edge(sim: &mut Simulator, clocks: &[String], clock: Option<&str>, rising: bool) -> Result<(), Error> {
+    match (clock, rising) {
+        (Some(name), true) => sim.tick_clock(name),
+        (Some(name), false) => sim.tock_clock(name),
+        (None, _) => {
+            for name in clocks {
+                sim.set_signal(name, BitVec::new(1, rising as u8))?;
+            }
+            if rising {
+                sim.tick()
+            } else {
+                sim.tock()
+            }
+        }
+    }
+}
+
+/// Ajuste la valeur attendue à la largeur du signal sans perdre de bit
+/// significatif ; None si elle ne tient pas dans cette largeur
+fn fit_expected(expected: BitVec, width: usize, signed: bool) -> Option<BitVec> {
+    let fitted = if signed {
+        expected.resize_sign(width)
+    } else {
+        expected.resize_zero(width)
+    };
+    let back = if signed {
+        fitted.resize_sign(expected.width())
+    } else {
+        fitted.resize_zero(expected.width())
+    };
+    (back == expected).then_some(fitted)
+}
+
 /// Exécute un test et retourne le résultat détaillé
 pub fn run_test(hdl: &str, test_script: &str, library: &HashMap<String, String>) -> Result<TestResult, Error> {
-    let (chip_name, commands) = parse_test_script(test_script)?;
+    run_test_with_options(hdl, test_script, library, &TestOptions::default())
+}
 
+/// Exécute un test avec des options (trace VCD, ...)
+pub fn run_test_with_options(
+    hdl: &str,
+    test_script: &str,
+    library: &HashMap<String, String>,
+    options: &TestOptions,
+) -> Result<TestResult, Error> {
     // Parse le circuit principal
     let main_design = parse_str(hdl)?;
 
     // Construit le Design complet avec la bibliothèque
 
     // Crée un Design combiné
-    let mut design = Design { entities: Vec::new(), architectures: Vec::new() };
+    let mut design = Design::default();
 
     // Parse toutes les sources de la bibliothèque
-    for (_name, src) in library {
+    for src in library.values() {
         if let Ok(d) = parse_str(src) {
-            design.entities.extend(d.entities);
-            design.architectures.extend(d.architectures);
+            design.append(d);
         }
     }
     // Ajoute le circuit principal
-    design.entities.extend(main_design.entities);
-    design.architectures.extend(main_design.architectures);
+    design.append(main_design);
+
+    run_test_design(&design, test_script, options)
+}
+
+/// Exécute un script de test sur un design déjà assemblé ; le top est
+/// l'entité nommée par `load`, sinon la dernière entité du design
+pub fn run_test_design(design: &Design, test_script: &str, options: &TestOptions) -> Result<TestResult, Error> {
+    let (files, commands) = parse_test_script(test_script)?;
 
     // Détermine le nom du top-level entity
-    let top_name = if chip_name.is_empty() {
+    let top_name = if files.top.is_empty() {
         design.entities.last().map(|e| e.name.clone()).unwrap_or_default()
     } else {
-        chip_name.clone()
+        files.top.clone()
+    };
+    let compare = match (&files.compare_to, &options.compare) {
+        (Some(_), Some(cmp)) => Some(cmp.lines().filter(|l| !l.trim().is_empty()).map(str::to_string).collect()),
+        (Some(path), None) => return Err(Error::new(format!("compare-to {}: compare file not provided", path))),
+        (None, _) => None,
     };
 
     // Élabore et crée le simulateur
-    let netlist = elaborate(&design, &top_name)?;
+    let netlist = elaborate(design, &top_name)?;
     let mut sim = Simulator::new(netlist);
+    if options.vcd {
+        sim.start_vcd(&top_name);
+    }
 
-    let mut total_checks = 0;
-    let mut passed_checks = 0;
-    let mut errors = Vec::new();
-    let mut failures = Vec::new();
-    let mut current_inputs: HashMap<String, String> = HashMap::new();
+    // `clk` est l'horloge par défaut quand le top en a une
+    let clocks: Vec<String> = sim.signal_info("clk").map(|_| vec!["clk".to_string()]).unwrap_or_default();
+    let mut runner = Runner {
+        sim,
+        top_name,
+        clocks,
+        vars: HashMap::new(),
+        vcd_file: None,
+        total_checks: 0,
+        passed_checks: 0,
+        errors: Vec::new(),
+        failures: Vec::new(),
+        current_inputs: HashMap::new(),
+        output_list: None,
+        output: Vec::new(),
+        compare,
+        design,
+        files: &options.files,
+        dumps: Vec::new(),
+        references: HashMap::new(),
+        vectors: VectorMode::Auto,
+    };
+    runner.exec_block(&commands)?;
+
+    Ok(TestResult {
+        passed: runner.failures.is_empty(),
+        total_checks: runner.total_checks,
+        passed_checks: runner.passed_checks,
+        errors: runner.errors,
+        failures: runner.failures,
+        vcd: runner.sim.vcd(),
+        vcd_file: runner.vcd_file,
+        output: (!runner.output.is_empty()).then(|| runner.output.iter().map(|l| format!("{}\n", l)).collect()),
+        dumps: runner.dumps,
+    })
+}
+
+/// État d'exécution d'un script
+struct Runner<'a> {
+    sim: Simulator,
+    top_name: String,
+    clocks: Vec<String>,
+    vars: HashMap<String, i64>,
+    vcd_file: Option<String>,
+    total_checks: usize,
+    passed_checks: usize,
+    errors: Vec<String>,
+    failures: Vec<TestFailure>,
+    current_inputs: HashMap<String, String>,
+    output_list: Option<Vec<OutputColumn>>,
+    /// Lignes de la table de sortie, en-tête compris
+    output: Vec<String>,
+    /// Lignes non vides de la table attendue
+    compare: Option<Vec<String>>,
+    design: &'a Design,
+    /// Fichiers fournis pour `romload` et `memload`
+    files: &'a HashMap<String, Vec<u8>>,
+    /// Fichiers produits par `memdump`
+    dumps: Vec<(String, Vec<u8>)>,
+    /// Simulateurs des entités de référence des `check ... against`
+    references: HashMap<String, Simulator>,
+    vectors: VectorMode,
+}
+
+impl Runner<'_> {
+    fn column_widths(&self, columns: &[OutputColumn]) -> Result<Vec<usize>, Error> {
+        columns.iter().map(|c| Ok(self.sim.get_signal(&c.signal)?.width())).collect()
+    }
+
+    /// Valeur d'un `set`/`expect`/`force` : `eval_value`, ou un littéral
+    /// de l'énumération du signal (`expect state FETCH`)
+    fn signal_value(&self, signal: &str, input: &str) -> Result<(BitVec, String, bool), Error> {
+        if let Some(code) = self.sim.signal_type(signal)?.literal(input) {
+            return Ok((BitVec::from_u64(64, code), input.to_string(), false));
+        }
+        eval_value(input, &self.vars)
+    }
+
+    /// Pilote les entrées d'un `check` avec les vecteurs choisis par
+    /// `vectors` et s'arrête au premier contre-exemple ; les entrées
+    /// reprennent ensuite leur valeur
+    fn check(&mut self, line_number: usize, outputs: &[String], reference: &CheckRef) -> Result<(), Error> {
+        let inputs = self.check_inputs(outputs, reference)?;
+        let bits: usize = inputs.iter().map(|(_, width)| width).sum();
+        let (count, mut rng) = match self.vectors {
+            VectorMode::Auto if bits <= 16 => (1u64 << bits, None),
+            VectorMode::Auto => (1000, Some(Rng::new(1))),
+            VectorMode::Exhaustive if bits <= EXHAUSTIVE_MAX_BITS => (1u64 << bits, None),
+            VectorMode::Exhaustive => {
+                return Err(Error::new(format!(
+                    "exhaustive check over {} input bits (max {}), use vectors random",
+                    bits, EXHAUSTIVE_MAX_BITS
+                )));
+            }
+            VectorMode::Random { count, seed } => (count, Some(Rng::new(seed))),
+        };
+        let saved = inputs
+            .iter()
+            .map(|(name, _)| self.sim.get_signal(name))
+            .collect::<Result<Vec<_>, _>>()?;
 
-    for (line_number, cmd) in commands {
+        self.total_checks += 1;
+        let mut failure = None;
+        for k in 0..count {
+            let mut offset = 0;
+            let values: Vec<BitVec> = inputs
+                .iter()
+                .map(|(_, width)| match &mut rng {
+                    Some(rng) => rng.bits(*width),
+                    None => {
+                        let value = BitVec::from_u64(*width, k >> offset);
+                        offset += width;
+                        value
+                    }
+                })
+                .collect();
+            for ((name, _), value) in inputs.iter().zip(&values) {
+                self.sim.set_signal(name, value.clone())?;
+            }
+            self.sim.eval_comb()?;
+
+            let expected = self.check_expected(outputs, reference, &inputs, &values)?;
+            for (output, expected) in outputs.iter().zip(expected) {
+                let actual = self.sim.get_signal(output)?;
+                if actual != expected {
+                    let shown = match reference {
+                        CheckRef::Expr(expr) | CheckRef::Entity(expr) => format!("{} ({})", hex(&expected), expr),
+                    };
+                    failure = Some(TestFailure {
+                        line_number,
+                        inputs: inputs.iter().zip(&values).map(|((name, _), v)| (name.clone(), hex(v))).collect(),
+                        signal: output.clone(),
+                        expected: shown,
+                        actual: hex(&actual),
+                    });
+                    break;
+                }
+            }
+            if failure.is_some() {
+                break;
+            }
+        }
+
+        for ((name, _), value) in inputs.iter().zip(saved) {
+            self.sim.set_signal(name, value)?;
+        }
+        self.sim.eval_comb()?;
+        match failure {
+            Some(failure) => {
+                self.errors.push(failure.format());
+                self.failures.push(failure);
+            }
+            None => self.passed_checks += 1,
+        }
+        Ok(())
+    }
+
+    /// Entrées pilotées par un `check`, avec leur largeur (les horloges
+    /// déclarées ne sont jamais pilotées)
+    fn check_inputs(&mut self, outputs: &[String], reference: &CheckRef) -> Result<Vec<(String, usize)>, Error> {
+        let mut inputs = Vec::new();
+        match reference {
+            CheckRef::Expr(expr) => {
+                for name in expr_identifiers(expr)? {
+                    if let Some((width, true, _)) = self.sim.signal_info(&name) {
+                        inputs.push((name, width));
+                    }
+                }
+            }
+            CheckRef::Entity(entity) => {
+                if !self.references.contains_key(entity) {
+                    let netlist = elaborate(self.design, entity)?;
+                    self.references.insert(entity.clone(), Simulator::new(netlist));
+                }
+                let ports = self.references[entity].scope_signals("")?;
+                for output in outputs {
+                    if !ports.iter().any(|p| &p.name == output && p.kind == ScopeSignalKind::Out) {
+                        return Err(Error::new(format!("{} has no output {}", entity, output)));
+                    }
+                }
+                for port in ports {
+                    if port.kind == ScopeSignalKind::Local || (port.kind == ScopeSignalKind::Out && !outputs.contains(&port.name)) {
+                        continue;
+                    }
+                    let width = match self.sim.signal_info(&port.name) {
+                        Some((width, ..)) => width,
+                        None => return Err(Error::new(format!("{} has no port {} of {}", self.top_name, port.name, entity))),
+                    };
+                    if width != port.width {
+                        return Err(Error::new(format!(
+                            "port {} is {} bits wide in {} but {} in {}",
+                            port.name, width, self.top_name, port.width, entity
+                        )));
+                    }
+                    if port.kind == ScopeSignalKind::In {
+                        inputs.push((port.name, width));
+                    }
+                }
+            }
+        }
+        inputs.retain(|(name, _)| !self.clocks.contains(name));
+        Ok(inputs)
+    }
+
+    /// Valeurs attendues des sorties d'un `check` pour un vecteur d'entrée
+    fn check_expected(
+        &mut self,
+        outputs: &[String],
+        reference: &CheckRef,
+        inputs: &[(String, usize)],
+        values: &[BitVec],
+    ) -> Result<Vec<BitVec>, Error> {
+        match reference {
+            CheckRef::Expr(expr) => {
+                // Les signaux masquent les variables du script
+                let mut vars = self.vars.clone();
+                for name in expr_identifiers(expr)? {
+                    if let Ok(value) = self.sim.get_signal(&name) {
+                        if value.width() > 64 {
+                            return Err(Error::new(format!("check: signal {} is wider than 64 bits", name)));
+                        }
+                        vars.insert(name, value.to_u64_trunc() as i64);
+                    }
+                }
+                let value = eval_expr(expr, &vars)?;
+                let width = self.sim.get_signal(&outputs[0])?.width();
+                Ok(vec![BitVec::from_i64(width, value)])
+            }
+            CheckRef::Entity(entity) => {
+                let reference = self
+                    .references
+                    .get_mut(entity)
+                    .ok_or_else(|| Error::new(format!("unknown entity {}", entity)))?;
+                for ((name, _), value) in inputs.iter().zip(values) {
+                    reference.set_signal(name, value.clone())?;
+                }
+                reference.eval_comb()?;
+                outputs.iter().map(|output| reference.get_signal(output)).collect()
+            }
+        }
+    }
+
+    /// Ajoute une ligne à la table de sortie et la compare à la ligne de
+    /// même rang de la table attendue, cellule par cellule
+    fn push_output(&mut self, line_number: usize, row: String) {
+        let index = self.output.len();
+        self.output.push(row);
+        let Some(compare) = &self.compare else {
+            return;
+        };
+        let row = &self.output[index];
+        self.total_checks += 1;
+        let expected = compare.get(index).map(String::as_str).unwrap_or("");
+        let (want, got) = (table_cells(expected), table_cells(row));
+        if want == got {
+            self.passed_checks += 1;
+            return;
+        }
+        // Colonnes en désaccord, ou toute la ligne si la forme diffère
+        let names: Vec<String> = match &self.output_list {
+            Some(columns) if index > 0 && want.len() == got.len() => columns
+                .iter()
+                .zip(want.iter().zip(&got))
+                .filter(|(_, (w, g))| w != g)
+                .map(|(c, _)| c.signal.clone())
+                .collect(),
+            _ => vec![format!("ligne {} de la table", index + 1)],
+        };
+        let failure = TestFailure {
+            line_number,
+            inputs: self.current_inputs.clone(),
+            signal: names.join(", "),
+            expected: if expected.is_empty() { "(fin de la table .cmp)".to_string() } else { expected.to_string() },
+            actual: row.clone(),
+        };
+        self.errors.push(failure.format());
+        self.failures.push(failure);
+    }
+
+    /// Exécute un bloc ; les erreurs sont préfixées par leur ligne
+    fn exec_block(&mut self, commands: &[(usize, TestCmd)]) -> Result<(), Error> {
+        for (line_number, cmd) in commands {
+            let in_line = |e: Error| Error::new(format!("line {}: {}", line_number, e));
+            match cmd {
+                TestCmd::Repeat { count, counter, body } => {
+                    let n = eval_expr(count, &self.vars).map_err(in_line)?;
+                    if n < 0 {
+                        return Err(in_line(Error::new(format!("negative repeat count {}", n))));
+                    }
+                    // Le compteur n'est visible que dans le bloc
+                    let saved = counter.as_ref().and_then(|c| self.vars.get(c).copied());
+                    for i in 0..n {
+                        if let Some(c) = counter {
+                            self.vars.insert(c.clone(), i);
+                        }
+                        self.exec_block(body)?;
+                    }
+                    if let Some(c) = counter {
+                        match saved {
+                            Some(v) => self.vars.insert(c.clone(), v),
+                            None => self.vars.remove(c),
+                        };
+                    }
+                }
+                _ => self.exec(*line_number, cmd).map_err(in_line)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn exec(&mut self, line_number: usize, cmd: &TestCmd) -> Result<(), Error> {
         match cmd {
             TestCmd::Set { signal, value } => {
-                let bv = parse_value(&value)?;
-                sim.set_signal(&signal, bv)?;
-                current_inputs.insert(signal, value);
+                let (bv, shown, _) = self.signal_value(signal, value)?;
+                self.sim.set_signal(signal, bv)?;
+                self.current_inputs.insert(signal.clone(), shown);
             }
             TestCmd::Eval => {
-                sim.eval_comb()?;
+                self.sim.eval_comb()?;
             }
-            TestCmd::Tick => {
-                sim.tick()?;
+            TestCmd::Clock { names } => {
+                self.clocks = names.clone();
             }
-            TestCmd::Tock => {
-                sim.tock()?;
+            TestCmd::Tick { clock } => {
+                clock_edge(&mut self.sim, &self.clocks, clock.as_deref(), true)?;
+            }
+            TestCmd::Tock { clock } => {
+                clock_edge(&mut self.sim, &self.clocks, clock.as_deref(), false)?;
+            }
+            TestCmd::Step { clock } => {
+                clock_edge(&mut self.sim, &self.clocks, clock.as_deref(), true)?;
+                clock_edge(&mut self.sim, &self.clocks, clock.as_deref(), false)?;
+            }
+            TestCmd::Vcd { path } => {
+                if self.sim.vcd().is_none() {
+                    self.sim.start_vcd(&self.top_name);
+                }
+                self.vcd_file = Some(path.clone());
             }
             TestCmd::Expect { signal, value } => {
-                total_checks += 1;
-                let expected = parse_value(&value)?;
-                let actual = sim.get_signal(&signal)?;
+                self.total_checks += 1;
+                let (expected, shown, signed) = self.signal_value(signal, value)?;
+                let actual = self.sim.get_signal(signal)?;
 
-                if actual == expected {
-                    passed_checks += 1;
+                if fit_expected(expected, actual.width(), signed).as_ref() == Some(&actual) {
+                    self.passed_checks += 1;
                 } else {
                     let failure = TestFailure {
                         line_number,
-                        inputs: current_inputs.clone(),
+                        inputs: self.current_inputs.clone(),
                         signal: signal.clone(),
-                        expected: value,
-                        actual: format!("0x{:X}", actual.to_u64_trunc()),
+                        expected: shown,
+                        actual: match self.sim.display_signal(signal)? {
+                            Some(symbol) => format!("{} (0x{:X})", symbol, actual.to_u64_trunc()),
+                            None => format!("0x{:X}", actual.to_u64_trunc()),
+                        },
                     };
-                    errors.push(failure.format());
-                    failures.push(failure);
+                    self.errors.push(failure.format());
+                    self.failures.push(failure);
                 }
             }
             TestCmd::RomLoad { rom_index, hex_data } => {
-                sim.load_rom_hex(rom_index, &hex_data)?;
+                self.sim.load_rom_hex(*rom_index, hex_data)?;
+            }
+            TestCmd::MemLoad { memory, file } => {
+                let bytes = self
+                    .files
+                    .get(file)
+                    .ok_or_else(|| Error::new(format!("{}: memory file not provided", file)))?;
+                let path = match memory {
+                    MemoryRef::Rom(index) => self.sim.rom_path(*index)?,
+                    MemoryRef::Path(path) => path.clone(),
+                };
+                self.sim
+                    .update_memory(&path, |mem| mem.load_file(file, bytes))
+                    .map_err(|e| Error::new(format!("{}: {}", file, e.message)))?;
+            }
+            TestCmd::MemDump { memory, file, range } => {
+                let mem = self.sim.memory(memory)?;
+                let range = match range {
+                    Some((start, count)) => {
+                        let start = eval_expr(start, &self.vars)?.max(0) as u64;
+                        start..start.saturating_add(eval_expr(count, &self.vars)?.max(0) as u64)
+                    }
+                    None => 0..mem.last_addr().saturating_add(1),
+                };
+                let bytes = mem.dump_file(file, range)?;
+                self.dumps.push((file.clone(), bytes));
+            }
+            TestCmd::Let { name, value } => {
+                let v = eval_expr(value, &self.vars)?;
+                self.vars.insert(name.clone(), v);
             }
-            TestCmd::Repeat { count, .. } => {
-                // For now, repeat just does N tick-tock cycles
-                for _ in 0..count {
-                    sim.tick()?;
-                    sim.tock()?;
+            TestCmd::Repeat { .. } => unreachable!("repeat blocks run in exec_block"),
+            TestCmd::OutputList { columns } => {
+                let widths = self.column_widths(columns)?;
+                let cells = columns
+                    .iter()
+                    .zip(widths)
+                    .map(|(c, w)| Ok(c.header(w, self.sim.signal_type(&c.signal)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                self.output_list = Some(columns.clone());
+                self.push_output(line_number, format!("|{}|", cells.join("|")));
+            }
+            TestCmd::Vectors { mode } => {
+                self.vectors = *mode;
+            }
+            TestCmd::Check { outputs, reference } => {
+                self.check(line_number, outputs, reference)?;
+            }
+            TestCmd::Force { signal, value } => {
+                let (bv, _, _) = self.signal_value(signal, value)?;
+                self.sim.force_signal(signal, bv)?;
+            }
+            TestCmd::Release { signal: Some(signal) } => self.sim.release_signal(signal)?,
+            TestCmd::Release { signal: None } => self.sim.release_all(),
+            TestCmd::Break { condition } => {
+                self.sim.add_breakpoint(condition)?;
+            }
+            TestCmd::Watch { signal } => {
+                self.sim.add_watchpoint(signal)?;
+            }
+            TestCmd::Run { max_cycles } => {
+                let max = eval_expr(max_cycles, &self.vars)?;
+                if self.sim.breakpoints().is_empty() {
+                    return Err(Error::new("run without breakpoint (use break or watch)"));
                 }
+                let clocks = &self.clocks;
+                let outcome = self.sim.run_until_break_with(max.max(0) as u64, |sim| {
+                    clock_edge(sim, clocks, None, true)?;
+                    clock_edge(sim, clocks, None, false)
+                })?;
+                if outcome.hits.is_empty() {
+                    return Err(Error::new(format!("no breakpoint hit within {} cycles", max)));
+                }
+            }
+            TestCmd::Output => {
+                let columns = self.output_list.clone().ok_or_else(|| Error::new("output without output-list"))?;
+                let cells = columns
+                    .iter()
+                    .map(|c| Ok(c.cell(&self.sim.get_signal(&c.signal)?, self.sim.signal_type(&c.signal)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                self.push_output(line_number, format!("|{}|", cells.join("|")));
             }
         }
+        Ok(())
     }
-
-    Ok(TestResult {
-        passed: failures.is_empty(),
-        total_checks,
-        passed_checks,
-        errors,
-        failures,
-    })
 }
 
 /// Exécute un fichier de test
@@ -322,6 +1324,498 @@ expect y 0
         let result = run_test(hdl, test_script, &library).unwrap();
         assert!(result.passed, "Test échoué: {:?}", result.errors);
         assert_eq!(result.passed_checks, 2);
+        assert!(result.vcd.is_none());
+    }
+
+    #[test]
+    fn test_vcd_option() {
+        let hdl = r#"
+entity NotGate is
+  port(a : in bit; y : out bit);
+end entity;
+
+architecture rtl of NotGate is
+begin
+  y <= not a;
+end architecture;
+"#;
+        let test_script = "load NotGate\nset a 0\neval\nset a 1\neval\nexpect y 0\n";
+        let options = TestOptions { vcd: true, ..Default::default() };
+        let result = run_test_with_options(hdl, test_script, &HashMap::new(), &options).unwrap();
+        assert!(result.passed);
+        let vcd = result.vcd.unwrap();
+        assert!(vcd.contains("$scope module NotGate $end"));
+        assert!(vcd.contains("#2\n1!\n0\"\n"));
+    }
+
+    #[test]
+    fn test_multiple_clocks() {
+        let hdl = r#"
+entity Reg is
+  port(clk : in bit; d : in bit; q : out bit);
+end entity;
+
+architecture rtl of Reg is
+begin
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      q <= d;
+    end if;
+  end process;
+end architecture;
+
+entity TwoClk is
+  port(clk_a : in bit; clk_b : in bit; d : in bit; qa : out bit; qb : out bit; qr : out bit);
+end entity;
+
+architecture rtl of TwoClk is
+begin
+  process(clk_a)
+  begin
+    if rising_edge(clk_a) then
+      qa <= d;
+    end if;
+  end process;
+
+  process(clk_b)
+  begin
+    if falling_edge(clk_b) then
+      qb <= d;
+    end if;
+  end process;
+
+  r: Reg port map (clk => clk_b, d => d, q => qr);
+end architecture;
+"#;
+        let test_script = "
+load TwoClk
+clock clk_a clk_b
+set d 1
+tick clk_a
+expect qa 1
+expect qb 0
+expect qr 0
+tick clk_b
+expect qr 1
+expect qb 0
+tock clk_b
+expect qb 1
+set d 0
+tick
+expect qa 0
+expect qr 0
+expect qb 1
+tock
+expect qb 0
+";
+        let result = run_test(hdl, test_script, &HashMap::new()).unwrap();
+        assert!(result.passed, "Test échoué: {:?}", result.errors);
+        assert_eq!(result.passed_checks, 10);
+    }
+
+    #[test]
+    fn test_async_reset() {
+        let hdl = include_str!("../../hdl_lib/regs/RegNReset.hdl");
+        // The reset clears q on eval, without any tick, and holds it across edges
+        let test_script = "
+load RegNReset
+set load 1
+set d 0x12345678
+tick
+tock
+expect q 0x12345678
+set rst 1
+eval
+expect q 0x00000000
+tick
+tock
+expect q 0x00000000
+set rst 0
+tick
+expect q 0x12345678
+";
+        let result = run_test(hdl, test_script, &HashMap::new()).unwrap();
+        assert!(result.passed, "Test échoué: {:?}", result.errors);
+        assert_eq!(result.passed_checks, 4);
+    }
+
+    #[test]
+    fn test_script_values_and_vcd_command() {
+        let hdl = r#"
+entity Inc is
+  port(clk : in bit; q : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Inc is
+  signal r : bits(7 downto 0);
+begin
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      r <= r + 1;
+    end if;
+  end process;
+  q <= r;
+end architecture;
+"#;
+        // Expected values are fitted to the signal width when no bit is lost
+        let test_script = "
+-- commentaire HDL
+load Inc
+vcd inc.vcd
+step
+step
+expect q 2
+expect q b\"10\"
+expect q x\"02\"
+expect q 0x0000002
+expect q 0x102
+";
+        let result = run_test(hdl, test_script, &HashMap::new()).unwrap();
+        assert_eq!(result.passed_checks, 4);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].line_number, 11);
+        assert_eq!(result.vcd_file.as_deref(), Some("inc.vcd"));
+        assert!(result.vcd.unwrap().contains("$scope module Inc $end"));
+    }
+
+    #[test]
+    fn test_force_break_and_run() {
+        let hdl = r#"
+entity Inc is
+  port(clk : in bit; q : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Inc is
+  signal r : bits(7 downto 0);
+begin
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      r <= r + 1;
+    end if;
+  end process;
+  q <= r;
+end architecture;
+"#;
+        // run s'arrête au premier point d'arrêt, force fige r jusqu'au release
+        let test_script = "
+load Inc
+break q = 10
+run
+expect q 10
+force r 0x80
+step
+step
+expect q 0x80
+release r
+watch q
+run
+expect q 0x81
+";
+        let result = run_test(hdl, test_script, &HashMap::new()).unwrap();
+        assert!(result.passed, "{:?}", result.errors);
+        assert_eq!(result.passed_checks, 3);
+
+        let err = run_test(hdl, "load Inc\nrun\n", &HashMap::new()).unwrap_err();
+        assert!(err.message.contains("run without breakpoint"));
+        let err = run_test(hdl, "load Inc\nbreak q = 0\nrun 3\n", &HashMap::new()).unwrap_err();
+        assert!(err.message.contains("no breakpoint hit within 3 cycles"));
+    }
+
+    #[test]
+    fn test_enum_and_integer_signals() {
+        let hdl = r#"
+entity Fsm is
+  port(clk : in bit; go : in bit; busy : out bit);
+end entity;
+
+architecture rtl of Fsm is
+  type state_t is (IDLE, FETCH, EXEC);
+  signal state : state_t;
+  signal cnt : integer range 0 to 9;
+begin
+  busy <= state = EXEC;
+  process(clk)
+  begin
+    if rising_edge(clk) then
+      case state is
+        when IDLE =>
+          if go = '1' then
+            state <= FETCH;
+          end if;
+        when FETCH => state <= EXEC;
+        when others =>
+          state <= IDLE;
+          cnt <= cnt + 1;
+      end case;
+    end if;
+  end process;
+end architecture;
+"#;
+        // Les littéraux de l'énumération servent de valeurs, %S les affiche
+        let test_script = "
+load Fsm
+output-list state%S cnt%S state
+expect state IDLE
+set go 1
+step
+output
+step
+expect state EXEC
+expect busy 1
+step
+output
+set state FETCH
+expect state FETCH
+";
+        let result = run_test(hdl, test_script, &HashMap::new()).unwrap();
+        assert!(result.passed, "{:?}", result.errors);
+        assert_eq!(
+            result.output.as_deref(),
+            Some("| state |cnt|stat|\n| FETCH | 0 | 01 |\n| IDLE  | 1 | 00 |\n")
+        );
+
+        let result = run_test(hdl, "load Fsm\nexpect state EXEC\n", &HashMap::new()).unwrap();
+        assert_eq!(result.failures[0].actual, "IDLE (0x0)");
+    }
+
+    #[test]
+    fn test_memory_files() {
+        let hdl = r#"
+entity Cpu is
+  port(clk : in bit; pc : in bits(7 downto 0); instr : out bits(31 downto 0);
+       we : in bit; addr : in bits(19 downto 0); din : in bits(7 downto 0); dout : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Cpu is
+begin
+  imem: rom port map (addr => pc, dout => instr);
+  dmem: ram port map (clk => clk, we => we, addr => addr, din => din, dout => dout);
+end architecture;
+"#;
+        // Image A32B : en-tête, un segment chargé à l'octet 8, deux mots
+        let mut image = b"A32B".to_vec();
+        for field in [1u16, 1] {
+            image.extend(field.to_le_bytes());
+        }
+        image.extend(8u32.to_le_bytes());
+        image.extend(1u16.to_le_bytes());
+        image.extend(24u16.to_le_bytes());
+        image.extend(32u32.to_le_bytes());
+        image.extend(64u32.to_le_bytes());
+        image.resize(32, 0);
+        for field in [1u32, 0b101, 8, 56, 8, 8] {
+            image.extend(field.to_le_bytes());
+        }
+        image.extend([0x78, 0x56, 0x34, 0x12, 0xEF, 0xBE, 0xAD, 0xDE]);
+        let test_script = "
+load Cpu
+romload prog.a32b
+set pc 3
+eval
+expect instr 0xDEADBEEF
+memload dmem data.hex
+set addr 0x80001
+eval
+expect dout 0x22
+set we 1, set addr 5, set din 0x99, tick, tock
+memdump dmem dump.hex
+memdump dmem dump.bin 4 2
+";
+        let options = TestOptions {
+            files: HashMap::from([
+                ("prog.a32b".to_string(), image),
+                ("data.hex".to_string(), b"@80000 11 22 // donnees".to_vec()),
+            ]),
+            ..TestOptions::default()
+        };
+        let design = parse_str(hdl).unwrap();
+        let result = run_test_design(&design, test_script, &options).unwrap();
+        assert!(result.passed, "{:?}", result.errors);
+        assert_eq!(script_files(test_script).unwrap().inputs, vec!["prog.a32b", "data.hex"]);
+        assert_eq!(
+            result.dumps,
+            vec![
+                ("dump.hex".to_string(), b"@00005\n99\n@80000\n11\n22\n".to_vec()),
+                ("dump.bin".to_string(), vec![0x00, 0x99]),
+            ]
+        );
+
+        let err = run_test_design(&design, "load Cpu\nmemload dmem missing.hex\n", &options).unwrap_err();
+        assert!(err.message.contains("missing.hex: memory file not provided"));
+        let err = run_test_design(&design, "load Cpu\nmemload cache data.hex\n", &options).unwrap_err();
+        assert!(err.message.contains("unknown memory cache"));
+        let err = run_test_design(&design, "load Cpu\nmemload dmem data.hex extra\n", &options).unwrap_err();
+        assert_eq!(err.message, "line 2: usage: memload <instance> <file>");
+    }
+
+    #[test]
+    fn test_repeat_blocks_and_variables() {
+        let hdl = r#"
+entity Mem is
+  port(clk : in bit; we : in bit; addr : in bits(3 downto 0); din : in bits(7 downto 0); dout : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Mem is
+begin
+  m: ram port map (clk => clk, we => we, addr => addr, din => din, dout => dout);
+end architecture;
+"#;
+        // Écrit addr * 3 + 1 dans chaque case, puis relit par blocs de 4
+        let test_script = "
+load Mem
+let base = 1
+repeat 16 i {
+  set we 1
+  set addr i
+  set din i * 3 + base
+  step
+}
+set we 0
+repeat 4 row {
+  repeat 4 col {
+    set addr row * 4 + col
+    eval
+    expect dout (row * 4 + col) * 3 + 1
+  }
+}
+repeat 2
+expect dout 0x2E
+";
+        let result = run_test(hdl, test_script, &HashMap::new()).unwrap();
+        assert!(result.passed, "Test échoué: {:?}", result.errors);
+        assert_eq!(result.passed_checks, 17);
+
+        // Un échec dans un bloc indique la ligne de l'expect et la valeur calculée
+        let script = "load Mem\nrepeat 3 i {\n  set addr i\n  eval\n  expect dout i + 100\n}\n";
+        let result = run_test(hdl, script, &HashMap::new()).unwrap();
+        assert_eq!(result.failures.len(), 3);
+        assert_eq!(result.failures[2].line_number, 5);
+        assert_eq!(result.failures[2].expected, "102");
+        assert_eq!(result.failures[2].inputs["addr"], "2");
+
+        let error = |script: &str| run_test(hdl, script, &HashMap::new()).unwrap_err().message;
+        assert_eq!(error("load Mem\nset we 0\nwait 10\n"), "line 3: unknown command wait");
+        assert_eq!(error("load Mem\nexpect dout\n"), "line 2: missing argument for expect");
+        assert_eq!(error("load Mem\nrepeat 2 {\n  step\n"), "line 2: repeat block is never closed");
+        assert_eq!(error("load Mem\n}\n"), "line 2: unmatched }");
+        assert_eq!(error("load Mem\nrepeat 2 i {\n  set addr j\n}\n"), "line 3: unknown variable j");
+    }
+
+    #[test]
+    fn test_output_list_and_compare() {
+        let hdl = r#"
+entity Neg is
+  port(a : in bits(7 downto 0); y : out bits(7 downto 0));
+end entity;
+
+architecture rtl of Neg is
+begin
+  y <= 0 - a;
+end architecture;
+"#;
+        // Syntaxe nand2tetris : ordres séparés par des virgules
+        let test_script = "
+load Neg.hdl, compare-to Neg.cmp, output-list a%B1.8.1 a%X1.2.1 y%D1.4.1 y;
+set a 0, eval, output;
+set a 5, eval, output;
+set a 0x80, eval, output;
+";
+        let out = "\
+|    a     | a  |  y   |    y     |
+| 00000000 | 00 |    0 | 00000000 |
+| 00000101 | 05 |   -5 | 11111011 |
+| 10000000 | 80 | -128 | 10000000 |
+";
+        let options = TestOptions { compare: Some(out.to_string()), ..Default::default() };
+        let design = parse_str(hdl).unwrap();
+        assert_eq!(script_files(test_script).unwrap().load, vec!["Neg.hdl"]);
+        let result = run_test_design(&design, test_script, &options).unwrap();
+        assert!(result.passed, "Test échoué: {:?}", result.errors);
+        assert_eq!(result.passed_checks, 4);
+        assert_eq!(result.output.as_deref(), Some(out));
+
+        // Les cellules sont comparées sans tenir compte des espaces
+        let cmp = "|a|a|y|y|\n|00000000|00|0|00000000|\n|00000101|05|5|11111011|\n";
+        let options = TestOptions { compare: Some(cmp.to_string()), ..Default::default() };
+        let result = run_test_design(&design, test_script, &options).unwrap();
+        assert_eq!(result.passed_checks, 2);
+        assert_eq!(result.failures[0].line_number, 4);
+        assert_eq!(result.failures[0].signal, "y");
+        assert_eq!(result.failures[0].expected, "|00000101|05|5|11111011|");
+        assert_eq!(result.failures[1].expected, "(fin de la table .cmp)");
+
+        let error = run_test_design(&design, test_script, &TestOptions::default()).unwrap_err();
+        assert_eq!(error.message, "compare-to Neg.cmp: compare file not provided");
+        let error = run_test_design(&design, "load Neg\noutput\n", &TestOptions::default()).unwrap_err();
+        assert_eq!(error.message, "line 2: output without output-list");
+    }
+
+    #[test]
+    fn test_check_stimulus() {
+        let hdl = r#"
+entity Add4 is
+  port(a : in bits(3 downto 0); b : in bits(3 downto 0); y : out bits(3 downto 0); c : out bit);
+end entity;
+
+architecture rtl of Add4 is
+  signal s : bits(4 downto 0);
+begin
+  s <= ('0' & a) + ('0' & b);
+  y <= s(3 downto 0);
+  c <= s(4);
+end architecture;
+
+entity BadAdd4 is
+  port(a : in bits(3 downto 0); b : in bits(3 downto 0); y : out bits(3 downto 0); c : out bit);
+end entity;
+
+architecture rtl of BadAdd4 is
+begin
+  y <= a or b;
+  c <= '0';
+end architecture;
+
+entity Add32 is
+  port(a : in bits(31 downto 0); b : in bits(31 downto 0); y : out bits(31 downto 0));
+end entity;
+
+architecture rtl of Add32 is
+begin
+  y <= a + b;
+end architecture;
+"#;
+        let design = parse_str(hdl).unwrap();
+        let run = |script: &str| run_test_design(&design, script, &TestOptions::default());
+
+        let result = run("load Add4\ncheck y = a + b\ncheck c = (a + b) >> 4\ncheck y c against BadAdd4\n").unwrap();
+        assert_eq!(result.passed_checks, 2);
+        // Premier contre-exemple dans l'ordre exhaustif : a = 1, b = 1
+        let failure = &result.failures[0];
+        assert_eq!(failure.line_number, 4);
+        assert_eq!(failure.signal, "y");
+        assert_eq!(failure.expected, "0x1 (BadAdd4)");
+        assert_eq!(failure.actual, "0x2");
+        assert_eq!(failure.inputs["a"], "0x1");
+        assert_eq!(failure.inputs["b"], "0x1");
+
+        // Les entrées pilotées reprennent leur valeur
+        let result = run("load BadAdd4\nset a 3\nset b 4\ncheck y = a + b\neval\nexpect y 7\n").unwrap();
+        assert_eq!(result.passed_checks, 1);
+        assert_eq!(result.failures[0].inputs.len(), 2);
+
+        // 64 bits d'entrée : vecteurs aléatoires reproductibles
+        let result = run("load Add32\nvectors random 200 7\ncheck y = a + b\ncheck y = a | b\n").unwrap();
+        assert_eq!(result.passed_checks, 1);
+        let again = run("load Add32\nvectors random 200 7\ncheck y = a + b\ncheck y = a | b\n").unwrap();
+        assert_eq!(result.failures[0].inputs, again.failures[0].inputs);
+
+        let error = run("load Add32\nvectors exhaustive\ncheck y = a + b\n").unwrap_err();
+        assert_eq!(error.message, "line 3: exhaustive check over 64 input bits (max 24), use vectors random");
+        let error = run("load Add32\ncheck y against Add4\n").unwrap_err();
+        assert_eq!(error.message, "line 2: port a is 32 bits wide in Add32 but 4 in Add4");
     }
 
     #[test]
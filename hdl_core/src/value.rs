@@ -1,223 +1,200 @@
 use crate::error::Error;
 use std::cmp::Ordering;
 
+const LIMB_BITS: usize = 64;
+
+/// Bit vector, bit 0 = lsb, packed in 64-bit limbs.
+/// The first limb is stored inline so values up to 64 bits never allocate.
+/// Invariant: bits above `width` are always zero, so the derived
+/// equality compares values.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct BitVec {
-    bits: Vec<u8>,
+    width: usize,
+    low: u64,
+    high: Vec<u64>,
+}
+
+fn limb_count(width: usize) -> usize {
+    width.div_ceil(LIMB_BITS).max(1)
+}
+
+/// Mask of the valid bits in the top limb
+fn top_mask(width: usize) -> u64 {
+    match width % LIMB_BITS {
+        0 if width == 0 => 0,
+        0 => !0,
+        r => (1u64 << r) - 1,
+    }
 }
 
 impl BitVec {
     pub fn new(width: usize, init: u8) -> Self {
-        let v = if init == 0 { 0 } else { 1 };
-        Self {
-            bits: vec![v; width],
-        }
+        let fill = if init == 0 { 0 } else { !0 };
+        Self::from_limbs(width, |_| fill)
     }
 
     pub fn width(&self) -> usize {
-        self.bits.len()
+        self.width
     }
 
     pub fn get(&self, idx: usize) -> u8 {
-        self.bits[idx]
+        assert!(idx < self.width, "bit index {} out of range for width {}", idx, self.width);
+        ((self.limb(idx / LIMB_BITS) >> (idx % LIMB_BITS)) & 1) as u8
     }
 
     pub fn set(&mut self, idx: usize, val: u8) {
-        self.bits[idx] = if val == 0 { 0 } else { 1 };
+        assert!(idx < self.width, "bit index {} out of range for width {}", idx, self.width);
+        let bit = 1u64 << (idx % LIMB_BITS);
+        let limb = self.limb_mut(idx / LIMB_BITS);
+        if val == 0 {
+            *limb &= !bit;
+        } else {
+            *limb |= bit;
+        }
     }
 
-    pub fn from_u64(width: usize, mut val: u64) -> Self {
-        let mut bits = Vec::with_capacity(width);
-        for _ in 0..width {
-            bits.push((val & 1) as u8);
-            val >>= 1;
-        }
-        Self { bits }
+    pub fn from_u64(width: usize, val: u64) -> Self {
+        Self::from_limbs(width, |i| if i == 0 { val } else { 0 })
     }
 
     pub fn from_i64(width: usize, val: i64) -> Self {
-        let mut bits = Vec::with_capacity(width);
-        let mut v = val as i128;
-        for _ in 0..width {
-            bits.push((v & 1) as u8);
-            v >>= 1;
-        }
-        Self { bits }
+        let fill = if val < 0 { !0 } else { 0 };
+        Self::from_limbs(width, |i| if i == 0 { val as u64 } else { fill })
     }
 
     pub fn from_bits_msb(s: &str) -> Result<Self, Error> {
-        let mut bits = Vec::with_capacity(s.len());
-        for ch in s.chars() {
+        let mut out = Self::new(s.chars().count(), 0);
+        for (i, ch) in s.chars().rev().enumerate() {
             match ch {
-                '0' => bits.push(0),
-                '1' => bits.push(1),
+                '0' => {}
+                '1' => out.set(i, 1),
                 _ => return Err(Error::new("invalid bit literal")),
             }
         }
-        bits.reverse();
-        Ok(Self { bits })
+        Ok(out)
     }
 
     pub fn from_hex_msb(s: &str) -> Result<Self, Error> {
-        let mut bits = Vec::with_capacity(s.len() * 4);
-        for ch in s.chars() {
+        let mut out = Self::new(s.chars().count() * 4, 0);
+        for (i, ch) in s.chars().rev().enumerate() {
             let nibble = ch.to_digit(16).ok_or_else(|| Error::new("invalid hex literal"))?;
-            for i in (0..4).rev() {
-                bits.push(((nibble >> i) & 1) as u8);
+            for b in 0..4 {
+                if (nibble >> b) & 1 != 0 {
+                    out.set(i * 4 + b, 1);
+                }
             }
         }
-        bits.reverse();
-        Ok(Self { bits })
+        Ok(out)
     }
 
     pub fn from_bits_lsb(bits: Vec<u8>) -> Self {
-        Self { bits }
+        let mut out = Self::new(bits.len(), 0);
+        for (i, bit) in bits.into_iter().enumerate() {
+            if bit != 0 {
+                out.set(i, 1);
+            }
+        }
+        out
     }
 
     pub fn to_u64_trunc(&self) -> u64 {
-        let mut v = 0u64;
-        let mut shift = 0u32;
-        for bit in &self.bits {
-            if *bit != 0 {
-                v |= 1u64 << shift;
-            }
-            shift = shift.saturating_add(1);
-            if shift >= 64 {
-                break;
-            }
-        }
-        v
+        self.low
     }
 
     pub fn resize_zero(&self, width: usize) -> Self {
-        if width <= self.width() {
-            let mut bits = self.bits[..width].to_vec();
-            bits.truncate(width);
-            return Self { bits };
-        }
-        let mut bits = self.bits.clone();
-        bits.resize(width, 0);
-        Self { bits }
+        Self::from_limbs(width, |i| self.limb(i))
     }
 
     pub fn resize_sign(&self, width: usize) -> Self {
-        if width <= self.width() {
-            let mut bits = self.bits[..width].to_vec();
-            bits.truncate(width);
-            return Self { bits };
+        Self::from_limbs(width, |i| self.limb_sext(i))
+    }
+
+    /// Bits `[lo, lo + width)`; bits past the end read as zero
+    pub fn slice(&self, lo: usize, width: usize) -> Self {
+        Self::from_limbs(width, |i| self.bits_at(lo.saturating_add(i * LIMB_BITS)))
+    }
+
+    /// Overwrites bits `[lo, lo + value.width())` with `value`
+    pub fn set_slice(&mut self, lo: usize, value: &BitVec) {
+        assert!(
+            lo + value.width <= self.width,
+            "slice {}..{} out of range for width {}",
+            lo,
+            lo + value.width,
+            self.width
+        );
+        for i in 0..limb_count(value.width) {
+            let len = (value.width - i * LIMB_BITS).min(LIMB_BITS);
+            if len == 0 {
+                break;
+            }
+            let mask = if len == LIMB_BITS { !0 } else { (1u64 << len) - 1 };
+            let bits = value.limb(i);
+            let pos = lo + i * LIMB_BITS;
+            let (j, shift) = (pos / LIMB_BITS, pos % LIMB_BITS);
+            let limb = self.limb_mut(j);
+            *limb = (*limb & !(mask << shift)) | (bits << shift);
+            if shift > 0 && shift + len > LIMB_BITS {
+                let limb = self.limb_mut(j + 1);
+                let back = LIMB_BITS - shift;
+                *limb = (*limb & !(mask >> back)) | (bits >> back);
+            }
         }
-        let sign = if self.bits.is_empty() { 0 } else { *self.bits.last().unwrap() };
-        let mut bits = self.bits.clone();
-        bits.resize(width, sign);
-        Self { bits }
     }
 
     pub fn concat(msb: &BitVec, lsb: &BitVec) -> Self {
-        let mut bits = Vec::with_capacity(msb.width() + lsb.width());
-        bits.extend_from_slice(&lsb.bits);
-        bits.extend_from_slice(&msb.bits);
-        Self { bits }
+        let width = msb.width + lsb.width;
+        Self::from_limbs(width, |i| lsb.limb(i) | msb.shifted_limb(i, lsb.width))
     }
 
     pub fn not(&self) -> Self {
-        let bits = self.bits.iter().map(|b| if *b == 0 { 1 } else { 0 }).collect();
-        Self { bits }
+        Self::from_limbs(self.width, |i| !self.limb(i))
     }
 
     pub fn and(a: &BitVec, b: &BitVec) -> Self {
-        let w = a.width().max(b.width());
-        let aa = a.resize_zero(w);
-        let bb = b.resize_zero(w);
-        let mut bits = Vec::with_capacity(w);
-        for i in 0..w {
-            bits.push(if aa.bits[i] != 0 && bb.bits[i] != 0 { 1 } else { 0 });
-        }
-        Self { bits }
+        Self::from_limbs(a.width.max(b.width), |i| a.limb(i) & b.limb(i))
     }
 
     pub fn or(a: &BitVec, b: &BitVec) -> Self {
-        let w = a.width().max(b.width());
-        let aa = a.resize_zero(w);
-        let bb = b.resize_zero(w);
-        let mut bits = Vec::with_capacity(w);
-        for i in 0..w {
-            bits.push(if aa.bits[i] != 0 || bb.bits[i] != 0 { 1 } else { 0 });
-        }
-        Self { bits }
+        Self::from_limbs(a.width.max(b.width), |i| a.limb(i) | b.limb(i))
     }
 
     pub fn xor(a: &BitVec, b: &BitVec) -> Self {
-        let w = a.width().max(b.width());
-        let aa = a.resize_zero(w);
-        let bb = b.resize_zero(w);
-        let mut bits = Vec::with_capacity(w);
-        for i in 0..w {
-            bits.push(if aa.bits[i] != bb.bits[i] { 1 } else { 0 });
-        }
-        Self { bits }
+        Self::from_limbs(a.width.max(b.width), |i| a.limb(i) ^ b.limb(i))
     }
 
     pub fn add(a: &BitVec, b: &BitVec) -> Self {
-        let w = a.width().max(b.width());
-        let aa = a.resize_sign(w);
-        let bb = b.resize_sign(w);
-        let mut bits = Vec::with_capacity(w);
-        let mut carry = 0u8;
-        for i in 0..w {
-            let sum = aa.bits[i] + bb.bits[i] + carry;
-            bits.push(sum & 1);
-            carry = if sum >= 2 { 1 } else { 0 };
-        }
-        Self { bits }
+        let mut carry = false;
+        Self::from_limbs(a.width.max(b.width), |i| {
+            let (s1, c1) = a.limb_sext(i).overflowing_add(b.limb_sext(i));
+            let (s2, c2) = s1.overflowing_add(carry as u64);
+            carry = c1 || c2;
+            s2
+        })
     }
 
     pub fn sub(a: &BitVec, b: &BitVec) -> Self {
-        let w = a.width().max(b.width());
-        let aa = a.resize_sign(w);
-        let bb = b.resize_sign(w);
-        let mut bits = Vec::with_capacity(w);
-        let mut borrow = 0i8;
-        for i in 0..w {
-            let ai = aa.bits[i] as i8;
-            let bi = bb.bits[i] as i8;
-            let mut diff = ai - bi - borrow;
-            if diff < 0 {
-                diff += 2;
-                borrow = 1;
-            } else {
-                borrow = 0;
-            }
-            bits.push((diff & 1) as u8);
-        }
-        Self { bits }
+        let mut borrow = false;
+        Self::from_limbs(a.width.max(b.width), |i| {
+            let (d1, b1) = a.limb_sext(i).overflowing_sub(b.limb_sext(i));
+            let (d2, b2) = d1.overflowing_sub(borrow as u64);
+            borrow = b1 || b2;
+            d2
+        })
     }
 
     pub fn shl(a: &BitVec, count: usize) -> Self {
-        let w = a.width();
-        let mut bits = vec![0u8; w];
-        for i in 0..w {
-            if i + count < w {
-                bits[i + count] = a.bits[i];
-            }
-        }
-        Self { bits }
+        Self::from_limbs(a.width, |i| a.shifted_limb(i, count))
     }
 
     pub fn shr(a: &BitVec, count: usize) -> Self {
-        let w = a.width();
-        let mut bits = vec![0u8; w];
-        for i in count..w {
-            bits[i - count] = a.bits[i];
-        }
-        Self { bits }
+        a.slice(count, a.width)
     }
 
     pub fn cmp_unsigned(a: &BitVec, b: &BitVec) -> Ordering {
-        let w = a.width().max(b.width());
-        let aa = a.resize_zero(w);
-        let bb = b.resize_zero(w);
-        for i in (0..w).rev() {
-            match aa.bits[i].cmp(&bb.bits[i]) {
+        let n = limb_count(a.width.max(b.width));
+        for i in (0..n).rev() {
+            match a.limb(i).cmp(&b.limb(i)) {
                 Ordering::Equal => continue,
                 ord => return ord,
             }
@@ -226,15 +203,92 @@ impl BitVec {
     }
 
     pub fn cmp_signed(a: &BitVec, b: &BitVec) -> Ordering {
-        let w = a.width().max(b.width());
-        let aa = a.resize_sign(w);
-        let bb = b.resize_sign(w);
-        let sa = *aa.bits.last().unwrap_or(&0);
-        let sb = *bb.bits.last().unwrap_or(&0);
+        let w = a.width.max(b.width);
+        if w == 0 {
+            return Ordering::Equal;
+        }
+        let top = (w - 1) / LIMB_BITS;
+        let sign_bit = (w - 1) % LIMB_BITS;
+        let sa = (a.limb_sext(top) >> sign_bit) & 1;
+        let sb = (b.limb_sext(top) >> sign_bit) & 1;
         if sa != sb {
             return if sa == 1 { Ordering::Less } else { Ordering::Greater };
         }
-        Self::cmp_unsigned(&aa, &bb)
+        // Same sign: the bits above w are identical on both sides
+        for i in (0..=top).rev() {
+            match a.limb_sext(i).cmp(&b.limb_sext(i)) {
+                Ordering::Equal => continue,
+                ord => return ord,
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Builds a vector from its limbs (called in order, lsb first), masking the top limb
+    fn from_limbs(width: usize, mut f: impl FnMut(usize) -> u64) -> Self {
+        let n = limb_count(width);
+        let low = f(0);
+        let high = (1..n).map(f).collect();
+        let mut out = Self { width, low, high };
+        *out.limb_mut(n - 1) &= top_mask(width);
+        out
+    }
+
+    /// Limb `i`, zero past the end
+    fn limb(&self, i: usize) -> u64 {
+        if i == 0 {
+            self.low
+        } else {
+            self.high.get(i - 1).copied().unwrap_or(0)
+        }
+    }
+
+    fn limb_mut(&mut self, i: usize) -> &mut u64 {
+        if i == 0 {
+            &mut self.low
+        } else {
+            &mut self.high[i - 1]
+        }
+    }
+
+    /// Limb `i` of the sign-extended value (infinite width)
+    fn limb_sext(&self, i: usize) -> u64 {
+        let negative = self.width > 0 && self.get(self.width - 1) == 1;
+        if !negative {
+            return self.limb(i);
+        }
+        let start = i * LIMB_BITS;
+        if start >= self.width {
+            !0
+        } else if start + LIMB_BITS <= self.width {
+            self.limb(i)
+        } else {
+            self.limb(i) | !top_mask(self.width)
+        }
+    }
+
+    /// 64 bits starting at bit position `pos`, zero past the end
+    fn bits_at(&self, pos: usize) -> u64 {
+        let (j, shift) = (pos / LIMB_BITS, pos % LIMB_BITS);
+        let mut out = self.limb(j) >> shift;
+        if shift > 0 {
+            out |= self.limb(j + 1) << (LIMB_BITS - shift);
+        }
+        out
+    }
+
+    /// Limb `i` of the value shifted left by `count`
+    fn shifted_limb(&self, i: usize, count: usize) -> u64 {
+        let (limbs, shift) = (count / LIMB_BITS, count % LIMB_BITS);
+        if i < limbs {
+            return 0;
+        }
+        let j = i - limbs;
+        let mut out = self.limb(j) << shift;
+        if shift > 0 && j > 0 {
+            out |= self.limb(j - 1) >> (LIMB_BITS - shift);
+        }
+        out
     }
 }
 
@@ -259,3 +313,232 @@ impl Value {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The previous one-byte-per-bit implementation, kept as an oracle
+    mod reference {
+        use std::cmp::Ordering;
+
+        #[derive(Clone, PartialEq, Eq, Debug)]
+        pub struct Bits(pub Vec<u8>);
+
+        impl Bits {
+            pub fn resize_zero(&self, width: usize) -> Self {
+                let mut bits = self.0.clone();
+                bits.resize(width, 0);
+                Self(bits)
+            }
+
+            pub fn resize_sign(&self, width: usize) -> Self {
+                let sign = *self.0.last().unwrap_or(&0);
+                let mut bits = self.0.clone();
+                bits.resize(width, if width <= self.0.len() { 0 } else { sign });
+                Self(bits)
+            }
+
+            pub fn concat(msb: &Bits, lsb: &Bits) -> Self {
+                Self(lsb.0.iter().chain(msb.0.iter()).copied().collect())
+            }
+
+            pub fn not(&self) -> Self {
+                Self(self.0.iter().map(|b| 1 - b).collect())
+            }
+
+            pub fn bitwise(a: &Bits, b: &Bits, f: impl Fn(u8, u8) -> u8) -> Self {
+                let w = a.0.len().max(b.0.len());
+                let (aa, bb) = (a.resize_zero(w), b.resize_zero(w));
+                Self((0..w).map(|i| f(aa.0[i], bb.0[i])).collect())
+            }
+
+            pub fn add(a: &Bits, b: &Bits) -> Self {
+                let w = a.0.len().max(b.0.len());
+                let (aa, bb) = (a.resize_sign(w), b.resize_sign(w));
+                let mut carry = 0u8;
+                let mut bits = Vec::with_capacity(w);
+                for i in 0..w {
+                    let sum = aa.0[i] + bb.0[i] + carry;
+                    bits.push(sum & 1);
+                    carry = sum >> 1;
+                }
+                Self(bits)
+            }
+
+            pub fn sub(a: &Bits, b: &Bits) -> Self {
+                let w = a.0.len().max(b.0.len());
+                let (aa, bb) = (a.resize_sign(w), b.resize_sign(w));
+                let mut borrow = 0i8;
+                let mut bits = Vec::with_capacity(w);
+                for i in 0..w {
+                    let mut diff = aa.0[i] as i8 - bb.0[i] as i8 - borrow;
+                    borrow = 0;
+                    if diff < 0 {
+                        diff += 2;
+                        borrow = 1;
+                    }
+                    bits.push(diff as u8);
+                }
+                Self(bits)
+            }
+
+            pub fn shl(a: &Bits, count: usize) -> Self {
+                let w = a.0.len();
+                let mut bits = vec![0u8; w];
+                for i in 0..w {
+                    if i + count < w {
+                        bits[i + count] = a.0[i];
+                    }
+                }
+                Self(bits)
+            }
+
+            pub fn shr(a: &Bits, count: usize) -> Self {
+                let w = a.0.len();
+                let mut bits = vec![0u8; w];
+                if count < w {
+                    bits[..w - count].copy_from_slice(&a.0[count..]);
+                }
+                Self(bits)
+            }
+
+            pub fn cmp_unsigned(a: &Bits, b: &Bits) -> Ordering {
+                let w = a.0.len().max(b.0.len());
+                let (aa, bb) = (a.resize_zero(w), b.resize_zero(w));
+                aa.0.iter().rev().cmp(bb.0.iter().rev())
+            }
+
+            pub fn cmp_signed(a: &Bits, b: &Bits) -> Ordering {
+                let w = a.0.len().max(b.0.len());
+                let (aa, bb) = (a.resize_sign(w), b.resize_sign(w));
+                let sa = *aa.0.last().unwrap_or(&0);
+                let sb = *bb.0.last().unwrap_or(&0);
+                if sa != sb {
+                    return if sa == 1 { Ordering::Less } else { Ordering::Greater };
+                }
+                Self::cmp_unsigned(&aa, &bb)
+            }
+        }
+    }
+
+    use reference::Bits;
+
+    /// xorshift64*, deterministic so failures are reproducible
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        /// Widths biased toward limb boundaries
+        fn width(&mut self) -> usize {
+            match self.below(4) {
+                0 => self.below(4),
+                1 => [31, 32, 33, 63, 64, 65, 127, 128, 129][self.below(9)],
+                _ => self.below(200),
+            }
+        }
+
+        fn pair(&mut self) -> (BitVec, Bits) {
+            let width = self.width();
+            let bits: Vec<u8> = (0..width).map(|_| (self.next() & 1) as u8).collect();
+            (BitVec::from_bits_lsb(bits.clone()), Bits(bits))
+        }
+    }
+
+    fn bits_of(v: &BitVec) -> Bits {
+        Bits((0..v.width()).map(|i| v.get(i)).collect())
+    }
+
+    #[test]
+    fn prop_matches_reference() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..3000 {
+            let (a, ra) = rng.pair();
+            let (b, rb) = rng.pair();
+            let w = rng.width();
+            let n = rng.below(a.width() + 70);
+            assert_eq!(bits_of(&a), ra);
+            assert_eq!(bits_of(&a.resize_zero(w)), ra.resize_zero(w));
+            assert_eq!(bits_of(&a.resize_sign(w)), ra.resize_sign(w));
+            assert_eq!(bits_of(&BitVec::concat(&a, &b)), Bits::concat(&ra, &rb));
+            assert_eq!(bits_of(&a.not()), ra.not());
+            assert_eq!(bits_of(&BitVec::and(&a, &b)), Bits::bitwise(&ra, &rb, |x, y| x & y));
+            assert_eq!(bits_of(&BitVec::or(&a, &b)), Bits::bitwise(&ra, &rb, |x, y| x | y));
+            assert_eq!(bits_of(&BitVec::xor(&a, &b)), Bits::bitwise(&ra, &rb, |x, y| x ^ y));
+            assert_eq!(bits_of(&BitVec::add(&a, &b)), Bits::add(&ra, &rb));
+            assert_eq!(bits_of(&BitVec::sub(&a, &b)), Bits::sub(&ra, &rb));
+            assert_eq!(bits_of(&BitVec::shl(&a, n)), Bits::shl(&ra, n));
+            assert_eq!(bits_of(&BitVec::shr(&a, n)), Bits::shr(&ra, n));
+            assert_eq!(BitVec::cmp_unsigned(&a, &b), Bits::cmp_unsigned(&ra, &rb));
+            assert_eq!(BitVec::cmp_signed(&a, &b), Bits::cmp_signed(&ra, &rb));
+            assert_eq!(a == b, ra == rb);
+        }
+    }
+
+    #[test]
+    fn prop_constructors_match_reference() {
+        let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+        for _ in 0..2000 {
+            let w = rng.width();
+            let v = rng.next();
+            let from_u64: Vec<u8> = (0..w).map(|i| if i < 64 { ((v >> i) & 1) as u8 } else { 0 }).collect();
+            assert_eq!(bits_of(&BitVec::from_u64(w, v)), Bits(from_u64));
+            let s = v as i64;
+            let from_i64: Vec<u8> = (0..w).map(|i| ((s as i128 >> i.min(127)) & 1) as u8).collect();
+            assert_eq!(bits_of(&BitVec::from_i64(w, s)), Bits(from_i64));
+
+            let (a, ra) = rng.pair();
+            let msb: String = ra.0.iter().rev().map(|b| if *b == 1 { '1' } else { '0' }).collect();
+            assert_eq!(BitVec::from_bits_msb(&msb).unwrap(), a);
+            let trunc = ra.0.iter().take(64).enumerate().fold(0u64, |acc, (i, b)| acc | ((*b as u64) << i));
+            assert_eq!(a.to_u64_trunc(), trunc);
+        }
+        let hex = BitVec::from_hex_msb("1F").unwrap();
+        assert_eq!(hex, BitVec::from_u64(8, 0x1F));
+        assert!(BitVec::from_bits_msb("102").is_err());
+        assert!(BitVec::from_hex_msb("G").is_err());
+    }
+
+    #[test]
+    fn prop_slices_match_reference() {
+        let mut rng = Rng(0x94D0_49BB_1331_11EB);
+        for _ in 0..2000 {
+            let (mut a, ra) = rng.pair();
+            if a.width() == 0 {
+                continue;
+            }
+            let lo = rng.below(a.width());
+            let len = rng.below(a.width() - lo + 1);
+            let expected = Bits(ra.0[lo..lo + len].to_vec());
+            assert_eq!(bits_of(&a.slice(lo, len)), expected);
+
+            let (v, rv) = {
+                let bits: Vec<u8> = (0..len).map(|_| (rng.next() & 1) as u8).collect();
+                (BitVec::from_bits_lsb(bits.clone()), Bits(bits))
+            };
+            let mut rexp = ra.clone();
+            rexp.0[lo..lo + len].copy_from_slice(&rv.0);
+            a.set_slice(lo, &v);
+            assert_eq!(bits_of(&a), rexp);
+        }
+    }
+
+    #[test]
+    fn test_unused_bits_stay_clear() {
+        let a = BitVec::new(70, 1);
+        assert_eq!(a.not(), BitVec::new(70, 0));
+        assert_eq!(BitVec::add(&a, &BitVec::from_u64(70, 1)), BitVec::new(70, 0));
+        assert_eq!(BitVec::from_i64(3, -1), BitVec::from_u64(3, 7));
+        assert_eq!(BitVec::new(0, 1), BitVec::from_u64(0, 5));
+    }
+}
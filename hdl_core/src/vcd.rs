@@ -0,0 +1,209 @@
+//! Value Change Dump (VCD) recording
+//! Enregistre les changements de signaux du simulateur pour GTKWave
+
+use crate::elab::Signal;
+use crate::value::BitVec;
+use std::fmt::Write;
+
+/// Trace VCD en cours d'enregistrement
+#[derive(Clone, Debug)]
+pub struct VcdTrace {
+    top: String,
+    time: u64,
+    last: Vec<BitVec>,
+    body: String,
+}
+
+impl VcdTrace {
+    /// Crée une trace et enregistre l'état initial des signaux au temps 0
+    pub fn new(top: &str, signals: &[Signal]) -> Self {
+        let mut body = String::from("#0\n$dumpvars\n");
+        for (idx, sig) in signals.iter().enumerate() {
+            push_change(&mut body, idx, &sig.value);
+        }
+        body.push_str("$end\n");
+        Self {
+            top: top.to_string(),
+            time: 0,
+            last: signals.iter().map(|s| s.value.clone()).collect(),
+            body,
+        }
+    }
+
+    /// Avance d'une unité de temps et enregistre les signaux modifiés
+    pub fn sample(&mut self, signals: &[Signal]) {
+        self.time += 1;
+        let mut changes = String::new();
+        for (idx, sig) in signals.iter().enumerate() {
+            if self.last[idx] != sig.value {
+                push_change(&mut changes, idx, &sig.value);
+                self.last[idx] = sig.value.clone();
+            }
+        }
+        let _ = writeln!(self.body, "#{}", self.time);
+        self.body.push_str(&changes);
+    }
+
+    /// Temps courant de la trace (nombre d'échantillons)
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Produit le fichier VCD complet (en-tête + changements)
+    pub fn render(&self, signals: &[Signal]) -> String {
+        let mut out = String::new();
+        out.push_str("$version hdl_core $end\n");
+        out.push_str("$timescale 1ns $end\n");
+        let root = build_scopes(signals);
+        let _ = writeln!(out, "$scope module {} $end", self.top);
+        write_scope(&mut out, &root, signals);
+        out.push_str("$upscope $end\n");
+        out.push_str("$enddefinitions $end\n");
+        out.push_str(&self.body);
+        out
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    name: String,
+    vars: Vec<usize>,
+    children: Vec<Scope>,
+}
+
+/// Regroupe les signaux par instance à partir des noms hiérarchiques `inst/sig`
+fn build_scopes(signals: &[Signal]) -> Scope {
+    let mut root = Scope::default();
+    for (idx, sig) in signals.iter().enumerate() {
+        let mut parts: Vec<&str> = sig.name.split('/').collect();
+        parts.pop();
+        let mut scope = &mut root;
+        for part in parts {
+            let pos = match scope.children.iter().position(|c| c.name == part) {
+                Some(pos) => pos,
+                None => {
+                    scope.children.push(Scope {
+                        name: part.to_string(),
+                        ..Scope::default()
+                    });
+                    scope.children.len() - 1
+                }
+            };
+            scope = &mut scope.children[pos];
+        }
+        scope.vars.push(idx);
+    }
+    root
+}
+
+fn write_scope(out: &mut String, scope: &Scope, signals: &[Signal]) {
+    for &idx in &scope.vars {
+        let sig = &signals[idx];
+        let local = sig.name.rsplit('/').next().unwrap_or(&sig.name);
+        if sig.width == 1 {
+            let _ = writeln!(out, "$var wire 1 {} {} $end", id_code(idx), local);
+        } else {
+            let _ = writeln!(
+                out,
+                "$var wire {} {} {} [{}:{}] $end",
+                sig.width,
+                id_code(idx),
+                local,
+                sig.msb,
+                sig.lsb
+            );
+        }
+    }
+    for child in &scope.children {
+        let _ = writeln!(out, "$scope module {} $end", child.name);
+        write_scope(out, child, signals);
+        out.push_str("$upscope $end\n");
+    }
+}
+
+fn push_change(out: &mut String, idx: usize, value: &BitVec) {
+    if value.width() == 1 {
+        let _ = writeln!(out, "{}{}", value.get(0), id_code(idx));
+    } else {
+        out.push('b');
+        for i in (0..value.width()).rev() {
+            out.push(if value.get(i) == 0 { '0' } else { '1' });
+        }
+        let _ = writeln!(out, " {}", id_code(idx));
+    }
+}
+
+/// Identifiant VCD compact (caractères ASCII imprimables `!`..`~`)
+fn id_code(mut idx: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (idx % 94) as u8) as char);
+        idx /= 94;
+        if idx == 0 {
+            break;
+        }
+        idx -= 1;
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elab::elaborate;
+    use crate::parser::parse_str;
+    use crate::sim::Simulator;
+
+    #[test]
+    fn test_id_codes_are_unique() {
+        let codes: std::collections::HashSet<String> = (0..20000).map(id_code).collect();
+        assert_eq!(codes.len(), 20000);
+        assert_eq!(id_code(0), "!");
+        assert_eq!(id_code(93), "~");
+    }
+
+    #[test]
+    fn test_vcd_records_changes_with_scopes() {
+        let hdl = r#"
+entity Inv is
+  port(a : in bit; y : out bit);
+end entity;
+
+architecture rtl of Inv is
+  component nand2
+    port(a : in bit; b : in bit; y : out bit);
+  end component;
+begin
+  u0: nand2 port map (a => a, b => a, y => y);
+end architecture;
+
+entity Top is
+  port(a : in bits(1 downto 0); y : out bit);
+end entity;
+
+architecture rtl of Top is
+  component Inv
+    port(a : in bit; y : out bit);
+  end component;
+begin
+  u_inv: Inv port map (a => a(0), y => y);
+end architecture;
+"#;
+        let design = parse_str(hdl).unwrap();
+        let netlist = elaborate(&design, "Top").unwrap();
+        let mut sim = Simulator::new(netlist);
+        sim.start_vcd("Top");
+        sim.eval_comb().unwrap();
+        sim.set_signal("a", BitVec::from_u64(2, 1)).unwrap();
+        sim.eval_comb().unwrap();
+
+        let vcd = sim.vcd().unwrap();
+        assert!(vcd.contains("$scope module Top $end"));
+        assert!(vcd.contains("$scope module u_inv $end"));
+        assert!(vcd.contains("$var wire 2 ! a [1:0] $end"));
+        assert!(vcd.contains("$enddefinitions $end"));
+        // y passe à 1 au premier eval, puis à 0 quand a(0) = 1
+        assert!(vcd.contains("#1\n1\""));
+        assert!(vcd.contains("#2\nb01 !\n0\"\n1#\n"));
+    }
+}
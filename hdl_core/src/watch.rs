@@ -0,0 +1,414 @@
+//! Conditions des points d'arrêt du simulateur.
+//!
+//! Une condition est une expression entière sur les signaux, évaluée après
+//! chaque cycle : `pc = 0x40`, `state /= 0 and ready`, `ir(15 downto 12) == 0b1010`.
+//! Les signaux sont désignés par leur nom de net ou par un chemin
+//! hiérarchique (`cpu/alu/y`), avec une sélection de bits optionnelle
+//! (`pc(3)`, `pc(7 downto 4)`). Les valeurs sont lues sur 64 bits au plus ;
+//! une condition est vraie si elle est non nulle.
+//!
+//! Opérateurs par priorité croissante : `or ||`, `and &&`, `|`, `^`, `&`,
+//! `= == /= !=`, `< <= > >=`, `<< >>`, `+ -`, `*`, puis les unaires
+//! `not ! ~ -`. `/` sépare les niveaux d'un chemin, il n'y a pas de division.
+
+use crate::ast::{RangeDir, Selector};
+use crate::elab::{Signal, TargetRef};
+use crate::error::Error;
+use crate::graph::target_span;
+use crate::value::BitVec;
+
+#[derive(Clone, Debug)]
+enum Cond {
+    Num(u64),
+    /// Bits `lo..=hi` d'un signal
+    Signal { signal: usize, lo: usize, hi: usize },
+    Unary(&'static str, Box<Cond>),
+    Binary(&'static str, Box<Cond>, Box<Cond>),
+}
+
+/// Condition compilée : les noms sont résolus une fois pour toutes
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub text: String,
+    expr: Cond,
+}
+
+impl Condition {
+    /// Compile `text` ; `resolve` donne le net désigné par un nom
+    pub fn parse(text: &str, signals: &[Signal], resolve: &dyn Fn(&str) -> Result<usize, Error>) -> Result<Self, Error> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0, signals, resolve };
+        let expr = parser.binary(0)?;
+        if parser.pos < tokens.len() {
+            return Err(Error::new(format!("invalid condition {}", text)));
+        }
+        Ok(Condition { text: text.trim().to_string(), expr })
+    }
+
+    /// Signaux lus par la condition
+    pub fn signals(&self) -> Vec<usize> {
+        let mut out = Vec::new();
+        collect_signals(&self.expr, &mut out);
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+
+    pub fn eval(&self, signals: &[Signal]) -> u64 {
+        eval(&self.expr, signals)
+    }
+
+    pub fn holds(&self, signals: &[Signal]) -> bool {
+        self.eval(signals) != 0
+    }
+}
+
+fn collect_signals(expr: &Cond, out: &mut Vec<usize>) {
+    match expr {
+        Cond::Num(_) => {}
+        Cond::Signal { signal, .. } => out.push(*signal),
+        Cond::Unary(_, e) => collect_signals(e, out),
+        Cond::Binary(_, l, r) => {
+            collect_signals(l, out);
+            collect_signals(r, out);
+        }
+    }
+}
+
+fn eval(expr: &Cond, signals: &[Signal]) -> u64 {
+    match expr {
+        Cond::Num(n) => *n,
+        Cond::Signal { signal, lo, hi } => signals[*signal].value.slice(*lo, hi - lo + 1).to_u64_trunc(),
+        Cond::Unary(op, e) => {
+            let v = eval(e, signals);
+            match *op {
+                "-" => v.wrapping_neg(),
+                "~" => !v,
+                _ => (v == 0) as u64,
+            }
+        }
+        Cond::Binary(op, l, r) => {
+            let a = eval(l, signals);
+            // Court-circuit des opérateurs logiques
+            match *op {
+                "or" if a != 0 => return 1,
+                "and" if a == 0 => return 0,
+                _ => {}
+            }
+            let b = eval(r, signals);
+            let shift = u32::try_from(b).ok().filter(|&s| s < 64);
+            match *op {
+                "or" | "and" => (b != 0) as u64,
+                "|" => a | b,
+                "^" => a ^ b,
+                "&" => a & b,
+                "=" => (a == b) as u64,
+                "/=" => (a != b) as u64,
+                "<" => (a < b) as u64,
+                "<=" => (a <= b) as u64,
+                ">" => (a > b) as u64,
+                ">=" => (a >= b) as u64,
+                "<<" => shift.map_or(0, |s| a << s),
+                ">>" => shift.map_or(0, |s| a >> s),
+                "+" => a.wrapping_add(b),
+                "-" => a.wrapping_sub(b),
+                _ => a.wrapping_mul(b),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Num(u64),
+    /// Nom de signal et sélection de bits éventuelle
+    Name(String, Option<Selector>),
+    Op(&'static str),
+}
+
+/// Opérateurs, les plus longs d'abord ; chaque graphie est ramenée à une
+/// forme canonique
+const OPS: [(&str, &str); 22] = [
+    ("&&", "and"),
+    ("||", "or"),
+    ("==", "="),
+    ("!=", "/="),
+    ("/=", "/="),
+    ("<<", "<<"),
+    (">>", ">>"),
+    ("<=", "<="),
+    (">=", ">="),
+    ("=", "="),
+    ("<", "<"),
+    (">", ">"),
+    ("&", "&"),
+    ("|", "|"),
+    ("^", "^"),
+    ("~", "~"),
+    ("!", "not"),
+    ("+", "+"),
+    ("-", "-"),
+    ("*", "*"),
+    ("(", "("),
+    (")", ")"),
+];
+
+/// Opérateurs binaires par priorité croissante
+const LEVELS: [&[&str]; 10] = [
+    &["or"],
+    &["and"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["=", "/="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*"],
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let invalid = || Error::new(format!("invalid condition {}", text));
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().filter(|&&c| c != '_').collect::<String>().to_lowercase();
+            let value = if let Some(hex) = word.strip_prefix("0x") {
+                u64::from_str_radix(hex, 16)
+            } else if let Some(bin) = word.strip_prefix("0b") {
+                u64::from_str_radix(bin, 2)
+            } else {
+                word.parse()
+            };
+            tokens.push(Token::Num(value.map_err(|_| invalid())?));
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '.' {
+            let (token, next) = name(&chars, i).ok_or_else(invalid)?;
+            tokens.push(token);
+            i = next;
+        } else {
+            let rest: String = chars[i..].iter().collect();
+            let (spelling, op) = OPS
+                .iter()
+                .find(|(s, _)| rest.starts_with(s))
+                .ok_or_else(invalid)?;
+            tokens.push(Token::Op(op));
+            i += spelling.len();
+        }
+    }
+    Ok(tokens)
+}
+
+/// Lit un nom à partir de `start` : chemin (`cpu/alu/y`, `g(2)/fa/s`) puis
+/// sélection optionnelle (`(3)`, `(7 downto 4)`) ; `and`, `or` et `not`
+/// sont des opérateurs
+fn name(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    let mut i = start;
+    loop {
+        while i < chars.len() && ident(chars[i]) {
+            i += 1;
+        }
+        // `(n)` suivi de `/` : itération de generate dans le chemin
+        if chars.get(i) == Some(&'(') {
+            let close = (i..chars.len()).find(|&j| chars[j] == ')')?;
+            let inner: String = chars[i + 1..close].iter().collect();
+            if chars.get(close + 1) == Some(&'/') && inner.trim().parse::<i64>().is_ok() {
+                i = close + 1;
+            }
+        }
+        // `/` suivi d'un nom : niveau suivant du chemin (`/=` est un opérateur)
+        if chars.get(i) == Some(&'/') && chars.get(i + 1).is_some_and(|&c| c.is_ascii_alphabetic() || c == '_' || c == '.') {
+            i += 1;
+            continue;
+        }
+        break;
+    }
+    let word: String = chars[start..i].iter().collect();
+    match word.to_ascii_lowercase().as_str() {
+        "and" => return Some((Token::Op("and"), i)),
+        "or" => return Some((Token::Op("or"), i)),
+        "not" => return Some((Token::Op("not"), i)),
+        _ => {}
+    }
+    // Sélection de bits : `(n)` ou `(a downto b)` / `(a to b)`
+    let mut j = i;
+    while chars.get(j).is_some_and(|c| c.is_whitespace()) {
+        j += 1;
+    }
+    if chars.get(j) == Some(&'(') {
+        let close = (j..chars.len()).find(|&k| chars[k] == ')')?;
+        let inner: String = chars[j + 1..close].iter().collect();
+        let words: Vec<&str> = inner.split_whitespace().collect();
+        let num = |s: &str| s.parse::<i64>().ok();
+        let sel = match words.as_slice() {
+            [index] => Selector::Index(num(index)?),
+            [msb, dir, lsb] if dir.eq_ignore_ascii_case("downto") => Selector::Range {
+                msb: num(msb)?,
+                lsb: num(lsb)?,
+                dir: RangeDir::Downto,
+            },
+            [msb, dir, lsb] if dir.eq_ignore_ascii_case("to") => Selector::Range {
+                msb: num(msb)?,
+                lsb: num(lsb)?,
+                dir: RangeDir::To,
+            },
+            // Pas une sélection : une parenthèse d'expression
+            _ => return Some((Token::Name(word, None), i)),
+        };
+        return Some((Token::Name(word, Some(sel)), close + 1));
+    }
+    Some((Token::Name(word, None), i))
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    signals: &'a [Signal],
+    resolve: &'a dyn Fn(&str) -> Result<usize, Error>,
+}
+
+impl Parser<'_> {
+    fn binary(&mut self, level: usize) -> Result<Cond, Error> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            if !ops.contains(op) {
+                break;
+            }
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Cond::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Cond, Error> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Num(n)) => Ok(Cond::Num(n)),
+            Some(Token::Name(name, sel)) => {
+                let signal = (self.resolve)(&name)?;
+                let width = self.signals[signal].width;
+                if let Some(Selector::Index(i)) | Some(Selector::Range { msb: i, .. }) = &sel {
+                    let sig = &self.signals[signal];
+                    let (min, max) = (sig.lsb.min(sig.msb), sig.lsb.max(sig.msb));
+                    let lsb = match &sel {
+                        Some(Selector::Range { lsb, .. }) => *lsb,
+                        _ => *i,
+                    };
+                    if [*i, lsb].iter().any(|&b| b < min || b > max) {
+                        return Err(Error::new(format!("index out of range for {} ({} bits)", name, width)));
+                    }
+                    if let Some(Selector::Range { msb, lsb, dir }) = &sel {
+                        let null = match dir {
+                            RangeDir::Downto => msb < lsb,
+                            RangeDir::To => msb > lsb,
+                        };
+                        if null {
+                            return Err(Error::new(format!("null range for {}", name)));
+                        }
+                    }
+                }
+                let span = target_span(&TargetRef { signal, sel }, self.signals);
+                Ok(Cond::Signal { signal, lo: span.lo, hi: span.hi })
+            }
+            Some(Token::Op(op @ ("not" | "~" | "-"))) => Ok(Cond::Unary(op, Box::new(self.unary()?))),
+            Some(Token::Op("(")) => {
+                let expr = self.binary(0)?;
+                if !matches!(self.tokens.get(self.pos), Some(Token::Op(")"))) {
+                    return Err(Error::new("missing )"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            _ => Err(Error::new("invalid condition")),
+        }
+    }
+}
+
+/// Ce qui arrête `Simulator::run_until_break`
+#[derive(Clone, Debug)]
+pub(crate) enum Breakpoint {
+    /// Condition vraie après un cycle
+    Condition(Condition),
+    /// Valeur d'un signal différente de celle du cycle précédent
+    Watch { signal: usize, last: BitVec },
+}
+
+/// Résultat d'une exécution jusqu'à un point d'arrêt
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunOutcome {
+    /// Cycles exécutés
+    pub cycles: u64,
+    /// Points d'arrêt atteints au dernier cycle ; vide si la limite de
+    /// cycles a été atteinte avant
+    pub hits: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(name: &str, width: usize, value: u64) -> Signal {
+        Signal {
+            name: name.to_string(),
+            width,
+            msb: width as i64 - 1,
+            lsb: 0,
+            dir: RangeDir::Downto,
+            value: BitVec::from_u64(width, value),
+            port_dir: None,
+            ty: Default::default(),
+        }
+    }
+
+    fn parse(text: &str, signals: &[Signal]) -> Result<Condition, Error> {
+        let resolve = |name: &str| {
+            signals
+                .iter()
+                .position(|s| s.name == name)
+                .ok_or_else(|| Error::new(format!("unknown signal {}", name)))
+        };
+        Condition::parse(text, signals, &resolve)
+    }
+
+    #[test]
+    fn test_condition_eval() {
+        let signals = vec![signal("pc", 16, 0x40), signal("ready", 1, 1), signal("cpu/alu/y", 8, 0xA5)];
+        let cases = [
+            ("pc = 0x40", 1),
+            ("pc /= 64", 0),
+            ("pc(6) and ready", 1),
+            ("pc(7 downto 4) == 0b0100 && !ready", 0),
+            ("cpu/alu/y(3 downto 0) + 1", 6),
+            ("(pc >> 4) * 2 - 1", 7),
+            ("not ready or cpu/alu/y < 0xA6", 1),
+            ("~cpu/alu/y & 0xFF ^ 1", 0x5B),
+        ];
+        for (text, expected) in cases {
+            let cond = parse(text, &signals).unwrap();
+            assert_eq!(cond.eval(&signals), expected, "{}", text);
+        }
+        assert_eq!(parse("pc = pc(3) or cpu/alu/y", &signals).unwrap().signals(), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_condition_errors() {
+        let signals = vec![signal("pc", 8, 0)];
+        for text in ["", "pc =", "(pc = 1", "pc(8)", "pc(2 downto 5)", "sp = 0", "pc = 1 1"] {
+            assert!(parse(text, &signals).is_err(), "{}", text);
+        }
+    }
+}
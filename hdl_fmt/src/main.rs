@@ -0,0 +1,103 @@
+use hdl_core::format::format_source;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage: hdl_fmt [--check] <file.hdl | dir>...
+       hdl_fmt -    (formats stdin to stdout)
+       hdl_fmt --help";
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Formats the given files in place; with `--check`, only lists the files
+/// that would change. Returns false if a file could not be parsed or, with
+/// `--check`, is not formatted.
+fn run() -> Result<bool, Box<dyn std::error::Error>> {
+    let mut check = false;
+    let mut targets = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(true);
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+            _ => targets.push(arg),
+        }
+    }
+    if targets.is_empty() {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    }
+    if targets == ["-"] {
+        let mut src = String::new();
+        io::stdin().read_to_string(&mut src)?;
+        let out = format_source(&src).map_err(|err| format!("<stdin>: {}", err))?;
+        if check {
+            return Ok(out == src);
+        }
+        print!("{}", out);
+        return Ok(true);
+    }
+
+    let mut files = Vec::new();
+    for target in &targets {
+        let path = Path::new(target);
+        if path.is_dir() {
+            collect_sources(path, &mut files)?;
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    let mut ok = true;
+    for file in &files {
+        let src = fs::read_to_string(file).map_err(|err| format!("{}: {}", file.display(), err))?;
+        let out = match format_source(&src) {
+            Ok(out) => out,
+            Err(err) => {
+                eprintln!("{}: {}", file.display(), err);
+                ok = false;
+                continue;
+            }
+        };
+        if out == src {
+            continue;
+        }
+        if check {
+            println!("{}", file.display());
+            ok = false;
+        } else {
+            fs::write(file, out).map_err(|err| format!("{}: {}", file.display(), err))?;
+            println!("formatted {}", file.display());
+        }
+    }
+    Ok(ok)
+}
+
+/// `.hdl` files under `dir`, recursively, in name order
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_sources(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "hdl") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+const FORMATTED: &str = "entity NotGate is
+  port(
+    a : in  bit;
+    y : out bit
+  );
+end entity;
+";
+
+const UNFORMATTED: &str = "ENTITY NotGate IS PORT(a: in bit; y: out bit); END ENTITY;\n";
+
+/// Empty scratch directory for one test
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hdl_fmt_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn hdl_fmt(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hdl_fmt")).args(args).output().unwrap()
+}
+
+#[test]
+fn test_check_exit_status() {
+    let dir = scratch("check");
+    let good = dir.join("Good.hdl");
+    let bad = dir.join("sub/Bad.hdl");
+    let broken = dir.join("Broken.hdl");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(&good, FORMATTED).unwrap();
+    fs::write(&bad, UNFORMATTED).unwrap();
+    fs::write(&broken, "entity is\n").unwrap();
+
+    let ok = hdl_fmt(&["--check", good.to_str().unwrap()]);
+    assert_eq!(ok.status.code(), Some(0));
+    assert!(ok.stdout.is_empty());
+
+    // A directory is searched recursively; the file is listed, not rewritten
+    let changed = hdl_fmt(&["--check", dir.join("sub").to_str().unwrap()]);
+    assert_eq!(changed.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&changed.stdout), format!("{}\n", bad.display()));
+    assert_eq!(fs::read_to_string(&bad).unwrap(), UNFORMATTED);
+
+    let error = hdl_fmt(&["--check", broken.to_str().unwrap()]);
+    assert_eq!(error.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&error.stderr).starts_with(&format!("{}: ", broken.display())));
+
+    // Without --check the file is rewritten, and then passes the check
+    assert_eq!(hdl_fmt(&[bad.to_str().unwrap()]).status.code(), Some(0));
+    assert_eq!(fs::read_to_string(&bad).unwrap(), FORMATTED);
+    assert_eq!(hdl_fmt(&["--check", bad.to_str().unwrap()]).status.code(), Some(0));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hdl_fmt"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(UNFORMATTED.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), FORMATTED);
+}
+
+#[test]
+fn test_help_and_usage() {
+    let help = hdl_fmt(&["--help"]);
+    assert_eq!(help.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&help.stdout).starts_with("usage: hdl_fmt"));
+    assert_eq!(hdl_fmt(&[]).status.code(), Some(2));
+    let unknown = hdl_fmt(&["--chek", "x.hdl"]);
+    assert_eq!(unknown.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&unknown.stderr).starts_with("unknown option --chek"));
+}
@@ -0,0 +1,302 @@
+//! Just enough JSON for the language server protocol messages
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Object with the given members, in order
+pub fn obj(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+pub fn str(s: impl Into<String>) -> Json {
+    Json::Str(s.into())
+}
+
+pub fn num(n: usize) -> Json {
+    Json::Num(n as f64)
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            idx: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_ws();
+        if parser.idx != parser.chars.len() {
+            return Err(format!("trailing characters at {}", parser.idx));
+        }
+        Ok(value)
+    }
+
+    /// Member `key` of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Member at a path of keys (`["textDocument", "uri"]`)
+    pub fn at(&self, path: &[&str]) -> Option<&Json> {
+        path.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Num(n) if *n >= 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Num(n) => write!(f, "{}", n),
+            Json::Str(s) => write_str(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Parser {
+    chars: Vec<char>,
+    idx: usize,
+}
+
+impl Parser {
+    fn skip_ws(&mut self) {
+        while self.chars.get(self.idx).is_some_and(|c| c.is_whitespace()) {
+            self.idx += 1;
+        }
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = *self.chars.get(self.idx).ok_or("unexpected end of JSON")?;
+        self.idx += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_ws();
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(format!("expected '{}', found '{}' at {}", expected, c, self.idx - 1)),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            if self.next()? != expected {
+                return Err(format!("invalid literal at {}", self.idx - 1));
+            }
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        match self.chars.get(self.idx).copied().ok_or("unexpected end of JSON")? {
+            'n' => self.keyword("null", Json::Null),
+            't' => self.keyword("true", Json::Bool(true)),
+            'f' => self.keyword("false", Json::Bool(false)),
+            '"' => Ok(Json::Str(self.string()?)),
+            '[' => {
+                self.idx += 1;
+                let mut items = Vec::new();
+                self.skip_ws();
+                if self.chars.get(self.idx) == Some(&']') {
+                    self.idx += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_ws();
+                    match self.next()? {
+                        ',' => continue,
+                        ']' => return Ok(Json::Array(items)),
+                        c => return Err(format!("expected ',' or ']', found '{}'", c)),
+                    }
+                }
+            }
+            '{' => {
+                self.idx += 1;
+                let mut members = Vec::new();
+                self.skip_ws();
+                if self.chars.get(self.idx) == Some(&'}') {
+                    self.idx += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_ws();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    self.skip_ws();
+                    match self.next()? {
+                        ',' => continue,
+                        '}' => return Ok(Json::Object(members)),
+                        c => return Err(format!("expected ',' or '}}', found '{}'", c)),
+                    }
+                }
+            }
+            _ => {
+                let start = self.idx;
+                while self.chars.get(self.idx).is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                    self.idx += 1;
+                }
+                let text: String = self.chars[start..self.idx].iter().collect();
+                text.parse().map(Json::Num).map_err(|_| format!("invalid number at {}", start))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(out),
+                '\\' => match self.next()? {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'b' => out.push('\u{8}'),
+                    'f' => out.push('\u{c}'),
+                    'u' => {
+                        let mut code = self.hex4()?;
+                        // Surrogate pair
+                        if (0xd800..0xdc00).contains(&code) && self.chars.get(self.idx..self.idx + 2) == Some(&['\\', 'u']) {
+                            self.idx += 2;
+                            code = 0x10000 + ((code - 0xd800) << 10) + self.hex4()?.saturating_sub(0xdc00);
+                        }
+                        out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    c => out.push(c),
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next()?.to_digit(16).ok_or("invalid \\u escape")?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        let text = r#"{"jsonrpc": "2.0", "id": 1,
+            "params": {"position": {"line": 3, "character": 7}, "uri": "file:///a.hdl"},
+            "items": [1, -2.5e1, true, false, null, [], {}]}"#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(json.get("id"), Some(&Json::Num(1.0)));
+        assert_eq!(json.at(&["params", "position", "line"]).and_then(Json::as_usize), Some(3));
+        assert_eq!(json.at(&["params", "uri"]).and_then(Json::as_str), Some("file:///a.hdl"));
+        assert_eq!(json.at(&["params", "missing"]), None);
+        assert_eq!(
+            json.get("items").and_then(Json::as_array).unwrap(),
+            [
+                Json::Num(1.0),
+                Json::Num(-25.0),
+                Json::Bool(true),
+                Json::Bool(false),
+                Json::Null,
+                Json::Array(Vec::new()),
+                Json::Object(Vec::new())
+            ]
+        );
+        assert_eq!(Json::Num(-1.0).as_usize(), None);
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let json = Json::parse(r#""a\n\t\"\\\/é𝄞""#).unwrap();
+        assert_eq!(json, str("a\n\t\"\\/é𝄞"));
+        assert_eq!(Json::parse(r#""\u00e9\ud834\udd1e""#).unwrap(), str("é𝄞"));
+        let value = obj(vec![("s", str("q\"\\\n\u{1}é")), ("n", num(42)), ("f", Json::Num(0.5))]);
+        let text = value.to_string();
+        assert_eq!(text, r#"{"s":"q\"\\\n\u0001é","n":42,"f":0.5}"#);
+        assert_eq!(Json::parse(&text).unwrap(), value);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in ["", "{", "[1,]", "tru", "1 2", r#"{"a" 1}"#, r#""\u12""#, r#"{"a":1,}"#] {
+            assert!(Json::parse(text).is_err(), "{}", text);
+        }
+    }
+}
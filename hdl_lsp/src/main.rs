@@ -0,0 +1,473 @@
+//! Language server for .hdl files over stdio: diagnostics on open and
+//! change, hover, go-to-definition and port completion in `port map`.
+//! The project sources are the `--lib` directories, then the workspace
+//! folders; without either, the directory of the edited file.
+
+mod json;
+
+use hdl_core::error::Span;
+use hdl_core::ide::{completions, definition, diagnostics, hover, Diagnostic};
+use hdl_core::library::{SearchPath, SourceProvider};
+use hdl_core::{msg, Error};
+use json::{num, obj, str, Json};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage: hdl_lsp [--lib <dir>]...";
+
+fn main() {
+    let mut lib_dirs = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--lib", Some(dir)) => lib_dirs.push(PathBuf::from(dir)),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    let mut server = Server {
+        lib_dirs,
+        roots: Vec::new(),
+        docs: HashMap::new(),
+        listings: RefCell::new(HashMap::new()),
+        shutdown: false,
+    };
+    if let Err(err) = server.run() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+struct Server {
+    lib_dirs: Vec<PathBuf>,
+    /// Workspace folders sent by the client
+    roots: Vec<PathBuf>,
+    /// Text of the open documents, by URI
+    docs: HashMap<String, String>,
+    /// `.hdl` files under each search directory, listed once; cleared
+    /// when a save or a watched file change may have added or removed one
+    listings: RefCell<HashMap<PathBuf, Vec<String>>>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn run(&mut self) -> io::Result<()> {
+        let mut input = BufReader::new(io::stdin().lock());
+        while let Some(body) = read_message(&mut input)? {
+            let message = match Json::parse(&body) {
+                Ok(message) => message,
+                Err(err) => {
+                    eprintln!("hdl_lsp: invalid message: {}", err);
+                    continue;
+                }
+            };
+            let method = message.get("method").and_then(Json::as_str).unwrap_or_default();
+            let params = message.get("params").cloned().unwrap_or(Json::Null);
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.request(method, &params) {
+                        Ok(result) => obj(vec![("jsonrpc", str("2.0")), ("id", id.clone()), ("result", result)]),
+                        Err((code, text)) => obj(vec![
+                            ("jsonrpc", str("2.0")),
+                            ("id", id.clone()),
+                            ("error", obj(vec![("code", Json::Num(code as f64)), ("message", str(text))])),
+                        ]),
+                    };
+                    write_message(&response)?;
+                }
+                None if method == "exit" => std::process::exit(if self.shutdown { 0 } else { 1 }),
+                None => self.notification(method, &params)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        match method {
+            "initialize" => {
+                let folders = params.get("workspaceFolders").and_then(Json::as_array).unwrap_or_default();
+                let uris = folders.iter().filter_map(|f| f.get("uri")).chain(params.get("rootUri"));
+                self.roots = uris.filter_map(Json::as_str).filter_map(uri_to_path).collect();
+                self.roots.dedup();
+                Ok(obj(vec![
+                    (
+                        "capabilities",
+                        obj(vec![
+                            ("textDocumentSync", num(1)),
+                            ("hoverProvider", Json::Bool(true)),
+                            ("definitionProvider", Json::Bool(true)),
+                            ("completionProvider", obj(vec![("triggerCharacters", Json::Array(vec![str("("), str(",")]))])),
+                        ]),
+                    ),
+                    ("serverInfo", obj(vec![("name", str("hdl_lsp"))])),
+                ]))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => {
+                let Some((uri, src, pos)) = self.position(params) else {
+                    return Ok(Json::Null);
+                };
+                let mut project = self.project(&uri);
+                Ok(match hover(&src, pos, &mut project) {
+                    Some(text) => obj(vec![(
+                        "contents",
+                        obj(vec![("kind", str("markdown")), ("value", str(format!("```vhdl\n{}\n```", text)))]),
+                    )]),
+                    None => Json::Null,
+                })
+            }
+            "textDocument/definition" => {
+                let Some((uri, src, pos)) = self.position(params) else {
+                    return Ok(Json::Null);
+                };
+                let mut project = self.project(&uri);
+                let Some(location) = definition(&src, pos, &mut project) else {
+                    return Ok(Json::Null);
+                };
+                let (target, text) = match location.file {
+                    None => (uri, src),
+                    Some(file) => {
+                        let text = project.read(&file).unwrap_or_default();
+                        (path_to_uri(Path::new(&file)), text)
+                    }
+                };
+                let start = to_position(&text, location.span);
+                Ok(obj(vec![("uri", str(target)), ("range", obj(vec![("start", start.clone()), ("end", start)]))]))
+            }
+            "textDocument/completion" => {
+                let Some((uri, src, pos)) = self.position(params) else {
+                    return Ok(Json::Array(Vec::new()));
+                };
+                let items = completions(&src, pos, &mut self.project(&uri))
+                    .into_iter()
+                    .map(|item| {
+                        obj(vec![
+                            ("label", str(&item.label)),
+                            // Field
+                            ("kind", num(5)),
+                            ("detail", str(item.detail)),
+                            ("insertText", str(format!("{} => ", item.label))),
+                        ])
+                    })
+                    .collect();
+                Ok(Json::Array(items))
+            }
+            _ => Err((-32601, format!("unsupported method {}", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let uri = params.at(&["textDocument", "uri"]).and_then(Json::as_str).unwrap_or_default().to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.at(&["textDocument", "text"]).and_then(Json::as_str).unwrap_or_default();
+                self.docs.insert(uri, text.to_string());
+                self.publish_all()
+            }
+            "textDocument/didChange" => {
+                // Full synchronization: the last change holds the whole text
+                let changes = params.get("contentChanges").and_then(Json::as_array).unwrap_or_default();
+                if let Some(text) = changes.last().and_then(|c| c.get("text")).and_then(Json::as_str) {
+                    self.docs.insert(uri, text.to_string());
+                }
+                self.publish_all()
+            }
+            // Other open files may use what was just saved
+            "textDocument/didSave" | "workspace/didChangeWatchedFiles" => {
+                self.listings.borrow_mut().clear();
+                self.publish_all()
+            }
+            "textDocument/didClose" => {
+                self.docs.remove(&uri);
+                publish(&uri, "", Vec::new())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Republishes the diagnostics of every open document, since an edit
+    /// can fix or break the files that instantiate it
+    fn publish_all(&self) -> io::Result<()> {
+        let mut uris: Vec<&String> = self.docs.keys().collect();
+        uris.sort();
+        for uri in uris {
+            let src = &self.docs[uri];
+            publish(uri, src, diagnostics(src, &mut self.project(uri)))?;
+        }
+        Ok(())
+    }
+
+    /// URI, text and cursor of a `TextDocumentPositionParams`
+    fn position(&self, params: &Json) -> Option<(String, String, Span)> {
+        let uri = params.at(&["textDocument", "uri"])?.as_str()?;
+        let src = self.docs.get(uri)?.clone();
+        let line = params.at(&["position", "line"])?.as_usize()?;
+        let character = params.at(&["position", "character"])?.as_usize()?;
+        let pos = to_span(&src, line, character);
+        Some((uri.to_string(), src, pos))
+    }
+
+    /// Sources seen from the document `uri`: open documents first, then
+    /// the files of the search path
+    fn project(&self, uri: &str) -> Project<'_> {
+        let mut dirs: Vec<PathBuf> = self.lib_dirs.iter().chain(&self.roots).cloned().collect();
+        if dirs.is_empty() {
+            dirs.extend(uri_to_path(uri).and_then(|p| p.parent().map(Path::to_path_buf)));
+        }
+        let mut listings = self.listings.borrow_mut();
+        let mut files = Vec::new();
+        for dir in dirs {
+            let listed = listings.entry(dir.clone()).or_insert_with(|| {
+                // A missing directory only hides its files
+                let found = SearchPath::new(vec![dir]).files().unwrap_or_default();
+                found.iter().map(|file| absolute(file)).collect()
+            });
+            files.extend(listed.iter().cloned());
+        }
+        Project {
+            files,
+            open: self
+                .docs
+                .iter()
+                .filter_map(|(uri, text)| Some((absolute(&uri_to_path(uri)?.display().to_string()), text.as_str())))
+                .collect(),
+        }
+    }
+}
+
+struct Project<'a> {
+    /// Files of the search path, absolute
+    files: Vec<String>,
+    /// Path and unsaved text of the open documents
+    open: Vec<(String, &'a str)>,
+}
+
+impl SourceProvider for Project<'_> {
+    fn files(&mut self) -> Result<Vec<String>, Error> {
+        let mut files: Vec<String> = self.open.iter().map(|(path, _)| path.clone()).collect();
+        files.sort();
+        for path in &self.files {
+            if !files.contains(path) {
+                files.push(path.clone());
+            }
+        }
+        Ok(files)
+    }
+
+    fn read(&mut self, file: &str) -> Result<String, Error> {
+        match self.open.iter().find(|(path, _)| path == file) {
+            Some((_, text)) => Ok(text.to_string()),
+            None => std::fs::read_to_string(file).map_err(|e| Error::new(format!("{}: {}", file, e))),
+        }
+    }
+}
+
+fn absolute(file: &str) -> String {
+    std::fs::canonicalize(file).map_or_else(|_| file.to_string(), |p| p.display().to_string())
+}
+
+fn publish(uri: &str, src: &str, diags: Vec<Diagnostic>) -> io::Result<()> {
+    let items = diags
+        .into_iter()
+        .map(|diag| {
+            let end = Span {
+                line: diag.span.line,
+                col: diag.span.col + diag.len,
+            };
+            // The French explanation follows the message
+            let message = match diag.code {
+                Some(code) => format!("{}\n{}", diag.message, msg(code)),
+                None => diag.message,
+            };
+            let mut fields = vec![
+                ("range", obj(vec![("start", to_position(src, diag.span)), ("end", to_position(src, end))])),
+                // Error
+                ("severity", num(1)),
+                ("source", str("hdl")),
+                ("message", str(message)),
+            ];
+            if let Some(code) = diag.code {
+                fields.push(("code", str(code.as_str())));
+            }
+            obj(fields)
+        })
+        .collect();
+    write_message(&obj(vec![
+        ("jsonrpc", str("2.0")),
+        ("method", str("textDocument/publishDiagnostics")),
+        ("params", obj(vec![("uri", str(uri)), ("diagnostics", Json::Array(items))])),
+    ]))
+}
+
+/// Body of the next `Content-Length` framed message; `None` at end of input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    let mut out = io::stdout().lock();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+/// Span of an LSP position (0-based line, UTF-16 character offset)
+fn to_span(src: &str, line: usize, character: usize) -> Span {
+    let text = src.lines().nth(line).unwrap_or_default();
+    let mut units = 0;
+    let col = text.chars().take_while(|c| {
+        units += c.len_utf16();
+        units <= character
+    });
+    Span {
+        line: line + 1,
+        col: col.count() + 1,
+    }
+}
+
+fn to_position(src: &str, span: Span) -> Json {
+    let text = src.lines().nth(span.line.saturating_sub(1)).unwrap_or_default();
+    let character: usize = text.chars().take(span.col.saturating_sub(1)).map(char::len_utf16).sum();
+    let extra = span.col.saturating_sub(1).saturating_sub(text.chars().count());
+    obj(vec![("line", num(span.line.saturating_sub(1))), ("character", num(character + extra))])
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(out).ok()?))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for byte in path.display().to_string().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_message_framing() {
+        let input = "Content-Length: 7\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{\"a\":1}content-length: 2\r\n\r\n[]";
+        let mut input = Cursor::new(input);
+        assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("{\"a\":1}"));
+        assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("[]"));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let err = read_message(&mut Cursor::new("X-Other: 1\r\n\r\n{}")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read_message(&mut Cursor::new("Content-Length: 10\r\n\r\n{}")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        // `é` is one UTF-16 unit, `𝄞` two
+        let src = "-- é𝄞\nsignal x : bit;";
+        assert_eq!(to_span(src, 0, 3), Span { line: 1, col: 4 });
+        assert_eq!(to_span(src, 0, 4), Span { line: 1, col: 5 });
+        assert_eq!(to_span(src, 0, 6), Span { line: 1, col: 6 });
+        assert_eq!(to_span(src, 1, 7), Span { line: 2, col: 8 });
+        let position = |line, col| to_position(src, Span { line, col }).to_string();
+        assert_eq!(position(1, 5), r#"{"line":0,"character":4}"#);
+        assert_eq!(position(1, 6), r#"{"line":0,"character":6}"#);
+        // Past the end of the line (the end of a diagnostic)
+        assert_eq!(position(1, 8), r#"{"line":0,"character":8}"#);
+        assert_eq!(position(2, 8), r#"{"line":1,"character":7}"#);
+    }
+
+    #[test]
+    fn test_uri_percent_encoding() {
+        assert_eq!(uri_to_path("file:///tmp/a%20b/%C3%A9t%c3%a9.hdl"), Some(PathBuf::from("/tmp/a b/été.hdl")));
+        assert_eq!(uri_to_path("file:///tmp/100%.hdl"), Some(PathBuf::from("/tmp/100%.hdl")));
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+        let uri = path_to_uri(Path::new("/no such dir/été #1.hdl"));
+        assert_eq!(uri, "file:///no%20such%20dir/%C3%A9t%C3%A9%20%231.hdl");
+        assert_eq!(uri_to_path(&uri), Some(PathBuf::from("/no such dir/été #1.hdl")));
+    }
+
+    #[test]
+    fn test_project_lists_files_once() {
+        let dir = env::temp_dir().join(format!("hdl_lsp_project_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/A.hdl"), "").unwrap();
+        let dir = fs::canonicalize(dir).unwrap();
+        let open = dir.join("Open.hdl");
+        let uri = path_to_uri(&open);
+        let server = Server {
+            lib_dirs: vec![dir.clone()],
+            roots: Vec::new(),
+            docs: HashMap::from([(uri.clone(), "entity Open is end entity;".to_string())]),
+            listings: RefCell::new(HashMap::new()),
+            shutdown: false,
+        };
+        let name = |file: &str| dir.join(file).display().to_string();
+
+        let mut project = server.project(&uri);
+        assert_eq!(project.files().unwrap(), [name("Open.hdl"), name("sub/A.hdl")]);
+        assert_eq!(project.read(&name("Open.hdl")).unwrap(), "entity Open is end entity;");
+
+        // New files show up once the listing is cleared (on save)
+        fs::write(dir.join("B.hdl"), "").unwrap();
+        assert_eq!(server.project(&uri).files().unwrap().len(), 2);
+        server.listings.borrow_mut().clear();
+        assert_eq!(server.project(&uri).files().unwrap(), [name("Open.hdl"), name("B.hdl"), name("sub/A.hdl")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,13 +1,27 @@
 use hdl_core::ast::Design;
-use hdl_core::elab::elaborate;
+use hdl_core::dot::DotView;
+use hdl_core::elab::{elaborate, InstanceNode};
+use hdl_core::hier::ScopeSignal;
+use hdl_core::library::{resolve, MemorySources};
+use hdl_core::mem::MemoryInfo;
 use hdl_core::parser::parse_str;
 use hdl_core::sim::Simulator;
+use hdl_core::stats::DesignStats;
 use hdl_core::value::BitVec;
+use hdl_core::watch::RunOutcome;
 use a32_core::{Machine, Reg, SimConfig, StepOutcome, SCREEN_WIDTH, SCREEN_HEIGHT};
 
 pub struct HdlSession {
     sim: Option<Simulator>,
     clock_name: String,
+    /// Library files `(file name, source)` searched for missing entities and packages
+    library: Vec<(String, String)>,
+}
+
+impl Default for HdlSession {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HdlSession {
@@ -15,22 +29,26 @@ impl HdlSession {
         Self {
             sim: None,
             clock_name: "clk".to_string(),
+            library: Vec::new(),
         }
     }
 
+    /// Sets the library that `load` searches, by file name (`Mux.hdl`), for
+    /// the entities and packages the sources lack
+    pub fn set_library(&mut self, files: Vec<(String, String)>) {
+        self.library = files;
+    }
+
     pub fn load(&mut self, top: &str, sources: &[String]) -> Result<(), String> {
-        if sources.is_empty() {
+        if sources.is_empty() && self.library.is_empty() {
             return Err("load requires at least one source".to_string());
         }
-        let mut design = Design {
-            entities: Vec::new(),
-            architectures: Vec::new(),
-        };
+        let mut design = Design::default();
         for src in sources {
-            let d = parse_str(src).map_err(|e| e.to_string())?;
-            design.entities.extend(d.entities);
-            design.architectures.extend(d.architectures);
+            design.append(parse_str(src).map_err(|e| e.to_string())?);
         }
+        let mut library = MemorySources::new(self.library.clone());
+        resolve(&mut design, &[top], &mut library).map_err(|e| e.to_string())?;
         let netlist = elaborate(&design, top).map_err(|e| e.to_string())?;
         self.sim = Some(Simulator::new(netlist));
         Ok(())
@@ -62,14 +80,43 @@ impl HdlSession {
         Ok(sim.signal_names())
     }
 
-    /// Returns all signal values as JSON object
+    /// Returns all signal values as JSON object; enumeration and integer
+    /// signals show their literal name or decimal value
     pub fn dump_signals(&self) -> Result<Vec<(String, String)>, String> {
         let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
-        Ok(sim
-            .dump_signals()
+        sim.dump_signals()
+            .into_iter()
+            .map(|(name, bits)| {
+                let text = sim.display_signal(&name).map_err(|e| e.to_string())?;
+                Ok((name, text.unwrap_or_else(|| format_value(&bits))))
+            })
+            .collect()
+    }
+
+    /// Returns the direct sub-instances of the instance at `path` ("" = top)
+    pub fn instance_children(&self, path: &str) -> Result<Vec<InstanceNode>, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        let children = sim.instance_children(path).map_err(|e| e.to_string())?;
+        Ok(children.into_iter().cloned().collect())
+    }
+
+    /// Returns the ports and locals of the instance at `path` with their values
+    pub fn scope_signals(&self, path: &str) -> Result<Vec<(ScopeSignal, String)>, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        let signals = sim.scope_signals(path).map_err(|e| e.to_string())?;
+        signals
             .into_iter()
-            .map(|(name, bits)| (name, format_value(&bits)))
-            .collect())
+            .map(|sig| {
+                let value = sim.get_signal(&sig.net).map_err(|e| e.to_string())?;
+                Ok((sig, format_value(&value)))
+            })
+            .collect()
+    }
+
+    /// Resolves a path relative to the instance `scope` to a net name
+    pub fn resolve_signal(&self, scope: &str, path: &str) -> Result<String, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        sim.resolve_signal(scope, path).map_err(|e| e.to_string())
     }
 
     /// Returns signal metadata: (width, is_input, is_output)
@@ -100,12 +147,103 @@ impl HdlSession {
         sim.tock().map_err(|e| e.to_string())
     }
 
+    /// Pins a signal to a value until `release`
+    pub fn force(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        let v = parse_value(value)?;
+        sim.force_signal(name, v).map_err(|e| e.to_string())
+    }
+
+    /// Releases one forced signal, or all of them when `name` is empty
+    pub fn release(&mut self, name: &str) -> Result<(), String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        if name.trim().is_empty() {
+            sim.release_all();
+            return Ok(());
+        }
+        sim.release_signal(name.trim()).map_err(|e| e.to_string())
+    }
+
+    pub fn add_breakpoint(&mut self, condition: &str) -> Result<usize, String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        sim.add_breakpoint(condition).map_err(|e| e.to_string())
+    }
+
+    pub fn add_watchpoint(&mut self, name: &str) -> Result<usize, String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        sim.add_watchpoint(name).map_err(|e| e.to_string())
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> Result<(), String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        sim.remove_breakpoint(id).map_err(|e| e.to_string())
+    }
+
+    /// Breakpoints as (id, description)
+    pub fn breakpoints(&self) -> Result<Vec<(usize, String)>, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        Ok(sim.breakpoints())
+    }
+
+    /// Clocks the session clock until a breakpoint hits or `max_cycles` pass
+    pub fn run_until_break(&mut self, max_cycles: u64) -> Result<RunOutcome, String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        let clock = self.clock_name.clone();
+        sim.run_until_break(Some(&clock), max_cycles).map_err(|e| e.to_string())
+    }
+
     /// Load hex data into ROM at given index
     /// hex_data is space-separated hex values like "0x1234 0x5678"
     pub fn load_rom(&mut self, rom_index: usize, hex_data: &str) -> Result<(), String> {
         let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
         sim.load_rom_hex(rom_index, hex_data).map_err(|e| e.to_string())
     }
+
+    /// RAM and ROM instances of the loaded design
+    pub fn memories(&self) -> Result<Vec<MemoryInfo>, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        Ok(sim.memories())
+    }
+
+    pub fn read_memory(&self, path: &str, addr: u64) -> Result<String, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        let v = sim.read_memory(path, addr).map_err(|e| e.to_string())?;
+        Ok(format_value(&v))
+    }
+
+    pub fn write_memory(&mut self, path: &str, addr: u64, value: &str) -> Result<(), String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        let v = parse_value(value)?;
+        sim.write_memory(path, addr, &v).map_err(|e| e.to_string())
+    }
+
+    /// Loads a memory image; the format follows the file name
+    /// (`.a32b`, `.bin`, otherwise hex words). Returns the words written
+    pub fn load_memory(&mut self, path: &str, file_name: &str, bytes: &[u8]) -> Result<usize, String> {
+        let sim = self.sim.as_mut().ok_or("simulator not loaded")?;
+        sim.update_memory(path, |mem| mem.load_file(file_name, bytes))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Non-zero words of a memory as `@addr` / hex word lines
+    pub fn dump_memory_hex(&self, path: &str) -> Result<String, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        let mem = sim.memory(path).map_err(|e| e.to_string())?;
+        Ok(mem.dump_hex(0..mem.last_addr().saturating_add(1)))
+    }
+
+    /// Gate counts per instance and critical path of the loaded design
+    pub fn design_stats(&self) -> Result<DesignStats, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        Ok(sim.design_stats())
+    }
+
+    /// Graphviz DOT schematic of the instance at `path` (`""` for the top)
+    pub fn to_dot(&self, path: &str, flat: bool) -> Result<String, String> {
+        let sim = self.sim.as_ref().ok_or("simulator not loaded")?;
+        let view = if flat { DotView::Flat } else { DotView::Level };
+        sim.to_dot(path, view).map_err(|e| e.to_string())
+    }
 }
 
 pub struct A32Session {
@@ -114,6 +252,12 @@ pub struct A32Session {
     config: SimConfig,
 }
 
+impl Default for A32Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl A32Session {
     pub fn new() -> Self {
         Self {
@@ -334,9 +478,9 @@ mod wasm_api {
     use a32_asm::assemble_a32b;
     use c32_core::{compile_to_a32, parse_program};
     use hdl_core::run_test;
+    use hdl_core::stats::{GateCounts, GateKind};
     use std::collections::HashMap;
     use wasm_bindgen::prelude::*;
-    use js_sys;
 
     #[wasm_bindgen]
     pub struct WasmHdl {
@@ -356,6 +500,15 @@ mod wasm_api {
             self.inner.load(top, &sources).map_err(js_err)
         }
 
+        /// Library files searched by `load`; `names[i]` is the file name of `sources[i]`
+        pub fn set_library(&mut self, names: Vec<String>, sources: Vec<String>) -> Result<(), JsValue> {
+            if names.len() != sources.len() {
+                return Err(JsValue::from_str("set_library expects one name per source"));
+            }
+            self.inner.set_library(names.into_iter().zip(sources).collect());
+            Ok(())
+        }
+
         pub fn set_clock(&mut self, name: &str) {
             self.inner.set_clock(name);
         }
@@ -399,6 +552,50 @@ mod wasm_api {
             ))
         }
 
+        /// Get the sub-instances of an instance ("" = top) as JSON array:
+        /// [{ name, path, entity, children }], entity is null for generate blocks
+        pub fn instance_children(&self, path: &str) -> Result<String, JsValue> {
+            let children = self.inner.instance_children(path).map_err(js_err)?;
+            let items: Vec<String> = children
+                .iter()
+                .map(|node| {
+                    format!(
+                        r#"{{"name":{},"path":{},"entity":{},"children":{}}}"#,
+                        json_str(&node.name),
+                        json_str(&node.path),
+                        node.entity.as_deref().map(json_str).unwrap_or_else(|| "null".to_string()),
+                        node.children.len()
+                    )
+                })
+                .collect();
+            Ok(format!("[{}]", items.join(",")))
+        }
+
+        /// Get the ports and locals of an instance as JSON array:
+        /// [{ name, net, width, kind: "in"|"out"|"local", value }]
+        pub fn scope_signals(&self, path: &str) -> Result<String, JsValue> {
+            let signals = self.inner.scope_signals(path).map_err(js_err)?;
+            let items: Vec<String> = signals
+                .iter()
+                .map(|(sig, value)| {
+                    format!(
+                        r#"{{"name":{},"net":{},"width":{},"kind":"{}","value":{}}}"#,
+                        json_str(&sig.name),
+                        json_str(&sig.net),
+                        sig.width,
+                        sig.kind.as_str(),
+                        json_str(value)
+                    )
+                })
+                .collect();
+            Ok(format!("[{}]", items.join(",")))
+        }
+
+        /// Resolve a path relative to an instance (supports "..") to a net name
+        pub fn resolve_signal(&self, scope: &str, path: &str) -> Result<String, JsValue> {
+            self.inner.resolve_signal(scope, path).map_err(js_err)
+        }
+
         pub fn eval(&mut self) -> Result<(), JsValue> {
             self.inner.eval().map_err(js_err)
         }
@@ -411,12 +608,153 @@ mod wasm_api {
             self.inner.tock().map_err(js_err)
         }
 
+        /// Force a signal to a value; it keeps it until release
+        pub fn force(&mut self, name: &str, value: &str) -> Result<(), JsValue> {
+            self.inner.force(name, value).map_err(js_err)
+        }
+
+        /// Release a forced signal ("" releases all of them)
+        pub fn release(&mut self, name: &str) -> Result<(), JsValue> {
+            self.inner.release(name).map_err(js_err)
+        }
+
+        /// Add a breakpoint condition like "pc = 0x40 and ready"; returns its id
+        pub fn add_breakpoint(&mut self, condition: &str) -> Result<usize, JsValue> {
+            self.inner.add_breakpoint(condition).map_err(js_err)
+        }
+
+        /// Break whenever a signal changes; returns the breakpoint id
+        pub fn add_watchpoint(&mut self, name: &str) -> Result<usize, JsValue> {
+            self.inner.add_watchpoint(name).map_err(js_err)
+        }
+
+        pub fn remove_breakpoint(&mut self, id: usize) -> Result<(), JsValue> {
+            self.inner.remove_breakpoint(id).map_err(js_err)
+        }
+
+        /// Get the breakpoints as JSON array: [{ id, text }]
+        pub fn list_breakpoints(&self) -> Result<String, JsValue> {
+            let items: Vec<String> = self
+                .inner
+                .breakpoints()
+                .map_err(js_err)?
+                .iter()
+                .map(|(id, text)| format!(r#"{{"id":{},"text":{}}}"#, id, json_str(text)))
+                .collect();
+            Ok(format!("[{}]", items.join(",")))
+        }
+
+        /// Run clock cycles until a breakpoint hits, as JSON: { cycles, hits: [id] }
+        /// hits is empty when max_cycles ran out first
+        pub fn run_until_break(&mut self, max_cycles: u32) -> Result<String, JsValue> {
+            let outcome = self.inner.run_until_break(max_cycles as u64).map_err(js_err)?;
+            let hits: Vec<String> = outcome.hits.iter().map(|id| id.to_string()).collect();
+            Ok(format!(r#"{{"cycles":{},"hits":[{}]}}"#, outcome.cycles, hits.join(",")))
+        }
+
         /// Load hex data into ROM
         /// hex_data is space-separated hex values like "0x1234 0x5678"
         pub fn load_rom(&mut self, rom_index: usize, hex_data: &str) -> Result<(), JsValue> {
             self.inner.load_rom(rom_index, hex_data).map_err(js_err)
         }
 
+        /// Get the memories as JSON array:
+        /// [{ path, kind: "ram"|"rom", addr_width, data_width }]
+        pub fn list_memories(&self) -> Result<String, JsValue> {
+            let items: Vec<String> = self
+                .inner
+                .memories()
+                .map_err(js_err)?
+                .iter()
+                .map(|m| {
+                    format!(
+                        r#"{{"path":{},"kind":"{}","addr_width":{},"data_width":{}}}"#,
+                        json_str(&m.path),
+                        m.kind.as_str(),
+                        m.addr_width,
+                        m.data_width
+                    )
+                })
+                .collect();
+            Ok(format!("[{}]", items.join(",")))
+        }
+
+        pub fn read_memory(&self, path: &str, addr: u32) -> Result<String, JsValue> {
+            self.inner.read_memory(path, addr as u64).map_err(js_err)
+        }
+
+        pub fn write_memory(&mut self, path: &str, addr: u32, value: &str) -> Result<(), JsValue> {
+            self.inner.write_memory(path, addr as u64, value).map_err(js_err)
+        }
+
+        /// Load a file into a memory: .a32b image, .bin words, else hex text
+        /// ("@addr" lines and one word per token). Returns the words written
+        pub fn load_memory(&mut self, path: &str, file_name: &str, bytes: Vec<u8>) -> Result<usize, JsValue> {
+            self.inner.load_memory(path, file_name, &bytes).map_err(js_err)
+        }
+
+        /// Dump the non-zero words of a memory as hex text
+        pub fn dump_memory(&self, path: &str) -> Result<String, JsValue> {
+            self.inner.dump_memory_hex(path).map_err(js_err)
+        }
+
+        /// Get gate statistics as JSON:
+        /// { instances: [{ path, entity, level, gates: { nand2, ..., dff },
+        ///   total, area, memory_bits, behavioural }],
+        ///   critical_path: { depth, from, to, steps: [{ depth, signal, through }] } }
+        /// Primitive instances are left out, their gates count in their parent
+        pub fn design_stats(&self) -> Result<String, JsValue> {
+            let stats = self.inner.design_stats().map_err(js_err)?;
+            let instances: Vec<String> = stats
+                .instances
+                .iter()
+                .filter(|i| !i.primitive)
+                .map(|i| {
+                    format!(
+                        r#"{{"path":{},"entity":{},"level":{},{}}}"#,
+                        json_str(&i.path),
+                        i.entity.as_deref().map(json_str).unwrap_or_else(|| "null".to_string()),
+                        i.level,
+                        gates_json(&i.gates)
+                    )
+                })
+                .collect();
+            let path = &stats.critical_path;
+            let opt = |s: &Option<String>| s.as_deref().map(json_str).unwrap_or_else(|| "null".to_string());
+            let steps: Vec<String> = path
+                .steps
+                .iter()
+                .map(|step| {
+                    format!(
+                        r#"{{"depth":{},"signal":{},"through":{}}}"#,
+                        step.depth,
+                        json_str(&step.signal),
+                        json_str(&step.through)
+                    )
+                })
+                .collect();
+            Ok(format!(
+                r#"{{"instances":[{}],"critical_path":{{"depth":{},"from":{},"to":{},"steps":[{}]}}}}"#,
+                instances.join(","),
+                path.depth,
+                opt(&path.from),
+                opt(&path.to),
+                steps.join(",")
+            ))
+        }
+
+        /// Gate statistics as the text table printed by `hdl_cli stats`
+        pub fn stats_report(&self, max_level: usize) -> Result<String, JsValue> {
+            let stats = self.inner.design_stats().map_err(js_err)?;
+            Ok(stats.report(max_level))
+        }
+
+        /// Schematic of the instance at `path` (`""` for the top) as a
+        /// Graphviz DOT string, one level deep or flattened to primitives
+        pub fn to_dot(&self, path: &str, flat: bool) -> Result<String, JsValue> {
+            self.inner.to_dot(path, flat).map_err(js_err)
+        }
+
         /// Run a test script against HDL source
         /// Returns JSON: { passed: bool, total: number, passed_checks: number, errors: string[] }
         pub fn run_test(
@@ -656,4 +994,23 @@ mod wasm_api {
     fn js_err(message: String) -> JsValue {
         JsValue::from_str(&message)
     }
+
+    fn gates_json(gates: &GateCounts) -> String {
+        let counts: Vec<String> = GateKind::ALL
+            .iter()
+            .map(|&kind| format!(r#""{}":{}"#, kind.as_str(), gates.get(kind)))
+            .collect();
+        format!(
+            r#""gates":{{{}}},"total":{},"area":{},"memory_bits":{},"behavioural":{}"#,
+            counts.join(","),
+            gates.total(),
+            gates.nand_area(),
+            gates.memory_bits,
+            gates.behavioural
+        )
+    }
+
+    fn json_str(s: &str) -> String {
+        serde_json_wasm::to_string(s).unwrap_or_else(|_| "\"\"".to_string())
+    }
 }